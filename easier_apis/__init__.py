@@ -1,4 +1,4 @@
-from .core import API, get, post, put, delete
+from .core import API, CancellationToken, ClientPool, IdempotencyTracker, get, post, put, patch, delete
 
-__all__ = ['API', 'get', 'post', 'put', 'delete']
+__all__ = ['API', 'CancellationToken', 'ClientPool', 'IdempotencyTracker', 'get', 'post', 'put', 'patch', 'delete']
 __version__ = "0.1.0"
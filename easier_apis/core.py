@@ -1,5 +1,8 @@
+import base64
 import functools
-from typing import Callable, Any, Dict
+import gzip
+import itertools
+from typing import Callable, Any, Dict, List, Optional, Tuple
 import json
 from cffi import FFI
 import time
@@ -8,13 +11,213 @@ from collections import OrderedDict
 ffi = FFI()
 ffi.cdef("""
     void* rust_core_new(const char* base_url);
+    void rust_core_destroy(void* core);
     char* rust_core_fetch(void* core, const char* path);
+    char* rust_core_memoized(void* core, const char* path);
+    void rust_core_invalidate_memo(void* core, const char* path);
+    char* rust_core_head(void* core, const char* path);
+    char* rust_core_size_stats(void* core);
+    int rust_core_exists(void* core, const char* path);
     char* rust_core_send(void* core, const char* path, const char* method, const char* data);
     void rust_core_free(char* ptr);
     void rust_core_set_auth(void* core, const char* auth_type, const char* key, const char* value);
+    void rust_core_clear_auth(void* core);
+    int rust_core_set_response_hmac(void* core, const char* key, const char* header);
+    void rust_core_set_hmac_signer(void* core, const char* key, uint64_t skew_window_ms);
+    int rust_core_set_statsd(void* core, const char* addr, bool dogstatsd, const char* tags);
+    void* rust_core_pages_open(void* core, const char* path, const char* strategy);
+    char* rust_core_pages_next(void* pages);
+    void rust_core_pages_close(void* pages);
+    void* rust_core_idempotency_new(uint64_t ttl_ms, const char* path);
+    int rust_core_idempotency_first_seen(void* tracker, const char* id);
+    void rust_core_idempotency_forget(void* tracker, const char* id);
+    void rust_core_idempotency_destroy(void* tracker);
+    void rust_core_sse_dedup(void* stream, void* tracker);
+    char* rust_core_fetch_paginated_dedup(void* core, const char* path, const char* strategy, void* tracker, const char* id);
+    void* rust_core_cancel_token_new(void);
+    void rust_core_cancel(void* token);
+    void rust_core_cancel_token_destroy(void* token);
+    char* rust_core_fetch_cancellable(void* core, const char* path, const char* options, void* token);
+    char* rust_core_send_cancellable(void* core, const char* path, const char* method, const char* data, const char* options, void* token);
+    char* rust_core_fetch_many(void* core, const char* paths, size_t concurrency);
+    void rust_core_set_maintenance(void* core, uint64_t default_pause_ms, void (*callback)(const char* event));
+    char* rust_core_maintenance_state(void* core);
+    void rust_core_resume_after_maintenance(void* core);
+    void rust_core_set_metrics_callback(void* core, void (*callback)(const char* event));
+    int rust_core_add_body_rule(void* core, const char* path, const char* values, bool retry);
+    void rust_core_set_circuit_breaker(void* core, uint32_t failure_threshold, uint64_t cooldown_ms);
+    char* rust_core_circuit_states(void* core);
+    void rust_core_set_cache(void* core, size_t capacity, uint64_t default_ttl_ms);
+    char* rust_core_graphql(void* core, const char* path, const char* query, const char* variables, bool persisted);
+    void* rust_core_pool_new(void);
+    void* rust_core_new_pooled(void* pool, const char* base_url);
+    void* rust_core_new_with_proxy(const char* base_url, const char* proxy);
+    void rust_core_pool_destroy(void* pool);
+    void* rust_core_sse_open(void* core, const char* path);
+    char* rust_core_sse_next(void* stream);
+    void rust_core_sse_close(void* stream);
+    char* rust_core_fetch_paginated(void* core, const char* path, const char* strategy);
+    void rust_core_set_rate_limit(void* core, double per_second, uint32_t burst);
+    int rust_core_set_profile(void* core, const char* name, const char* route);
+    int rust_core_load_profiles(void* core, const char* path);
+    char* rust_core_last_error();
+    int rust_core_last_error_code();
+    void rust_core_clear_last_error();
+    char* rust_core_send_form(void* core, const char* path, const char* method, const char* fields);
+    char* rust_core_send_multipart(void* core, const char* path, const char* parts);
+    void rust_core_set_decompression_limits(void* core, uint64_t max_bytes, uint64_t max_ratio);
+    char* rust_core_grpc_web(void* core, const char* method, const char* message);
+    int64_t rust_core_download(void* core, const char* path, const char* dest, void (*progress)(uint64_t, int64_t));
+    char* rust_core_export(void* core, const char* path, const char* dest_dir);
+    int rust_core_enable_cache(void* core, const char* dir, uint64_t max_bytes);
+    int rust_core_cache_clear(void* core);
+    void rust_core_set_oauth2(void* core, const char* token_url, const char* client_id, const char* client_secret, const char* scopes);
+    int rust_core_set_default_header(void* core, const char* name, const char* value);
+    char* rust_core_fetch_with_options(void* core, const char* path, const char* options);
+    char* rust_core_send_with_options(void* core, const char* path, const char* method, const char* data, const char* options);
+    char* rust_core_send_multi_status(void* core, const char* path, const char* method, const char* data);
+    char* rust_core_dav(void* core, const char* method, const char* path, const char* depth, const char* xml);
+    char* rust_core_fetch_with_params(void* core, const char* path, const char* params);
+    char* rust_core_send_with_params(void* core, const char* path, const char* method, const char* data, const char* params);
+    void rust_core_set_retry_policy(void* core, uint32_t max_attempts, uint64_t base_delay_ms, uint64_t max_delay_ms, double jitter, bool retry_non_idempotent);
+    void rust_core_set_retry_limits(void* core, uint64_t deadline_ms, uint64_t backoff_budget_ms);
+    void rust_core_add_default_query(void* core, const char* name, const char* value);
+    char* rust_core_pending(void* core);
+    int rust_core_set_quota_store(void* core, const char* path);
+    char* rust_core_quota_state(void* core);
+    int rust_core_set_regions(void* core, const char* names, const char* base_urls, const char* probe_path, uint64_t interval_ms);
+    int rust_core_pin_region(void* core, const char* name);
+    char* rust_core_region_status(void* core);
+    void rust_core_start_capture(void* core, uint64_t duration_ms, size_t max_count);
+    char* rust_core_support_bundle(void* core, bool stop);
+    void rust_core_set_error_translator(void* core, int (*callback)(uint16_t status, const char* code));
+    char* rust_core_bulk(void* core, const char* path, const char* method, const char* items, size_t chunk_size, size_t concurrency);
+    char* rust_core_send_and_follow(void* core, const char* path, const char* method, const char* data, uint64_t interval_ms, uint64_t timeout_ms);
+    void rust_core_set_deprecation_monitoring(void* core, bool enabled);
+    int rust_core_set_api_version(void* core, const char* strategy, const char* name, const char* version);
+    int rust_core_add_traffic_split(void* core, const char* path, const char* base_url, double percent, const char* tenant_header, const char* tenants);
+    int rust_core_set_egress_policy(void* core, const char* hosts, const char* cidrs, bool block_private);
+    int rust_core_set_key_transform(void* core, const char* outgoing, const char* incoming);
+    void rust_core_set_locale(void* core, const char* languages, const char* fallback);
+    int rust_core_shutdown(void* core, uint64_t timeout_ms);
+    void rust_core_notify_connectivity(void* core, bool online);
+    int rust_core_enable_offline_queue(void* core, const char* journal_path);
+    void rust_core_set_queue_failure_callback(void* core, void (*callback)(const char* item, const char* error));
+    int rust_core_enqueue(void* core, const char* path, const char* method, const char* data);
+    int rust_core_flush_queue(void* core);
+    int rust_core_load_script(void* core, const char* path);
+    int rust_core_reload_scripts(void* core);
+    int rust_core_load_plugin(void* core, const char* path);
+    void* rust_core_ws_connect(void* core, const char* path);
+    int rust_core_ws_send(void* socket, const char* data, bool binary);
+    char* rust_core_ws_receive(void* socket);
+    void rust_core_ws_close(void* socket);
 """)
 lib = ffi.dlopen("libeasier_apis_core.so")  # Adjust path as needed
 
+# Negative codes returned by int functions that make requests
+_ERROR_KINDS = {
+    -1: "failed",
+    -2: "connection failed",
+    -3: "timed out",
+    -4: "returned an HTTP error",
+    -5: "returned an unexpected body",
+    -6: "was rejected by middleware",
+    -7: "got an invalid argument",
+}
+
+class ApiError(RuntimeError):
+    def __init__(self, message: str, code: int = -1):
+        super().__init__(message)
+        self.code = code
+
+# Some items of a batch failed; `failed` and `succeeded` are lists of
+# (index, error message) and (index, response) pairs
+class AggregateError(ApiError):
+    def __init__(self, failed: List[Any], succeeded: List[Any]):
+        total = len(failed) + len(succeeded)
+        index, error = failed[0]
+        super().__init__(f"{len(failed)} of {total} items failed; item {index}: {error}")
+        self.failed = failed
+        self.succeeded = succeeded
+
+# The GraphQL server answered with errors. `errors` is the list from the
+# response, `data` whatever partial data came with it
+class GraphQLError(ApiError):
+    def __init__(self, errors: List[Dict[str, Any]], data: Any = None):
+        messages = "; ".join(error.get("message", "") for error in errors) or "no data"
+        super().__init__(f"GraphQL error: {messages}")
+        self.errors = errors
+        self.data = data
+
+# Takes the core's last error on this thread, if any, and adds it to `message`
+def _error(message: str) -> ApiError:
+    code = lib.rust_core_last_error_code()
+    detail = lib.rust_core_last_error()
+    lib.rust_core_clear_last_error()
+    if detail == ffi.NULL:
+        return ApiError(message, code or -1)
+    reason = ffi.string(detail).decode('utf-8', 'replace')
+    lib.rust_core_free(detail)
+    return ApiError(f"{message}: {reason}", code)
+
+_METHODS = ("GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS")
+
+def _method(method: str) -> bytes:
+    if method.upper() not in _METHODS:
+        raise ValueError(f"Unsupported method: {method}")
+    return method.upper().encode('utf-8')
+
+# Per-call options for the Rust core, as JSON; timeouts are in seconds
+def _request_options(params, headers, timeout, connect_timeout, deadline) -> str:
+    options = {"params": params, "headers": headers}
+    for key, seconds in (("timeout_ms", timeout), ("connect_timeout_ms", connect_timeout), ("deadline_ms", deadline)):
+        if seconds is not None:
+            options[key] = int(seconds * 1000)
+    return json.dumps(options)
+
+# Same rules as the Rust core: "*" is one path segment, or any number at the end
+def _route_matches(route: str, method: str, path: str) -> bool:
+    route_method, _, route_path = route.strip().rpartition(" ")
+    if route_method and route_method.strip().upper() != method.upper():
+        return False
+    pattern = [segment for segment in route_path.split("/") if segment]
+    segments = [segment for segment in path.split("?")[0].split("/") if segment]
+    for i, segment in enumerate(pattern):
+        if segment == "*" and i == len(pattern) - 1:
+            return True
+        if i >= len(segments) or segment not in ("*", segments[i]):
+            return False
+    return len(segments) == len(pattern)
+
+# Entries whose JSON is at least this big are kept gzipped, if that saves at
+# least 20%, and decompressed on each read
+_COMPRESS_THRESHOLD = 16 * 1024
+_MAX_COMPRESSED_RATIO = 0.8
+
+class _Compressed:
+    __slots__ = ("data",)
+
+    def __init__(self, data: bytes):
+        self.data = data
+
+def _pack(value: Any) -> Any:
+    try:
+        encoded = json.dumps(value).encode('utf-8')
+    except (TypeError, ValueError):
+        return value
+    if len(encoded) < _COMPRESS_THRESHOLD:
+        return value
+    compressed = gzip.compress(encoded, compresslevel=1)
+    if len(compressed) < len(encoded) * _MAX_COMPRESSED_RATIO:
+        return _Compressed(compressed)
+    return value
+
+def _unpack(value: Any) -> Any:
+    if isinstance(value, _Compressed):
+        return json.loads(gzip.decompress(value.data))
+    return value
+
 class LRUCache:
     def __init__(self, capacity: int = 100):
         self.cache = OrderedDict()
@@ -28,7 +231,7 @@ class LRUCache:
             del self.cache[key]
             return None
         self.cache.move_to_end(key)
-        return value
+        return _unpack(value)
 
     def put(self, key: str, value: Any, ttl: int = None):
         if key in self.cache:
@@ -36,21 +239,654 @@ class LRUCache:
         elif len(self.cache) >= self.capacity:
             self.cache.popitem(last=False)
         expiry = time.time() + ttl if ttl else None
-        self.cache[key] = (value, expiry)
+        self.cache[key] = (_pack(value), expiry)
         self.cache.move_to_end(key)
 
+# Connections shared by every API created with it, so integrations on the same
+# host reuse sockets and TLS sessions. APIs keep working after the pool closes
+class ClientPool:
+    def __init__(self):
+        self.pool = lib.rust_core_pool_new()
+        if self.pool == ffi.NULL:
+            raise _error("Could not create the connection pool")
+
+    def close(self):
+        if self.pool != ffi.NULL:
+            lib.rust_core_pool_destroy(self.pool)
+            self.pool = ffi.NULL
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        if getattr(self, "pool", ffi.NULL) != ffi.NULL:
+            self.close()
+
+# Pass as `cancel=` to API.fetch or API.send, then call cancel() from another
+# thread to abort the call, including a retry it's waiting out. The call
+# raises; a cancelled token stays cancelled
+class CancellationToken:
+    def __init__(self):
+        self.token = lib.rust_core_cancel_token_new()
+
+    def cancel(self):
+        lib.rust_core_cancel(self.token)
+
+    def __del__(self):
+        if getattr(self, "token", ffi.NULL) != ffi.NULL:
+            lib.rust_core_cancel_token_destroy(self.token)
+            self.token = ffi.NULL
+
+# Delivery IDs seen in the last `ttl` seconds, kept in the file at `path` too
+# if given so they survive a restart. Pass as `dedup=` to API.fetch_sse or
+# API.fetch_paginated, or call first_seen from a webhook handler with the
+# sender's delivery ID
+class IdempotencyTracker:
+    def __init__(self, ttl: float = 86400, path: str = None):
+        self.tracker = lib.rust_core_idempotency_new(int(ttl * 1000), path.encode('utf-8') if path else ffi.NULL)
+        if self.tracker == ffi.NULL:
+            raise _error(f"Could not open the idempotency file: {path}")
+
+    # True the first time, False for a duplicate
+    def first_seen(self, id: str) -> bool:
+        return lib.rust_core_idempotency_first_seen(self.tracker, str(id).encode('utf-8')) == 1
+
+    # Lets a redelivery of `id` through, e.g. after handling it failed
+    def forget(self, id: str):
+        lib.rust_core_idempotency_forget(self.tracker, str(id).encode('utf-8'))
+
+    def __del__(self):
+        if getattr(self, "tracker", ffi.NULL) != ffi.NULL:
+            lib.rust_core_idempotency_destroy(self.tracker)
+            self.tracker = ffi.NULL
+
+# A WebSocket from API.connect_ws; needs the core built with the `websocket`
+# feature. Iterating yields messages until the server closes the connection
+class WebSocket:
+    def __init__(self, api: "API", socket):
+        self.api = api
+        self.socket = socket
+
+    def send(self, message: Any):
+        if self.socket is None:
+            raise ApiError("WebSocket is closed")
+        if isinstance(message, (bytes, bytearray)):
+            result = lib.rust_core_ws_send(self.socket, base64.b64encode(message), True)
+        else:
+            result = lib.rust_core_ws_send(self.socket, str(message).encode('utf-8'), False)
+        if result != 0:
+            raise _error("WebSocket send failed")
+
+    # str for text messages, bytes for binary ones, None once closed
+    def receive(self) -> Any:
+        if self.socket is None:
+            return None
+        result = lib.rust_core_ws_receive(self.socket)
+        if result == ffi.NULL:
+            raise _error("WebSocket receive failed")
+        message = json.loads(ffi.string(result).decode('utf-8'))
+        lib.rust_core_free(result)
+        if message["type"] == "close":
+            self.close()
+            return None
+        if message["type"] == "binary":
+            return base64.b64decode(message["data"])
+        return message["data"]
+
+    def close(self):
+        if self.socket is not None:
+            lib.rust_core_ws_close(self.socket)
+            self.socket = None
+
+    def __iter__(self):
+        while True:
+            message = self.receive()
+            if message is None:
+                return
+            yield message
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        self.close()
+
 class API:
-    def __init__(self, base_url: str, cache_capacity: int = 100):
+    # `proxies` is one proxy URL for everything, or a dict with "http", "https"
+    # and "all" keys, as in requests; socks5:// URLs need the core built with
+    # the `socks` feature. `no_proxy` lists hosts reached directly, as in
+    # NO_PROXY, and `proxy_auth` is a (username, password) pair. With
+    # system_proxy=False the HTTP_PROXY, HTTPS_PROXY and NO_PROXY environment
+    # variables are ignored
+    def __init__(self, base_url: str, cache_capacity: int = 100, pool: Optional[ClientPool] = None,
+                 proxies: Any = None, no_proxy: str = None, proxy_auth: Tuple[str, str] = None, system_proxy: bool = True):
         self.base_url = base_url
-        self.rust_core = lib.rust_core_new(base_url.encode('utf-8'))
+        proxy = {"all": proxies} if isinstance(proxies, str) else dict(proxies or {})
+        if set(proxy) - {"all", "http", "https"}:
+            raise ValueError(f"Unsupported proxy schemes: {', '.join(set(proxy) - {'all', 'http', 'https'})}")
+        if no_proxy is not None:
+            proxy["no_proxy"] = no_proxy
+        if proxy_auth is not None:
+            proxy["username"], proxy["password"] = proxy_auth
+        if not system_proxy:
+            proxy["system_proxy"] = False
+        if proxy and pool is not None:
+            raise ValueError("Proxies can't be combined with a ClientPool")
+        if proxy:
+            self.rust_core = lib.rust_core_new_with_proxy(base_url.encode('utf-8'), json.dumps(proxy).encode('utf-8'))
+            if self.rust_core == ffi.NULL:
+                raise _error("Could not create the API")
+        elif pool is None:
+            self.rust_core = lib.rust_core_new(base_url.encode('utf-8'))
+        elif pool.pool == ffi.NULL:
+            raise ApiError("ClientPool is closed")
+        else:
+            self.rust_core = lib.rust_core_new_pooled(pool.pool, base_url.encode('utf-8'))
+            if self.rust_core == ffi.NULL:
+                raise _error("Could not create the API")
         self.middleware = []
+        self._middleware_seq = itertools.count()
         self.cache = LRUCache(cache_capacity)
 
+    # Frees the Rust client and its connections; the API can't be used after
+    def close(self):
+        if self.rust_core != ffi.NULL:
+            lib.rust_core_destroy(self.rust_core)
+            self.rust_core = ffi.NULL
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        if getattr(self, "rust_core", ffi.NULL) != ffi.NULL:
+            self.close()
+
     def set_auth(self, auth_type: str, key: str = "", value: str = ""):
         lib.rust_core_set_auth(self.rust_core, auth_type.encode('utf-8'), key.encode('utf-8'), value.encode('utf-8'))
 
-    def add_middleware(self, middleware: Callable[[Dict[str, Any]], Dict[str, Any]]):
-        self.middleware.append(middleware)
+    # Requests go out without credentials from now on, e.g. after logout
+    def clear_auth(self):
+        lib.rust_core_clear_auth(self.rust_core)
+
+    def verify_responses(self, key: str, header: str = "X-Signature"):
+        if lib.rust_core_set_response_hmac(self.rust_core, key.encode('utf-8'), header.encode('utf-8')) != 0:
+            raise _error(f"Invalid signature header: {header}")
+
+    def set_hmac_signer(self, key: str, skew_window_ms: int = 0):
+        lib.rust_core_set_hmac_signer(self.rust_core, key.encode('utf-8'), skew_window_ms)
+
+    def set_statsd(self, addr: str, dogstatsd: bool = False, tags: Dict[str, str] = None):
+        tag_list = ",".join(f"{k}:{v}" for k, v in (tags or {}).items())
+        if lib.rust_core_set_statsd(self.rust_core, addr.encode('utf-8'), dogstatsd, tag_list.encode('utf-8')) != 0:
+            raise _error(f"Failed to set up StatsD sink: {addr}")
+
+    # Yields the pages of `path` one at a time as dicts with "url", "items" and
+    # "meta": "number", "page_size", "total" (None unless the API says),
+    # "cursor" and "has_more". Options are as for fetch_paginated
+    def fetch_pages(self, path: str, strategy: str = "link", items: str = None, **options):
+        config = {"type": strategy, **options}
+        if items is not None:
+            config["items"] = items
+        pages = lib.rust_core_pages_open(self.rust_core, path.encode('utf-8'), json.dumps(config).encode('utf-8'))
+        if pages == ffi.NULL:
+            raise _error(f"Failed to fetch pages of {path}")
+        try:
+            while True:
+                result = lib.rust_core_pages_next(pages)
+                if result == ffi.NULL:
+                    raise _error(f"Failed to fetch pages of {path}")
+                page = json.loads(ffi.string(result).decode('utf-8'))
+                lib.rust_core_free(result)
+                if page is None:
+                    return
+                yield page
+        finally:
+            lib.rust_core_pages_close(pages)
+
+    # Fetches `paths` in parallel, at most `concurrency` at a time, and returns
+    # the responses in the same order. Raises AggregateError if any failed
+    def fetch_many(self, paths: List[str], concurrency: int = 8) -> List[Any]:
+        result = lib.rust_core_fetch_many(self.rust_core, json.dumps(list(paths)).encode('utf-8'), concurrency)
+        if result == ffi.NULL:
+            raise _error("Fetching many paths failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        results = json.loads(json_str)
+        failed = [(i, item["error"]) for i, item in enumerate(results) if not item["ok"]]
+        if failed:
+            succeeded = [(i, item["response"]) for i, item in enumerate(results) if item["ok"]]
+            raise AggregateError(failed, succeeded)
+        return [item["response"] for item in results]
+
+    # A 503 mentioning maintenance pauses the client until its Retry-After, or
+    # `default_pause` seconds; meanwhile requests fail fast instead of piling
+    # up errors. `on_event` gets {"event": "started", "resume_in_ms", "message"}
+    # and {"event": "ended"}, e.g. to show and hide a banner
+    def set_maintenance(self, default_pause: float = 60, on_event: Callable[[Dict[str, Any]], Any] = None):
+        callback = ffi.NULL
+        if on_event is not None:
+            @ffi.callback("void(const char*)")
+            def callback(event):
+                on_event(json.loads(ffi.string(event).decode('utf-8')))
+        # Keep the callback alive for as long as the core can call it
+        self._maintenance_callback = callback
+        lib.rust_core_set_maintenance(self.rust_core, int(default_pause * 1000), callback)
+
+    # {"resume_in_ms": ..., "message": ...} while paused, otherwise None
+    def maintenance_state(self) -> Optional[Dict[str, Any]]:
+        result = lib.rust_core_maintenance_state(self.rust_core)
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def resume_after_maintenance(self):
+        lib.rust_core_resume_after_maintenance(self.rust_core)
+
+    # `sink(event)` gets a dict per HTTP attempt, per finished call and per
+    # response body read, told apart by event["kind"]: "attempt", "request"
+    # or "body". shutdown() sends a last "flush" event with "timeout_ms" left
+    # for sinks that buffer. It runs on the requesting thread, so keep it quick
+    def set_metrics_sink(self, sink: Callable[[Dict[str, Any]], Any]):
+        @ffi.callback("void(const char*)")
+        def callback(event):
+            sink(json.loads(ffi.string(event).decode('utf-8')))
+        # Keep the callback alive for as long as the core can call it
+        self._metrics_callback = callback
+        lib.rust_core_set_metrics_callback(self.rust_core, callback)
+
+    # Treats 200 responses whose JSON at `path` (a JSON pointer or "$.a.b")
+    # is one of `values` as failures: "retry" sends them again under the retry
+    # policy, "fail" raises straight away
+    def add_body_rule(self, path: str, values: List[Any], action: str = "retry"):
+        if action not in ("retry", "fail"):
+            raise ValueError(f"Unknown body rule action: {action}")
+        if lib.rust_core_add_body_rule(self.rust_core, path.encode('utf-8'), json.dumps(list(values)).encode('utf-8'), action == "retry") != 0:
+            raise _error(f"Failed to add body rule for {path}")
+
+    # After `failure_threshold` consecutive 5xx, timeouts or connection errors
+    # from a host, its requests fail fast for `cooldown` seconds; then one probe
+    # decides whether it's healthy again. None removes the breaker
+    def set_circuit_breaker(self, failure_threshold: Optional[int] = 5, cooldown: float = 30):
+        lib.rust_core_set_circuit_breaker(self.rust_core, failure_threshold or 0, int(cooldown * 1000))
+
+    # {host: "open" | "half-open" | "closed"} for hosts that failed lately
+    def circuit_states(self) -> Dict[str, str]:
+        result = lib.rust_core_circuit_states(self.rust_core)
+        states = json.loads(ffi.string(result).decode('utf-8'))
+        lib.rust_core_free(result)
+        return states
+
+    # HTTP caching in the Rust core: GET responses are kept while Cache-Control
+    # or Expires says they're fresh, then revalidated with ETag/Last-Modified.
+    # default_ttl (seconds) applies to responses without caching headers;
+    # capacity=0 turns it off
+    def use_http_cache(self, capacity: int = 1000, default_ttl: float = 0):
+        lib.rust_core_set_cache(self.rust_core, capacity, int(default_ttl * 1000))
+
+    # Runs `query` against `path` (the base URL itself by default) and returns
+    # its data. Errors the server reports raise GraphQLError; persisted=True
+    # sends only the query's hash until the server asks for the text
+    def graphql(self, query: str, variables: Dict[str, Any] = None, path: str = "", persisted: bool = False) -> Any:
+        encoded = json.dumps(variables).encode('utf-8') if variables is not None else ffi.NULL
+        result = lib.rust_core_graphql(self.rust_core, path.encode('utf-8'), query.encode('utf-8'), encoded, persisted)
+        if result == ffi.NULL:
+            raise _error("GraphQL request failed")
+        response = json.loads(ffi.string(result).decode('utf-8'))
+        lib.rust_core_free(result)
+        if response["errors"] or response["data"] is None:
+            raise GraphQLError(response["errors"], response["data"])
+        return response["data"]
+
+    # Yields Server-Sent Events from `path` as dicts with "id", "event" and
+    # "data", reconnecting with Last-Event-ID when the connection drops. With
+    # `callback`, calls it for each event instead, until it returns False.
+    # With `dedup`, events whose ID it has seen are skipped
+    def fetch_sse(self, path: str, callback: Callable[[Dict[str, Any]], Any] = None,
+                  dedup: IdempotencyTracker = None):
+        events = self._sse_events(path, dedup)
+        if callback is None:
+            return events
+        for event in events:
+            if callback(event) is False:
+                events.close()
+                break
+
+    def _sse_events(self, path: str, dedup: IdempotencyTracker = None):
+        stream = lib.rust_core_sse_open(self.rust_core, path.encode('utf-8'))
+        if stream == ffi.NULL:
+            raise _error(f"Failed to open event stream: {path}")
+        if dedup is not None:
+            lib.rust_core_sse_dedup(stream, dedup.tracker)
+        try:
+            while True:
+                result = lib.rust_core_sse_next(stream)
+                if result == ffi.NULL:
+                    raise _error(f"Event stream {path} failed")
+                event = json.loads(ffi.string(result).decode('utf-8'))
+                lib.rust_core_free(result)
+                if event is None:
+                    return
+                yield event
+        finally:
+            lib.rust_core_sse_close(stream)
+
+    # Follows every page of `path` and returns the items of all of them.
+    # `strategy` is "link" (Link: rel="next" headers), "cursor" (with `param`
+    # and a JSON pointer `cursor` to the next cursor in the body) or "offset"
+    # (with `limit`); `items` points at the array of items on each page. With
+    # `dedup`, items whose ID at JSON pointer `id_pointer` it has seen are left
+    # out, so polling a feed again only returns what's new
+    def fetch_paginated(self, path: str, strategy: str = "link", items: str = None, dedup: IdempotencyTracker = None,
+                        id_pointer: str = "/id", **options) -> List[Any]:
+        config = {"type": strategy, **options}
+        if items is not None:
+            config["items"] = items
+        if dedup is not None:
+            result = lib.rust_core_fetch_paginated_dedup(self.rust_core, path.encode('utf-8'), json.dumps(config).encode('utf-8'),
+                                                         dedup.tracker, id_pointer.encode('utf-8'))
+        else:
+            result = lib.rust_core_fetch_paginated(self.rust_core, path.encode('utf-8'), json.dumps(config).encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"Failed to fetch pages of {path}")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # At most `burst` requests at once (by default one second's worth) and
+    # requests_per_second on average; None removes the limit
+    def set_rate_limit(self, requests_per_second: Optional[float], burst: int = None):
+        if requests_per_second is None:
+            lib.rust_core_set_rate_limit(self.rust_core, 0.0, 0)
+            return
+        if burst is None:
+            burst = max(1, int(requests_per_second))
+        lib.rust_core_set_rate_limit(self.rust_core, float(requests_per_second), burst)
+
+    # "aggressive", "conservative", "read-heavy" or a profile from load_profiles,
+    # for the whole client or the requests matching `route`, e.g. "GET /reports/*"
+    def use_profile(self, name: str, route: str = None):
+        if lib.rust_core_set_profile(self.rust_core, name.encode('utf-8'), (route or "").encode('utf-8')) != 0:
+            raise _error(f"Failed to use policy profile: {name}")
+
+    def load_profiles(self, path: str):
+        if lib.rust_core_load_profiles(self.rust_core, path.encode('utf-8')) != 0:
+            raise _error(f"Failed to load policy profiles: {path}")
+
+    # `fields` is a dict or a list of (name, value) pairs; lists may repeat names
+    def send_form(self, path: str, method: str = "POST", fields: Any = None) -> Dict[str, Any]:
+        pairs = list(fields.items()) if isinstance(fields, dict) else list(fields or [])
+        pairs = [[str(name), str(value)] for name, value in pairs]
+        result = lib.rust_core_send_form(self.rust_core, path.encode('utf-8'), _method(method), json.dumps(pairs).encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"{method} {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # `files` maps field names to a file path, or to a (filename, bytes) or
+    # (filename, bytes, content_type) tuple
+    def send_multipart(self, path: str, fields: Dict[str, str] = None, files: Dict[str, Any] = None) -> Dict[str, Any]:
+        parts = [{"name": name, "value": str(value)} for name, value in (fields or {}).items()]
+        for name, file in (files or {}).items():
+            if isinstance(file, str):
+                parts.append({"name": name, "path": file})
+                continue
+            part = {"name": name, "filename": file[0], "base64": base64.b64encode(file[1]).decode('ascii')}
+            if len(file) > 2:
+                part["content_type"] = file[2]
+            parts.append(part)
+        result = lib.rust_core_send_multipart(self.rust_core, path.encode('utf-8'), json.dumps(parts).encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"Upload to {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # Compressed responses that would expand past max_bytes, or by more than
+    # max_ratio times their received size, fail; None removes a limit
+    def set_decompression_limits(self, max_bytes: Optional[int] = 100 * 1024 * 1024, max_ratio: Optional[int] = 200):
+        lib.rust_core_set_decompression_limits(self.rust_core, max_bytes or 0, max_ratio or 0)
+
+    # Unary gRPC-Web call; `message` is the serialized protobuf request, e.g.
+    # from `request.SerializeToString()`, and the serialized response is returned
+    def grpc_web_unary(self, method: str, message: bytes) -> bytes:
+        result = lib.rust_core_grpc_web(self.rust_core, method.encode('utf-8'), base64.b64encode(message))
+        if result == ffi.NULL:
+            raise _error(f"gRPC-Web call {method} failed")
+        encoded = ffi.string(result)
+        lib.rust_core_free(result)
+        return base64.b64decode(encoded)
+
+    # `progress(downloaded, total)` is called as chunks arrive; total is None when
+    # the server didn't send a Content-Length
+    def download_to_file(self, path: str, dest: str, progress: Callable[[int, Optional[int]], None] = None) -> int:
+        callback = ffi.NULL
+        if progress is not None:
+            @ffi.callback("void(uint64_t, int64_t)")
+            def callback(downloaded, total):
+                progress(downloaded, None if total < 0 else total)
+        written = lib.rust_core_download(self.rust_core, path.encode('utf-8'), dest.encode('utf-8'), callback)
+        if written < 0:
+            raise _error(f"Download of {path} failed")
+        return written
+
+    # Follows Link rel="next" headers from path, writing each page to dest_dir
+    # and a manifest.json listing them at the end
+    def export(self, path: str, dest_dir: str) -> Dict[str, Any]:
+        result = lib.rust_core_export(self.rust_core, path.encode('utf-8'), dest_dir.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"Export of {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # GET responses are kept in dir and served from there while offline or when
+    # the network fails
+    def enable_disk_cache(self, dir: str, max_bytes: int = 50 * 1024 * 1024):
+        if lib.rust_core_enable_cache(self.rust_core, dir.encode('utf-8'), max_bytes) != 0:
+            raise _error(f"Failed to open cache directory: {dir}")
+
+    def clear_disk_cache(self):
+        if lib.rust_core_cache_clear(self.rust_core) != 0:
+            raise _error("Failed to clear the disk cache")
+
+    # Client credentials grant; the token is fetched on first use and refreshed
+    # when it expires or the API answers 401
+    def set_oauth2(self, token_url: str, client_id: str, client_secret: str, scopes: List[str] = None):
+        lib.rust_core_set_oauth2(self.rust_core, token_url.encode('utf-8'), client_id.encode('utf-8'),
+                                 client_secret.encode('utf-8'), " ".join(scopes or []).encode('utf-8'))
+
+    def set_default_header(self, name: str, value: str):
+        if lib.rust_core_set_default_header(self.rust_core, name.encode('utf-8'), value.encode('utf-8')) != 0:
+            raise _error(f"Invalid header: {name}")
+
+    # One result per item of a 207 Multi-Status answer, each with its own status
+    def send_multi_status(self, path: str, method: str, data: Dict[str, Any] = None) -> List[Dict[str, Any]]:
+        if data is not None:
+            data = self._apply_middleware(data, method, path)
+        result = lib.rust_core_send_multi_status(self.rust_core, path.encode('utf-8'), _method(method),
+                                                 json.dumps(data).encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"{method} {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # WebDAV requests like PROPFIND and REPORT; depth is "0", "1" or "infinity"
+    def dav(self, method: str, path: str, xml: str, depth: str = None) -> List[Dict[str, Any]]:
+        result = lib.rust_core_dav(self.rust_core, method.encode('utf-8'), path.encode('utf-8'),
+                                   depth.encode('utf-8') if depth is not None else ffi.NULL, xml.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"{method} {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # `deadline` caps a call's total time including retries, `backoff_budget` the
+    # time spent waiting between them; both in seconds
+    def set_retry_policy(self, max_attempts: int = 4, base_delay: float = 1.0, max_delay: float = 60.0, jitter: float = 0.0,
+                         retry_non_idempotent: bool = False, deadline: float = None, backoff_budget: float = None):
+        lib.rust_core_set_retry_policy(self.rust_core, max_attempts, int(base_delay * 1000), int(max_delay * 1000), jitter,
+                                       retry_non_idempotent)
+        lib.rust_core_set_retry_limits(self.rust_core, int((deadline or 0) * 1000), int((backoff_budget or 0) * 1000))
+
+    def add_default_query(self, name: str, value: str):
+        lib.rust_core_add_default_query(self.rust_core, name.encode('utf-8'), value.encode('utf-8'))
+
+    def pending(self) -> Dict[str, Any]:
+        result = lib.rust_core_pending(self.rust_core)
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def set_quota_store(self, path: str):
+        if lib.rust_core_set_quota_store(self.rust_core, path.encode('utf-8')) != 0:
+            raise _error(f"Failed to open quota store: {path}")
+
+    def quota_state(self) -> Dict[str, Dict[str, Any]]:
+        result = lib.rust_core_quota_state(self.rust_core)
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def set_regions(self, regions: Dict[str, str], probe_path: str = "/", interval: float = 30.0):
+        names = ",".join(regions.keys())
+        base_urls = ",".join(regions.values())
+        if lib.rust_core_set_regions(self.rust_core, names.encode('utf-8'), base_urls.encode('utf-8'), probe_path.encode('utf-8'),
+                                     int(interval * 1000)) != 0:
+            raise _error("Failed to set regions")
+
+    def pin_region(self, name: str = None):
+        if lib.rust_core_pin_region(self.rust_core, (name or "").encode('utf-8')) != 0:
+            raise _error(f"Unknown region: {name}")
+
+    def region_status(self) -> List[Dict[str, Any]]:
+        result = lib.rust_core_region_status(self.rust_core)
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def start_capture(self, duration: float = 0.0, count: int = 0):
+        if not duration and not count:
+            raise ValueError("start_capture needs a duration or a count")
+        lib.rust_core_start_capture(self.rust_core, int(duration * 1000), count)
+
+    def support_bundle(self, stop: bool = False) -> Dict[str, Any]:
+        result = lib.rust_core_support_bundle(self.rust_core, stop)
+        if result == ffi.NULL:
+            raise RuntimeError("No capture was started")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def set_error_translator(self, translator: Callable[[int, str], str]):
+        # `translator(status, code)` returns "retryable", "auth", "validation", "quota" or None
+        categories = ["retryable", "auth", "validation", "quota"]
+
+        @ffi.callback("int(uint16_t, const char*)")
+        def callback(status, code):
+            code = None if code == ffi.NULL else ffi.string(code).decode('utf-8')
+            category = translator(status, code)
+            return categories.index(category) if category in categories else -1
+        # Keep the callback alive for as long as the core can call it
+        self._error_translator_callback = callback
+        lib.rust_core_set_error_translator(self.rust_core, callback)
+
+    def set_deprecation_monitoring(self, enabled: bool):
+        lib.rust_core_set_deprecation_monitoring(self.rust_core, enabled)
+
+    def set_api_version(self, version: str, strategy: str = "path_prefix", name: str = ""):
+        if lib.rust_core_set_api_version(self.rust_core, strategy.encode('utf-8'), name.encode('utf-8'), version.encode('utf-8')) != 0:
+            raise _error(f"Invalid API version strategy: {strategy} {name}")
+
+    def add_traffic_split(self, path: str, base_url: str, percent: float = 0.0, tenant_header: str = None, tenants: list = None):
+        tenant_list = ",".join(tenants or []).encode('utf-8')
+        if lib.rust_core_add_traffic_split(self.rust_core, path.encode('utf-8'), base_url.encode('utf-8'), percent, (tenant_header or "").encode('utf-8'), tenant_list) != 0:
+            raise _error(f"Invalid tenant header: {tenant_header}")
+
+    def set_egress_policy(self, hosts: list = None, cidrs: list = None, block_private: bool = True):
+        host_list = ",".join(hosts or []).encode('utf-8')
+        cidr_list = ",".join(cidrs or []).encode('utf-8')
+        if lib.rust_core_set_egress_policy(self.rust_core, host_list, cidr_list, block_private) != 0:
+            raise _error(f"Invalid egress policy: {cidrs}")
+
+    def set_key_transform(self, outgoing: str = None, incoming: str = None):
+        if lib.rust_core_set_key_transform(self.rust_core, (outgoing or "").encode('utf-8'), (incoming or "").encode('utf-8')) != 0:
+            raise _error(f"Unknown key case: {outgoing!r} / {incoming!r}")
+
+    def set_locale(self, languages: list, fallback: list = None):
+        lib.rust_core_set_locale(self.rust_core, ",".join(languages).encode('utf-8'), ",".join(fallback or []).encode('utf-8'))
+
+    def shutdown(self, timeout: float = 30.0) -> bool:
+        return lib.rust_core_shutdown(self.rust_core, int(timeout * 1000)) == 0
+
+    def notify_connectivity(self, online: bool):
+        lib.rust_core_notify_connectivity(self.rust_core, online)
+
+    def enable_offline_queue(self, journal_path: str, on_failure: Callable[[Dict[str, Any], str], None] = None) -> int:
+        if on_failure is not None:
+            @ffi.callback("void(const char*, const char*)")
+            def callback(item, error):
+                on_failure(json.loads(ffi.string(item).decode('utf-8')), ffi.string(error).decode('utf-8'))
+            # Keep the callback alive for as long as the core can call it
+            self._queue_failure_callback = callback
+            lib.rust_core_set_queue_failure_callback(self.rust_core, callback)
+        delivered = lib.rust_core_enable_offline_queue(self.rust_core, journal_path.encode('utf-8'))
+        if delivered < 0:
+            raise _error(f"Failed to open offline queue journal: {journal_path}")
+        return delivered
+
+    def enqueue(self, path: str, method: str, data: Dict[str, Any]):
+        json_data = json.dumps(data)
+        if lib.rust_core_enqueue(self.rust_core, path.encode('utf-8'), _method(method), json_data.encode('utf-8')) != 0:
+            raise _error("Offline queue is not enabled")
+
+    def flush_queue(self) -> int:
+        return lib.rust_core_flush_queue(self.rust_core)
+
+    def load_script(self, path: str):
+        if lib.rust_core_load_script(self.rust_core, path.encode('utf-8')) != 0:
+            raise _error(f"Failed to load middleware script: {path}")
+
+    def reload_scripts(self):
+        if lib.rust_core_reload_scripts(self.rust_core) != 0:
+            raise _error("Failed to reload middleware scripts")
+
+    def load_plugin(self, path: str):
+        if lib.rust_core_load_plugin(self.rust_core, path.encode('utf-8')) != 0:
+            raise _error(f"Failed to load WASM plugin: {path}")
+
+    # Opens a WebSocket with this client's auth and headers; reconnects with
+    # the retry policy's backoff if the connection drops
+    def connect_ws(self, path: str) -> WebSocket:
+        socket = lib.rust_core_ws_connect(self.rust_core, path.encode('utf-8'))
+        if socket == ffi.NULL:
+            raise _error(f"Failed to open WebSocket: {path}")
+        return WebSocket(self, socket)
+
+    # Lower order runs first, ties in registration order; a name registered
+    # again replaces the earlier middleware. A route like "/admin/*" or
+    # "DELETE /users/*" limits it to matching requests
+    def add_middleware(self, middleware: Callable[[Dict[str, Any]], Dict[str, Any]], name: str = None, order: int = 0,
+                       route: str = None):
+        if name is not None:
+            self.remove_middleware(name)
+        self.middleware.append((order, next(self._middleware_seq), name, route, middleware))
+        self.middleware.sort(key=lambda entry: entry[:2])
+
+    def remove_middleware(self, name: str) -> bool:
+        before = len(self.middleware)
+        self.middleware = [entry for entry in self.middleware if entry[2] != name]
+        return len(self.middleware) != before
 
     def route(self, path: str):
         def decorator(func: Callable):
@@ -60,41 +896,135 @@ class API:
             return wrapper
         return decorator
 
-    def fetch(self, path: str, cache_ttl: int = None) -> Dict[str, Any]:
+    # `timeout` and `connect_timeout` replace the client's for each attempt;
+    # `deadline` limits the whole call, retries included
+    def fetch(self, path: str, cache_ttl: int = None, params: Dict[str, Any] = None,
+              headers: Dict[str, str] = None, cancel: CancellationToken = None, timeout: float = None,
+              connect_timeout: float = None, deadline: float = None) -> Dict[str, Any]:
         cache_key = f"GET:{path}"
+        if params:
+            cache_key += f":{json.dumps(params, sort_keys=True)}"
+        if headers:
+            cache_key += f":{json.dumps(headers, sort_keys=True)}"
         cached_data = self.cache.get(cache_key)
         if cached_data:
             return cached_data
 
-        result = lib.rust_core_fetch(self.rust_core, path.encode('utf-8'))
+        options = _request_options(params, headers, timeout, connect_timeout, deadline)
+        limited = timeout is not None or connect_timeout is not None or deadline is not None
+        if cancel is not None:
+            result = lib.rust_core_fetch_cancellable(self.rust_core, path.encode('utf-8'), options.encode('utf-8'), cancel.token)
+        elif params or headers or limited:
+            result = lib.rust_core_fetch_with_options(self.rust_core, path.encode('utf-8'), options.encode('utf-8'))
+        else:
+            result = lib.rust_core_fetch(self.rust_core, path.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"GET {path} failed")
         json_str = ffi.string(result).decode('utf-8')
         lib.rust_core_free(result)
         data = json.loads(json_str)
-        data = self._apply_middleware(data)
+        data = self._apply_middleware(data, "GET", path)
 
         if cache_ttl is not None:
             self.cache.put(cache_key, data, cache_ttl)
 
         return data
 
-    def send(self, path: str, method: str, data: Dict[str, Any]) -> Dict[str, Any]:
-        data = self._apply_middleware(data)
+    def memoized(self, path: str) -> Dict[str, Any]:
+        result = lib.rust_core_memoized(self.rust_core, path.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"Failed to fetch {path}")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def invalidate_memo(self, path: str = None):
+        lib.rust_core_invalidate_memo(self.rust_core, path.encode('utf-8') if path else ffi.NULL)
+
+    def head(self, path: str) -> Dict[str, Any]:
+        result = lib.rust_core_head(self.rust_core, path.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"HEAD {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def size_stats(self) -> Dict[str, Any]:
+        result = lib.rust_core_size_stats(self.rust_core)
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    def exists(self, path: str) -> bool:
+        result = lib.rust_core_exists(self.rust_core, path.encode('utf-8'))
+        if result < 0:
+            raise _error(f"HEAD {path} {_ERROR_KINDS.get(result, 'failed')}")
+        return result == 1
+
+    def send(self, path: str, method: str, data: Dict[str, Any] = None, params: Dict[str, Any] = None,
+             headers: Dict[str, str] = None, cancel: CancellationToken = None, timeout: float = None,
+             connect_timeout: float = None, deadline: float = None) -> Any:
+        # GET, DELETE, HEAD and OPTIONS go out without a body when data is None
+        if data is not None:
+            data = self._apply_middleware(data, method, path)
+        json_data = json.dumps(data)
+        options = _request_options(params, headers, timeout, connect_timeout, deadline)
+        limited = timeout is not None or connect_timeout is not None or deadline is not None
+        if cancel is not None:
+            result = lib.rust_core_send_cancellable(self.rust_core, path.encode('utf-8'), _method(method), json_data.encode('utf-8'),
+                                                    options.encode('utf-8'), cancel.token)
+        elif params or headers or limited:
+            result = lib.rust_core_send_with_options(self.rust_core, path.encode('utf-8'), _method(method), json_data.encode('utf-8'),
+                                                     options.encode('utf-8'))
+        else:
+            result = lib.rust_core_send(self.rust_core, path.encode('utf-8'), _method(method), json_data.encode('utf-8'))
+        if result == ffi.NULL:
+            raise _error(f"{method} {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        return json.loads(json_str)
+
+    # Polls every `interval` seconds, unless a pending response asks for another
+    # wait with X-Poll-Interval or Retry-After
+    def send_and_follow(self, path: str, method: str, data: Dict[str, Any], interval: float = 1.0, timeout: float = 60.0) -> Any:
+        data = self._apply_middleware(data, method, path)
         json_data = json.dumps(data)
-        result = lib.rust_core_send(self.rust_core, path.encode('utf-8'), method.encode('utf-8'), json_data.encode('utf-8'))
+        result = lib.rust_core_send_and_follow(self.rust_core, path.encode('utf-8'), _method(method), json_data.encode('utf-8'),
+                                               int(interval * 1000), int(timeout * 1000))
+        if result == ffi.NULL:
+            raise _error(f"{method} {path} failed")
         json_str = ffi.string(result).decode('utf-8')
         lib.rust_core_free(result)
         return json.loads(json_str)
 
-    def _apply_middleware(self, data: Dict[str, Any]) -> Dict[str, Any]:
-        for middleware in self.middleware:
-            data = middleware(data)
+    # With strict=True, raises AggregateError if any item failed
+    def bulk(self, path: str, items: List[Dict[str, Any]], chunk_size: int = 500, method: str = "POST", concurrency: int = 4,
+             strict: bool = False) -> Dict[str, Any]:
+        json_items = json.dumps(items)
+        result = lib.rust_core_bulk(self.rust_core, path.encode('utf-8'), _method(method), json_items.encode('utf-8'), chunk_size, concurrency)
+        if result == ffi.NULL:
+            raise _error(f"Bulk {method} {path} failed")
+        json_str = ffi.string(result).decode('utf-8')
+        lib.rust_core_free(result)
+        report = json.loads(json_str)
+        failed = [(item["index"], item["error"]) for item in report["items"] if not item["ok"]]
+        if strict and failed:
+            succeeded = [(item["index"], item["response"]) for item in report["items"] if item["ok"]]
+            raise AggregateError(failed, succeeded)
+        return report
+
+    def _apply_middleware(self, data: Dict[str, Any], method: str, path: str) -> Dict[str, Any]:
+        for _, _, _, route, middleware in self.middleware:
+            if route is None or _route_matches(route, method, path):
+                data = middleware(data)
         return data
 
     def invalidate_cache(self, path: str = None):
         if path:
             cache_key = f"GET:{path}"
-            if cache_key in self.cache.cache:
-                del self.cache.cache[cache_key]
+            # Also drops entries fetched with params.
+            for key in [k for k in self.cache.cache if k == cache_key or k.startswith(cache_key + ":")]:
+                del self.cache.cache[key]
         else:
             self.cache.cache.clear()
 
@@ -125,10 +1055,19 @@ def put(func: Callable) -> Callable:
         return self.send(formatted_path, "PUT", data)
     return wrapper
 
+def patch(func: Callable) -> Callable:
+    @functools.wraps(func)
+    def wrapper(self, *args, **kwargs):
+        path = self.route.__closure__[0].cell_contents
+        data = func(*args, **kwargs)
+        formatted_path = path.format(*args, **kwargs)
+        return self.send(formatted_path, "PATCH", data)
+    return wrapper
+
 def delete(func: Callable) -> Callable:
     @functools.wraps(func)
     def wrapper(self, *args, **kwargs):
         path = self.route.__closure__[0].cell_contents
         formatted_path = path.format(*args, **kwargs)
-        return self.fetch(formatted_path)
+        return self.send(formatted_path, "DELETE")
     return wrapper
@@ -0,0 +1,63 @@
+use crate::ApiError;
+use std::fmt;
+
+// A batch where some items failed. Keeps every failure and every success, each
+// with its position in the input, instead of just the first error.
+#[derive(Debug)]
+pub struct AggregateError<T> {
+    succeeded: Vec<(usize, T)>,
+    failed: Vec<(usize, ApiError)>,
+}
+
+impl<T> AggregateError<T> {
+    // All the values in input order, or an `AggregateError` if any failed.
+    pub fn collect<I: IntoIterator<Item = Result<T, ApiError>>>(results: I) -> Result<Vec<T>, Self> {
+        let mut succeeded = Vec::new();
+        let mut failed = Vec::new();
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(value) => succeeded.push((index, value)),
+                Err(error) => failed.push((index, error)),
+            }
+        }
+        if failed.is_empty() {
+            return Ok(succeeded.into_iter().map(|(_, value)| value).collect());
+        }
+        Err(AggregateError { succeeded, failed })
+    }
+
+    pub fn succeeded(&self) -> &[(usize, T)] {
+        &self.succeeded
+    }
+
+    pub fn failed(&self) -> &[(usize, ApiError)] {
+        &self.failed
+    }
+
+    // Number of items in the batch.
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    // The successes, for callers that carry on with what worked.
+    pub fn into_succeeded(self) -> Vec<(usize, T)> {
+        self.succeeded
+    }
+}
+
+impl<T> fmt::Display for AggregateError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} items failed", self.failed.len(), self.total())?;
+        if let Some((index, error)) = self.failed.first() {
+            write!(f, "; item {}: {}", index, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for AggregateError<T> {
+    // The first failure.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.failed.first().map(|(_, error)| error as &(dyn std::error::Error + 'static))
+    }
+}
@@ -0,0 +1,188 @@
+use crate::pointer;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const MASK: &str = "***";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldAction {
+    // Replace the value with `***`.
+    Mask,
+    // Replace the value with a salted SHA-256 digest, so equal inputs stay
+    // correlatable across a capture without being recoverable.
+    Hash,
+    // Drop the field entirely.
+    Remove,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    // Object key (case-insensitive) anywhere in the document, or header name.
+    Key(String),
+    // JSON pointer, `*` matching any single segment.
+    Path(String),
+}
+
+// Scrubs configured fields from captured traffic (URLs, headers and bodies of
+// `RustCore::start_anonymized_capture`) before it leaves the security boundary.
+#[derive(Clone)]
+pub struct Anonymizer {
+    rules: Vec<(Matcher, FieldAction)>,
+    salt: Vec<u8>,
+}
+
+impl std::fmt::Debug for Anonymizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Anonymizer").field("rules", &self.rules).field("salt", &MASK).finish()
+    }
+}
+
+impl Anonymizer {
+    // `salt` keys the `Hash` digests; without a secret one, hashed emails and
+    // the like can be recovered by hashing guesses.
+    pub fn new(salt: &[u8]) -> Self {
+        Anonymizer {
+            rules: Vec::new(),
+            salt: salt.to_vec(),
+        }
+    }
+
+    // With a random salt, for when hashes only need to match within this
+    // anonymizer's lifetime.
+    pub fn with_random_salt() -> Result<Self, getrandom::Error> {
+        let mut salt = [0u8; 32];
+        getrandom::getrandom(&mut salt)?;
+        Ok(Self::new(&salt))
+    }
+
+    pub fn field(mut self, key: &str, action: FieldAction) -> Self {
+        self.rules.push((Matcher::Key(key.to_ascii_lowercase()), action));
+        self
+    }
+
+    pub fn path(mut self, path: &str, action: FieldAction) -> Self {
+        self.rules.push((Matcher::Path(path.to_string()), action));
+        self
+    }
+
+    pub fn anonymize(&self, value: &Value) -> Value {
+        let mut value = value.clone();
+        self.apply(&mut value);
+        value
+    }
+
+    pub fn apply(&self, value: &mut Value) {
+        self.apply_at(&mut Vec::new(), value);
+    }
+
+    // Header names are matched against `field` rules; `Remove` drops the header.
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let names: Vec<_> = headers.keys().cloned().collect();
+        for name in names {
+            let action = self.rules.iter().find_map(|(matcher, action)| match matcher {
+                Matcher::Key(key) if key == name.as_str() => Some(*action),
+                _ => None,
+            });
+            match action {
+                Some(FieldAction::Remove) => {
+                    headers.remove(&name);
+                }
+                Some(FieldAction::Mask) => {
+                    headers.insert(&name, HeaderValue::from_static(MASK));
+                }
+                Some(FieldAction::Hash) => {
+                    let hashed = headers
+                        .get(&name)
+                        .map(|v| self.hash(v.as_bytes()))
+                        .and_then(|h| HeaderValue::from_str(&h).ok());
+                    if let Some(hashed) = hashed {
+                        headers.insert(&name, hashed);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn action_for(&self, path: &[String]) -> Option<FieldAction> {
+        self.rules.iter().find_map(|(matcher, action)| {
+            let matched = match matcher {
+                Matcher::Key(key) => path.last().is_some_and(|last| last.to_ascii_lowercase() == *key),
+                Matcher::Path(pattern) => pointer::matches(pattern, path),
+            };
+            matched.then_some(*action)
+        })
+    }
+
+    fn apply_at(&self, path: &mut Vec<String>, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                let keys: Vec<String> = map.keys().cloned().collect();
+                for key in keys {
+                    path.push(key.clone());
+                    match self.action_for(path) {
+                        Some(FieldAction::Remove) => {
+                            map.remove(&key);
+                        }
+                        Some(action) => {
+                            if let Some(field) = map.get_mut(&key) {
+                                *field = self.replace(field, action);
+                            }
+                        }
+                        None => {
+                            if let Some(field) = map.get_mut(&key) {
+                                self.apply_at(path, field);
+                            }
+                        }
+                    }
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                let mut index = 0;
+                items.retain_mut(|item| {
+                    path.push(index.to_string());
+                    index += 1;
+                    let keep = match self.action_for(path) {
+                        Some(FieldAction::Remove) => false,
+                        Some(action) => {
+                            *item = self.replace(item, action);
+                            true
+                        }
+                        None => {
+                            self.apply_at(path, item);
+                            true
+                        }
+                    };
+                    path.pop();
+                    keep
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn replace(&self, value: &Value, action: FieldAction) -> Value {
+        match action {
+            FieldAction::Mask => Value::String(MASK.to_string()),
+            FieldAction::Hash => {
+                let bytes = match value {
+                    Value::String(s) => s.as_bytes().to_vec(),
+                    other => other.to_string().into_bytes(),
+                };
+                Value::String(self.hash(&bytes))
+            }
+            FieldAction::Remove => Value::Null,
+        }
+    }
+
+    fn hash(&self, input: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(input);
+        let digest = hasher.finalize();
+        let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        format!("sha256:{}", hex)
+    }
+}
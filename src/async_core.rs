@@ -0,0 +1,398 @@
+#[cfg(feature = "revocation")]
+use crate::revocation;
+use crate::error::{ApiError, TimeoutError};
+use crate::report::{self, Attempt, RetryError};
+use crate::size_stats::TransferSize;
+use crate::typed_headers::TypedHeaders;
+use crate::{egress, encoding, http_cache, retry_stats, ApiResponse, Auth, Method, Query, RequestOptions, RetryPolicy, RustCore};
+use reqwest::blocking::Request;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Async counterpart of `RustCore` for tokio-based services. Calls go through
+// the wrapped core's pipeline (auth, middleware, versioning, routing, signing,
+// retries, response verification and middleware) but are sent on reqwest's
+// async client, so no thread blocks while a request is in flight.
+//
+// Not available on this path: the offline queue, request scopes, the 406
+// locale fallback, streaming request bodies, response bodies in anonymized
+// captures, and the cancellation tokens, connect timeouts and deadlines of
+// `RequestOptions`. Compressed response bodies are received in full before
+// they're decoded rather than decoded as they arrive; the decompression
+// limits still cap what they expand to.
+pub struct AsyncRustCore {
+    core: Arc<RustCore>,
+    client: reqwest::Client,
+}
+
+impl AsyncRustCore {
+    // Configure `core` first; its settings are fixed from here on.
+    pub fn new(core: RustCore) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = core.client_config.nonblocking();
+        if let Some(egress) = &core.egress {
+            builder = builder.redirect(egress.redirect_policy(core.client_config.max_redirects));
+        }
+        #[cfg(feature = "revocation")]
+        if let Some(policy) = core.revocation {
+            builder = builder.use_preconfigured_tls(revocation::tls_config(policy));
+        }
+        Ok(AsyncRustCore {
+            core: Arc::new(core),
+            client: builder.build()?,
+        })
+    }
+
+    // For the blocking methods and stats, which share state with this client.
+    pub fn core(&self) -> &RustCore {
+        &self.core
+    }
+
+    pub async fn fetch(&self, path: &str) -> Result<Value, ApiError> {
+        self.fetch_url(&format!("{}{}", self.core.base_url, path)).await
+    }
+
+    pub async fn fetch_with_query(&self, path: &str, query: &Query) -> Result<Value, ApiError> {
+        self.fetch_with(path, &RequestOptions::new().query(query.clone())).await
+    }
+
+    pub async fn fetch_with(&self, path: &str, options: &RequestOptions) -> Result<Value, ApiError> {
+        let url = format!("{}{}", self.core.base_url, path);
+        self.refresh_token().await?;
+        let request = self.core.build_request(options.apply(self.core.client.get(&url)))?;
+        self.execute_get(request).await
+    }
+
+    pub async fn fetch_url(&self, url: &str) -> Result<Value, ApiError> {
+        self.refresh_token().await?;
+        let request = self.core.build_request(self.core.client.get(url))?;
+        self.execute_get(request).await
+    }
+
+    pub async fn fetch_as<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        let json = self.fetch(path).await?;
+        Ok(self.core.typed(json)?)
+    }
+
+    pub async fn send_as<T: DeserializeOwned, B: Serialize + ?Sized>(&self, path: &str, method: Method, data: &B) -> Result<T, ApiError> {
+        let data = serde_json::to_value(data).map_err(|e| ApiError::Other(e.into()))?;
+        let json = self.send(path, method, data).await?;
+        Ok(self.core.typed(json)?)
+    }
+
+    pub async fn send(&self, path: &str, method: Method, data: Value) -> Result<Value, ApiError> {
+        self.send_with_query(path, method, data, &Query::new()).await
+    }
+
+    pub async fn send_with_query(&self, path: &str, method: Method, data: Value, query: &Query) -> Result<Value, ApiError> {
+        self.send_with(path, method, data, &RequestOptions::new().query(query.clone())).await
+    }
+
+    pub async fn send_with(&self, path: &str, method: Method, data: Value, options: &RequestOptions) -> Result<Value, ApiError> {
+        let request = options.apply(self.core.with_body(path, method, data)?);
+        self.refresh_token().await?;
+        let request = self.core.build_request(request)?;
+        Ok(self.execute_json(request).await?)
+    }
+
+    // Building a request attaches the OAuth2 token synchronously; fetch it
+    // here first so that never blocks.
+    async fn refresh_token(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.core.auth().as_deref() {
+            Some(Auth::OAuth2(oauth)) => oauth.refresh(&self.client, self.core.clock.now()).await,
+            _ => Ok(()),
+        }
+    }
+
+    // As on the blocking client, a 401 under OAuth2 is retried once with a
+    // fresh token.
+    async fn send_authorized(&self, request: Request, endpoint: &str, body_decoded: u64) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let auth = self.core.auth();
+        let Some(Auth::OAuth2(oauth)) = auth.as_deref() else {
+            return self.send_with_retry(request, endpoint, body_decoded).await;
+        };
+        let retry = request.try_clone();
+        match (self.send_with_retry(request, endpoint, body_decoded).await, retry) {
+            (Err(e), Some(mut retry)) if report::final_status(e.as_ref()) == Some(401) => {
+                oauth.invalidate();
+                self.refresh_token().await?;
+                self.core.apply_auth(&mut retry)?;
+                self.send_with_retry(retry, endpoint, body_decoded).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    // Through the profile's cache and the HTTP cache, as on the blocking client.
+    async fn execute_get(&self, request: Request) -> Result<Value, ApiError> {
+        let core = &*self.core;
+        let Some(profile) = core.active_profile(&request) else {
+            return Ok(self.execute_cached(request).await?);
+        };
+        let url = request.url().to_string();
+        if let Some(body) = profile.cached(&url, core.clock.now()) {
+            return Ok(body);
+        }
+        let body = self.execute_cached(request).await?;
+        profile.store(&url, &body, core.clock.now());
+        Ok(body)
+    }
+
+    async fn execute_cached(&self, mut request: Request) -> Result<Value, Box<dyn std::error::Error>> {
+        let core = &*self.core;
+        let Some(cache) = &core.http_cache else {
+            return self.execute_json(request).await;
+        };
+        let url = request.url().to_string();
+        let cached = cache.lookup(&url);
+        if let Some(cached) = &cached {
+            if cached.is_fresh(core.clock.system_time()) {
+                return Ok(cached.body.clone());
+            }
+            http_cache::make_conditional(&mut request, cached);
+        }
+        let (endpoint, path, bodyless, response) = self.execute_response(request).await?;
+        match (response.status(), cached) {
+            (reqwest::StatusCode::NOT_MODIFIED, Some(cached)) => Ok(cache.refresh(&url, cached, response.headers(), core.clock.system_time())),
+            (reqwest::StatusCode::OK, _) => {
+                let headers = response.headers().clone();
+                let body = self.response_json(&endpoint, &path, bodyless, response)?;
+                cache.store_response(&url, &headers, body.clone(), core.clock.system_time());
+                Ok(body)
+            }
+            (_, _) => self.response_json(&endpoint, &path, bodyless, response),
+        }
+    }
+
+    async fn execute_json(&self, request: Request) -> Result<Value, Box<dyn std::error::Error>> {
+        let (endpoint, path, bodyless, response) = self.execute_response(request).await?;
+        self.response_json(&endpoint, &path, bodyless, response)
+    }
+
+    // Sends `request` and reads its body. Returns the endpoint, the path and
+    // whether the response has no body to parse, along with the response.
+    async fn execute_response(&self, mut request: Request) -> Result<(String, String, bool, ApiResponse), Box<dyn std::error::Error>> {
+        let core = &*self.core;
+        let _in_flight = core.lifecycle.enter()?;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let path = request.url().path().to_string();
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        // A successful unsafe request makes the cached response for its URL stale.
+        let invalidates = (!request.method().is_safe()).then(|| request.url().to_string());
+        let requested = match (&core.version, &core.version_hook) {
+            (Some(version), Some(_)) => version.requested(&request, &core.base_url),
+            _ => None,
+        };
+        let bodyless = request.method() == reqwest::Method::HEAD;
+        let body_decoded = core.prepare(&mut request)?;
+        let response = self.send_authorized(request, &endpoint, body_decoded).await?;
+        core.inspect_response(&endpoint, &host, requested, response.headers());
+        if let (Some(cache), Some(url)) = (&core.http_cache, &invalidates) {
+            cache.invalidate(url);
+        }
+        let (status, url) = (response.status(), response.url().clone());
+        let headers = response.headers().clone();
+        let bodyless = bodyless || matches!(status, reqwest::StatusCode::NO_CONTENT | reqwest::StatusCode::NOT_MODIFIED);
+        let bytes = response.bytes().await?;
+        let body = encoding::decode_body(&headers, &bytes, &core.decompression_limits)?;
+        let method = endpoint.split(' ').next().unwrap_or_default();
+        core.record_received(&endpoint, method, bytes.len() as u64, body.len() as u64);
+        Ok((endpoint, path, bodyless, ApiResponse::new(status, url, headers, body)))
+    }
+
+    // HEAD, 204 and 304 responses come back as null.
+    fn response_json(&self, endpoint: &str, path: &str, bodyless: bool, mut response: ApiResponse) -> Result<Value, Box<dyn std::error::Error>> {
+        if bodyless {
+            response.set_body(Vec::new());
+            self.core.intercept(endpoint, response)?;
+            return Ok(Value::Null);
+        }
+        self.core.decode_json(endpoint, path, response)
+    }
+
+    async fn send_with_retry(&self, request: Request, endpoint: &str, body_decoded: u64) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let core = &*self.core;
+        core.retry_stats.request(endpoint);
+        let started = core.clock.now();
+        let mut history = Vec::new();
+        let mut response_id = None;
+        // Owned across awaits: blocking requests are Send but not Sync.
+        let (request, result) = self.send_attempts(request, endpoint, body_decoded, &mut history, &mut response_id).await;
+        core.report_call(
+            &request,
+            started,
+            &history,
+            response_id,
+            result.as_ref().ok().map(|r| r.headers()),
+            result.as_ref().err().map(|e| e.as_ref()),
+        );
+        match result {
+            Ok(response) => Ok(response),
+            #[cfg(feature = "revocation")]
+            Err(error) if error.is::<crate::RevocationError>() => Err(error),
+            Err(error) => Err(RetryError { attempts: history, error }.into()),
+        }
+    }
+
+    // Same retry rules as the blocking client.
+    async fn send_attempts(
+        &self,
+        request: Request,
+        endpoint: &str,
+        body_decoded: u64,
+        history: &mut Vec<Attempt>,
+        response_id: &mut Option<String>,
+    ) -> (Request, Result<reqwest::Response, Box<dyn std::error::Error>>) {
+        let core = &*self.core;
+        let mut retries = 0;
+        let mut stale_retried = false;
+        let call_started = core.clock.now();
+        let mut backed_off = Duration::ZERO;
+        let policy = core.retry_policy_for(&request);
+        let result = loop {
+            *response_id = None;
+            self.wait(core.throttle_delay(&request)).await;
+            let (attempt_request, sent) = match self.attempt_request(&request, policy, body_decoded, call_started) {
+                Ok(attempt) => attempt,
+                Err(e) => break Err(e),
+            };
+            let host = attempt_request.url().host_str().unwrap_or_default().to_string();
+            if let Err(paused) = core.check_maintenance() {
+                break Err(paused.into());
+            }
+            if let Err(open) = core.check_breaker(&host) {
+                break Err(open.into());
+            }
+            let started = Instant::now();
+            let result = self.client.execute(attempt_request).await;
+            let elapsed = started.elapsed();
+            core.record_breaker(&host, result.as_ref().map(reqwest::Response::status));
+            let outcome = match &result {
+                Ok(response) => Ok((response.status(), response.headers())),
+                Err(e) => Err(e.to_string()),
+            };
+            history.push(core.record_attempt(endpoint, &request, sent, outcome, elapsed, response_id));
+            let call_elapsed = core.clock.now().saturating_duration_since(call_started);
+            match result {
+                Ok(response) if response.status().is_success() => break Ok(response),
+                Ok(response) if response.status() == reqwest::StatusCode::NOT_MODIFIED && http_cache::is_conditional(request.headers()) => {
+                    break Ok(response)
+                }
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let retry = policy.should_retry(retries + 1, request.method(), Some(status));
+                    let retry_after = response.headers().retry_after();
+                    let delay = policy.delay(retries + 1, Some(status), retry_after);
+                    let headers = response.headers().clone();
+                    let bytes = response.bytes().await.unwrap_or_default();
+                    let body = encoding::decode_body(&headers, &bytes, &core.decompression_limits).unwrap_or_default();
+                    let error = ApiError::http(status, String::from_utf8_lossy(&body).into_owned(), core.error_translator.as_ref());
+                    if let Some(paused) = core.observe_maintenance(&headers, &error, retry_after) {
+                        break Err(paused.into());
+                    }
+                    if retry {
+                        retries += 1;
+                        if let Some(limit) = policy.exhausted(call_elapsed, backed_off, delay) {
+                            break Err(limit.into());
+                        }
+                        if self.backoff(endpoint, &format!("status {}", status), delay).await {
+                            backed_off += delay;
+                            continue;
+                        }
+                    }
+                    break Err(error.into());
+                }
+                Err(e) => {
+                    #[cfg(feature = "revocation")]
+                    if let Some(revocation) = revocation::find(&e) {
+                        break Err(revocation.into());
+                    }
+                    if let Some(egress) = egress::find(&e) {
+                        break Err(egress.into());
+                    }
+                    let timeout = TimeoutError::from_transport(&e, call_elapsed, elapsed, policy.deadline_limit());
+                    if let Some(deadline @ TimeoutError::Deadline { .. }) = timeout {
+                        break Err(deadline.into());
+                    }
+                    if !stale_retried && request.method().is_idempotent() && retry_stats::is_stale_connection(&e) {
+                        stale_retried = true;
+                        core.retry_stats.retry(endpoint, "stale_connection", Duration::ZERO);
+                        continue;
+                    }
+                    if policy.should_retry(retries + 1, request.method(), None) {
+                        retries += 1;
+                        let delay = policy.delay(retries, None, None);
+                        if let Some(limit) = policy.exhausted(call_elapsed, backed_off, delay) {
+                            break Err(limit.into());
+                        }
+                        if self.backoff(endpoint, retry_stats::reason(&e), delay).await {
+                            backed_off += delay;
+                            continue;
+                        }
+                    }
+                    break Err(match timeout {
+                        Some(timeout) => timeout.into(),
+                        None => e.into(),
+                    });
+                }
+            }
+        };
+        (request, result)
+    }
+
+    fn attempt_request(
+        &self,
+        request: &Request,
+        policy: &RetryPolicy,
+        body_decoded: u64,
+        call_started: Instant,
+    ) -> Result<(reqwest::Request, TransferSize), Box<dyn std::error::Error>> {
+        let (mut request, sent) = self.core.attempt_request(request, body_decoded)?;
+        self.core.limit_to_deadline(policy, &mut request, call_started)?;
+        Ok((to_async(request)?, sent))
+    }
+
+    async fn wait(&self, delay: Duration) {
+        if delay.is_zero() {
+            return;
+        }
+        match &self.core.sleeper {
+            Some(sleeper) => sleeper.sleep(delay),
+            None => tokio::time::sleep(delay).await,
+        }
+    }
+
+    // Sleeps on the runtime's timer, cut short by the shutdown deadline.
+    async fn backoff(&self, endpoint: &str, reason: &str, delay: Duration) -> bool {
+        let core = &*self.core;
+        let started = core.clock.now();
+        match &core.sleeper {
+            Some(sleeper) => sleeper.sleep(delay),
+            None => {
+                let delay = core
+                    .lifecycle
+                    .cancel_at()
+                    .map_or(delay, |at| at.saturating_duration_since(Instant::now()).min(delay));
+                tokio::time::sleep(delay).await;
+            }
+        }
+        core.retry_stats.retry(endpoint, reason, core.clock.now().saturating_duration_since(started));
+        !core.lifecycle.cancelled()
+    }
+}
+
+// Requests are built with the blocking builder so middleware sees the same
+// type on both clients; bodies are always buffered by then.
+fn to_async(request: Request) -> Result<reqwest::Request, Box<dyn std::error::Error>> {
+    let mut out = reqwest::Request::new(request.method().clone(), request.url().clone());
+    *out.headers_mut() = request.headers().clone();
+    *out.timeout_mut() = request.timeout().copied();
+    *out.version_mut() = request.version();
+    if let Some(body) = request.body() {
+        let bytes = body.as_bytes().ok_or("Streaming request bodies aren't supported on the async client")?;
+        *out.body_mut() = Some(bytes.to_vec().into());
+    }
+    Ok(out)
+}
@@ -0,0 +1,161 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug)]
+pub struct BatchRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<Value>,
+}
+
+impl BatchRequest {
+    pub fn new(method: &str, path: &str) -> Self {
+        BatchRequest {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn get(path: &str) -> Self {
+        Self::new("GET", path)
+    }
+
+    pub fn delete(path: &str) -> Self {
+        Self::new("DELETE", path)
+    }
+
+    pub fn post(path: &str, body: Value) -> Self {
+        Self::new("POST", path).body(body)
+    }
+
+    pub fn put(path: &str, body: Value) -> Self {
+        Self::new("PUT", path).body(body)
+    }
+
+    pub fn patch(path: &str, body: Value) -> Self {
+        Self::new("PATCH", path).body(body)
+    }
+
+    pub fn body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+// One part of a `$batch` response. Bodies that aren't JSON come back as
+// `Value::String`; header names are lowercased.
+#[derive(Clone, Debug)]
+pub struct BatchResponse {
+    pub content_id: Option<String>,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+impl BatchResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+// Each sub-request becomes an `application/http` part numbered by Content-ID
+// from 1; `prefix` is the base URL's path, so request lines carry absolute paths.
+pub(crate) fn encode(requests: &[BatchRequest], prefix: &str, boundary: &str) -> Result<String, serde_json::Error> {
+    let mut out = String::new();
+    for (i, request) in requests.iter().enumerate() {
+        out.push_str(&format!("--{}\r\n", boundary));
+        out.push_str("Content-Type: application/http\r\n");
+        out.push_str("Content-Transfer-Encoding: binary\r\n");
+        out.push_str(&format!("Content-ID: {}\r\n\r\n", i + 1));
+        out.push_str(&format!("{} {}{} HTTP/1.1\r\n", request.method, prefix.trim_end_matches('/'), request.path));
+        for (name, value) in &request.headers {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        match &request.body {
+            Some(body) => {
+                let body = serde_json::to_string(body)?;
+                out.push_str("Content-Type: application/json\r\n\r\n");
+                out.push_str(&body);
+                out.push_str("\r\n");
+            }
+            None => out.push_str("\r\n"),
+        }
+    }
+    out.push_str(&format!("--{}--\r\n", boundary));
+    Ok(out)
+}
+
+pub(crate) fn boundary_of(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+// Parses a multipart/mixed batch response, flattening changesets (nested
+// multipart parts) in order.
+pub(crate) fn decode(body: &str, boundary: &str) -> Result<Vec<BatchResponse>, Box<dyn std::error::Error>> {
+    let delimiter = format!("--{}", boundary);
+    let mut responses = Vec::new();
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+        let part = part.strip_prefix("\r\n").or_else(|| part.strip_prefix('\n')).unwrap_or(part);
+        let part = part.strip_suffix("\r\n").or_else(|| part.strip_suffix('\n')).unwrap_or(part);
+        let (head, rest) = split_head(part);
+        let headers = parse_headers(head);
+        let content_type = headers.get("content-type").map(String::as_str).unwrap_or_default();
+        if content_type.to_ascii_lowercase().starts_with("multipart/mixed") {
+            let inner = boundary_of(content_type).ok_or("Changeset is missing its boundary")?;
+            responses.extend(decode(rest, &inner)?);
+            continue;
+        }
+        let (status_line, rest) = rest.split_once('\n').ok_or("Batch part has no status line")?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| format!("Invalid status line in batch part: {}", status_line.trim()))?;
+        let (head, body) = split_head(rest);
+        let body = body.trim_end_matches(['\r', '\n']);
+        responses.push(BatchResponse {
+            content_id: headers.get("content-id").cloned(),
+            status,
+            headers: parse_headers(head),
+            body: (!body.is_empty())
+                .then(|| serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.to_string()))),
+        });
+    }
+    Ok(responses)
+}
+
+// Splits a header block from what follows the first blank line.
+fn split_head(text: &str) -> (&str, &str) {
+    if let Some(rest) = text.strip_prefix("\r\n").or_else(|| text.strip_prefix('\n')) {
+        return ("", rest);
+    }
+    let crlf = text.find("\r\n\r\n").map(|i| (i, 4));
+    let lf = text.find("\n\n").map(|i| (i, 2));
+    match [crlf, lf].into_iter().flatten().min_by_key(|(i, _)| *i) {
+        Some((i, len)) => (&text[..i], &text[i + len..]),
+        None => (text, ""),
+    }
+}
+
+fn parse_headers(head: &str) -> BTreeMap<String, String> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect()
+}
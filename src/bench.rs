@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+pub struct BenchOptions {
+    pub warmup: usize,
+    pub iterations: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            warmup: 10,
+            iterations: 100,
+        }
+    }
+}
+
+// Open-loop load: requests are scheduled at a fixed rate regardless of how
+// fast earlier ones complete, with at most `concurrency` in flight.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions {
+    pub rps: f64,
+    pub duration: Duration,
+    pub concurrency: usize,
+}
+
+const CSV_HEADER: &str = "requests,errors,elapsed_ms,throughput,min_ms,mean_ms,p50_ms,p90_ms,p99_ms,max_ms";
+
+// Latencies are in milliseconds and cover every measured request, failed ones
+// included, with retries counted as part of the request.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BenchReport {
+    pub requests: usize,
+    pub errors: usize,
+    pub elapsed_ms: f64,
+    pub throughput: f64,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl BenchReport {
+    pub(crate) fn from_samples(mut latencies: Vec<Duration>, errors: usize, elapsed: Duration) -> Self {
+        if latencies.is_empty() {
+            return BenchReport::default();
+        }
+        latencies.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let total: Duration = latencies.iter().sum();
+        BenchReport {
+            requests: latencies.len(),
+            errors,
+            elapsed_ms: ms(elapsed),
+            throughput: latencies.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            min_ms: ms(latencies[0]),
+            mean_ms: ms(total) / latencies.len() as f64,
+            p50_ms: ms(percentile(&latencies, 50.0)),
+            p90_ms: ms(percentile(&latencies, 90.0)),
+            p99_ms: ms(percentile(&latencies, 99.0)),
+            max_ms: ms(latencies[latencies.len() - 1]),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    // Header line plus one row, so several reports can be gathered by
+    // concatenating rows under a single header.
+    pub fn to_csv(&self) -> String {
+        format!("{}\n{}\n", CSV_HEADER, self.csv_row())
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            self.requests,
+            self.errors,
+            self.elapsed_ms,
+            self.throughput,
+            self.min_ms,
+            self.mean_ms,
+            self.p50_ms,
+            self.p90_ms,
+            self.p99_ms,
+            self.max_ms
+        )
+    }
+}
+
+// Nearest-rank percentile over sorted samples.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Runs `warmup` unmeasured calls, then `iterations` measured ones, back to back.
+pub(crate) fn run<T, E, F>(options: &BenchOptions, call: F) -> BenchReport
+where
+    F: Fn() -> Result<T, E>,
+{
+    for _ in 0..options.warmup {
+        let _ = call();
+    }
+    let mut latencies = Vec::with_capacity(options.iterations);
+    let mut errors = 0;
+    let started = Instant::now();
+    for _ in 0..options.iterations {
+        let start = Instant::now();
+        if call().is_err() {
+            errors += 1;
+        }
+        latencies.push(start.elapsed());
+    }
+    BenchReport::from_samples(latencies, errors, started.elapsed())
+}
+
+// Latency is measured from each request's scheduled start, so time spent
+// waiting for a free worker counts against the request rather than being
+// hidden (coordinated omission).
+pub(crate) fn run_load<T, E, F>(options: &LoadOptions, call: F) -> BenchReport
+where
+    F: Fn() -> Result<T, E> + Sync,
+{
+    let interval = Duration::from_secs_f64(1.0 / options.rps.max(f64::EPSILON));
+    let total = (options.duration.as_secs_f64() * options.rps).ceil() as usize;
+    let next = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    let latencies = Mutex::new(Vec::with_capacity(total));
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..options.concurrency.max(1) {
+            scope.spawn(|| loop {
+                let slot = next.fetch_add(1, Ordering::SeqCst);
+                if slot >= total {
+                    break;
+                }
+                let scheduled = started + interval.mul_f64(slot as f64);
+                if let Some(wait) = scheduled.checked_duration_since(Instant::now()) {
+                    std::thread::sleep(wait);
+                }
+                if call().is_err() {
+                    errors.fetch_add(1, Ordering::SeqCst);
+                }
+                latencies.lock().unwrap().push(scheduled.elapsed());
+            });
+        }
+    });
+    BenchReport::from_samples(latencies.into_inner().unwrap(), errors.into_inner(), started.elapsed())
+}
@@ -0,0 +1,64 @@
+// Benchmarks or load-tests one endpoint through the full client pipeline:
+//
+//     easier-apis-bench https://api.example.com /users --iterations 500
+//     easier-apis-bench https://api.example.com /users --rps 200 --duration 30 --concurrency 32 --format csv
+//
+// `--rps` switches from back-to-back calls to open-loop load. The report is
+// printed to stdout as JSON, or as CSV with `--format csv`.
+
+use easier_apis_core::{BenchOptions, LoadOptions, RustCore};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "usage: easier-apis-bench <base-url> <path> [--warmup N] [--iterations N] \
+[--rps N --duration SECS --concurrency N] [--format json|csv]";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(report) => {
+            print!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<String, String> {
+    let mut positional = Vec::new();
+    let mut bench = BenchOptions::default();
+    let mut load = LoadOptions {
+        rps: 0.0,
+        duration: Duration::from_secs(10),
+        concurrency: 16,
+    };
+    let mut csv = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+        let number = |value: &str| value.parse::<f64>().ok().filter(|n| *n >= 0.0).ok_or_else(|| format!("Invalid {}: {}", arg, value));
+        match arg.as_str() {
+            "--warmup" => bench.warmup = number(&value)? as usize,
+            "--iterations" => bench.iterations = number(&value)? as usize,
+            "--rps" => load.rps = number(&value)?,
+            "--duration" => load.duration = Duration::from_secs_f64(number(&value)?),
+            "--concurrency" => load.concurrency = number(&value)? as usize,
+            "--format" => match value.as_str() {
+                "json" => csv = false,
+                "csv" => csv = true,
+                _ => return Err(format!("Unknown format: {}", value)),
+            },
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    let [base_url, path] = <[String; 2]>::try_from(positional).map_err(|_| "Expected a base URL and a path".to_string())?;
+    let core = RustCore::builder(&base_url).build().map_err(|e| e.to_string())?;
+    let report = if load.rps > 0.0 { core.load(&path, &load) } else { core.bench(&path, &bench) };
+    Ok(if csv { report.to_csv() } else { format!("{}\n", report.to_json()) })
+}
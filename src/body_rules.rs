@@ -0,0 +1,91 @@
+use crate::pointer;
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyAction {
+    // Sent again under the retry policy, as if the response had been a 429.
+    Retry,
+    // Fails the call with `BodyError` straight away.
+    Fail,
+}
+
+// Treats a successful response as failed when the JSON at `path` is one of
+// `values`, for APIs that answer 200 with `{"status": "RATE_LIMITED"}`.
+// `path` is a JSON pointer (`/status`) or a simple JSONPath (`$.meta.code`,
+// `$.errors[0].code`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyRule {
+    pointer: String,
+    values: Vec<Value>,
+    action: BodyAction,
+}
+
+impl BodyRule {
+    pub fn new(path: &str, values: Vec<Value>, action: BodyAction) -> Self {
+        BodyRule {
+            pointer: to_pointer(path),
+            values,
+            action,
+        }
+    }
+
+    pub fn retry(path: &str, values: Vec<Value>) -> Self {
+        Self::new(path, values, BodyAction::Retry)
+    }
+
+    pub fn fail(path: &str, values: Vec<Value>) -> Self {
+        Self::new(path, values, BodyAction::Fail)
+    }
+
+    pub fn action(&self) -> BodyAction {
+        self.action
+    }
+
+    // The error for `body` if the rule matches it.
+    pub(crate) fn check(&self, body: &Value) -> Option<BodyError> {
+        let value = body.pointer(&self.pointer)?;
+        self.values.contains(value).then(|| BodyError {
+            pointer: self.pointer.clone(),
+            value: value.clone(),
+            body: body.clone(),
+        })
+    }
+}
+
+// A response a `BodyRule` turned into a failure, with the value that matched
+// and the whole body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyError {
+    pub pointer: String,
+    pub value: Value,
+    pub body: Value,
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Response body has {} = {}", self.pointer, self.value)
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+// The first rule matching `body`, and its error.
+pub(crate) fn matching<'a>(rules: &'a [BodyRule], body: &Value) -> Option<(&'a BodyRule, BodyError)> {
+    rules.iter().find_map(|rule| rule.check(body).map(|error| (rule, error)))
+}
+
+// `$.a.b[0]` to `/a/b/0`; JSON pointers are kept as they are.
+fn to_pointer(path: &str) -> String {
+    let Some(rest) = path.strip_prefix('$') else {
+        return path.to_string();
+    };
+    let segments: Vec<String> = rest
+        .replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.trim_matches(['\'', '"']).to_string())
+        .collect();
+    pointer::to_pointer(&segments)
+}
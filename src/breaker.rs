@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    // Requests go through.
+    Closed,
+    // Requests fail fast with `CircuitOpen` until the cooldown is over.
+    Open,
+    // The cooldown is over and one request is let through as a probe; its
+    // outcome closes the circuit or opens it again.
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        }
+    }
+}
+
+// A request refused without being sent because its host's circuit is open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitOpen {
+    pub host: String,
+    // Until the next probe is let through.
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circuit for {} is open; next attempt in {}ms", self.host, self.retry_in.as_millis())
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+// Stops sending to a host after `failure_threshold` consecutive attempts
+// failed with a 5xx, a timeout or a connection error, so a struggling API
+// isn't hammered through the retry loop. Other statuses count as successes:
+// the server answered. After `cooldown` a single probe decides whether the
+// circuit closes again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+        }
+    }
+}
+
+// Five failures, 30 seconds.
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new(5, Duration::from_secs(30))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum HostState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    // The probe is in flight; everyone else still fails fast.
+    HalfOpen,
+}
+
+pub(crate) struct Breakers {
+    config: CircuitBreaker,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+impl Breakers {
+    pub fn new(config: CircuitBreaker) -> Self {
+        Breakers {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Called before every attempt.
+    pub fn check(&self, host: &str, now: Instant) -> Result<(), CircuitOpen> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(state) = hosts.get_mut(host) else {
+            return Ok(());
+        };
+        match *state {
+            HostState::Closed { .. } => Ok(()),
+            HostState::Open { until } if now >= until => {
+                *state = HostState::HalfOpen;
+                Ok(())
+            }
+            HostState::Open { until } => Err(CircuitOpen {
+                host: host.to_string(),
+                retry_in: until - now,
+            }),
+            HostState::HalfOpen => Err(CircuitOpen {
+                host: host.to_string(),
+                retry_in: Duration::ZERO,
+            }),
+        }
+    }
+
+    pub fn record(&self, host: &str, failed: bool, now: Instant) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if !failed {
+            hosts.remove(host);
+            return;
+        }
+        let state = hosts.entry(host.to_string()).or_insert(HostState::Closed { failures: 0 });
+        *state = match *state {
+            HostState::Closed { failures } if failures + 1 < self.config.failure_threshold => HostState::Closed { failures: failures + 1 },
+            _ => HostState::Open {
+                until: now + self.config.cooldown,
+            },
+        };
+    }
+
+    // Only hosts that have failed lately; the rest are closed.
+    pub fn states(&self, now: Instant) -> BTreeMap<String, CircuitState> {
+        let hosts = self.hosts.lock().unwrap();
+        hosts
+            .iter()
+            .map(|(host, state)| {
+                let state = match state {
+                    HostState::Closed { .. } => CircuitState::Closed,
+                    HostState::Open { until } if now < *until => CircuitState::Open,
+                    HostState::Open { .. } | HostState::HalfOpen => CircuitState::HalfOpen,
+                };
+                (host.clone(), state)
+            })
+            .collect()
+    }
+}
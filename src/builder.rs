@@ -0,0 +1,347 @@
+use crate::capture;
+use crate::{ClientPool, RustCore};
+use reqwest::redirect::Policy;
+use reqwest::tls;
+use reqwest::{Certificate, NoProxy, Proxy, Url};
+use std::fmt;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+// HTTP client settings, kept on the core so the client can be rebuilt with
+// them when the egress policy or revocation checking changes.
+#[derive(Clone, Debug)]
+pub(crate) struct ClientConfig {
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub user_agent: Option<String>,
+    // Tried in order; the first whose scheme matches is used.
+    pub proxies: Vec<Proxy>,
+    pub system_proxy: bool,
+    pub max_redirects: usize,
+    pub root_certificates: Vec<Certificate>,
+    pub min_tls_version: Option<tls::Version>,
+    pub accept_invalid_certs: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            timeout: Some(DEFAULT_TIMEOUT),
+            connect_timeout: None,
+            user_agent: None,
+            proxies: Vec::new(),
+            system_proxy: true,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            root_certificates: Vec::new(),
+            min_tls_version: None,
+            accept_invalid_certs: false,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn redirect_policy(&self) -> Policy {
+        match self.max_redirects {
+            0 => Policy::none(),
+            max => Policy::limited(max),
+        }
+    }
+
+    pub fn blocking(&self) -> reqwest::blocking::ClientBuilder {
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .redirect(self.redirect_policy())
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        // Also drops proxies added before it.
+        if !self.system_proxy {
+            builder = builder.no_proxy();
+        }
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(version) = self.min_tls_version {
+            builder = builder.min_tls_version(version);
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        builder
+    }
+
+    #[cfg(feature = "async")]
+    pub fn nonblocking(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .redirect(self.redirect_policy())
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        // Also drops proxies added before it.
+        if !self.system_proxy {
+            builder = builder.no_proxy();
+        }
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(version) = self.min_tls_version {
+            builder = builder.min_tls_version(version);
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        builder
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProxyScheme {
+    All,
+    Http,
+    Https,
+}
+
+// Configures the HTTP client behind a `RustCore`. Values are checked in
+// `build`, which fails instead of panicking on a bad proxy URL, certificate,
+// header or TLS setup.
+#[derive(Clone)]
+pub struct RustCoreBuilder {
+    base_url: String,
+    config: ClientConfig,
+    proxies: Vec<(ProxyScheme, String)>,
+    no_proxy: Option<String>,
+    proxy_auth: Option<(String, Zeroizing<String>)>,
+    root_certificates: Vec<Vec<u8>>,
+    default_headers: Vec<(String, String)>,
+    pool: Option<ClientPool>,
+    #[cfg(feature = "tracing")]
+    tracing: bool,
+}
+
+impl RustCoreBuilder {
+    pub fn new(base_url: &str) -> Self {
+        RustCoreBuilder {
+            base_url: base_url.to_string(),
+            config: ClientConfig::default(),
+            proxies: Vec::new(),
+            no_proxy: None,
+            proxy_auth: None,
+            root_certificates: Vec::new(),
+            default_headers: Vec::new(),
+            pool: None,
+            #[cfg(feature = "tracing")]
+            tracing: false,
+        }
+    }
+
+    // For the whole request, including reading the body. Defaults to 30s.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    pub fn no_timeout(mut self) -> Self {
+        self.config.timeout = None;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.config.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    // As `RustCore::set_default_header`; later values for a name win.
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.default_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Sends all requests through `url`, e.g. `http://proxy.internal:3128`, or
+    // `socks5://proxy.internal:1080` with the `socks` feature. Credentials in
+    // the URL are used for proxy auth. Replaces earlier calls.
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.set_proxy(ProxyScheme::All, url);
+        self
+    }
+
+    // Only for `http://` requests; wins over `proxy`.
+    pub fn http_proxy(mut self, url: &str) -> Self {
+        self.set_proxy(ProxyScheme::Http, url);
+        self
+    }
+
+    // Only for `https://` requests, tunnelled with CONNECT; wins over `proxy`.
+    pub fn https_proxy(mut self, url: &str) -> Self {
+        self.set_proxy(ProxyScheme::Https, url);
+        self
+    }
+
+    fn set_proxy(&mut self, scheme: ProxyScheme, url: &str) {
+        self.proxies.retain(|(existing, _)| *existing != scheme);
+        self.proxies.push((scheme, url.to_string()));
+        self.proxies.sort_by_key(|(scheme, _)| *scheme == ProxyScheme::All);
+    }
+
+    // Hosts reached without the proxies, in the format of `NO_PROXY`:
+    // `localhost, .internal.example.com, 10.0.0.0/8`.
+    pub fn no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy = Some(hosts.to_string());
+        self
+    }
+
+    // Basic auth for the proxies, where credentials can't go in the URL.
+    pub fn proxy_auth(mut self, username: &str, password: &str) -> Self {
+        self.proxy_auth = Some((username.to_string(), Zeroizing::new(password.to_string())));
+        self
+    }
+
+    // Whether `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` from the
+    // environment apply. On by default; proxies set here replace them.
+    pub fn system_proxy(mut self, enabled: bool) -> Self {
+        self.config.system_proxy = enabled;
+        self
+    }
+
+    // `{"all": ..., "http": ..., "https": ..., "no_proxy": "...", "username":
+    // ..., "password": ..., "system_proxy": false}` for the C API; all keys
+    // are optional.
+    pub(crate) fn proxy_json(mut self, options: &serde_json::Value) -> Option<Self> {
+        let text = |key: &str| match options.get(key) {
+            None | Some(serde_json::Value::Null) => Some(None),
+            Some(value) => value.as_str().map(Some),
+        };
+        if let Some(url) = text("all")? {
+            self = self.proxy(url);
+        }
+        if let Some(url) = text("http")? {
+            self = self.http_proxy(url);
+        }
+        if let Some(url) = text("https")? {
+            self = self.https_proxy(url);
+        }
+        if let Some(hosts) = text("no_proxy")? {
+            self = self.no_proxy(hosts);
+        }
+        if let Some(username) = text("username")? {
+            self = self.proxy_auth(username, text("password")?.unwrap_or_default());
+        }
+        match options.get("system_proxy") {
+            None | Some(serde_json::Value::Null) => {}
+            Some(enabled) => self = self.system_proxy(enabled.as_bool()?),
+        }
+        Some(self)
+    }
+
+    // How many redirects to follow before failing; 0 doesn't follow any.
+    // Defaults to 10.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.config.max_redirects = max;
+        self
+    }
+
+    // Trusts a PEM-encoded CA in addition to the system roots.
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    pub fn min_tls_version(mut self, version: tls::Version) -> Self {
+        self.config.min_tls_version = Some(version);
+        self
+    }
+
+    // Skips certificate verification entirely. Only for local development
+    // against self-signed servers.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.config.accept_invalid_certs = accept;
+        self
+    }
+
+    // Draws connections from `pool` instead of opening its own. The pool's
+    // client settings apply, so timeouts, proxy and TLS options set here are
+    // ignored. An egress policy or revocation check gives the core a client
+    // of its own again.
+    pub fn pool(mut self, pool: &ClientPool) -> Self {
+        self.pool = Some(pool.clone());
+        self
+    }
+
+    // As `RustCore::set_tracing`.
+    #[cfg(feature = "tracing")]
+    pub fn tracing(mut self, enabled: bool) -> Self {
+        self.tracing = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<RustCore, Box<dyn std::error::Error>> {
+        let mut core = match &self.pool {
+            Some(pool) => RustCore::with_client(&self.base_url, pool.client(), pool.config()),
+            None => {
+                let mut config = self.config;
+                for (scheme, url) in &self.proxies {
+                    let mut proxy = match scheme {
+                        ProxyScheme::All => Proxy::all(url)?,
+                        ProxyScheme::Http => Proxy::http(url)?,
+                        ProxyScheme::Https => Proxy::https(url)?,
+                    };
+                    if let Some((username, password)) = &self.proxy_auth {
+                        proxy = proxy.basic_auth(username, password);
+                    }
+                    config.proxies.push(proxy.no_proxy(self.no_proxy.as_deref().and_then(NoProxy::from_string)));
+                }
+                for pem in &self.root_certificates {
+                    config.root_certificates.push(Certificate::from_pem(pem)?);
+                }
+                RustCore::with_config(&self.base_url, config)?
+            }
+        };
+        for (name, value) in &self.default_headers {
+            core.set_default_header(name, value)?;
+        }
+        #[cfg(feature = "tracing")]
+        core.set_tracing(self.tracing);
+        Ok(core)
+    }
+}
+
+// Without proxy credentials or default header values, which may hold API keys.
+impl fmt::Debug for RustCoreBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let proxies: Vec<(ProxyScheme, String)> = self
+            .proxies
+            .iter()
+            .map(|(scheme, url)| (*scheme, Url::parse(url).map_or_else(|_| capture::MASK.to_string(), |url| capture::sanitize_url(&url))))
+            .collect();
+        let proxy_auth = self.proxy_auth.as_ref().map(|(username, _)| username);
+        let default_headers: Vec<&str> = self.default_headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("RustCoreBuilder")
+            .field("base_url", &self.base_url)
+            .field("config", &self.config)
+            .field("proxies", &proxies)
+            .field("no_proxy", &self.no_proxy)
+            .field("proxy_auth", &proxy_auth)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("default_headers", &default_headers)
+            .field("pool", &self.pool)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{AggregateError, ApiError, Method};
+use serde::Serialize;
+use serde_json::Value;
+
+// How `bulk_with` splits and sends an item set. Each chunk goes out as one
+// request whose body is a JSON array of its items.
+#[derive(Clone, Debug)]
+pub struct BulkOptions {
+    method: Method,
+    chunk_size: usize,
+    concurrency: usize,
+}
+
+impl BulkOptions {
+    pub fn new(chunk_size: usize) -> Self {
+        BulkOptions {
+            method: Method::Post,
+            chunk_size: chunk_size.max(1),
+            concurrency: 4,
+        }
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    // Chunks in flight at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub(crate) fn method_kind(&self) -> Method {
+        self.method
+    }
+
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub(crate) fn workers(&self, chunks: usize) -> usize {
+        self.concurrency.min(chunks)
+    }
+}
+
+// Outcome for one input item; `index` is its position in the input.
+// `response` is the item's element of the chunk response when the server
+// answers with an array aligned to the request.
+#[derive(Clone, Debug, Serialize)]
+pub struct BulkItem {
+    pub index: usize,
+    pub ok: bool,
+    pub response: Option<Value>,
+    pub error: Option<String>,
+    // Status of the failed chunk request, for errors that were HTTP errors.
+    pub status: Option<u16>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BulkReport {
+    pub items: Vec<BulkItem>,
+}
+
+impl BulkReport {
+    pub fn succeeded(&self) -> impl Iterator<Item = &BulkItem> {
+        self.items.iter().filter(|item| item.ok)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &BulkItem> {
+        self.items.iter().filter(|item| !item.ok)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| item.ok)
+    }
+
+    // Every item's response (None where the server didn't answer per item),
+    // or an `AggregateError` with both the failures and the successes.
+    pub fn into_result(self) -> Result<Vec<Option<Value>>, AggregateError<Option<Value>>> {
+        AggregateError::collect(self.items.into_iter().map(|item| match (item.ok, item.status) {
+            (true, _) => Ok(item.response),
+            (false, Some(status)) => Err(ApiError::http(status, String::new(), None)),
+            (false, None) => Err(ApiError::Other(item.error.unwrap_or_default().into())),
+        }))
+    }
+}
+
+// A failed request fails every item in its chunk. A successful one is split
+// per item only if the response is an array of the same length; elements
+// with a non-null `error` count as failures. A failed request's error comes
+// with its HTTP status, if it had one.
+pub(crate) fn outcomes(start: usize, len: usize, result: Result<Value, (String, Option<u16>)>) -> Vec<BulkItem> {
+    let item = |i: usize, ok: bool, response: Option<Value>, error: Option<String>| BulkItem {
+        index: start + i,
+        ok,
+        response,
+        error,
+        status: None,
+    };
+    match result {
+        Err((e, status)) => (0..len)
+            .map(|i| BulkItem {
+                status,
+                ..item(i, false, None, Some(e.clone()))
+            })
+            .collect(),
+        Ok(Value::Array(elements)) if elements.len() == len => elements
+            .into_iter()
+            .enumerate()
+            .map(|(i, element)| match element.get("error").filter(|e| !e.is_null()) {
+                Some(Value::String(e)) => item(i, false, Some(element.clone()), Some(e.clone())),
+                Some(e) => {
+                    let e = e.to_string();
+                    item(i, false, Some(element), Some(e))
+                }
+                None => item(i, true, Some(element), None),
+            })
+            .collect(),
+        Ok(_) => (0..len).map(|i| item(i, true, None, None)).collect(),
+    }
+}
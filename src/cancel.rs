@@ -0,0 +1,142 @@
+use reqwest::blocking::{Client, Request, Response};
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Listener = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    next_id: AtomicU64,
+    listeners: Mutex<Vec<(u64, Listener)>>,
+}
+
+// Aborts the calls it's passed to through `RequestOptions::cancel_token`,
+// from any thread: a call waiting for its response returns at once, one
+// sleeping out a backoff wakes up, and one not sent yet isn't sent. They fail
+// with `Cancelled`. A response whose headers already arrived is still read.
+// Cancelling is permanent; use a new token for the next call.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for (_, listener) in self.inner.listeners.lock().unwrap().iter() {
+            listener();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Runs `listener` on cancellation, or right away if that already
+    // happened, until the returned guard is dropped.
+    fn on_cancel<F: Fn() + Send + Sync + 'static>(&self, listener: F) -> Registration {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let listener: Listener = Arc::new(listener);
+        self.inner.listeners.lock().unwrap().push((id, Arc::clone(&listener)));
+        // Checked after registering, so a concurrent `cancel` can't slip
+        // between the two unnoticed; at worst the listener runs twice.
+        if self.is_cancelled() {
+            listener();
+        }
+        Registration {
+            token: self.clone(),
+            id,
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+struct Registration {
+    token: CancellationToken,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.token.inner.listeners.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+// The call's `CancellationToken` fired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Request cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+// The token of the call running on this thread, set for the guard's lifetime.
+pub(crate) struct Entered {
+    previous: Option<CancellationToken>,
+    _wake: Registration,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+// `wake` interrupts backoff sleeps when the token fires.
+pub(crate) fn enter<F: Fn() + Send + Sync + 'static>(token: &CancellationToken, wake: F) -> Entered {
+    let previous = CURRENT.with(|current| current.borrow_mut().replace(token.clone()));
+    Entered {
+        previous,
+        _wake: token.on_cancel(wake),
+    }
+}
+
+pub(crate) fn cancelled() -> bool {
+    CURRENT.with(|current| current.borrow().as_ref().is_some_and(CancellationToken::is_cancelled))
+}
+
+// Sends `request`, or gives up waiting for it when the current token fires
+// (None). With a token the request goes out on its own thread, which is left
+// to finish and drop the response on its own.
+pub(crate) fn execute(client: &Client, request: Request) -> Option<reqwest::Result<Response>> {
+    let Some(token) = CURRENT.with(|current| current.borrow().clone()) else {
+        return Some(client.execute(request));
+    };
+    if token.is_cancelled() {
+        return None;
+    }
+    let (sender, receiver) = mpsc::channel();
+    let on_cancel = sender.clone();
+    let _registration = token.on_cancel(move || {
+        let _ = on_cancel.send(None);
+    });
+    let client = client.clone();
+    thread::spawn(move || {
+        let _ = sender.send(Some(client.execute(request)));
+    });
+    receiver.recv().ok().flatten()
+}
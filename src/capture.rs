@@ -0,0 +1,277 @@
+use crate::Anonymizer;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+// Ring size for duration-limited captures.
+pub const DEFAULT_CAPTURE_CAPACITY: usize = 500;
+
+pub(crate) const MASK: &str = "***";
+
+// Headers whose values never make it into a capture.
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureLimit {
+    // Record everything for this long, keeping the most recent
+    // `DEFAULT_CAPTURE_CAPACITY` calls.
+    Duration(Duration),
+    // Keep the most recent `n` calls until the capture is stopped.
+    Count(usize),
+}
+
+// Summary of one call: header values of credentials masked and query values
+// dropped. JSON bodies are only kept by captures with an `Anonymizer`, after
+// it has scrubbed them.
+#[derive(Clone, Debug, Serialize)]
+pub struct CapturedExchange {
+    pub at: String,
+    pub method: String,
+    pub url: String,
+    pub request_headers: BTreeMap<String, String>,
+    pub status: Option<u16>,
+    pub response_headers: BTreeMap<String, String>,
+    pub attempts: usize,
+    pub elapsed_ms: u64,
+    pub request_id: Option<String>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_body: Option<Value>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SupportBundle {
+    pub base_url: String,
+    pub capture_started: String,
+    pub generated: String,
+    // Calls pushed out of the ring before the bundle was taken.
+    pub dropped: u64,
+    pub exchanges: Vec<CapturedExchange>,
+}
+
+// A finished call as seen by `send_with_retry`, before sanitizing.
+pub(crate) struct Exchange<'a> {
+    pub at: SystemTime,
+    pub method: &'a str,
+    pub url: &'a Url,
+    pub request_headers: &'a HeaderMap,
+    pub request_body: Option<&'a [u8]>,
+    pub status: Option<u16>,
+    // None when the call failed, since the response was consumed for the error.
+    pub response_headers: Option<&'a HeaderMap>,
+    pub attempts: usize,
+    pub elapsed: Duration,
+    pub request_id: Option<String>,
+    pub error: Option<String>,
+}
+
+struct Session {
+    started_at: SystemTime,
+    deadline: Option<Instant>,
+    capacity: usize,
+    // Each with the number `record` handed out for it.
+    exchanges: VecDeque<(u64, CapturedExchange)>,
+    dropped: u64,
+    recorded: u64,
+    anonymizer: Option<Anonymizer>,
+}
+
+// Put on a response whose body the capture still wants, by `record`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CaptureSlot(pub u64);
+
+#[derive(Default)]
+pub(crate) struct Capture {
+    session: Mutex<Option<Session>>,
+    // Extra header names to mask, e.g. a custom auth header.
+    sensitive: Mutex<BTreeSet<String>>,
+}
+
+impl Capture {
+    pub(crate) fn start(&self, limit: CaptureLimit, anonymizer: Option<Anonymizer>, now: Instant, now_at: SystemTime) {
+        let (deadline, capacity) = match limit {
+            CaptureLimit::Duration(duration) => (Some(now + duration), DEFAULT_CAPTURE_CAPACITY),
+            CaptureLimit::Count(count) => (None, count.max(1)),
+        };
+        *self.session.lock().unwrap() = Some(Session {
+            started_at: now_at,
+            deadline,
+            capacity,
+            exchanges: VecDeque::new(),
+            dropped: 0,
+            recorded: 0,
+            anonymizer,
+        });
+    }
+
+    // Returns what was recorded so far.
+    pub(crate) fn stop(&self, base_url: &str, now_at: SystemTime) -> Option<SupportBundle> {
+        let session = self.session.lock().unwrap().take()?;
+        Some(bundle(base_url, &session, now_at))
+    }
+
+    pub(crate) fn bundle(&self, base_url: &str, now_at: SystemTime) -> Option<SupportBundle> {
+        Some(bundle(base_url, self.session.lock().unwrap().as_ref()?, now_at))
+    }
+
+    pub(crate) fn mask_header(&self, name: &str) {
+        self.sensitive.lock().unwrap().insert(name.to_ascii_lowercase());
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn is_sensitive(&self, name: &str) -> bool {
+        SENSITIVE_HEADERS.contains(&name) || self.sensitive.lock().unwrap().contains(name)
+    }
+
+    // Returns a slot for the response body if the capture keeps bodies.
+    pub(crate) fn record(&self, now: Instant, exchange: Exchange<'_>) -> Option<CaptureSlot> {
+        let mut guard = self.session.lock().unwrap();
+        let session = guard.as_mut()?;
+        if session.deadline.is_some_and(|deadline| now >= deadline) {
+            return None;
+        }
+        let sensitive = self.sensitive.lock().unwrap();
+        let anonymizer = session.anonymizer.as_ref();
+        let response_headers = exchange
+            .response_headers
+            .map_or_else(BTreeMap::new, |headers| headers_of(headers, &sensitive, anonymizer));
+        if session.exchanges.len() == session.capacity {
+            session.exchanges.pop_front();
+            session.dropped += 1;
+        }
+        let captured = CapturedExchange {
+            at: timestamp(exchange.at),
+            method: exchange.method.to_string(),
+            url: anonymize_url(exchange.url, anonymizer),
+            request_headers: headers_of(exchange.request_headers, &sensitive, anonymizer),
+            status: exchange.status,
+            response_headers,
+            attempts: exchange.attempts,
+            elapsed_ms: exchange.elapsed.as_millis() as u64,
+            request_id: exchange.request_id,
+            error: exchange.error,
+            request_body: anonymizer.zip(exchange.request_body).and_then(|(anonymizer, body)| json_body(anonymizer, body)),
+            response_body: None,
+        };
+        session.recorded += 1;
+        session.exchanges.push_back((session.recorded, captured));
+        anonymizer.map(|_| CaptureSlot(session.recorded))
+    }
+
+    // Fills in the response body of the call `record` gave `slot` for, unless
+    // it has left the ring or the capture was restarted since.
+    pub(crate) fn record_response_body(&self, slot: CaptureSlot, body: &[u8]) {
+        let mut guard = self.session.lock().unwrap();
+        let Some(session) = guard.as_mut() else {
+            return;
+        };
+        let Some(anonymizer) = &session.anonymizer else {
+            return;
+        };
+        let body = json_body(anonymizer, body);
+        if let Some((_, exchange)) = session.exchanges.iter_mut().find(|(n, _)| *n == slot.0) {
+            exchange.response_body = body;
+        }
+    }
+}
+
+// Bodies that aren't JSON are left out, since there's no knowing what to scrub.
+fn json_body(anonymizer: &Anonymizer, body: &[u8]) -> Option<Value> {
+    serde_json::from_slice(body).ok().map(|body| anonymizer.anonymize(&body))
+}
+
+fn bundle(base_url: &str, session: &Session, now_at: SystemTime) -> SupportBundle {
+    SupportBundle {
+        base_url: base_url.to_string(),
+        capture_started: timestamp(session.started_at),
+        generated: timestamp(now_at),
+        dropped: session.dropped,
+        exchanges: session.exchanges.iter().map(|(_, exchange)| exchange.clone()).collect(),
+    }
+}
+
+fn timestamp(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn headers_of(headers: &HeaderMap, sensitive: &BTreeSet<String>, anonymizer: Option<&Anonymizer>) -> BTreeMap<String, String> {
+    let anonymized;
+    let headers = match anonymizer {
+        Some(anonymizer) => {
+            let mut copy = headers.clone();
+            anonymizer.apply_headers(&mut copy);
+            anonymized = copy;
+            &anonymized
+        }
+        None => headers,
+    };
+    let mut out = BTreeMap::new();
+    for (name, value) in headers {
+        let name = name.as_str();
+        let value = if SENSITIVE_HEADERS.contains(&name) || sensitive.contains(name) {
+            MASK.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        out.entry(name.to_string())
+            .and_modify(|existing: &mut String| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    out
+}
+
+// Query values can carry tokens, so only the parameter names are kept.
+pub(crate) fn sanitize_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_password(None);
+    let _ = url.set_username("");
+    if url.query().is_some() {
+        let names: Vec<String> = url.query_pairs().map(|(name, _)| name.into_owned()).collect();
+        url.query_pairs_mut().clear().extend_pairs(names.iter().map(|name| (name.as_str(), MASK)));
+    }
+    url.to_string()
+}
+
+// `sanitize_url`, except that query parameters the anonymizer has a `field`
+// rule for are hashed or removed as it says.
+fn anonymize_url(url: &Url, anonymizer: Option<&Anonymizer>) -> String {
+    let Some(anonymizer) = anonymizer.filter(|_| url.query().is_some()) else {
+        return sanitize_url(url);
+    };
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter_map(|(name, value)| {
+            let field = Value::Object(Map::from_iter([(name.to_string(), Value::String(value.to_string()))]));
+            match anonymizer.anonymize(&field).get(name.as_ref()) {
+                None => None,
+                Some(Value::String(changed)) if *changed != value => Some((name.into_owned(), changed.clone())),
+                Some(_) => Some((name.into_owned(), MASK.to_string())),
+            }
+        })
+        .collect();
+    let mut url = url.clone();
+    let _ = url.set_password(None);
+    let _ = url.set_username("");
+    url.set_query(None);
+    if !kept.is_empty() {
+        url.query_pairs_mut().extend_pairs(&kept);
+    }
+    url.to_string()
+}
@@ -0,0 +1,118 @@
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCase {
+    Snake,
+    Camel,
+}
+
+impl KeyCase {
+    pub fn convert(self, key: &str) -> String {
+        match self {
+            KeyCase::Snake => to_snake(key),
+            KeyCase::Camel => to_camel(key),
+        }
+    }
+}
+
+// Rewrites object keys in request bodies (`outgoing`) and parsed responses
+// (`incoming`), recursively. Routes whose URL path starts with an excepted
+// prefix are passed through untouched in both directions.
+#[derive(Clone, Debug, Default)]
+pub struct KeyTransform {
+    outgoing: Option<KeyCase>,
+    incoming: Option<KeyCase>,
+    exceptions: Vec<String>,
+}
+
+impl KeyTransform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // snake_case structs against a camelCase API.
+    pub fn camel_case_api() -> Self {
+        Self::new().outgoing(KeyCase::Camel).incoming(KeyCase::Snake)
+    }
+
+    pub fn outgoing(mut self, case: KeyCase) -> Self {
+        self.outgoing = Some(case);
+        self
+    }
+
+    pub fn incoming(mut self, case: KeyCase) -> Self {
+        self.incoming = Some(case);
+        self
+    }
+
+    pub fn except(mut self, prefix: &str) -> Self {
+        self.exceptions.push(prefix.to_string());
+        self
+    }
+
+    pub(crate) fn outgoing_body(&self, path: &str, body: Value) -> Value {
+        self.apply(self.outgoing, path, body)
+    }
+
+    pub(crate) fn incoming_body(&self, path: &str, body: Value) -> Value {
+        self.apply(self.incoming, path, body)
+    }
+
+    fn apply(&self, case: Option<KeyCase>, path: &str, body: Value) -> Value {
+        match case {
+            Some(case) if !self.exceptions.iter().any(|prefix| path.starts_with(prefix.as_str())) => {
+                convert_keys(case, body)
+            }
+            _ => body,
+        }
+    }
+}
+
+fn convert_keys(case: KeyCase, value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, field)| (case.convert(&key), convert_keys(case, field)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| convert_keys(case, item)).collect()),
+        other => other,
+    }
+}
+
+// `userId` -> `user_id`, `HTTPStatus` -> `http_status`; existing underscores
+// are kept.
+fn to_snake(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut out = String::with_capacity(key.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev != '_' && (prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower)) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+// `user_id` -> `userId`; leading underscores are kept and keys without
+// underscores are left alone.
+fn to_camel(key: &str) -> String {
+    let trimmed = key.trim_start_matches('_');
+    let mut out = key[..key.len() - trimmed.len()].to_string();
+    let mut upper = false;
+    for c in trimmed.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
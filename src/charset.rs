@@ -0,0 +1,52 @@
+use bytes::Bytes;
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use std::fmt;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CharsetError {
+    // The Content-Type named a charset there's no decoder for.
+    Unsupported(String),
+}
+
+impl fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharsetError::Unsupported(charset) => write!(f, "Unsupported response charset: {}", charset),
+        }
+    }
+}
+
+impl std::error::Error for CharsetError {}
+
+// The `charset` parameter of the Content-Type, as sent.
+pub(crate) fn declared(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?.parse::<mime::Mime>().ok()?;
+    Some(content_type.get_param(mime::CHARSET)?.as_str().to_string())
+}
+
+// `body` as UTF-8, and the encoding it was decoded from if it wasn't UTF-8
+// already. A byte order mark wins over the declared charset, as in browsers;
+// bytes the encoding can't map become U+FFFD.
+pub(crate) fn to_utf8(headers: &HeaderMap, body: Bytes) -> Result<(Bytes, Option<&'static str>), CharsetError> {
+    let encoding = match declared(headers) {
+        Some(label) => Encoding::for_label(label.trim().as_bytes()).ok_or(CharsetError::Unsupported(label))?,
+        None => UTF_8,
+    };
+    let encoding = Encoding::for_bom(&body).map_or(encoding, |(bom, _)| bom);
+    if encoding == UTF_8 {
+        return Ok((strip_bom(body), None));
+    }
+    let (text, _, _) = encoding.decode(&body);
+    Ok((Bytes::from(text.into_owned()), Some(encoding.name())))
+}
+
+fn strip_bom(body: Bytes) -> Bytes {
+    if body.starts_with(UTF8_BOM) {
+        body.slice(UTF8_BOM.len()..)
+    } else {
+        body
+    }
+}
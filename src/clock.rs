@@ -0,0 +1,98 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+// Where the client reads time from for backoff accounting and signing
+// timestamps. Request latency is always measured with the real clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    fn system_time(&self) -> SystemTime;
+}
+
+pub trait Sleeper: Send + Sync {
+    fn sleep(&self, duration: Duration);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+impl Sleeper for SystemClock {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+// Time that only moves when told to: `sleep` advances it instantly and records
+// the requested duration, so retry schedules can be checked without waiting.
+#[derive(Debug)]
+pub struct VirtualClock {
+    start: Instant,
+    start_system: SystemTime,
+    state: Mutex<VirtualState>,
+}
+
+#[derive(Debug, Default)]
+struct VirtualState {
+    elapsed: Duration,
+    sleeps: Vec<Duration>,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    pub fn starting_at(system_time: SystemTime) -> Self {
+        VirtualClock {
+            start: Instant::now(),
+            start_system: system_time,
+            state: Mutex::new(VirtualState::default()),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.state.lock().unwrap().elapsed += duration;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+
+    // Every duration passed to `sleep`, oldest first.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.state.lock().unwrap().sleeps.clone()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start_system + self.elapsed()
+    }
+}
+
+impl Sleeper for VirtualClock {
+    fn sleep(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.elapsed += duration;
+        state.sleeps.push(duration);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::pointer;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use std::fmt;
+
+// How a vendor encodes a field, rewritten into the form the usual serde
+// implementations expect: RFC 3339 strings for timestamps (chrono's
+// `DateTime`, or `time::serde::rfc3339`), `YYYY-MM-DD` for dates, and plain
+// decimal strings for `rust_decimal`.
+#[derive(Clone, Debug)]
+pub enum Coercion {
+    EpochSeconds,
+    EpochMillis,
+    Rfc3339,
+    // A chrono `strftime` pattern. Timestamps without an offset are taken as
+    // UTC; patterns without a time produce a date.
+    Format(String),
+    // Strips `_`, `,` and whitespace group separators. Numbers that arrive as
+    // JSON numbers have already been through an f64.
+    Decimal,
+}
+
+impl Coercion {
+    fn name(&self) -> String {
+        match self {
+            Coercion::EpochSeconds => "epoch seconds".to_string(),
+            Coercion::EpochMillis => "epoch milliseconds".to_string(),
+            Coercion::Rfc3339 => "an RFC 3339 timestamp".to_string(),
+            Coercion::Format(format) => format!("the format {:?}", format),
+            Coercion::Decimal => "a decimal".to_string(),
+        }
+    }
+
+    fn apply(&self, value: &Value) -> Option<Value> {
+        let text = match value {
+            Value::String(s) => s.trim().to_string(),
+            Value::Number(n) => n.to_string(),
+            _ => return None,
+        };
+        let coerced = match self {
+            Coercion::EpochSeconds => epoch(&text, 1)?,
+            Coercion::EpochMillis => epoch(&text, 1000)?,
+            Coercion::Rfc3339 => DateTime::parse_from_rfc3339(&text).ok()?.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            Coercion::Format(format) => formatted(&text, format)?,
+            Coercion::Decimal => decimal(&text)?,
+        };
+        Some(Value::String(coerced))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoercionError {
+    pub path: String,
+    pub expected: String,
+}
+
+impl fmt::Display for CoercionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Field {} is not {}", self.path, self.expected)
+    }
+}
+
+impl std::error::Error for CoercionError {}
+
+// Rewrites values at JSON pointers (`*` matching any single segment) before
+// typed deserialization. Missing and null fields are left alone so `Option`
+// fields keep working.
+#[derive(Clone, Debug, Default)]
+pub struct Coercions {
+    rules: Vec<(String, Coercion)>,
+}
+
+impl Coercions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn path(mut self, path: &str, coercion: Coercion) -> Self {
+        self.rules.push((path.to_string(), coercion));
+        self
+    }
+
+    pub(crate) fn apply(&self, value: &mut Value) -> Result<(), CoercionError> {
+        self.walk(&mut Vec::new(), value)
+    }
+
+    fn walk(&self, path: &mut Vec<String>, value: &mut Value) -> Result<(), CoercionError> {
+        let rule = self.rules.iter().find(|(pattern, _)| {
+            pattern.split('/').count() - 1 == path.len() && pointer::matches(pattern, path)
+        });
+        if let (Some((_, coercion)), false) = (rule, value.is_null()) {
+            *value = coercion.apply(value).ok_or_else(|| CoercionError {
+                path: pointer::to_pointer(path),
+                expected: coercion.name(),
+            })?;
+            return Ok(());
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    path.push(key.clone());
+                    let result = self.walk(path, field);
+                    path.pop();
+                    result?;
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    let result = self.walk(path, item);
+                    path.pop();
+                    result?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn epoch(text: &str, per_second: i64) -> Option<String> {
+    let (seconds, nanos) = match text.parse::<i64>() {
+        Ok(n) => (n.div_euclid(per_second), n.rem_euclid(per_second) * (1_000_000_000 / per_second)),
+        Err(_) => {
+            let f = text.parse::<f64>().ok()? / per_second as f64;
+            (f.floor() as i64, ((f - f.floor()) * 1e9).round() as i64)
+        }
+    };
+    let time = DateTime::<Utc>::from_timestamp(seconds, nanos.clamp(0, 999_999_999) as u32)?;
+    Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+fn formatted(text: &str, format: &str) -> Option<String> {
+    if let Ok(time) = DateTime::parse_from_str(text, format) {
+        return Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(text, format) {
+        return Some(time.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+    NaiveDate::parse_from_str(text, format).ok().map(|date| date.to_string())
+}
+
+fn decimal(text: &str) -> Option<String> {
+    let digits: String = text.chars().filter(|c| !matches!(c, '_' | ',') && !c.is_whitespace()).collect();
+    let unsigned = digits.strip_prefix(['-', '+']).unwrap_or(&digits);
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let valid = !(whole.is_empty() && fraction.is_empty())
+        && whole.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit());
+    valid.then(|| digits.trim_start_matches('+').to_string())
+}
@@ -0,0 +1,63 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+struct State {
+    online: bool,
+    // Bumped on every offline -> online transition to wake sleeping retries.
+    reconnects: u64,
+    // Bumped to wake sleepers so they can re-check their deadline.
+    interrupts: u64,
+}
+
+pub(crate) struct Connectivity {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Connectivity {
+    pub fn new() -> Self {
+        Connectivity {
+            state: Mutex::new(State {
+                online: true,
+                reconnects: 0,
+                interrupts: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn set_online(&self, online: bool) {
+        let mut state = self.state.lock().unwrap();
+        if online && !state.online {
+            state.reconnects += 1;
+            self.changed.notify_all();
+        }
+        state.online = online;
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.state.lock().unwrap().online
+    }
+
+    pub fn interrupt(&self) {
+        self.state.lock().unwrap().interrupts += 1;
+        self.changed.notify_all();
+    }
+
+    // Sleeps for `duration`, returning early if the network comes back (true)
+    // or `interrupt` is called (false) meanwhile.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        let reconnects = state.reconnects;
+        let interrupts = state.interrupts;
+        while state.reconnects == reconnects && state.interrupts == interrupts {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        state.reconnects != reconnects
+    }
+}
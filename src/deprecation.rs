@@ -0,0 +1,50 @@
+use reqwest::header::{HeaderMap, WARNING};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+// Raised the first time an endpoint answers with Deprecation, Sunset or
+// Warning headers. Values are passed through as sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeprecationNotice {
+    pub endpoint: String,
+    pub deprecation: Option<String>,
+    pub sunset: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+pub(crate) type DeprecationHook = Arc<dyn Fn(&DeprecationNotice) + Send + Sync>;
+
+// Shared by every client so each endpoint is reported once per process.
+static REPORTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// `endpoint` includes the host so two APIs sharing a path are reported apart.
+pub(crate) fn check(endpoint: &str, headers: &HeaderMap, hook: Option<&DeprecationHook>) {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let notice = DeprecationNotice {
+        endpoint: endpoint.to_string(),
+        deprecation: header("deprecation"),
+        sunset: header("sunset"),
+        warnings: headers
+            .get_all(WARNING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .map(str::to_string)
+            .collect(),
+    };
+    if notice.deprecation.is_none() && notice.sunset.is_none() && notice.warnings.is_empty() {
+        return;
+    }
+    if !REPORTED.lock().unwrap().insert(endpoint.to_string()) {
+        return;
+    }
+    match hook {
+        Some(hook) => hook(&notice),
+        None => eprintln!(
+            "easier_apis: {} sent deprecation headers (deprecation: {}, sunset: {}, warnings: {:?})",
+            notice.endpoint,
+            notice.deprecation.as_deref().unwrap_or("-"),
+            notice.sunset.as_deref().unwrap_or("-"),
+            notice.warnings
+        ),
+    }
+}
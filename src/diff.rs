@@ -0,0 +1,109 @@
+use crate::pointer;
+use serde::Serialize;
+use serde_json::Value;
+
+// Paths are JSON pointers (`/data/0/id`); a `*` segment in an ignored path
+// matches any key or index, and ignoring a path also ignores everything below it.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    pub ignore_paths: Vec<String>,
+}
+
+impl DiffOptions {
+    pub fn ignore(mut self, path: &str) -> Self {
+        self.ignore_paths.push(path.to_string());
+        self
+    }
+
+    fn is_ignored(&self, path: &[String]) -> bool {
+        self.ignore_paths.iter().any(|ignored| pointer::matches(ignored, path))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Difference {
+    pub path: String,
+    pub kind: DiffKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<Value>,
+}
+
+pub fn diff(left: &Value, right: &Value, options: &DiffOptions) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_at(&mut Vec::new(), left, right, options, &mut differences);
+    differences
+}
+
+fn diff_at(
+    path: &mut Vec<String>,
+    left: &Value,
+    right: &Value,
+    options: &DiffOptions,
+    out: &mut Vec<Difference>,
+) {
+    if options.is_ignored(path) {
+        return;
+    }
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            for (key, l_value) in l {
+                path.push(key.clone());
+                match r.get(key) {
+                    Some(r_value) => diff_at(path, l_value, r_value, options, out),
+                    None => push(path, DiffKind::Removed, Some(l_value), None, options, out),
+                }
+                path.pop();
+            }
+            for (key, r_value) in r {
+                if !l.contains_key(key) {
+                    path.push(key.clone());
+                    push(path, DiffKind::Added, None, Some(r_value), options, out);
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(l), Value::Array(r)) => {
+            for index in 0..l.len().max(r.len()) {
+                path.push(index.to_string());
+                match (l.get(index), r.get(index)) {
+                    (Some(l_value), Some(r_value)) => diff_at(path, l_value, r_value, options, out),
+                    (Some(l_value), None) => push(path, DiffKind::Removed, Some(l_value), None, options, out),
+                    (None, Some(r_value)) => push(path, DiffKind::Added, None, Some(r_value), options, out),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        _ if left != right => push(path, DiffKind::Changed, Some(left), Some(right), options, out),
+        _ => {}
+    }
+}
+
+fn push(
+    path: &[String],
+    kind: DiffKind,
+    left: Option<&Value>,
+    right: Option<&Value>,
+    options: &DiffOptions,
+    out: &mut Vec<Difference>,
+) {
+    if options.is_ignored(path) {
+        return;
+    }
+    out.push(Difference {
+        path: pointer::to_pointer(path),
+        kind,
+        left: left.cloned(),
+        right: right.cloned(),
+    });
+}
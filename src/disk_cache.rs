@@ -0,0 +1,136 @@
+use crate::signing;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    url: String,
+    // Epoch seconds.
+    stored_at: u64,
+    body: Value,
+}
+
+// GET responses kept on disk, one file per URL, for reading while offline.
+// When the files outgrow `max_bytes` the least recently used are deleted.
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    entries: Mutex<CacheEntries>,
+}
+
+struct CacheEntries {
+    tick: u64,
+    // File name to (size, last use). Files found at startup are ordered by
+    // modification time.
+    files: HashMap<String, (u64, u64)>,
+}
+
+impl DiskCache {
+    pub fn open<P: AsRef<Path>>(dir: P, max_bytes: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".json") {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            found.push((metadata.modified().unwrap_or(UNIX_EPOCH), name, metadata.len()));
+        }
+        found.sort();
+        let files = found
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, name, size))| (name, (size, i as u64)))
+            .collect::<HashMap<_, _>>();
+        let cache = DiskCache {
+            dir,
+            max_bytes,
+            entries: Mutex::new(CacheEntries {
+                tick: files.len() as u64,
+                files,
+            }),
+        };
+        cache.evict(&mut cache.entries.lock().unwrap())?;
+        Ok(cache)
+    }
+
+    pub fn get(&self, url: &str) -> Option<Value> {
+        let name = file_name(url);
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.files.get_mut(&name)?.1 = tick;
+        drop(entries);
+        let stored: StoredResponse = serde_json::from_slice(&std::fs::read(self.dir.join(&name)).ok()?).ok()?;
+        (stored.url == url).then_some(stored.body)
+    }
+
+    pub fn insert(&self, url: &str, body: &Value, now: SystemTime) -> Result<(), Box<dyn std::error::Error>> {
+        let name = file_name(url);
+        let contents = serde_json::to_vec(&StoredResponse {
+            url: url.to_string(),
+            stored_at: now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            body: body.clone(),
+        })?;
+        let size = contents.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+        let path = self.dir.join(&name);
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, &path)?;
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.files.insert(name, (size, tick));
+        self.evict(&mut entries)
+    }
+
+    pub fn clear(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut entries = self.entries.lock().unwrap();
+        for name in entries.files.keys() {
+            remove(&self.dir.join(name))?;
+        }
+        entries.files.clear();
+        Ok(())
+    }
+
+    fn evict(&self, entries: &mut CacheEntries) -> Result<(), Box<dyn std::error::Error>> {
+        let mut total: u64 = entries.files.values().map(|(size, _)| size).sum();
+        while total > self.max_bytes {
+            let Some((name, size)) = entries
+                .files
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(name, (size, _))| (name.clone(), *size))
+            else {
+                break;
+            };
+            entries.files.remove(&name);
+            total -= size;
+            remove(&self.dir.join(&name))?;
+        }
+        Ok(())
+    }
+}
+
+// Already gone is fine.
+fn remove(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn file_name(url: &str) -> String {
+    format!("{}.json", signing::hex(&Sha256::digest(url.as_bytes())))
+}
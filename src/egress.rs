@@ -0,0 +1,200 @@
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Url;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EgressError {
+    HostNotAllowed(String),
+    PrivateAddress(String, IpAddr),
+    InvalidCidr(String),
+}
+
+impl fmt::Display for EgressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EgressError::HostNotAllowed(host) => write!(f, "Requests to {} are not allowed", host),
+            EgressError::PrivateAddress(host, ip) => write!(f, "{} resolves to the private address {}", host, ip),
+            EgressError::InvalidCidr(cidr) => write!(f, "Invalid CIDR: {}", cidr),
+        }
+    }
+}
+
+impl std::error::Error for EgressError {}
+
+#[derive(Clone, Copy, Debug)]
+struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn parse(cidr: &str) -> Option<Self> {
+        let (addr, prefix) = match cidr.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+            None => (cidr.parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Network { addr: addr.to_canonical(), prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let mask = |bits: u8, width: u8| if bits == 0 { 0 } else { u128::MAX << (width - bits) };
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = mask(self.prefix, 32) as u32;
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = mask(self.prefix, 128);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Restricts where requests (and every redirect hop) may go. With no allowed
+// hosts or networks any public host is reachable; otherwise the host must be
+// listed by name (`*.example.com` covers subdomains) or resolve into an allowed
+// network. Loopback, private, link-local and similar addresses are refused
+// unless an allowed network covers them, even for allowed host names.
+//
+// Names are resolved when checked and again when connecting, so a resolver
+// that answers differently the second time (DNS rebinding) isn't caught.
+#[derive(Clone, Debug)]
+pub struct EgressPolicy {
+    hosts: Vec<String>,
+    networks: Vec<Network>,
+    block_private: bool,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        EgressPolicy {
+            hosts: Vec::new(),
+            networks: Vec::new(),
+            block_private: true,
+        }
+    }
+}
+
+impl EgressPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    // `10.0.0.0/8`, `2001:db8::/32`, or a bare address.
+    pub fn allow_cidr(mut self, cidr: &str) -> Result<Self, EgressError> {
+        let network = Network::parse(cidr.trim()).ok_or_else(|| EgressError::InvalidCidr(cidr.to_string()))?;
+        self.networks.push(network);
+        Ok(self)
+    }
+
+    pub fn block_private(mut self, enabled: bool) -> Self {
+        self.block_private = enabled;
+        self
+    }
+
+    // Allowed names that don't resolve pass, so the request fails with the
+    // client's own connect error.
+    pub(crate) fn check(&self, url: &Url) -> Result<(), EgressError> {
+        let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']).to_ascii_lowercase();
+        let addrs: Vec<IpAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => (host.as_str(), url.port_or_known_default().unwrap_or(80))
+                .to_socket_addrs()
+                .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+                .unwrap_or_default(),
+        };
+        let named = (self.hosts.is_empty() && self.networks.is_empty()) || self.hosts.iter().any(|h| host_matches(h, &host));
+        if !named && addrs.is_empty() {
+            return Err(EgressError::HostNotAllowed(host));
+        }
+        for ip in addrs {
+            let listed = self.networks.iter().any(|network| network.contains(ip));
+            if !named && !listed {
+                return Err(EgressError::HostNotAllowed(host));
+            }
+            if self.block_private && !listed && is_internal(ip) {
+                return Err(EgressError::PrivateAddress(host, ip));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn redirect_policy(self: &Arc<Self>, max_redirects: usize) -> Policy {
+        let policy = Arc::clone(self);
+        Policy::custom(move |attempt: Attempt| {
+            if max_redirects == 0 {
+                return attempt.stop();
+            }
+            if attempt.previous().len() >= max_redirects {
+                return attempt.error("Too many redirects");
+            }
+            match policy.check(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        })
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => is_internal_v6(ip),
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10.
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking, 198.18.0.0/15.
+        || (a == 198 && (b == 18 || b == 19))
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10.
+        || (first & 0xffc0) == 0xfe80
+}
+
+// A blocked redirect surfaces as a reqwest error wrapping the policy's error.
+pub(crate) fn find(error: &(dyn std::error::Error + 'static)) -> Option<EgressError> {
+    let mut current = Some(error);
+    while let Some(err) = current {
+        if let Some(egress) = err.downcast_ref::<EgressError>() {
+            return Some(egress.clone());
+        }
+        current = err.source();
+    }
+    None
+}
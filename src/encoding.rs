@@ -0,0 +1,287 @@
+use bytes::Bytes;
+use flate2::read::MultiGzDecoder;
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH};
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "zstd")]
+use reqwest::blocking::{Body, Request};
+#[cfg(feature = "zstd")]
+use reqwest::header::HeaderValue;
+
+const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_RATIO: u64 = 200;
+// The ratio is only checked once this much has been decoded, so small bodies
+// that happen to compress very well aren't rejected.
+const RATIO_GRACE: u64 = 1024 * 1024;
+// Below this simd-json's setup costs more than it saves.
+#[cfg(feature = "simd-json")]
+const SIMD_JSON_MIN: usize = 64 * 1024;
+
+// Caps on what a compressed response body may expand to, so a malicious or
+// misconfigured server can't exhaust memory or disk. Bodies past either limit
+// fail with `DecompressionError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecompressionLimits {
+    // Decoded bytes.
+    pub max_bytes: u64,
+    // Decoded bytes per received byte.
+    pub max_ratio: u64,
+}
+
+impl DecompressionLimits {
+    pub fn new(max_bytes: u64, max_ratio: u64) -> Self {
+        DecompressionLimits { max_bytes, max_ratio }
+    }
+
+    pub fn unlimited() -> Self {
+        DecompressionLimits::new(u64::MAX, u64::MAX)
+    }
+}
+
+// 100 MiB and 200:1.
+impl Default for DecompressionLimits {
+    fn default() -> Self {
+        DecompressionLimits::new(DEFAULT_MAX_BYTES, DEFAULT_MAX_RATIO)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecompressionError {
+    TooLarge { encoding: &'static str, limit: u64 },
+    RatioExceeded { encoding: &'static str, received: u64, decoded: u64, limit: u64 },
+}
+
+impl fmt::Display for DecompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompressionError::TooLarge { encoding, limit } => {
+                write!(f, "{} response body decompresses to more than {} bytes", encoding, limit)
+            }
+            DecompressionError::RatioExceeded { encoding, received, decoded, limit } => write!(
+                f,
+                "{} response body expanded from {} to over {} bytes, past the {}:1 limit",
+                encoding, received, decoded, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecompressionError {}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "brotli")]
+    Brotli,
+}
+
+impl Encoding {
+    fn of(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            return Some(Encoding::Gzip);
+        }
+        #[cfg(feature = "zstd")]
+        if value.eq_ignore_ascii_case("zstd") {
+            return Some(Encoding::Zstd);
+        }
+        #[cfg(feature = "brotli")]
+        if value.eq_ignore_ascii_case("br") {
+            return Some(Encoding::Brotli);
+        }
+        None
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br",
+        }
+    }
+
+    fn decoder<'a, R: Read + Send + 'a>(self, input: R) -> io::Result<Box<dyn Read + Send + 'a>> {
+        match self {
+            Encoding::Gzip => Ok(Box::new(MultiGzDecoder::new(input))),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Ok(Box::new(zstd::stream::Decoder::new(input)?)),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Ok(Box::new(brotli_decompressor::Decompressor::new(input, 4096))),
+        }
+    }
+}
+
+// Value for `Accept-Encoding` covering the decoders compiled in.
+pub(crate) fn accept_encoding() -> &'static str {
+    match (cfg!(feature = "zstd"), cfg!(feature = "brotli")) {
+        (true, true) => "zstd, br, gzip",
+        (true, false) => "zstd, gzip",
+        (false, true) => "br, gzip",
+        (false, false) => "gzip",
+    }
+}
+
+// Reads the body, undoing any content-encoding we advertised. Also returns the
+// size of the body as received. Encoded bodies are decoded as they arrive, so
+// one past the limits is never held in full.
+pub(crate) fn read_body(response: Response, limits: &DecompressionLimits) -> Result<(Bytes, u64), Box<dyn std::error::Error>> {
+    let Some(encoding) = Encoding::of(response.headers()) else {
+        let bytes = response.bytes()?;
+        let received = bytes.len() as u64;
+        return Ok((bytes, received));
+    };
+    let received = Arc::new(AtomicU64::new(0));
+    let counted = Counted {
+        inner: response,
+        count: Arc::clone(&received),
+    };
+    let mut decoded = Vec::new();
+    Limited::new(encoding.decoder(counted)?, encoding, *limits, Arc::clone(&received))
+        .take(limits.max_bytes.saturating_add(1))
+        .read_to_end(&mut decoded)
+        .map_err(io_error)?;
+    Ok((Bytes::from(decoded), received.load(Ordering::Relaxed)))
+}
+
+// Undoes any Content-Encoding reqwest doesn't handle itself. Bodies without
+// one are passed on as received, without a copy.
+#[cfg(feature = "async")]
+pub(crate) fn decode_body(headers: &HeaderMap, bytes: &Bytes, limits: &DecompressionLimits) -> Result<Bytes, Box<dyn std::error::Error>> {
+    let Some(encoding) = Encoding::of(headers) else {
+        return Ok(bytes.clone());
+    };
+    let received = Arc::new(AtomicU64::new(bytes.len() as u64));
+    let mut decoded = Vec::new();
+    Limited::new(encoding.decoder(&bytes[..])?, encoding, *limits, received)
+        .take(limits.max_bytes.saturating_add(1))
+        .read_to_end(&mut decoded)
+        .map_err(io_error)?;
+    Ok(Bytes::from(decoded))
+}
+
+// Parses a decoded body. With the `simd-json` feature, large bodies go through
+// simd-json, which parses in place; a body nothing else holds is reused
+// rather than copied.
+pub(crate) fn parse_json(body: Bytes) -> Result<Value, Box<dyn std::error::Error>> {
+    #[cfg(feature = "simd-json")]
+    if body.len() >= SIMD_JSON_MIN {
+        let mut body = Vec::from(body);
+        return Ok(simd_json::serde::from_slice(&mut body)?);
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
+
+// Streaming counterpart of `read_body`. Decoded responses lose their
+// Content-Encoding/Content-Length headers, which no longer describe the body.
+pub(crate) fn body_reader(
+    response: Response,
+    headers: &mut HeaderMap,
+    limits: &DecompressionLimits,
+) -> Result<Box<dyn Read + Send>, Box<dyn std::error::Error>> {
+    let Some(encoding) = Encoding::of(headers) else {
+        return Ok(Box::new(response));
+    };
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    let received = Arc::new(AtomicU64::new(0));
+    let counted = Counted {
+        inner: response,
+        count: Arc::clone(&received),
+    };
+    Ok(Box::new(Limited::new(encoding.decoder(counted)?, encoding, *limits, received)))
+}
+
+// Unwraps a `DecompressionError` that had to travel inside an `io::Error`, so
+// it can be found in the error chain.
+pub(crate) fn io_error(error: io::Error) -> Box<dyn std::error::Error> {
+    match error.downcast::<DecompressionError>() {
+        Ok(decompression) => Box::new(decompression),
+        Err(error) => error.into(),
+    }
+}
+
+// Counts the compressed bytes read from the network.
+struct Counted<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+struct Limited<'a> {
+    inner: Box<dyn Read + Send + 'a>,
+    encoding: Encoding,
+    limits: DecompressionLimits,
+    received: Arc<AtomicU64>,
+    decoded: u64,
+}
+
+impl<'a> Limited<'a> {
+    fn new(inner: Box<dyn Read + Send + 'a>, encoding: Encoding, limits: DecompressionLimits, received: Arc<AtomicU64>) -> Self {
+        Limited {
+            inner,
+            encoding,
+            limits,
+            received,
+            decoded: 0,
+        }
+    }
+}
+
+impl Read for Limited<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.decoded += n as u64;
+        let encoding = self.encoding.name();
+        if self.decoded > self.limits.max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                DecompressionError::TooLarge {
+                    encoding,
+                    limit: self.limits.max_bytes,
+                },
+            ));
+        }
+        let received = self.received.load(Ordering::Relaxed).max(1);
+        if self.decoded > RATIO_GRACE && self.decoded > received.saturating_mul(self.limits.max_ratio) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                DecompressionError::RatioExceeded {
+                    encoding,
+                    received,
+                    decoded: self.decoded,
+                    limit: self.limits.max_ratio,
+                },
+            ));
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "zstd")]
+pub(crate) fn compress_request(request: &mut Request) -> Result<(), Box<dyn std::error::Error>> {
+    if request.headers().contains_key(CONTENT_ENCODING) {
+        return Ok(());
+    }
+    let compressed = match request.body().and_then(Body::as_bytes) {
+        Some(bytes) if !bytes.is_empty() => zstd::stream::encode_all(bytes, 0)?,
+        _ => return Ok(()),
+    };
+    *request.body_mut() = Some(Body::from(compressed));
+    request.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static("zstd"));
+    Ok(())
+}
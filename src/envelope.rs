@@ -0,0 +1,85 @@
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvelopeError {
+    // The envelope's error field was set, or its status field didn't match.
+    Api(Value),
+    Missing(String),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::Api(error) => match error.get("message").unwrap_or(error) {
+                Value::String(message) => write!(f, "API error: {}", message),
+                other => write!(f, "API error: {}", other),
+            },
+            EnvelopeError::Missing(field) => write!(f, "Response envelope has no {} field", field),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+// Describes how an API wraps its payloads, e.g. `{"status":"ok","data":{..}}`
+// or `{"result":..,"error":null}`. Fields are top-level keys, or JSON pointers
+// when they start with `/`.
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    data: String,
+    error: Option<String>,
+    status: Option<(String, Value)>,
+}
+
+impl Envelope {
+    pub fn new(data: &str) -> Self {
+        Envelope {
+            data: data.to_string(),
+            error: None,
+            status: None,
+        }
+    }
+
+    // A non-null value here fails the call with `EnvelopeError::Api`.
+    pub fn error(mut self, field: &str) -> Self {
+        self.error = Some(field.to_string());
+        self
+    }
+
+    // Any other value in `field` fails the call, carrying the error field if
+    // it is set and the whole body otherwise.
+    pub fn success(mut self, field: &str, value: Value) -> Self {
+        self.status = Some((field.to_string(), value));
+        self
+    }
+
+    pub(crate) fn unwrap(&self, mut body: Value) -> Result<Value, EnvelopeError> {
+        let error = self.error.as_deref().and_then(|field| lookup(&body, field));
+        if let Some(error) = error.filter(|error| !error.is_null()) {
+            return Err(EnvelopeError::Api(error.clone()));
+        }
+        if let Some((field, expected)) = &self.status {
+            if lookup(&body, field) != Some(expected) {
+                return Err(EnvelopeError::Api(body));
+            }
+        }
+        take(&mut body, &self.data).ok_or_else(|| EnvelopeError::Missing(self.data.clone()))
+    }
+}
+
+fn lookup<'a>(body: &'a Value, field: &str) -> Option<&'a Value> {
+    if field.starts_with('/') {
+        body.pointer(field)
+    } else {
+        body.get(field)
+    }
+}
+
+fn take(body: &mut Value, field: &str) -> Option<Value> {
+    if field.starts_with('/') {
+        body.pointer_mut(field).map(Value::take)
+    } else {
+        body.as_object_mut()?.remove(field)
+    }
+}
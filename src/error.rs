@@ -0,0 +1,323 @@
+use crate::coerce::CoercionError;
+use crate::encoding::DecompressionError;
+use crate::envelope::EnvelopeError;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+// What a failed call means for the user, independent of the vendor's codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    Retryable,
+    Auth,
+    Validation,
+    Quota,
+}
+
+impl ErrorCategory {
+    // Used where no translator is registered, or it returns None.
+    pub fn from_status(status: u16) -> Option<Self> {
+        match status {
+            401 | 403 => Some(ErrorCategory::Auth),
+            400 | 409 | 422 => Some(ErrorCategory::Validation),
+            429 => Some(ErrorCategory::Quota),
+            408 | 500..=599 => Some(ErrorCategory::Retryable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Retryable => "retryable",
+            ErrorCategory::Auth => "auth",
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Quota => "quota",
+        }
+    }
+}
+
+// Maps (status, vendor error code) to a category.
+pub(crate) type ErrorTranslator = Arc<dyn Fn(u16, Option<&str>) -> Option<ErrorCategory> + Send + Sync>;
+
+// Why a request failed. Request methods return this; configuration methods
+// still return boxed errors, which `find` can search.
+#[derive(Debug)]
+pub enum ApiError {
+    // No response: DNS, connect, TLS or the connection dropped.
+    Connection(Box<dyn std::error::Error>),
+    // A `TimeoutError` somewhere in the chain says which limit ran out, or a
+    // `PollError` when polling gave up.
+    Timeout(Box<dyn std::error::Error>),
+    // A non-success response. `code` is the vendor's error code from the
+    // body, if it has one.
+    Http {
+        status: u16,
+        body: String,
+        code: Option<String>,
+        category: Option<ErrorCategory>,
+    },
+    // The body wasn't the JSON, envelope or type expected.
+    Decode(Box<dyn std::error::Error>),
+    // A pipeline layer rejected the request or response: signing and
+    // verification, encryption, versioning.
+    Middleware(Box<dyn std::error::Error>),
+    // Anything else, e.g. policy rejections or a client shutting down.
+    Other(Box<dyn std::error::Error>),
+}
+
+impl ApiError {
+    pub(crate) fn http(status: u16, body: String, translator: Option<&ErrorTranslator>) -> Self {
+        let code = vendor_code(&body);
+        let category = translator
+            .and_then(|translate| translate(status, code.as_deref()))
+            .or_else(|| ErrorCategory::from_status(status));
+        ApiError::Http { status, body, code, category }
+    }
+
+    // The `ApiError` anywhere in an error's source chain.
+    pub fn find<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a ApiError> {
+        let mut current = Some(error);
+        while let Some(err) = current {
+            if let Some(api) = err.downcast_ref::<ApiError>() {
+                return Some(api);
+            }
+            current = err.source();
+        }
+        None
+    }
+
+    // Which timeout a `Timeout` error hit, and after how long.
+    pub fn timeout(&self) -> Option<&TimeoutError> {
+        let ApiError::Timeout(error) = self else {
+            return None;
+        };
+        let mut current = Some(error.as_ref());
+        while let Some(err) = current {
+            if let Some(timeout) = err.downcast_ref::<TimeoutError>() {
+                return Some(timeout);
+            }
+            current = err.source();
+        }
+        None
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ApiError::Http { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Transport failures are always retryable.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match self {
+            ApiError::Http { category, .. } => *category,
+            ApiError::Connection(_) | ApiError::Timeout(_) => Some(ErrorCategory::Retryable),
+            _ => None,
+        }
+    }
+
+    // Stable numbers for the C API, where functions returning an int report
+    // failures as one of these. `rust_core_last_error_code` also uses -7 for
+    // arguments that couldn't be parsed.
+    pub fn ffi_code(&self) -> i32 {
+        match self {
+            ApiError::Other(_) => -1,
+            ApiError::Connection(_) => -2,
+            ApiError::Timeout(_) => -3,
+            ApiError::Http { .. } => -4,
+            ApiError::Decode(_) => -5,
+            ApiError::Middleware(_) => -6,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Http { status, .. } => match reqwest::StatusCode::from_u16(*status) {
+                Ok(status) => write!(f, "HTTP error: {}", status),
+                Err(_) => write!(f, "HTTP error: {}", status),
+            },
+            ApiError::Connection(error)
+            | ApiError::Timeout(error)
+            | ApiError::Decode(error)
+            | ApiError::Middleware(error)
+            | ApiError::Other(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Http { .. } => None,
+            ApiError::Connection(error)
+            | ApiError::Timeout(error)
+            | ApiError::Decode(error)
+            | ApiError::Middleware(error)
+            | ApiError::Other(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+// Sorts a pipeline error by what's in its source chain. HTTP errors are
+// lifted out of the retry wrapper; everything else keeps it as the source, so
+// the attempt history stays reachable.
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        let error = match error.downcast::<ApiError>() {
+            Ok(api) => return *api,
+            Err(error) => error,
+        };
+        let mut current = Some(error.as_ref());
+        while let Some(err) = current {
+            if err.is::<TimeoutError>() {
+                return ApiError::Timeout(error);
+            }
+            if let Some(ApiError::Http { status, body, code, category }) = err.downcast_ref::<ApiError>() {
+                return ApiError::Http {
+                    status: *status,
+                    body: body.clone(),
+                    code: code.clone(),
+                    category: *category,
+                };
+            }
+            if let Some(e) = err.downcast_ref::<reqwest::Error>() {
+                return if e.is_timeout() {
+                    ApiError::Timeout(error)
+                } else if e.is_decode() {
+                    ApiError::Decode(error)
+                } else {
+                    ApiError::Connection(error)
+                };
+            }
+            if err.is::<serde_json::Error>() || err.is::<EnvelopeError>() || err.is::<CoercionError>() || err.is::<DecompressionError>() {
+                return ApiError::Decode(error);
+            }
+            #[cfg(feature = "simd-json")]
+            if err.is::<simd_json::Error>() {
+                return ApiError::Decode(error);
+            }
+            current = err.source();
+        }
+        ApiError::Other(error)
+    }
+}
+
+// An `ApiError` that can be handed back from a worker thread. The variant
+// and HTTP details are kept; other sources are reduced to their message.
+pub(crate) enum DetachedError {
+    Http {
+        status: u16,
+        body: String,
+        code: Option<String>,
+        category: Option<ErrorCategory>,
+    },
+    Connection(String),
+    Timeout(String),
+    Decode(String),
+    Middleware(String),
+    Other(String),
+}
+
+impl From<ApiError> for DetachedError {
+    fn from(error: ApiError) -> Self {
+        match error {
+            ApiError::Http { status, body, code, category } => DetachedError::Http { status, body, code, category },
+            ApiError::Connection(e) => DetachedError::Connection(e.to_string()),
+            ApiError::Timeout(e) => DetachedError::Timeout(e.to_string()),
+            ApiError::Decode(e) => DetachedError::Decode(e.to_string()),
+            ApiError::Middleware(e) => DetachedError::Middleware(e.to_string()),
+            ApiError::Other(e) => DetachedError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<DetachedError> for ApiError {
+    fn from(error: DetachedError) -> Self {
+        match error {
+            DetachedError::Http { status, body, code, category } => ApiError::Http { status, body, code, category },
+            DetachedError::Connection(message) => ApiError::Connection(message.into()),
+            DetachedError::Timeout(message) => ApiError::Timeout(message.into()),
+            DetachedError::Decode(message) => ApiError::Decode(message.into()),
+            DetachedError::Middleware(message) => ApiError::Middleware(message.into()),
+            DetachedError::Other(message) => ApiError::Other(message.into()),
+        }
+    }
+}
+
+// The limit that ended a call, with how long it had been running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutError {
+    // No connection within the client's connect timeout.
+    Connect { elapsed: Duration },
+    // Connected, but the response didn't arrive within the request timeout.
+    Read { elapsed: Duration },
+    // The retry policy's overall deadline ran out.
+    Deadline { elapsed: Duration, deadline: Duration },
+    // Another retry would have waited past the retry policy's backoff budget.
+    BackoffBudget { elapsed: Duration, budget: Duration },
+}
+
+impl TimeoutError {
+    // For a failed attempt that took `attempt_elapsed`, `elapsed` into the call.
+    pub(crate) fn from_transport(error: &reqwest::Error, elapsed: Duration, attempt_elapsed: Duration, deadline: Option<Duration>) -> Option<Self> {
+        if !error.is_timeout() {
+            return None;
+        }
+        Some(match deadline {
+            Some(deadline) if elapsed >= deadline => TimeoutError::Deadline { elapsed, deadline },
+            _ if error.is_connect() => TimeoutError::Connect { elapsed: attempt_elapsed },
+            _ => TimeoutError::Read { elapsed: attempt_elapsed },
+        })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            TimeoutError::Connect { elapsed }
+            | TimeoutError::Read { elapsed }
+            | TimeoutError::Deadline { elapsed, .. }
+            | TimeoutError::BackoffBudget { elapsed, .. } => *elapsed,
+        }
+    }
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutError::Connect { elapsed } => write!(f, "Connect timed out after {:?}", elapsed),
+            TimeoutError::Read { elapsed } => write!(f, "Response timed out after {:?}", elapsed),
+            TimeoutError::Deadline { elapsed, deadline } => write!(f, "Deadline of {:?} exceeded after {:?}", deadline, elapsed),
+            TimeoutError::BackoffBudget { elapsed, budget } => {
+                write!(f, "Backoff budget of {:?} exhausted after {:?}", budget, elapsed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+// `{"code": ...}`, `{"error_code": ...}`, `{"error": "..."}` or
+// `{"error": {"code": ...}}`; numeric codes are kept as text.
+fn vendor_code(body: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(body).ok()?;
+    let code = json
+        .get("code")
+        .or_else(|| json.get("error_code"))
+        .or_else(|| json.pointer("/error/code"))
+        .or_else(|| json.get("error").filter(|e| e.is_string()))?;
+    match code {
+        Value::String(code) => Some(code.clone()),
+        Value::Number(code) => Some(code.to_string()),
+        _ => None,
+    }
+}
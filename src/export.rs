@@ -0,0 +1,137 @@
+use crate::encoding;
+use crate::signing;
+use crate::stream::ResponseStream;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LINK};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const MANIFEST_FILE: &str = "manifest.json";
+
+// One page of an export, as written to disk.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportPart {
+    // Relative to the export directory.
+    pub file: String,
+    pub url: String,
+    pub content_type: Option<String>,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+// What `export` wrote, saved next to the parts as manifest.json.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub source: String,
+    // Epoch seconds.
+    pub completed_at: u64,
+    pub parts: Vec<ExportPart>,
+}
+
+impl ExportManifest {
+    pub(crate) fn new(source: &str, parts: Vec<ExportPart>, now: SystemTime) -> Self {
+        ExportManifest {
+            source: source.to_string(),
+            completed_at: now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            parts,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.parts.iter().map(|part| part.bytes).sum()
+    }
+}
+
+// The `rel="next"` target of an RFC 8288 Link header, resolved against the
+// URL that answered.
+pub(crate) fn next_link(headers: &HeaderMap, base: &Url) -> Option<Url> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+            let is_next = params.split(';').any(|param| {
+                let Some((name, value)) = param.split_once('=') else {
+                    return false;
+                };
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value.trim().trim_matches('"').split_whitespace().any(|rel| rel.eq_ignore_ascii_case("next"))
+            });
+            is_next.then(|| base.join(target).ok()).flatten()
+        })
+}
+
+// Streams the body into part-NNNNN.<ext>, hashing it on the way. The file only
+// appears under its final name once it's complete.
+pub(crate) fn write_part(dir: &Path, index: usize, stream: &mut ResponseStream) -> Result<ExportPart, Box<dyn std::error::Error>> {
+    let content_type = stream.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let file = format!("part-{:05}.{}", index, extension(content_type.as_deref()));
+    let path = dir.join(&file);
+    let tmp = path.with_extension("tmp");
+    let mut out = HashingWriter {
+        inner: io::BufWriter::new(std::fs::File::create(&tmp)?),
+        hasher: Sha256::new(),
+        bytes: 0,
+    };
+    let copied = io::copy(stream, &mut out).and_then(|_| out.flush());
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(encoding::io_error(e));
+    }
+    drop(out.inner);
+    std::fs::rename(&tmp, &path)?;
+    Ok(ExportPart {
+        file,
+        url: stream.url().to_string(),
+        content_type,
+        bytes: out.bytes,
+        sha256: signing::hex(&out.hasher.finalize()),
+    })
+}
+
+pub(crate) fn write_manifest(dir: &Path, manifest: &ExportManifest) -> Result<(), Box<dyn std::error::Error>> {
+    let path = dir.join(MANIFEST_FILE);
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(manifest)?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+fn extension(content_type: Option<&str>) -> &'static str {
+    let Some(mime) = content_type.and_then(|v| v.parse::<mime::Mime>().ok()) else {
+        return "bin";
+    };
+    match (mime.type_(), mime.subtype().as_str(), mime.suffix()) {
+        (_, "x-ndjson" | "ndjson" | "jsonl" | "jsonlines", _) => "ndjson",
+        (_, "json", _) | (_, _, Some(mime::JSON)) => "json",
+        (_, "csv", _) => "csv",
+        (_, "xml", _) | (_, _, Some(mime::XML)) => "xml",
+        (_, "gzip" | "x-gzip", _) => "gz",
+        (mime::TEXT, _, _) => "txt",
+        _ => "bin",
+    }
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
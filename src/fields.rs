@@ -0,0 +1,141 @@
+use crate::pointer;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::fmt;
+
+const PREFIX: &str = "enc:";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldEncryptionError {
+    InvalidKey,
+    Malformed(String),
+    DecryptionFailed(String),
+}
+
+impl fmt::Display for FieldEncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldEncryptionError::InvalidKey => write!(f, "Field encryption keys must be 16 or 32 bytes"),
+            FieldEncryptionError::Malformed(path) => write!(f, "Encrypted field {} is malformed", path),
+            FieldEncryptionError::DecryptionFailed(path) => write!(f, "Failed to decrypt field {}", path),
+        }
+    }
+}
+
+impl std::error::Error for FieldEncryptionError {}
+
+enum Cipher {
+    Aes128(Box<Aes128Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+// Encrypts the values at the configured JSON pointers (`*` matching any single
+// segment) with AES-GCM. Each value is serialized to JSON and replaced by
+// `enc:` + base64(12-byte nonce || ciphertext || tag); values already in that
+// form are left alone, so encrypting twice is harmless.
+pub struct FieldEncryptor {
+    cipher: Cipher,
+    paths: Vec<String>,
+}
+
+impl FieldEncryptor {
+    pub fn new(key: &[u8]) -> Result<Self, FieldEncryptionError> {
+        let cipher = match key.len() {
+            16 => Cipher::Aes128(Box::new(Aes128Gcm::new_from_slice(key).map_err(|_| FieldEncryptionError::InvalidKey)?)),
+            32 => Cipher::Aes256(Box::new(Aes256Gcm::new_from_slice(key).map_err(|_| FieldEncryptionError::InvalidKey)?)),
+            _ => return Err(FieldEncryptionError::InvalidKey),
+        };
+        Ok(FieldEncryptor { cipher, paths: Vec::new() })
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.paths.push(path.to_string());
+        self
+    }
+
+    pub fn encrypt(&self, value: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+        self.walk(&mut Vec::new(), value, &|path, field| {
+            if field.as_str().is_some_and(|s| s.starts_with(PREFIX)) {
+                return Ok(());
+            }
+            let mut nonce = [0u8; 12];
+            getrandom::getrandom(&mut nonce)?;
+            let plaintext = serde_json::to_vec(field)?;
+            let sealed = self
+                .seal(&nonce, &plaintext)
+                .map_err(|_| format!("Failed to encrypt field {}", pointer::to_pointer(path)))?;
+            let mut packed = nonce.to_vec();
+            packed.extend(sealed);
+            *field = Value::String(format!("{}{}", PREFIX, STANDARD.encode(packed)));
+            Ok(())
+        })
+    }
+
+    // Values at the configured paths that aren't in the `enc:` form are left
+    // as they are.
+    pub fn decrypt(&self, value: &mut Value) -> Result<(), Box<dyn std::error::Error>> {
+        self.walk(&mut Vec::new(), value, &|path, field| {
+            let Some(encoded) = field.as_str().and_then(|s| s.strip_prefix(PREFIX)) else {
+                return Ok(());
+            };
+            let malformed = || FieldEncryptionError::Malformed(pointer::to_pointer(path));
+            let packed = STANDARD.decode(encoded).map_err(|_| malformed())?;
+            if packed.len() < 12 + 16 {
+                return Err(malformed().into());
+            }
+            let (nonce, sealed) = packed.split_at(12);
+            let plaintext = self
+                .open(nonce, sealed)
+                .map_err(|_| FieldEncryptionError::DecryptionFailed(pointer::to_pointer(path)))?;
+            *field = serde_json::from_slice(&plaintext).map_err(|_| malformed())?;
+            Ok(())
+        })
+    }
+
+    fn seal(&self, nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, aes_gcm::Error> {
+        match &self.cipher {
+            Cipher::Aes128(cipher) => cipher.encrypt(Nonce::from_slice(nonce), plaintext),
+            Cipher::Aes256(cipher) => cipher.encrypt(Nonce::from_slice(nonce), plaintext),
+        }
+    }
+
+    fn open(&self, nonce: &[u8], sealed: &[u8]) -> Result<Vec<u8>, aes_gcm::Error> {
+        match &self.cipher {
+            Cipher::Aes128(cipher) => cipher.decrypt(Nonce::from_slice(nonce), sealed),
+            Cipher::Aes256(cipher) => cipher.decrypt(Nonce::from_slice(nonce), sealed),
+        }
+    }
+
+    // Calls `f` on the outermost values whose path matches a configured pointer.
+    fn walk<F>(&self, path: &mut Vec<String>, value: &mut Value, f: &F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: Fn(&[String], &mut Value) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        if !path.is_empty() && self.paths.iter().any(|pattern| pointer::matches(pattern, path)) {
+            return f(path, value);
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    path.push(key.clone());
+                    let result = self.walk(path, field, f);
+                    path.pop();
+                    result?;
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    let result = self.walk(path, item, f);
+                    path.pop();
+                    result?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
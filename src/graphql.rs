@@ -0,0 +1,166 @@
+use crate::signing;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// One entry of a response's `errors` list. `path` names the field that
+// failed, e.g. `["user", "posts", 1]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphQLError {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Value>,
+}
+
+impl GraphQLError {
+    // `extensions.code`, where servers put machine-readable error codes.
+    pub fn code(&self) -> Option<&str> {
+        self.extensions.as_ref()?.get("code")?.as_str()
+    }
+}
+
+impl fmt::Display for GraphQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|p| p.as_str().map_or_else(|| p.to_string(), str::to_string)).collect();
+        if path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} (at {})", self.message, path.join("."))
+        }
+    }
+}
+
+impl std::error::Error for GraphQLError {}
+
+// A response the server answered with the GraphQL envelope. GraphQL errors
+// land here rather than in `ApiError`, which is left for requests that
+// failed on the way: connection, HTTP status without an envelope, bad JSON.
+// `data` may be partial when `errors` isn't empty.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphQLResponse {
+    #[serde(default)]
+    pub data: Option<Value>,
+    #[serde(default)]
+    pub errors: Vec<GraphQLError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Value>,
+}
+
+impl GraphQLResponse {
+    // `data`, or the errors if there were any.
+    pub fn into_data(self) -> Result<Value, GraphQLErrors> {
+        match (self.data, self.errors.is_empty()) {
+            (Some(data), true) => Ok(data),
+            (data, _) => Err(GraphQLErrors { errors: self.errors, data }),
+        }
+    }
+
+    pub(crate) fn parse(body: &Value) -> Option<Self> {
+        if body.get("data").is_none() && body.get("errors").is_none() {
+            return None;
+        }
+        serde_json::from_value(body.clone()).ok()
+    }
+
+    // The server doesn't know a persisted query's hash yet.
+    pub(crate) fn persisted_query_not_found(&self) -> bool {
+        self.errors
+            .iter()
+            .any(|e| e.message == "PersistedQueryNotFound" || e.code() == Some("PERSISTED_QUERY_NOT_FOUND"))
+    }
+}
+
+// The errors of a `GraphQLResponse`, with whatever data came back alongside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphQLErrors {
+    pub errors: Vec<GraphQLError>,
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for GraphQLErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.errors.as_slice() {
+            [] => write!(f, "GraphQL response has no data"),
+            [error] => write!(f, "GraphQL error: {}", error),
+            [error, rest @ ..] => write!(f, "GraphQL error: {} (and {} more)", error, rest.len()),
+        }
+    }
+}
+
+impl std::error::Error for GraphQLErrors {}
+
+// A query for `RustCore::graphql_request`. `path` defaults to the base URL
+// itself, for cores that talk to nothing but the GraphQL endpoint.
+#[derive(Clone, Debug)]
+pub struct GraphQLRequest {
+    query: String,
+    variables: Value,
+    operation_name: Option<String>,
+    path: String,
+    persisted: bool,
+}
+
+impl GraphQLRequest {
+    pub fn new(query: &str) -> Self {
+        GraphQLRequest {
+            query: query.to_string(),
+            variables: Value::Null,
+            operation_name: None,
+            path: String::new(),
+            persisted: false,
+        }
+    }
+
+    pub fn variables(mut self, variables: Value) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    // Picks the operation to run when the document holds several.
+    pub fn operation_name(mut self, name: &str) -> Self {
+        self.operation_name = Some(name.to_string());
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    // Automatic persisted queries: sends only the query's SHA-256, and the
+    // full query once if the server hasn't seen that hash yet.
+    pub fn persisted(mut self, enabled: bool) -> Self {
+        self.persisted = enabled;
+        self
+    }
+
+    pub(crate) fn path_str(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn is_persisted(&self) -> bool {
+        self.persisted
+    }
+
+    // The JSON envelope, with or without the query text.
+    pub(crate) fn body(&self, include_query: bool) -> Value {
+        let mut body = Map::new();
+        if include_query {
+            body.insert("query".to_string(), Value::String(self.query.clone()));
+        }
+        if !self.variables.is_null() {
+            body.insert("variables".to_string(), self.variables.clone());
+        }
+        if let Some(name) = &self.operation_name {
+            body.insert("operationName".to_string(), Value::String(name.clone()));
+        }
+        if self.persisted {
+            let hash = signing::hex(&Sha256::digest(self.query.as_bytes()));
+            body.insert("extensions".to_string(), json!({"persistedQuery": {"version": 1, "sha256Hash": hash}}));
+        }
+        Value::Object(body)
+    }
+}
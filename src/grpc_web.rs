@@ -0,0 +1,138 @@
+use flate2::read::GzDecoder;
+use reqwest::header::HeaderMap;
+use std::fmt;
+use std::io::Read;
+
+pub(crate) const CONTENT_TYPE: &str = "application/grpc-web+proto";
+
+const COMPRESSED: u8 = 0x01;
+const TRAILERS: u8 = 0x80;
+
+// A gRPC-Web call that reached the server and came back with a non-OK
+// `grpc-status`. `code` is the gRPC status code, e.g. 5 for NOT_FOUND.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrpcError {
+    pub code: u32,
+    pub message: String,
+}
+
+impl GrpcError {
+    // UNAVAILABLE, like a 503, is worth retrying later.
+    pub fn is_unavailable(&self) -> bool {
+        self.code == 14
+    }
+}
+
+impl fmt::Display for GrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gRPC status {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for GrpcError {}
+
+// A single uncompressed message frame: flag byte, big-endian length, message.
+pub(crate) fn frame(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len() + 5);
+    out.push(0);
+    out.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    out.extend_from_slice(message);
+    out
+}
+
+// The message from a unary response. The status comes from the trailer frame,
+// or from the headers for trailers-only responses.
+pub(crate) fn unary_response(headers: &HeaderMap, body: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut message = None;
+    let mut trailers = Trailers::from_headers(headers);
+    let mut rest = body;
+    while !rest.is_empty() {
+        if rest.len() < 5 {
+            return Err("Truncated gRPC-Web frame header".into());
+        }
+        let flags = rest[0];
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let payload = rest.get(5..5 + len).ok_or("Truncated gRPC-Web frame")?;
+        rest = &rest[5 + len..];
+        let payload = if flags & COMPRESSED != 0 {
+            decompress(headers, payload)?
+        } else {
+            payload.to_vec()
+        };
+        if flags & TRAILERS != 0 {
+            trailers.extend(&String::from_utf8_lossy(&payload));
+        } else if message.replace(payload).is_some() {
+            return Err("Unary gRPC-Web response had more than one message".into());
+        }
+    }
+    match trailers.status {
+        Some(0) => Ok(message.unwrap_or_default()),
+        Some(code) => Err(GrpcError {
+            code,
+            message: trailers.message.unwrap_or_default(),
+        }
+        .into()),
+        None => Err("gRPC-Web response had no grpc-status".into()),
+    }
+}
+
+fn decompress(headers: &HeaderMap, payload: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match headers.get("grpc-encoding").and_then(|v| v.to_str().ok()) {
+        Some("gzip") => {
+            let mut out = Vec::new();
+            GzDecoder::new(payload).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        other => Err(format!("Unsupported grpc-encoding: {}", other.unwrap_or("none")).into()),
+    }
+}
+
+#[derive(Default)]
+struct Trailers {
+    status: Option<u32>,
+    message: Option<String>,
+}
+
+impl Trailers {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        Trailers {
+            status: headers.get("grpc-status").and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse().ok()),
+            message: headers.get("grpc-message").and_then(|v| v.to_str().ok()).map(percent_decode),
+        }
+    }
+
+    // `name: value` lines separated by CRLF, as in an HTTP/1.1 header block.
+    fn extend(&mut self, block: &str) {
+        for line in block.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            match name.trim().to_ascii_lowercase().as_str() {
+                "grpc-status" => self.status = value.trim().parse().ok(),
+                "grpc-message" => self.message = Some(percent_decode(value.trim())),
+                _ => {}
+            }
+        }
+    }
+}
+
+// grpc-message is percent-encoded UTF-8.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,63 @@
+use crate::report::FinalAttempt;
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+
+// Status and headers of a HEAD request; no body is transferred.
+#[derive(Clone, Debug)]
+pub struct ResourceInfo {
+    status: StatusCode,
+    url: Url,
+    headers: HeaderMap,
+    final_attempt: Option<FinalAttempt>,
+}
+
+impl ResourceInfo {
+    pub(crate) fn new(response: &Response) -> Self {
+        ResourceInfo {
+            status: response.status(),
+            url: response.url().clone(),
+            headers: response.headers().clone(),
+            final_attempt: response.extensions().get::<FinalAttempt>().cloned(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    // After redirects; the base for `TypedHeaders::location`.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    // Which retry attempt answered.
+    pub fn final_attempt(&self) -> Option<&FinalAttempt> {
+        self.final_attempt.as_ref()
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.header(CONTENT_LENGTH.as_str())?.parse().ok()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.header(CONTENT_TYPE.as_str())
+    }
+
+    // Raw HTTP-date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.header(LAST_MODIFIED.as_str())
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.header(ETAG.as_str())
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::DateTime;
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderMap, HeaderValue, AGE, CACHE_CONTROL, DATE, ETAG, EXPIRES, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+// A cached GET response: the parsed body plus what's needed to tell whether
+// it's still fresh and to revalidate it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedResponse {
+    pub body: Value,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub stored_at: SystemTime,
+    // Served without asking the server until then; after that it's
+    // revalidated with If-None-Match / If-Modified-Since.
+    pub fresh_until: SystemTime,
+}
+
+impl CachedResponse {
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        now < self.fresh_until
+    }
+
+    fn has_validator(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+// Where `CachePolicy` keeps responses, keyed by URL. Implement it to share a
+// cache between processes, e.g. in Redis.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, url: &str) -> Option<CachedResponse>;
+    fn put(&self, url: &str, response: CachedResponse);
+    fn remove(&self, url: &str);
+    fn clear(&self);
+}
+
+// In memory, evicting the least recently read entry once `capacity` is
+// reached.
+pub struct MemoryStore {
+    entries: Mutex<StoreEntries>,
+}
+
+struct StoreEntries {
+    capacity: usize,
+    tick: u64,
+    responses: HashMap<String, (CachedResponse, u64)>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        MemoryStore {
+            entries: Mutex::new(StoreEntries {
+                capacity,
+                tick: 0,
+                responses: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let (response, last_read) = entries.responses.get_mut(url)?;
+        *last_read = tick;
+        Some(response.clone())
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.responses.insert(url.to_string(), (response, tick));
+        while entries.responses.len() > entries.capacity {
+            let Some(oldest) = entries
+                .responses
+                .iter()
+                .min_by_key(|(_, (_, last_read))| *last_read)
+                .map(|(url, _)| url.clone())
+            else {
+                break;
+            };
+            entries.responses.remove(&oldest);
+        }
+    }
+
+    fn remove(&self, url: &str) {
+        self.entries.lock().unwrap().responses.remove(url);
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().responses.clear();
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore").field("len", &self.len()).finish()
+    }
+}
+
+// HTTP caching for GET requests, as a private (per-client) cache: responses
+// are stored by URL, served while `Cache-Control: max-age` or `Expires` says
+// they're fresh, and revalidated with their `ETag` or `Last-Modified` after
+// that; a 304 serves the stored body. `no-store` responses aren't kept, and
+// `no-cache` ones are revalidated every time. Unsafe requests to a URL drop
+// its entry.
+#[derive(Clone)]
+pub struct CachePolicy {
+    store: Arc<dyn CacheStore>,
+    default_ttl: Duration,
+}
+
+impl CachePolicy {
+    // A `MemoryStore` of `capacity` responses.
+    pub fn memory(capacity: usize) -> Self {
+        Self::with_store(Arc::new(MemoryStore::new(capacity)))
+    }
+
+    pub fn with_store(store: Arc<dyn CacheStore>) -> Self {
+        CachePolicy {
+            store,
+            default_ttl: Duration::ZERO,
+        }
+    }
+
+    // How long responses without Cache-Control or Expires stay fresh. Zero by
+    // default, so they're revalidated on every read, and only kept at all if
+    // they carry a validator.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn CacheStore> {
+        &self.store
+    }
+
+    pub(crate) fn lookup(&self, url: &str) -> Option<CachedResponse> {
+        self.store.get(url)
+    }
+
+    // Stores a 200 response, unless its headers forbid it or it would be
+    // useless: stale straight away with nothing to revalidate it by.
+    pub(crate) fn store_response(&self, url: &str, headers: &HeaderMap, body: Value, now: SystemTime) {
+        let Some(fresh_until) = self.fresh_until(headers, now) else {
+            self.store.remove(url);
+            return;
+        };
+        let response = CachedResponse {
+            body,
+            etag: header(headers, ETAG.as_str()).map(str::to_string),
+            last_modified: header(headers, LAST_MODIFIED.as_str()).map(str::to_string),
+            stored_at: now,
+            fresh_until,
+        };
+        if response.is_fresh(now) || response.has_validator() {
+            self.store.put(url, response);
+        } else {
+            self.store.remove(url);
+        }
+    }
+
+    // A 304 confirmed `cached`; its headers may replace the validators and the
+    // freshness lifetime, which otherwise starts over.
+    pub(crate) fn refresh(&self, url: &str, mut cached: CachedResponse, headers: &HeaderMap, now: SystemTime) -> Value {
+        if let Some(etag) = header(headers, ETAG.as_str()) {
+            cached.etag = Some(etag.to_string());
+        }
+        if let Some(last_modified) = header(headers, LAST_MODIFIED.as_str()) {
+            cached.last_modified = Some(last_modified.to_string());
+        }
+        let fresh_until = if headers.contains_key(CACHE_CONTROL) || headers.contains_key(EXPIRES) {
+            self.fresh_until(headers, now)
+        } else {
+            Some(now + cached.fresh_until.duration_since(cached.stored_at).unwrap_or_default())
+        };
+        match fresh_until {
+            Some(fresh_until) => {
+                cached.stored_at = now;
+                cached.fresh_until = fresh_until;
+                self.store.put(url, cached.clone());
+            }
+            None => self.store.remove(url),
+        }
+        cached.body
+    }
+
+    pub(crate) fn invalidate(&self, url: &str) {
+        self.store.remove(url);
+    }
+
+    // None for `no-store`.
+    fn fresh_until(&self, headers: &HeaderMap, now: SystemTime) -> Option<SystemTime> {
+        let mut max_age = None;
+        let mut no_cache = false;
+        for directive in headers.get_all(CACHE_CONTROL).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(',')) {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => return None,
+                "no-cache" => no_cache = true,
+                "max-age" => max_age = value.trim().trim_matches('"').parse().ok().map(Duration::from_secs),
+                _ => {}
+            }
+        }
+        if no_cache {
+            return Some(now);
+        }
+        if let Some(max_age) = max_age {
+            let age = header(headers, AGE.as_str()).and_then(|v| v.parse().ok()).map_or(Duration::ZERO, Duration::from_secs);
+            return Some(now + max_age.saturating_sub(age));
+        }
+        // Relative to the server's Date, so a skewed local clock doesn't
+        // matter. An invalid Expires, often "0", means already expired.
+        if let Some(expires) = header(headers, EXPIRES.as_str()) {
+            let expires = http_date(expires).unwrap_or(UNIX_EPOCH);
+            let date = header(headers, DATE.as_str()).and_then(http_date).unwrap_or(now);
+            return Some(now + expires.duration_since(date).unwrap_or_default());
+        }
+        Some(now + self.default_ttl)
+    }
+}
+
+// 1000 responses in memory.
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self::memory(DEFAULT_CAPACITY)
+    }
+}
+
+impl fmt::Debug for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachePolicy").field("default_ttl", &self.default_ttl).finish_non_exhaustive()
+    }
+}
+
+// Adds the validators of a stale entry, turning the GET conditional.
+pub(crate) fn make_conditional(request: &mut Request, cached: &CachedResponse) {
+    let headers = request.headers_mut();
+    if let Some(etag) = cached.etag.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+        headers.insert(IF_NONE_MATCH, etag);
+    }
+    if let Some(date) = cached.last_modified.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+        headers.insert(IF_MODIFIED_SINCE, date);
+    }
+}
+
+// A 304 is the answer to a conditional request rather than a failure.
+pub(crate) fn is_conditional(headers: &HeaderMap) -> bool {
+    headers.contains_key(IF_NONE_MATCH) || headers.contains_key(IF_MODIFIED_SINCE)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok().map(str::trim)
+}
+
+fn http_date(value: &str) -> Option<SystemTime> {
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(at.timestamp().max(0) as u64))
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Rewritten once this many lines of it are expired or superseded.
+const COMPACT_AFTER: usize = 1024;
+
+// Delivery IDs seen within the last `ttl`, for sources that deliver at least
+// once: an event stream resumed with Last-Event-ID, a feed polled again, a
+// webhook its sender retried. `first_seen` is true only the first time for
+// each ID. Clones share the same set.
+#[derive(Clone)]
+pub struct IdempotencyTracker {
+    ttl: Duration,
+    seen: Arc<Mutex<Seen>>,
+}
+
+struct Seen {
+    // ID to expiry, in epoch milliseconds.
+    expires: HashMap<String, u64>,
+    // In the order they expire, which is the order they came in. IDs seen
+    // again after expiring have a stale entry here as well.
+    order: VecDeque<(u64, String)>,
+    log: Option<Log>,
+}
+
+// One `[expires, "id"]` JSON line per ID; an expiry of 0 forgets it.
+struct Log {
+    path: PathBuf,
+    file: File,
+    lines: usize,
+}
+
+impl IdempotencyTracker {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyTracker {
+            ttl,
+            seen: Arc::new(Mutex::new(Seen {
+                expires: HashMap::new(),
+                order: VecDeque::new(),
+                log: None,
+            })),
+        }
+    }
+
+    // Keeps the IDs in `path` as well, so they're still known after a
+    // restart. IDs already in the file are loaded.
+    pub fn open<P: AsRef<Path>>(path: P, ttl: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let mut expires = HashMap::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                // A line cut short by a crash is skipped.
+                if let Ok((at, id)) = serde_json::from_str::<(u64, String)>(&line?) {
+                    expires.insert(id, at);
+                }
+            }
+        }
+        let now = millis(SystemTime::now());
+        expires.retain(|_, at| *at > now);
+        let mut order: VecDeque<(u64, String)> = expires.iter().map(|(id, at)| (*at, id.clone())).collect();
+        order.make_contiguous().sort();
+        let tracker = IdempotencyTracker::new(ttl);
+        let mut seen = tracker.seen.lock().unwrap();
+        seen.expires = expires;
+        seen.order = order;
+        seen.log = Some(Log {
+            file: write_log(&path, &seen.expires)?,
+            lines: seen.expires.len(),
+            path,
+        });
+        drop(seen);
+        Ok(tracker)
+    }
+
+    // Records `id` and returns true, or returns false if it was already seen
+    // and hasn't expired. A failed write to the file only costs the ID its
+    // persistence.
+    pub fn first_seen(&self, id: &str) -> bool {
+        let now = millis(SystemTime::now());
+        let mut seen = self.seen.lock().unwrap();
+        seen.prune(now);
+        if seen.expires.contains_key(id) {
+            return false;
+        }
+        let at = now + self.ttl.as_millis() as u64;
+        seen.expires.insert(id.to_string(), at);
+        seen.order.push_back((at, id.to_string()));
+        seen.append(at, id);
+        true
+    }
+
+    // Lets `id` through again, e.g. after handling its delivery failed and
+    // the sender will retry it.
+    pub fn forget(&self, id: &str) {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.expires.remove(id).is_some() {
+            seen.append(0, id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let mut seen = self.seen.lock().unwrap();
+        seen.prune(millis(SystemTime::now()));
+        seen.expires.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for IdempotencyTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seen = self.seen.lock().unwrap();
+        f.debug_struct("IdempotencyTracker")
+            .field("ttl", &self.ttl)
+            .field("ids", &seen.expires.len())
+            .field("path", &seen.log.as_ref().map(|log| &log.path))
+            .finish()
+    }
+}
+
+impl Seen {
+    fn prune(&mut self, now: u64) {
+        while let Some((at, _)) = self.order.front().filter(|(at, _)| *at <= now) {
+            let at = *at;
+            let (_, id) = self.order.pop_front().unwrap();
+            if self.expires.get(&id) == Some(&at) {
+                self.expires.remove(&id);
+            }
+        }
+    }
+
+    fn append(&mut self, at: u64, id: &str) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let _ = writeln!(log.file, "{}", serde_json::json!([at, id]));
+        log.lines += 1;
+        if log.lines > self.expires.len() + COMPACT_AFTER {
+            if let Ok(file) = write_log(&log.path, &self.expires) {
+                log.file = file;
+                log.lines = self.expires.len();
+            }
+        }
+    }
+}
+
+// Replaces the file with just the live IDs and opens it for appending.
+fn write_log(path: &Path, expires: &HashMap<String, u64>) -> Result<File, std::io::Error> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let mut contents = String::new();
+    for (id, at) in expires {
+        contents.push_str(&serde_json::json!([at, id]).to_string());
+        contents.push('\n');
+    }
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)?;
+    OpenOptions::new().append(true).open(path)
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
@@ -0,0 +1,169 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::blocking::{Body, Request};
+use reqwest::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::fmt;
+use zeroize::Zeroizing;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JweError {
+    InvalidKey,
+    Malformed,
+    Unsupported(String),
+    DecryptionFailed,
+}
+
+impl fmt::Display for JweError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JweError::InvalidKey => write!(f, "JWE keys must be 16 or 32 bytes"),
+            JweError::Malformed => write!(f, "Body is not a compact JWE"),
+            JweError::Unsupported(what) => write!(f, "Unsupported JWE parameter: {}", what),
+            JweError::DecryptionFailed => write!(f, "JWE decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for JweError {}
+
+// Direct symmetric encryption (`alg: dir`) only; the content-encryption key is
+// the key itself, and its length picks A128GCM or A256GCM.
+#[derive(Clone)]
+pub struct JweKey {
+    key: Zeroizing<Vec<u8>>,
+    kid: Option<String>,
+}
+
+impl JweKey {
+    pub fn new(key: &[u8]) -> Result<Self, JweError> {
+        if key.len() != 16 && key.len() != 32 {
+            return Err(JweError::InvalidKey);
+        }
+        Ok(JweKey {
+            key: Zeroizing::new(key.to_vec()),
+            kid: None,
+        })
+    }
+
+    pub fn kid(mut self, kid: &str) -> Self {
+        self.kid = Some(kid.to_string());
+        self
+    }
+
+    fn enc(&self) -> &'static str {
+        if self.key.len() == 16 {
+            "A128GCM"
+        } else {
+            "A256GCM"
+        }
+    }
+
+    fn seal(&self, aad: &[u8], nonce: &[u8; 12], plaintext: &[u8]) -> Result<Vec<u8>, JweError> {
+        let payload = Payload { msg: plaintext, aad };
+        let nonce = Nonce::from_slice(nonce);
+        let sealed = if self.key.len() == 16 {
+            Aes128Gcm::new_from_slice(&self.key).map_err(|_| JweError::InvalidKey)?.encrypt(nonce, payload)
+        } else {
+            Aes256Gcm::new_from_slice(&self.key).map_err(|_| JweError::InvalidKey)?.encrypt(nonce, payload)
+        };
+        sealed.map_err(|_| JweError::InvalidKey)
+    }
+
+    fn open(&self, aad: &[u8], nonce: &[u8], sealed: &[u8]) -> Result<Vec<u8>, JweError> {
+        if nonce.len() != 12 {
+            return Err(JweError::Malformed);
+        }
+        let payload = Payload { msg: sealed, aad };
+        let nonce = Nonce::from_slice(nonce);
+        let opened = if self.key.len() == 16 {
+            Aes128Gcm::new_from_slice(&self.key).map_err(|_| JweError::InvalidKey)?.decrypt(nonce, payload)
+        } else {
+            Aes256Gcm::new_from_slice(&self.key).map_err(|_| JweError::InvalidKey)?.decrypt(nonce, payload)
+        };
+        opened.map_err(|_| JweError::DecryptionFailed)
+    }
+
+    // Compact serialization: header..iv.ciphertext.tag (no encrypted key for `dir`).
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+        let mut header = json!({ "alg": "dir", "enc": self.enc() });
+        if let Some(kid) = &self.kid {
+            header["kid"] = Value::String(kid.clone());
+        }
+        let header = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?);
+        let mut nonce = [0u8; 12];
+        getrandom::getrandom(&mut nonce)?;
+        let mut sealed = self.seal(header.as_bytes(), &nonce, plaintext)?;
+        let tag = sealed.split_off(sealed.len() - 16);
+        Ok(format!(
+            "{}..{}.{}.{}",
+            header,
+            URL_SAFE_NO_PAD.encode(nonce),
+            URL_SAFE_NO_PAD.encode(sealed),
+            URL_SAFE_NO_PAD.encode(tag)
+        ))
+    }
+
+    pub fn decrypt(&self, compact: &[u8]) -> Result<Vec<u8>, JweError> {
+        let compact = std::str::from_utf8(compact).map_err(|_| JweError::Malformed)?.trim();
+        let parts: Vec<&str> = compact.split('.').collect();
+        let [header, encrypted_key, iv, ciphertext, tag] = parts[..] else {
+            return Err(JweError::Malformed);
+        };
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| JweError::Malformed);
+        let parsed: Value = serde_json::from_slice(&decode(header)?).map_err(|_| JweError::Malformed)?;
+        if parsed["alg"] != "dir" || !encrypted_key.is_empty() {
+            return Err(JweError::Unsupported(format!("alg {}", parsed["alg"])));
+        }
+        if parsed["enc"] != self.enc() {
+            return Err(JweError::Unsupported(format!("enc {}", parsed["enc"])));
+        }
+        let mut sealed = decode(ciphertext)?;
+        sealed.extend(decode(tag)?);
+        self.open(header.as_bytes(), &decode(iv)?, &sealed)
+    }
+}
+
+// Encrypts request bodies and decrypts response bodies for routes whose path
+// starts with a registered prefix; the longest matching prefix wins.
+#[derive(Clone, Default)]
+pub struct JweLayer {
+    routes: Vec<(String, JweKey)>,
+}
+
+impl JweLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, prefix: &str, key: JweKey) -> Self {
+        self.routes.push((prefix.to_string(), key));
+        self
+    }
+
+    pub(crate) fn key_for(&self, path: &str) -> Option<&JweKey> {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, key)| key)
+    }
+
+    pub(crate) fn encrypt_request(&self, request: &mut Request) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(key) = self.key_for(request.url().path()) else {
+            return Ok(());
+        };
+        let Some(plaintext) = request.body().and_then(Body::as_bytes) else {
+            return Ok(());
+        };
+        let compact = key.encrypt(plaintext)?;
+        request.headers_mut().remove(CONTENT_LENGTH);
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/jose"));
+        *request.body_mut() = Some(Body::from(compact));
+        Ok(())
+    }
+}
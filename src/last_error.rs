@@ -0,0 +1,57 @@
+use crate::ApiError;
+use std::cell::RefCell;
+use std::error::Error;
+use std::os::raw::{c_char, c_int};
+
+// For C API arguments that couldn't be parsed, e.g. an unknown method or
+// malformed JSON. Follows the codes of `ApiError::ffi_code`.
+pub(crate) const INVALID_ARGUMENT: i32 = -7;
+
+thread_local! {
+    // The last failure on this thread, kept until the next one or until it's
+    // cleared. Like errno, successful calls leave it alone.
+    static LAST_ERROR: RefCell<Option<(i32, String)>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn get() -> Option<(i32, String)> {
+    LAST_ERROR.with(|last| last.borrow().clone())
+}
+
+pub(crate) fn clear() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+fn set(code: i32, message: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((code, message)));
+}
+
+// Records `error` and returns its code. Anything that isn't already an
+// `ApiError` is sorted the same way pipeline errors are.
+pub(crate) fn code<E: Into<Box<dyn Error>>>(error: E) -> c_int {
+    let error = ApiError::from(error.into());
+    let code = error.ffi_code();
+    set(code, error.to_string());
+    code
+}
+
+// Records `error` for functions that return -1 on failure.
+pub(crate) fn fail<E: Into<Box<dyn Error>>>(error: E) -> c_int {
+    code(error);
+    -1
+}
+
+// Records `error` for functions that return null on failure.
+pub(crate) fn null<E: Into<Box<dyn Error>>>(error: E) -> *mut c_char {
+    code(error);
+    std::ptr::null_mut()
+}
+
+pub(crate) fn invalid(message: String) -> c_int {
+    set(INVALID_ARGUMENT, message);
+    -1
+}
+
+pub(crate) fn invalid_null(message: String) -> *mut c_char {
+    set(INVALID_ARGUMENT, message);
+    std::ptr::null_mut()
+}
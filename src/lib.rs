@@ -1,179 +1,4189 @@
-use reqwest::{Client, Request, Response};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+// Every `rust_core_*` entry point expects `core` to come from `rust_core_new` and
+// string arguments to be valid NUL-terminated C strings.
+#![allow(clippy::missing_safety_doc)]
+
+use bytes::Bytes;
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, EXPECT};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::Value;
-use std::time::Duration;
-use std::sync::Arc;
-use std::os::raw::c_char;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, RwLock};
+use std::os::raw::{c_char, c_int};
 use std::ffi::{CStr, CString};
+use zeroize::{Zeroize, Zeroizing};
+
+use breaker::Breakers;
+use builder::ClientConfig;
+use capture::{Capture, CaptureSlot, Exchange};
+use connectivity::Connectivity;
+use deprecation::DeprecationHook;
+use disk_cache::DiskCache;
+use error::{DetachedError, ErrorTranslator};
+use lifecycle::Lifecycle;
+use maintenance::{Maintenance, MaintenanceHook};
+use profile::{ActiveProfile, Profiles};
+use queue::{FailureHandler, OfflineQueue};
+use quota::QuotaStore;
+use region::RegionRouter;
+use memo::{Memo, DEFAULT_MEMO_CAPACITY};
+use middleware::{Chain, RequestMiddleware, ResponseInterceptor, ResponseMiddleware, Route};
+use report::{ErrorHook, StoreErrorHook, REQUEST_ID};
+use retry_stats::RetryRecorder;
+use size_stats::SizeRecorder;
+use throttle::TokenBucket;
+use version::VersionHook;
+
+mod aggregate;
+mod anonymize;
+mod batch;
+mod body_rules;
+mod breaker;
+mod builder;
+mod bulk;
+mod cancel;
+mod capture;
+mod case;
+mod charset;
+mod clock;
+mod coerce;
+mod connectivity;
+mod deprecation;
+mod diff;
+mod disk_cache;
+mod egress;
+mod encoding;
+mod envelope;
+mod error;
+mod export;
+mod graphql;
+mod grpc_web;
+mod head;
+mod http_cache;
+mod idempotency;
+mod last_error;
+mod lifecycle;
+mod locale;
+mod maintenance;
+mod memo;
+mod metrics;
+mod method;
+mod middleware;
+mod multipart;
+mod oauth;
+mod options;
+mod multistatus;
+mod paginate;
+mod pointer;
+mod poll;
+mod pool;
+mod profile;
+mod query;
+mod queue;
+mod quota;
+mod region;
+mod report;
+mod retry;
+mod retry_stats;
+mod schema;
+mod scope;
+mod signing;
+mod size_stats;
+mod split;
+mod sse;
+mod statsd;
+mod stream;
+mod throttle;
+mod typed_headers;
+mod verify;
+mod version;
+#[cfg(any(feature = "scripting", feature = "wasm"))]
+mod snapshot;
+#[cfg(feature = "scripting")]
+mod script;
+#[cfg(feature = "wasm")]
+mod plugin;
+#[cfg(feature = "revocation")]
+mod revocation;
+#[cfg(feature = "stub")]
+mod stub;
+#[cfg(feature = "stub")]
+mod token_server;
+#[cfg(feature = "bench")]
+mod bench;
+#[cfg(feature = "jwe")]
+mod jwe;
+#[cfg(feature = "field-encryption")]
+mod fields;
+#[cfg(feature = "async")]
+mod async_core;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "tracing")]
+mod trace;
+
+pub use aggregate::AggregateError;
+pub use anonymize::{Anonymizer, FieldAction};
+pub use batch::{BatchRequest, BatchResponse};
+pub use body_rules::{BodyAction, BodyError, BodyRule};
+pub use breaker::{CircuitBreaker, CircuitOpen, CircuitState};
+pub use builder::RustCoreBuilder;
+pub use bulk::{BulkItem, BulkOptions, BulkReport};
+pub use cancel::{Cancelled, CancellationToken};
+pub use capture::{CaptureLimit, CapturedExchange, SupportBundle, DEFAULT_CAPTURE_CAPACITY};
+pub use case::{KeyCase, KeyTransform};
+pub use charset::CharsetError;
+pub use clock::{Clock, Sleeper, SystemClock, VirtualClock};
+pub use coerce::{Coercion, CoercionError, Coercions};
+pub use deprecation::DeprecationNotice;
+pub use diff::{diff, DiffKind, DiffOptions, Difference};
+pub use egress::{EgressError, EgressPolicy};
+pub use encoding::{DecompressionError, DecompressionLimits};
+pub use error::{ApiError, ErrorCategory, TimeoutError};
+pub use envelope::{Envelope, EnvelopeError};
+pub use export::{ExportManifest, ExportPart};
+pub use graphql::{GraphQLError, GraphQLErrors, GraphQLRequest, GraphQLResponse};
+pub use grpc_web::GrpcError;
+pub use head::ResourceInfo;
+pub use http_cache::{CachePolicy, CacheStore, CachedResponse, MemoryStore};
+pub use idempotency::IdempotencyTracker;
+pub use lifecycle::Pending;
+pub use locale::Locale;
+pub use maintenance::{MaintenanceError, MaintenanceEvent, MaintenancePolicy};
+pub use method::{Method, UnsupportedMethod};
+pub use metrics::{AttemptMetrics, BodyMetrics, MetricsSink, RequestMetrics};
+pub use middleware::{ApiResponse, AuthKind, Phase, RequestContext};
+pub use multipart::{Multipart, Part};
+pub use oauth::{OAuth2, OAuth2Error};
+pub use options::RequestOptions;
+pub use multistatus::MultiStatusItem;
+pub use paginate::{Cursor, Items, LinkHeader, OffsetLimit, Page, PageMeta, Pages, PaginationStrategy};
+pub use poll::{PollError, PollOptions};
+pub use pool::{ClientPool, ClientPoolBuilder};
+pub use profile::{PolicyProfile, ProfileError};
+pub use query::{ArrayStyle, Query};
+pub use queue::QueuedRequest;
+pub use quota::{QuotaExhausted, QuotaState};
+pub use region::{RegionStatus, Regions};
+pub use report::{Attempt, ErrorReport, FinalAttempt, RetryError, Store, StoreError};
+pub use retry::RetryPolicy;
+pub use retry_stats::RetryStats;
+pub use schema::{DriftKind, Schema, SchemaDrift, SchemaTracker};
+pub use scope::{Scope, ScopeError, Task};
+pub use signing::HmacSigner;
+pub use size_stats::{SizeStats, TransferSize};
+pub use split::TrafficSplit;
+pub use statsd::{StatsdFormat, StatsdSink};
+pub use sse::{EventStream, SseError, SseEvent};
+pub use stream::{Chunks, ResponseStream};
+pub use typed_headers::{RateLimit, TypedHeaders};
+pub use verify::{ResponseVerifier, SignatureError};
+pub use version::{ApiVersion, VersionInfo};
+#[cfg(feature = "scripting")]
+pub use script::ScriptMiddleware;
+#[cfg(feature = "wasm")]
+pub use plugin::{WasmMiddleware, PLUGIN_ABI_VERSION};
+#[cfg(feature = "revocation")]
+pub use revocation::{RevocationError, RevocationPolicy};
+#[cfg(feature = "stub")]
+pub use stub::{RecordedRequest, StubServer};
+#[cfg(feature = "stub")]
+pub use token_server::TokenServer;
+#[cfg(feature = "bench")]
+pub use bench::{BenchOptions, BenchReport, LoadOptions};
+#[cfg(feature = "sentry")]
+pub use report::sentry_reporter;
+#[cfg(feature = "jwe")]
+pub use jwe::{JweError, JweKey, JweLayer};
+#[cfg(feature = "field-encryption")]
+pub use fields::{FieldEncryptionError, FieldEncryptor};
+#[cfg(feature = "async")]
+pub use async_core::AsyncRustCore;
+#[cfg(feature = "websocket")]
+pub use websocket::{Message, WebSocket, WebSocketError};
+#[cfg(feature = "stub")]
+#[doc(hidden)]
+pub use serde_json::json as __stub_json;
+
+// The blocking client starts its own runtime, which tokio refuses to do from
+// inside another one; build it on a plain thread so cores can be created in
+// async code.
+fn build_client(builder: reqwest::blocking::ClientBuilder) -> reqwest::Result<Client> {
+    #[cfg(feature = "async")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return std::thread::scope(|scope| scope.spawn(|| builder.build()).join().unwrap());
+    }
+    builder.build()
+}
+
+pub struct RustCore {
+    client: Client,
+    client_config: ClientConfig,
+    // Built on demand for calls with their own connect timeout.
+    connect_clients: Mutex<HashMap<Duration, Client>>,
+    base_url: String,
+    // Behind a lock so it can be swapped, e.g. on logout, while requests run.
+    auth: RwLock<Option<Arc<Auth>>>,
+    middleware: Chain<Phase, RequestMiddleware>,
+    response_middleware: Chain<(), ResponseMiddleware>,
+    response_interceptors: Chain<(), ResponseInterceptor>,
+    #[cfg(feature = "scripting")]
+    scripts: Vec<Arc<ScriptMiddleware>>,
+    schema_tracker: Option<Arc<SchemaTracker>>,
+    envelope: Option<Envelope>,
+    coercions: Option<Coercions>,
+    key_transform: Option<KeyTransform>,
+    traffic_splits: Vec<TrafficSplit>,
+    default_query: Vec<(String, String)>,
+    default_headers: HeaderMap,
+    regions: Option<RegionRouter>,
+    #[cfg(feature = "zstd")]
+    compress_requests: bool,
+    expect_continue_threshold: Option<u64>,
+    egress: Option<Arc<EgressPolicy>>,
+    #[cfg(feature = "revocation")]
+    revocation: Option<RevocationPolicy>,
+    clock: Arc<dyn Clock>,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    retry_policy: RetryPolicy,
+    profiles: Profiles,
+    rate_limit: Option<TokenBucket>,
+    connectivity: Arc<Connectivity>,
+    lifecycle: Lifecycle,
+    offline_queue: Option<OfflineQueue>,
+    disk_cache: Option<DiskCache>,
+    http_cache: Option<CachePolicy>,
+    breakers: Option<Breakers>,
+    maintenance: Option<Maintenance>,
+    maintenance_hook: Option<MaintenanceHook>,
+    body_rules: Vec<BodyRule>,
+    quota: Option<QuotaStore>,
+    queue_failure_handler: Option<FailureHandler>,
+    statsd: Option<Arc<StatsdSink>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    error_hook: Option<ErrorHook>,
+    store_error_hook: Option<StoreErrorHook>,
+    error_translator: Option<ErrorTranslator>,
+    capture: Capture,
+    memo: Memo,
+    retry_stats: RetryRecorder,
+    size_stats: SizeRecorder,
+    locale: Option<Locale>,
+    locale_fallback: Option<Locale>,
+    version: Option<ApiVersion>,
+    version_hook: Option<VersionHook>,
+    monitor_deprecations: bool,
+    deprecation_hook: Option<DeprecationHook>,
+    signer: Option<Arc<HmacSigner>>,
+    response_verifier: Option<Arc<ResponseVerifier>>,
+    decompression_limits: DecompressionLimits,
+    #[cfg(feature = "jwe")]
+    jwe: Option<Arc<JweLayer>>,
+    #[cfg(feature = "field-encryption")]
+    field_encryption: Option<Arc<FieldEncryptor>>,
+    #[cfg(feature = "tracing")]
+    tracing: bool,
+    #[cfg(feature = "tracing")]
+    trace_redactor: Option<trace::Redactor>,
+}
+
+pub enum Auth {
+    Bearer(String),
+    Basic(String, String),
+    Custom(String, String),
+    OAuth2(OAuth2),
+}
+
+impl Auth {
+    pub fn kind(&self) -> AuthKind {
+        match self {
+            Auth::Bearer(_) => AuthKind::Bearer,
+            Auth::Basic(_, _) => AuthKind::Basic,
+            Auth::Custom(header, _) => AuthKind::Custom(header.clone()),
+            Auth::OAuth2(_) => AuthKind::OAuth2,
+        }
+    }
+}
+
+// Credentials are wiped from memory when the last request using them is done.
+impl Drop for Auth {
+    fn drop(&mut self) {
+        match self {
+            Auth::Bearer(token) => token.zeroize(),
+            Auth::Basic(_, password) => password.zeroize(),
+            Auth::Custom(_, value) => value.zeroize(),
+            Auth::OAuth2(_) => {}
+        }
+    }
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Bearer(_) => f.write_str("Bearer(***)"),
+            Auth::Basic(username, _) => write!(f, "Basic({:?}, ***)", username),
+            Auth::Custom(header, _) => write!(f, "Custom({:?}, ***)", header),
+            Auth::OAuth2(oauth) => f.debug_tuple("OAuth2").field(oauth).finish(),
+        }
+    }
+}
+
+impl RustCore {
+    // A client with the default settings: 30s timeout, up to 10 redirects.
+    // Panics if the TLS backend can't be initialized; `builder` reports that
+    // as an error instead.
+    pub fn new(base_url: &str) -> Self {
+        Self::builder(base_url).build().unwrap()
+    }
+
+    pub fn builder(base_url: &str) -> RustCoreBuilder {
+        RustCoreBuilder::new(base_url)
+    }
+
+    fn with_config(base_url: &str, client_config: ClientConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::with_client(base_url, build_client(client_config.blocking())?, client_config))
+    }
+
+    fn with_client(base_url: &str, client: Client, client_config: ClientConfig) -> Self {
+        RustCore {
+            client,
+            client_config,
+            connect_clients: Mutex::new(HashMap::new()),
+            base_url: base_url.to_string(),
+            auth: RwLock::new(None),
+            middleware: Chain::new(),
+            response_middleware: Chain::new(),
+            response_interceptors: Chain::new(),
+            #[cfg(feature = "scripting")]
+            scripts: Vec::new(),
+            schema_tracker: None,
+            envelope: None,
+            coercions: None,
+            key_transform: None,
+            traffic_splits: Vec::new(),
+            default_query: Vec::new(),
+            default_headers: HeaderMap::new(),
+            regions: None,
+            #[cfg(feature = "zstd")]
+            compress_requests: false,
+            expect_continue_threshold: None,
+            egress: None,
+            #[cfg(feature = "revocation")]
+            revocation: None,
+            clock: Arc::new(SystemClock),
+            sleeper: None,
+            retry_policy: RetryPolicy::default(),
+            profiles: Profiles::default(),
+            rate_limit: None,
+            connectivity: Arc::new(Connectivity::new()),
+            lifecycle: Lifecycle::new(),
+            offline_queue: None,
+            disk_cache: None,
+            http_cache: None,
+            breakers: None,
+            maintenance: None,
+            maintenance_hook: None,
+            body_rules: Vec::new(),
+            quota: None,
+            queue_failure_handler: None,
+            statsd: None,
+            metrics: None,
+            error_hook: None,
+            store_error_hook: None,
+            error_translator: None,
+            capture: Capture::default(),
+            memo: Memo::new(DEFAULT_MEMO_CAPACITY),
+            retry_stats: RetryRecorder::default(),
+            size_stats: SizeRecorder::default(),
+            locale: None,
+            locale_fallback: None,
+            version: None,
+            version_hook: None,
+            monitor_deprecations: true,
+            deprecation_hook: None,
+            signer: None,
+            response_verifier: None,
+            decompression_limits: DecompressionLimits::default(),
+            #[cfg(feature = "jwe")]
+            jwe: None,
+            #[cfg(feature = "field-encryption")]
+            field_encryption: None,
+            #[cfg(feature = "tracing")]
+            tracing: false,
+            #[cfg(feature = "tracing")]
+            trace_redactor: None,
+        }
+    }
+
+    // Takes effect for requests built from now on; ones already sent, or
+    // waiting to retry, keep the credentials they went out with.
+    pub fn set_auth(&self, auth: Auth) {
+        if let Auth::Custom(key, _) = &auth {
+            self.capture.mask_header(key);
+        }
+        *self.auth.write().unwrap() = Some(Arc::new(auth));
+    }
+
+    // Sends requests without credentials again, e.g. after logout.
+    pub fn clear_auth(&self) {
+        *self.auth.write().unwrap() = None;
+    }
+
+    pub(crate) fn auth(&self) -> Option<Arc<Auth>> {
+        self.auth.read().unwrap().clone()
+    }
+
+    // Signing runs per attempt, after all middleware and body encoding, so
+    // retries go out with a fresh timestamp and nonce.
+    pub fn set_signer(&mut self, signer: HmacSigner) {
+        self.signer = Some(Arc::new(signer));
+    }
+
+    // Applies to gzip, zstd and brotli response bodies, buffered or streamed.
+    pub fn set_decompression_limits(&mut self, limits: DecompressionLimits) {
+        self.decompression_limits = limits;
+    }
+
+    // Responses without a valid signature fail with `SignatureError`.
+    pub fn set_response_verifier(&mut self, verifier: ResponseVerifier) {
+        self.response_verifier = Some(Arc::new(verifier));
+    }
+
+    // Request bodies on matching routes go out as compact JWE after middleware
+    // has run; responses on those routes must be JWE and are decrypted before
+    // response middleware sees them.
+    #[cfg(feature = "jwe")]
+    pub fn set_jwe(&mut self, layer: JweLayer) {
+        self.jwe = Some(Arc::new(layer));
+    }
+
+    // Configured fields are encrypted before request middleware and the offline
+    // queue see the body, and decrypted only after response middleware and
+    // schema tracking have run.
+    #[cfg(feature = "field-encryption")]
+    pub fn set_field_encryption(&mut self, fields: FieldEncryptor) {
+        self.field_encryption = Some(Arc::new(fields));
+    }
+
+    // Runs in the `PostAuth` phase, after everything already registered there
+    // with order 0 or less.
+    pub fn add_middleware<F>(&mut self, middleware: F)
+    where
+        F: Fn(Request) -> Request + Send + Sync + 'static,
+    {
+        self.middleware.add(None, Phase::PostAuth, 0, None, Arc::new(middleware));
+    }
+
+    // Like `add_middleware`, but only for requests matching `route`, e.g.
+    // `/admin/*` or `DELETE /users/*`. `*` matches one path segment, or any
+    // number at the end; paths are relative to the base URL.
+    pub fn add_middleware_for<F>(&mut self, route: &str, middleware: F)
+    where
+        F: Fn(Request) -> Request + Send + Sync + 'static,
+    {
+        self.middleware.add(None, Phase::PostAuth, 0, Some(Route::parse(route)), Arc::new(middleware));
+    }
+
+    pub fn add_response_middleware<F>(&mut self, middleware: F)
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        self.response_middleware.add(None, (), 0, None, Arc::new(middleware));
+    }
+
+    pub fn add_response_middleware_for<F>(&mut self, route: &str, middleware: F)
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        self.response_middleware.add(None, (), 0, Some(Route::parse(route)), Arc::new(middleware));
+    }
+
+    // Within a phase, lower `order` runs first; equal orders run in
+    // registration order. Registering a name again replaces that middleware.
+    pub fn register_middleware<F>(&mut self, name: &str, phase: Phase, order: i32, middleware: F)
+    where
+        F: Fn(Request) -> Request + Send + Sync + 'static,
+    {
+        self.middleware.add(Some(name), phase, order, None, Arc::new(middleware));
+    }
+
+    // `register_middleware` scoped to `route`, as for `add_middleware_for`.
+    pub fn register_middleware_for<F>(&mut self, name: &str, route: &str, phase: Phase, order: i32, middleware: F)
+    where
+        F: Fn(Request) -> Request + Send + Sync + 'static,
+    {
+        self.middleware.add(Some(name), phase, order, Some(Route::parse(route)), Arc::new(middleware));
+    }
+
+    pub fn register_response_middleware<F>(&mut self, name: &str, order: i32, middleware: F)
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        self.response_middleware.add(Some(name), (), order, None, Arc::new(middleware));
+    }
+
+    // Runs on every successful response before its body is parsed, so it can
+    // read headers, rewrite or unwrap the body, or turn the response into an
+    // error. Interceptors run in registration order.
+    pub fn add_response_interceptor<F>(&mut self, interceptor: F)
+    where
+        F: Fn(ApiResponse) -> Result<ApiResponse, ApiError> + Send + Sync + 'static,
+    {
+        self.response_interceptors.add(None, (), 0, None, Arc::new(interceptor));
+    }
+
+    pub fn add_response_interceptor_for<F>(&mut self, route: &str, interceptor: F)
+    where
+        F: Fn(ApiResponse) -> Result<ApiResponse, ApiError> + Send + Sync + 'static,
+    {
+        self.response_interceptors.add(None, (), 0, Some(Route::parse(route)), Arc::new(interceptor));
+    }
+
+    pub fn register_response_interceptor<F>(&mut self, name: &str, order: i32, interceptor: F)
+    where
+        F: Fn(ApiResponse) -> Result<ApiResponse, ApiError> + Send + Sync + 'static,
+    {
+        self.response_interceptors.add(Some(name), (), order, None, Arc::new(interceptor));
+    }
+
+    // Removes the request middleware, response middleware and response
+    // interceptors registered as `name`.
+    pub fn remove_middleware(&mut self, name: &str) -> bool {
+        let request = self.middleware.remove(name);
+        let response = self.response_middleware.remove(name);
+        let intercepted = self.response_interceptors.remove(name);
+        request || response || intercepted
+    }
+
+    // Named request middleware in the order it runs.
+    pub fn middleware_names(&self) -> Vec<String> {
+        self.middleware.names()
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn add_script(&mut self, script: ScriptMiddleware) {
+        let script = Arc::new(script);
+        let on_request = Arc::clone(&script);
+        self.add_middleware(move |request| on_request.on_request(request));
+        let on_response = Arc::clone(&script);
+        self.add_response_middleware(move |body| on_response.on_response(body));
+        self.scripts.push(script);
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn add_plugin(&mut self, plugin: WasmMiddleware) {
+        let plugin = Arc::new(plugin);
+        let on_request = Arc::clone(&plugin);
+        self.add_middleware(move |request| on_request.on_request(request));
+        self.add_response_middleware(move |body| plugin.on_response(body));
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn reload_scripts(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for script in &self.scripts {
+            script.reload()?;
+        }
+        Ok(())
+    }
+
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = Some(locale);
+    }
+
+    // Re-sent once with these languages when the server answers 406.
+    pub fn set_locale_fallback(&mut self, fallback: Locale) {
+        self.locale_fallback = Some(fallback);
+    }
+
+    pub fn clear_locale_fallback(&mut self) {
+        self.locale_fallback = None;
+    }
+
+    // Added to every request, e.g. `api-version`, unless the request's URL
+    // already has the parameter.
+    pub fn add_default_query(&mut self, name: &str, value: &str) {
+        self.default_query.push((name.to_string(), value.to_string()));
+    }
+
+    // Sent on every request that doesn't set the header itself. Setting a
+    // name again replaces its value.
+    pub fn set_default_header(&mut self, name: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let name = HeaderName::from_bytes(name.as_bytes())?;
+        self.default_headers.insert(name, HeaderValue::from_str(value)?);
+        Ok(())
+    }
+
+    // Checked in order after request middleware, so middleware can set the
+    // tenant header; the first split that selects a request rewrites its base URL.
+    pub fn add_traffic_split(&mut self, split: TrafficSplit) {
+        self.traffic_splits.push(split);
+    }
+
+    // Routes requests to the fastest healthy region, probing in the background
+    // until the client is dropped. Traffic splits take precedence.
+    pub fn set_regions(&mut self, regions: Regions) -> Result<(), Box<dyn std::error::Error>> {
+        self.regions = Some(RegionRouter::start(regions)?);
+        Ok(())
+    }
+
+    // Sends everything to `name` regardless of probes; None resumes selection.
+    pub fn pin_region(&self, name: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        self.regions.as_ref().ok_or("No regions configured")?.pin(name)
+    }
+
+    pub fn region_status(&self) -> Vec<RegionStatus> {
+        self.regions.as_ref().map(RegionRouter::status).unwrap_or_default()
+    }
+
+    pub fn set_api_version(&mut self, version: ApiVersion) {
+        self.version = Some(version);
+    }
+
+    // Called for every response that reports a version or carries Deprecation
+    // or Sunset headers.
+    pub fn on_version<F>(&mut self, hook: F)
+    where
+        F: Fn(&VersionInfo) + Send + Sync + 'static,
+    {
+        self.version_hook = Some(Arc::new(hook));
+    }
+
+    // On by default; each endpoint is reported once per process, to the hook
+    // if one is set and on stderr otherwise.
+    pub fn set_deprecation_monitoring(&mut self, enabled: bool) {
+        self.monitor_deprecations = enabled;
+    }
+
+    pub fn on_deprecation<F>(&mut self, hook: F)
+    where
+        F: Fn(&DeprecationNotice) + Send + Sync + 'static,
+    {
+        self.deprecation_hook = Some(Arc::new(hook));
+    }
+
+    pub fn set_schema_tracker(&mut self, tracker: Arc<SchemaTracker>) {
+        self.schema_tracker = Some(tracker);
+    }
+
+    // Applied by the typed `fetch_as`; `fetch` keeps returning the raw body.
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = Some(envelope);
+    }
+
+    // Also applied by `fetch_as` only, to the payload after envelope
+    // unwrapping; pointers are relative to the payload.
+    pub fn set_coercions(&mut self, coercions: Coercions) {
+        self.coercions = Some(coercions);
+    }
+
+    // Runs before field encryption on the way out and after decryption on the
+    // way in, so encrypted field paths use the API's key names.
+    pub fn set_key_transform(&mut self, transform: KeyTransform) {
+        self.key_transform = Some(transform);
+    }
+
+    // zstd-compresses request bodies; only enable for servers known to accept it.
+    #[cfg(feature = "zstd")]
+    pub fn set_compress_requests(&mut self, enabled: bool) {
+        self.compress_requests = enabled;
+    }
+
+    // Sends `Expect: 100-continue` on bodies of at least `threshold` bytes so servers
+    // can reject (auth, size) before reading the upload. The underlying client
+    // doesn't pause for the interim 100 response, so this only helps with servers
+    // that answer early and close the connection.
+    pub fn set_expect_continue(&mut self, threshold: Option<u64>) {
+        self.expect_continue_threshold = threshold;
+    }
+
+    pub fn set_statsd(&mut self, sink: StatsdSink) {
+        self.statsd = Some(Arc::new(sink));
+    }
+
+    // Alongside StatsD, if both are set.
+    pub fn set_metrics_sink<S: MetricsSink + 'static>(&mut self, sink: S) {
+        self.metrics = Some(Arc::new(sink));
+    }
+
+    // Per-endpoint (`METHOD /path`) retry counters since creation or the last reset.
+    pub fn retry_stats(&self) -> BTreeMap<String, RetryStats> {
+        self.retry_stats.snapshot()
+    }
+
+    pub fn reset_retry_stats(&self) {
+        self.retry_stats.reset();
+    }
+
+    // Bytes sent and received per endpoint; `total_size_stats` sums them for
+    // the whole client.
+    pub fn size_stats(&self) -> BTreeMap<String, SizeStats> {
+        self.size_stats.snapshot()
+    }
+
+    pub fn total_size_stats(&self) -> SizeStats {
+        self.size_stats.total()
+    }
+
+    pub fn reset_size_stats(&self) {
+        self.size_stats.reset();
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    // Client-side throttling: at most `burst` requests at once and
+    // `per_second` on average, retries included. A 429 empties the bucket and
+    // its Retry-After, if any, keeps it empty until then. Profiles with a rate
+    // limit of their own apply on top of this one.
+    pub fn set_rate_limit(&mut self, per_second: f64, burst: u32) {
+        self.rate_limit = Some(TokenBucket::new(per_second, burst, self.clock.now()));
+        self.restore_buckets();
+    }
+
+    pub fn clear_rate_limit(&mut self) {
+        self.rate_limit = None;
+    }
+
+    // Applies `profile` to every request that no route group's profile
+    // matches. Its retry policy replaces the one from `set_retry_policy`.
+    pub fn set_profile(&mut self, profile: PolicyProfile) {
+        self.profiles.set_default(profile, self.clock.now());
+        self.restore_buckets();
+    }
+
+    // Applies `profile` to requests matching `route`, e.g. "GET /reports/*".
+    // The first matching group wins; each has its own rate limit and cache.
+    pub fn set_profile_for(&mut self, route: &str, profile: PolicyProfile) {
+        self.profiles.set_route(route, profile, self.clock.now());
+        self.restore_buckets();
+    }
+
+    // A built-in profile, or one from the loaded config file.
+    pub fn profile(&self, name: &str) -> Result<PolicyProfile, ProfileError> {
+        self.profiles.named(name)
+    }
+
+    // Replaces all profiles with the ones in a JSON config file. Nothing
+    // changes if the file can't be read or refers to an unknown profile.
+    pub fn load_profiles<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let config: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        self.profiles.load(&config, self.clock.now())?;
+        self.restore_buckets();
+        Ok(())
+    }
+
+    fn active_profile(&self, request: &Request) -> Option<&ActiveProfile> {
+        self.profiles.matching(request.method().as_str(), self.route_path(request.url().path()))
+    }
+
+    pub(crate) fn retry_policy_for(&self, request: &Request) -> &RetryPolicy {
+        self.active_profile(request).map_or(&self.retry_policy, ActiveProfile::retry_policy)
+    }
+
+    // Replaces the time source for backoff and signing. A custom sleeper always
+    // sleeps out the full backoff; reconnects and the shutdown deadline only
+    // cut the default, real-time sleep short.
+    pub fn set_clock<C>(&mut self, clock: Arc<C>)
+    where
+        C: Clock + Sleeper + 'static,
+    {
+        self.clock = clock.clone();
+        self.sleeper = Some(clock);
+    }
+
+    // Called once per call that fails after all retries, with the attempt history.
+    pub fn on_error<F>(&mut self, hook: F)
+    where
+        F: Fn(&ErrorReport) + Send + Sync + 'static,
+    {
+        self.error_hook = Some(Arc::new(hook));
+    }
+
+    // Called when saving quota state or a cached response to disk fails.
+    pub fn on_store_error<F>(&mut self, hook: F)
+    where
+        F: Fn(&StoreError) + Send + Sync + 'static,
+    {
+        self.store_error_hook = Some(Arc::new(hook));
+    }
+
+    // Emits an `http.request` span per call through `tracing`, with the
+    // method, URL, status, latency and retry count.
+    #[cfg(feature = "tracing")]
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    // Rewrites request header values in trace output, or drops them with
+    // None. Authorization, cookies and API keys are masked regardless.
+    #[cfg(feature = "tracing")]
+    pub fn set_trace_redactor<F>(&mut self, redactor: F)
+    where
+        F: Fn(&str, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.trace_redactor = Some(Arc::new(redactor));
+    }
+
+    // Categorizes error responses from their status and the vendor's error
+    // code. Returning None falls back to `ErrorCategory::from_status`.
+    pub fn set_error_translator<F>(&mut self, translator: F)
+    where
+        F: Fn(u16, Option<&str>) -> Option<ErrorCategory> + Send + Sync + 'static,
+    {
+        self.error_translator = Some(Arc::new(translator));
+    }
+
+    // Records a sanitized summary of every call (no bodies, credentials and
+    // query values masked) until `limit` is reached. Restarting discards the
+    // previous capture.
+    pub fn start_capture(&self, limit: CaptureLimit) {
+        self.capture.start(limit, None, self.clock.now(), self.clock.system_time());
+    }
+
+    // Like `start_capture`, but also keeps JSON request and response bodies,
+    // and runs URLs, headers and bodies through `anonymizer` first.
+    pub fn start_anonymized_capture(&self, limit: CaptureLimit, anonymizer: Anonymizer) {
+        self.capture.start(limit, Some(anonymizer), self.clock.now(), self.clock.system_time());
+    }
+
+    // The capture so far, or None if none was started.
+    pub fn support_bundle(&self) -> Option<SupportBundle> {
+        self.capture.bundle(&self.base_url, self.clock.system_time())
+    }
+
+    pub fn stop_capture(&self) -> Option<SupportBundle> {
+        self.capture.stop(&self.base_url, self.clock.system_time())
+    }
+
+    // Stops accepting requests and waits up to `timeout` for in-flight ones to
+    // finish. Retries still backing off at the deadline are abandoned with
+    // their last error; requests already on the wire can't be aborted and run
+    // to their own timeout. The metrics sink and Sentry are then flushed
+    // within what's left of the deadline. Returns true if everything drained
+    // in time.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.lifecycle.begin_shutdown(deadline);
+        self.connectivity.interrupt();
+        let drained = self.lifecycle.wait_idle(deadline) == 0;
+        if let Some(metrics) = &self.metrics {
+            metrics.flush(deadline);
+        }
+        #[cfg(feature = "sentry")]
+        if let Some(client) = sentry_core::Hub::current().client() {
+            client.flush(Some(deadline.saturating_duration_since(Instant::now())));
+        }
+        drained
+    }
+
+    // Lets the host app report network changes; going back online wakes any
+    // retry that is sleeping out its backoff.
+    pub fn notify_connectivity(&self, online: bool) {
+        self.connectivity.set_online(online);
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.connectivity.is_online()
+    }
+
+    // Opens (or creates) the journal at `path` and replays anything left over
+    // from a previous run. Returns the number of items delivered.
+    pub fn enable_offline_queue<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, Box<dyn std::error::Error>> {
+        self.offline_queue = Some(OfflineQueue::open(path)?);
+        self.flush_queue()
+    }
+
+    // Keeps GET responses in `dir`, up to `max_bytes` in total, so they can be
+    // read while offline or when the network fails.
+    pub fn enable_disk_cache<P: AsRef<Path>>(&mut self, dir: P, max_bytes: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.disk_cache = Some(DiskCache::open(dir, max_bytes)?);
+        Ok(())
+    }
+
+    pub fn clear_disk_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.disk_cache {
+            Some(cache) => cache.clear(),
+            None => Ok(()),
+        }
+    }
+
+    // HTTP caching for GETs made through `fetch` and friends.
+    pub fn set_cache(&mut self, policy: CachePolicy) {
+        self.http_cache = Some(policy);
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.http_cache {
+            cache.store().clear();
+        }
+    }
+
+    // Checked against every JSON response, in the order added; the first
+    // match decides.
+    pub fn add_body_rule(&mut self, rule: BodyRule) {
+        self.body_rules.push(rule);
+    }
+
+    pub fn clear_body_rules(&mut self) {
+        self.body_rules.clear();
+    }
+
+    // Fails requests to a host fast, with `CircuitOpen`, while it keeps failing.
+    pub fn set_circuit_breaker(&mut self, breaker: CircuitBreaker) {
+        self.breakers = Some(Breakers::new(breaker));
+    }
+
+    pub fn clear_circuit_breaker(&mut self) {
+        self.breakers = None;
+    }
+
+    // Hosts whose circuit isn't fully closed, or has failures counted.
+    pub fn circuit_states(&self) -> BTreeMap<String, CircuitState> {
+        self.breakers.as_ref().map(|b| b.states(self.clock.now())).unwrap_or_default()
+    }
+
+    // While paused by a maintenance response, every request fails fast with
+    // `MaintenanceError` until the scheduled resume.
+    pub fn set_maintenance(&mut self, policy: MaintenancePolicy) {
+        self.maintenance = Some(Maintenance::new(policy));
+    }
+
+    pub fn clear_maintenance(&mut self) {
+        self.maintenance = None;
+    }
+
+    // Called when a pause starts and when it ends, e.g. to show a banner.
+    pub fn on_maintenance<F>(&mut self, hook: F)
+    where
+        F: Fn(&MaintenanceEvent) + Send + Sync + 'static,
+    {
+        self.maintenance_hook = Some(Arc::new(hook));
+    }
+
+    // The current pause, if any.
+    pub fn maintenance_state(&self) -> Option<MaintenanceError> {
+        self.maintenance.as_ref()?.state(self.clock.now())
+    }
+
+    // Ends a pause early, e.g. when a status page says the API is back.
+    pub fn resume_after_maintenance(&self) {
+        if let Some(maintenance) = &self.maintenance {
+            maintenance.resume(self.maintenance_hook.as_ref());
+        }
+    }
+
+    // Remembers each host's rate-limit headers in `path`, so repeated runs of a
+    // short-lived process see the quota left by the previous one. Requests to
+    // a host with nothing left fail with `QuotaExhausted` until it resets. The
+    // client's and profiles' rate limits are kept there too and carry on from
+    // where the last run left them.
+    pub fn set_quota_store<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        self.quota = Some(QuotaStore::open(path)?);
+        self.restore_buckets();
+        Ok(())
+    }
+
+    fn buckets(&self) -> impl Iterator<Item = (&str, &TokenBucket)> {
+        self.rate_limit.iter().map(|bucket| ("client", bucket)).chain(self.profiles.buckets())
+    }
+
+    fn restore_buckets(&self) {
+        let Some(quota) = &self.quota else {
+            return;
+        };
+        for (key, bucket) in self.buckets() {
+            if let Some(saved) = quota.bucket(key) {
+                bucket.restore(saved, self.clock.now(), self.clock.system_time());
+            }
+        }
+    }
+
+    fn save_bucket(&self, key: &str, bucket: &TokenBucket) {
+        if let Some(quota) = &self.quota {
+            if let Err(e) = quota.save_bucket(key, bucket.save(self.clock.now(), self.clock.system_time())) {
+                self.store_failed(Store::Quota, key, e.as_ref());
+            }
+        }
+    }
+
+    pub fn quota_state(&self) -> BTreeMap<String, QuotaState> {
+        self.quota.as_ref().map(QuotaStore::snapshot).unwrap_or_default()
+    }
+
+    // Called for queued items that failed for a reason other than connectivity
+    // and were dropped from the queue.
+    pub fn set_queue_failure_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&QueuedRequest, &dyn std::error::Error) + Send + Sync + 'static,
+    {
+        self.queue_failure_handler = Some(Arc::new(handler));
+    }
+
+    pub fn enqueue(&self, path: &str, method: Method, data: Value) -> Result<u64, Box<dyn std::error::Error>> {
+        let queue = self.offline_queue.as_ref().ok_or("Offline queue is not enabled")?;
+        let data = self.outgoing_body(path, data)?;
+        queue.push(method, path, data, self.clock.system_time())
+    }
+
+    // Sends immediately, queueing the request instead if the network is
+    // unavailable. Returns `None` when the request was queued.
+    pub fn send_or_enqueue(&self, path: &str, method: Method, data: Value) -> Result<Option<Value>, ApiError> {
+        if self.is_online() {
+            match self.send(path, method, data.clone()) {
+                Err(ApiError::Connection(_) | ApiError::Timeout(_)) => {}
+                result => return result.map(Some),
+            }
+        }
+        self.enqueue(path, method, data)?;
+        Ok(None)
+    }
+
+    pub fn flush_queue(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        match &self.offline_queue {
+            Some(queue) => queue.flush(
+                |item| self.send(&item.path, item.method, item.body.clone()),
+                self.queue_failure_handler.as_ref(),
+            ),
+            None => Ok(0),
+        }
+    }
+
+    pub fn queued(&self) -> usize {
+        self.offline_queue.as_ref().map_or(0, OfflineQueue::len)
+    }
+
+    pub fn pending(&self) -> Pending {
+        let (in_flight, oldest_in_flight) = self.lifecycle.in_flight();
+        let oldest_queued = self
+            .offline_queue
+            .as_ref()
+            .and_then(|queue| queue.oldest_wait(self.clock.system_time()));
+        Pending {
+            queued: self.queued(),
+            in_flight,
+            oldest_wait: oldest_queued.max(oldest_in_flight),
+        }
+    }
+
+    // Switches the client to rustls with stapled-OCSP checking. Handshakes with
+    // revoked (or, per policy, unstapled) certificates fail with `RevocationError`.
+    #[cfg(feature = "revocation")]
+    pub fn set_revocation_check(&mut self, policy: RevocationPolicy) -> Result<(), Box<dyn std::error::Error>> {
+        self.revocation = Some(policy);
+        self.rebuild_client()
+    }
+
+    // Checked before every attempt (after middleware) and on every redirect
+    // hop; refused requests fail with `EgressError` and aren't retried.
+    pub fn set_egress_policy(&mut self, policy: EgressPolicy) -> Result<(), Box<dyn std::error::Error>> {
+        self.egress = Some(Arc::new(policy));
+        self.rebuild_client()
+    }
+
+    fn rebuild_client(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.client = build_client(self.client_builder())?;
+        self.connect_clients.get_mut().unwrap().clear();
+        Ok(())
+    }
+
+    fn client_builder(&self) -> reqwest::blocking::ClientBuilder {
+        let mut builder = self.client_config.blocking();
+        if let Some(egress) = &self.egress {
+            builder = builder.redirect(egress.redirect_policy(self.client_config.max_redirects));
+        }
+        #[cfg(feature = "revocation")]
+        if let Some(policy) = self.revocation {
+            builder = builder.use_preconfigured_tls(revocation::tls_config(policy));
+        }
+        builder
+    }
+
+    // The client for a call that may override the connect timeout.
+    fn client_for(&self, connect_timeout: Option<Duration>) -> Result<Client, Box<dyn std::error::Error>> {
+        let Some(timeout) = connect_timeout.filter(|timeout| Some(*timeout) != self.client_config.connect_timeout) else {
+            return Ok(self.client.clone());
+        };
+        let mut clients = self.connect_clients.lock().unwrap();
+        if let Some(client) = clients.get(&timeout) {
+            return Ok(client.clone());
+        }
+        let client = build_client(self.client_builder().connect_timeout(timeout))?;
+        clients.insert(timeout, client.clone());
+        Ok(client)
+    }
+
+    // Set on the built request so `PreAuth` middleware can run first. Auth
+    // replaces a header of the same name set earlier.
+    fn apply_auth(&self, request: &mut Request) -> Result<(), Box<dyn std::error::Error>> {
+        let (name, mut value) = match self.auth().as_deref() {
+            Some(Auth::Bearer(token)) => (AUTHORIZATION, HeaderValue::from_str(&Zeroizing::new(format!("Bearer {}", token)))?),
+            Some(Auth::Basic(username, password)) => {
+                let credentials = Zeroizing::new(STANDARD.encode(Zeroizing::new(format!("{}:{}", username, password)).as_bytes()));
+                (AUTHORIZATION, HeaderValue::from_str(&Zeroizing::new(format!("Basic {}", credentials.as_str())))?)
+            }
+            Some(Auth::Custom(key, value)) => (HeaderName::from_bytes(key.as_bytes())?, HeaderValue::from_str(value)?),
+            Some(Auth::OAuth2(oauth)) => {
+                let token = oauth.access_token(&self.client, self.clock.now())?;
+                (AUTHORIZATION, HeaderValue::from_str(&Zeroizing::new(format!("Bearer {}", token.as_str())))?)
+            }
+            None => return Ok(()),
+        };
+        value.set_sensitive(true);
+        request.headers_mut().insert(name, value);
+        Ok(())
+    }
+
+    // Later phases see the path after versioning and routing, so scoped
+    // middleware is matched against the request as it is at each phase.
+    fn apply_middleware(&self, phase: Phase, mut request: Request) -> Request {
+        let method = request.method().to_string();
+        let path = self.route_path(request.url().path()).to_string();
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let context = RequestContext::new(self.auth().as_deref().map(Auth::kind), host, phase);
+        context.run(|| {
+            for middleware in self.middleware.matching(phase, &method, &path) {
+                request = middleware(request);
+            }
+            request
+        })
+    }
+
+    fn intercept(&self, endpoint: &str, mut response: ApiResponse) -> Result<ApiResponse, ApiError> {
+        let (method, path) = endpoint.split_once(' ').unwrap_or_default();
+        for interceptor in self.response_interceptors.matching((), method, self.route_path(path)) {
+            response = interceptor(response)?;
+        }
+        Ok(response)
+    }
+
+    fn apply_response_middleware(&self, endpoint: &str, mut body: Value) -> Value {
+        let (method, path) = endpoint.split_once(' ').unwrap_or_default();
+        for middleware in self.response_middleware.matching((), method, self.route_path(path)) {
+            body = middleware(body);
+        }
+        body
+    }
+
+    // `path` relative to the base URL's path, for matching routes.
+    fn route_path<'a>(&self, path: &'a str) -> &'a str {
+        let base_path = reqwest::Url::parse(&self.base_url).map(|url| url.path().trim_end_matches('/').to_string()).unwrap_or_default();
+        match path.strip_prefix(base_path.as_str()) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+            _ => path,
+        }
+    }
+
+    pub fn fetch(&self, path: &str) -> Result<Value, ApiError> {
+        self.fetch_url(&format!("{}{}", self.base_url, path))
+    }
+
+    // Like `fetch`, with `params` added to the query string.
+    pub fn fetch_with_params(&self, path: &str, params: &[(&str, &str)]) -> Result<Value, ApiError> {
+        self.fetch_with_query(path, &Query::from(params))
+    }
+
+    pub fn fetch_with_query(&self, path: &str, query: &Query) -> Result<Value, ApiError> {
+        self.fetch_with(path, &RequestOptions::new().query(query.clone()))
+    }
+
+    pub fn fetch_with(&self, path: &str, options: &RequestOptions) -> Result<Value, ApiError> {
+        let _call = self.enter_call(options);
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.build_request(options.apply(self.client.get(&url)))?;
+        self.execute_get(request)
+    }
+
+    // Makes the options' cancellation token and limits the current ones for
+    // this thread.
+    fn enter_call(&self, options: &RequestOptions) -> (Option<cancel::Entered>, options::Entered) {
+        let connectivity = Arc::clone(&self.connectivity);
+        let cancel = options.cancellation().map(|token| cancel::enter(token, move || connectivity.interrupt()));
+        (cancel, options.enter())
+    }
+
+    // Unwraps the configured envelope, if any, applies coercions and
+    // deserializes the payload.
+    pub fn fetch_as<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        Ok(self.typed(self.fetch(path)?)?)
+    }
+
+    // Typed on both ends: `data` is serialized as the body and the response
+    // goes through the same steps as `fetch_as`. Use `()` for HEAD and 204.
+    pub fn send_as<T: DeserializeOwned, B: Serialize + ?Sized>(&self, path: &str, method: Method, data: &B) -> Result<T, ApiError> {
+        let data = serde_json::to_value(data).map_err(|e| ApiError::Other(e.into()))?;
+        Ok(self.typed(self.send(path, method, data)?)?)
+    }
+
+    fn typed<T: DeserializeOwned>(&self, body: Value) -> Result<T, Box<dyn std::error::Error>> {
+        let mut payload = match &self.envelope {
+            Some(envelope) => envelope.unwrap(body)?,
+            None => body,
+        };
+        if let Some(coercions) = &self.coercions {
+            coercions.apply(&mut payload)?;
+        }
+        Ok(serde_json::from_value(payload)?)
+    }
+
+    // Like `fetch`, but serves repeat reads from memory until the entry is
+    // invalidated or evicted; HTTP caching headers are ignored.
+    pub fn memoized(&self, path: &str) -> Result<Value, ApiError> {
+        if let Some(value) = self.memo.get(path) {
+            return Ok(value);
+        }
+        let value = self.fetch(path)?;
+        self.memo.insert(path, value.clone());
+        Ok(value)
+    }
+
+    pub fn invalidate_memo(&self, path: &str) -> bool {
+        self.memo.invalidate(path)
+    }
+
+    pub fn clear_memo(&self) {
+        self.memo.clear();
+    }
+
+    pub fn set_memo_capacity(&self, capacity: usize) {
+        self.memo.set_capacity(capacity);
+    }
+
+    fn fetch_url(&self, url: &str) -> Result<Value, ApiError> {
+        let request = self.build_request(self.client.get(url))?;
+        self.execute_get(request)
+    }
+
+    // GETs are answered from the profile's cache while fresh.
+    fn execute_get(&self, request: Request) -> Result<Value, ApiError> {
+        let Some(profile) = self.active_profile(&request) else {
+            return self.execute_stored_get(request);
+        };
+        let url = request.url().to_string();
+        if let Some(body) = profile.cached(&url, self.clock.now()) {
+            return Ok(body);
+        }
+        let body = self.execute_stored_get(request)?;
+        profile.store(&url, &body, self.clock.now());
+        Ok(body)
+    }
+
+    // GETs go through the disk cache when it's enabled: answered from it while
+    // offline or when the network fails, and stored after every success.
+    fn execute_stored_get(&self, request: Request) -> Result<Value, ApiError> {
+        let Some(cache) = &self.disk_cache else {
+            return Ok(self.with_body_rules(request, |request| self.execute_cached(request))?);
+        };
+        let url = request.url().to_string();
+        if !self.is_online() {
+            if let Some(body) = cache.get(&url) {
+                return Ok(body);
+            }
+        }
+        match self.with_body_rules(request, |request| self.execute_cached(request)).map_err(ApiError::from) {
+            Ok(body) => {
+                if let Err(e) = cache.insert(&url, &body, self.clock.system_time()) {
+                    self.store_failed(Store::DiskCache, &url, e.as_ref());
+                }
+                Ok(body)
+            }
+            Err(e @ (ApiError::Connection(_) | ApiError::Timeout(_))) => cache.get(&url).ok_or(e),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Fresh entries of the HTTP cache are served as they are, and stale ones
+    // are revalidated, served again if the server answers 304.
+    fn execute_cached(&self, mut request: Request) -> Result<Value, Box<dyn std::error::Error>> {
+        let Some(cache) = &self.http_cache else {
+            return self.send_json(request);
+        };
+        let url = request.url().to_string();
+        let now = self.clock.system_time();
+        let cached = cache.lookup(&url);
+        if let Some(cached) = &cached {
+            if cached.is_fresh(now) {
+                return Ok(cached.body.clone());
+            }
+            http_cache::make_conditional(&mut request, cached);
+        }
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let path = request.url().path().to_string();
+        let response = self.execute(request)?;
+        match (response.status(), cached) {
+            (reqwest::StatusCode::NOT_MODIFIED, Some(cached)) => {
+                let headers = response.headers().clone();
+                self.read_body(&endpoint, response)?;
+                Ok(cache.refresh(&url, cached, &headers, self.clock.system_time()))
+            }
+            (reqwest::StatusCode::OK, _) => {
+                let headers = response.headers().clone();
+                let body = self.read_json(&endpoint, &path, response)?;
+                cache.store_response(&url, &headers, body.clone(), self.clock.system_time());
+                Ok(body)
+            }
+            (_, _) => self.response_json(&endpoint, &path, false, response),
+        }
+    }
+
+    // Like `fetch`, with `locale` taking precedence over the client's.
+    pub fn fetch_localized(&self, path: &str, locale: &Locale) -> Result<Value, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self
+            .client
+            .get(&url)
+            .header(ACCEPT_LANGUAGE, locale.accept_language());
+        Ok(self.execute_json(self.build_request(request)?)?)
+    }
+
+    // Like `fetch`, asking for `version` instead of the client's.
+    pub fn fetch_versioned(&self, path: &str, version: &ApiVersion) -> Result<Value, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.build_versioned(self.client.get(&url), Some(version))?;
+        Ok(self.execute_json(request)?)
+    }
+
+    pub fn fetch_stream(&self, path: &str) -> Result<ResponseStream, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.build_request(self.client.get(&url))?;
+        Ok(ResponseStream::new(self.execute(request)?, &self.decompression_limits)?)
+    }
+
+    // Server-Sent Events from `path`, one per iteration:
+    //
+    //     for event in core.fetch_sse("/notifications")? {
+    //         let event = event?;
+    //         println!("{} {}", event.event, event.data);
+    //     }
+    pub fn fetch_sse(&self, path: &str) -> Result<EventStream<'_>, ApiError> {
+        EventStream::connect(self, path)
+    }
+
+    // Calls `on_event` for each event until it returns false or the stream
+    // ends.
+    pub fn fetch_sse_with<F>(&self, path: &str, mut on_event: F) -> Result<(), ApiError>
+    where
+        F: FnMut(&SseEvent) -> bool,
+    {
+        for event in self.fetch_sse(path)? {
+            if !on_event(&event?) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn open_event_stream(&self, path: &str, last_event_id: Option<&str>) -> Result<ResponseStream, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = self
+            .client
+            .get(&url)
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .timeout(sse::STREAM_TIMEOUT);
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+        let request = self.build_request(request)?;
+        Ok(ResponseStream::new(self.execute(request)?, &self.decompression_limits)?)
+    }
+
+    // The raw body in chunks of up to 64 KiB, read as they're consumed.
+    pub fn fetch_bytes_stream(&self, path: &str) -> Result<Chunks, ApiError> {
+        Ok(self.fetch_stream(path)?.chunks(stream::DEFAULT_CHUNK_SIZE))
+    }
+
+    // Streams the body of `path` into `dest` and returns its size.
+    pub fn download_to_file<P: AsRef<Path>>(&self, path: &str, dest: P) -> Result<u64, ApiError> {
+        self.download_to_file_with_progress(path, dest, |_, _| {})
+    }
+
+    // `progress(downloaded, total)` runs after every chunk; `total` is the
+    // Content-Length, if the server sent one.
+    pub fn download_to_file_with_progress<P, F>(&self, path: &str, dest: P, progress: F) -> Result<u64, ApiError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(u64, Option<u64>),
+    {
+        let stream = self.fetch_stream(path)?;
+        Ok(stream::download(stream, dest.as_ref(), progress)?)
+    }
+
+    // Opens a WebSocket to `path` on the base URL, wss:// for an https:// base.
+    // The handshake is built like a GET, with auth and middleware; proxies
+    // and extra root certificates don't apply to it.
+    #[cfg(feature = "websocket")]
+    pub fn connect_ws(&self, path: &str) -> Result<WebSocket<'_>, ApiError> {
+        WebSocket::connect(self, path)
+    }
+
+    #[cfg(feature = "websocket")]
+    pub(crate) fn websocket_request(&self, path: &str) -> Result<Request, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.build_request(self.client.get(&url))?;
+        if let Some(egress) = &self.egress {
+            egress.check(request.url()).map_err(|e| ApiError::Other(e.into()))?;
+        }
+        Ok(request)
+    }
+
+    // POSTs `query` to the base URL. The result is `Err` only when the request
+    // failed; errors the GraphQL server reported are in the response, and
+    // `.into_data()` turns them into an error.
+    pub fn graphql(&self, query: &str, variables: Value) -> Result<GraphQLResponse, ApiError> {
+        self.graphql_request(&GraphQLRequest::new(query).variables(variables))
+    }
+
+    pub fn graphql_request(&self, request: &GraphQLRequest) -> Result<GraphQLResponse, ApiError> {
+        let response = self.post_graphql(request, !request.is_persisted())?;
+        if request.is_persisted() && response.persisted_query_not_found() {
+            return self.post_graphql(request, true);
+        }
+        Ok(response)
+    }
+
+    fn post_graphql(&self, request: &GraphQLRequest, include_query: bool) -> Result<GraphQLResponse, ApiError> {
+        let url = format!("{}{}", self.base_url, request.path_str());
+        let http = self
+            .client
+            .post(&url)
+            .header(ACCEPT, "application/graphql-response+json, application/json")
+            .json(&request.body(include_query));
+        let body = match self.execute_json(self.build_request(http)?).map_err(ApiError::from) {
+            Ok(body) => body,
+            // Servers following GraphQL over HTTP answer invalid queries with
+            // a 4xx that still carries the envelope.
+            Err(ApiError::Http { status, body, code, category }) => match serde_json::from_str::<Value>(&body) {
+                Ok(json) if GraphQLResponse::parse(&json).is_some() => json,
+                _ => return Err(ApiError::Http { status, body, code, category }),
+            },
+            Err(e) => return Err(e),
+        };
+        GraphQLResponse::parse(&body).ok_or_else(|| ApiError::Decode("Response isn't a GraphQL envelope".into()))
+    }
+
+    // Unary gRPC-Web call through the usual auth, middleware and retries.
+    // `method` is the full method path, e.g. `/shop.v1.Orders/GetOrder`, and
+    // `message` the encoded protobuf request. Returns the encoded response; a
+    // non-OK grpc-status fails with `GrpcError`.
+    pub fn grpc_web_unary(&self, method: &str, message: &[u8]) -> Result<Vec<u8>, ApiError> {
+        let url = format!("{}{}", self.base_url, method);
+        let request = self
+            .client
+            .post(&url)
+            .header(CONTENT_TYPE, grpc_web::CONTENT_TYPE)
+            .header(ACCEPT, grpc_web::CONTENT_TYPE)
+            .header("X-Grpc-Web", "1")
+            .body(grpc_web::frame(message));
+        let request = self.build_request(request)?;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let response = self.execute(request)?;
+        let headers = response.headers().clone();
+        let body = self.read_body(&endpoint, response)?;
+        Ok(grpc_web::unary_response(&headers, &body)?)
+    }
+
+    // Downloads `path` and every page after it, following `Link: rel="next"`
+    // headers, into `dest_dir` as part-00001.json, part-00002.json, ... Bodies
+    // are streamed to disk, and manifest.json listing the parts is written
+    // last, so its presence means the export finished.
+    pub fn export<P: AsRef<Path>>(&self, path: &str, dest_dir: P) -> Result<ExportManifest, ApiError> {
+        let dir = dest_dir.as_ref();
+        std::fs::create_dir_all(dir).map_err(|e| ApiError::Other(e.into()))?;
+        let source = format!("{}{}", self.base_url, path);
+        let mut url = reqwest::Url::parse(&source).map_err(|e| ApiError::Other(e.into()))?;
+        let mut visited = std::collections::HashSet::new();
+        let mut parts = Vec::new();
+        loop {
+            let request = self.build_request(self.client.get(url.clone()))?;
+            let mut stream = ResponseStream::new(self.execute(request)?, &self.decompression_limits)?;
+            let next = export::next_link(stream.headers(), stream.url());
+            parts.push(export::write_part(dir, parts.len() + 1, &mut stream)?);
+            visited.insert(url);
+            match next {
+                // A server linking back to a page already fetched would loop.
+                Some(next) if !visited.contains(&next) => url = next,
+                _ => break,
+            }
+        }
+        let manifest = ExportManifest::new(&source, parts, std::time::SystemTime::now());
+        export::write_manifest(dir, &manifest)?;
+        Ok(manifest)
+    }
+
+    // Pages of `path` as `strategy` finds them, fetched lazily. `.items()`
+    // flattens them:
+    //
+    //     for user in core.fetch_paginated("/users", Cursor::new("cursor", "/next").items("/data")).items() {
+    //         println!("{}", user?["name"]);
+    //     }
+    pub fn fetch_paginated<'a, S: PaginationStrategy + 'a>(&'a self, path: &str, strategy: S) -> Pages<'a> {
+        let url = reqwest::Url::parse(&format!("{}{}", self.base_url, path)).map_err(|e| ApiError::Other(e.into()));
+        Pages::new(self, url, Box::new(strategy))
+    }
+
+    pub(crate) fn fetch_page(&self, url: reqwest::Url) -> Result<Page, ApiError> {
+        let request = self.build_request(self.client.get(url))?;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let path = request.url().path().to_string();
+        let response = self.execute(request)?;
+        let (url, headers) = (response.url().clone(), response.headers().clone());
+        let body = self.read_json(&endpoint, &path, response)?;
+        Ok(Page::new(url, headers, body))
+    }
+
+    // HEAD asks for the identity encoding so Content-Length describes the
+    // resource itself rather than a compressed representation.
+    pub fn head(&self, path: &str) -> Result<ResourceInfo, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.client.head(&url).header(ACCEPT_ENCODING, "identity");
+        let response = self.execute(self.build_request(request)?)?;
+        Ok(ResourceInfo::new(&response))
+    }
+
+    // 404 and 410 mean the resource doesn't exist; other failures are errors.
+    pub fn exists(&self, path: &str) -> Result<bool, ApiError> {
+        match self.head(path) {
+            Ok(_) => Ok(true),
+            Err(e) => match e.status() {
+                Some(404) | Some(410) => Ok(false),
+                _ => Err(e),
+            },
+        }
+    }
+
+    // HEAD never carries a body, and GET, DELETE and OPTIONS don't when `data`
+    // is null. HEAD and 204 responses come back as null.
+    pub fn send(&self, path: &str, method: Method, data: Value) -> Result<Value, ApiError> {
+        self.send_with_query(path, method, data, &Query::new())
+    }
+
+    pub fn send_with_query(&self, path: &str, method: Method, data: Value, query: &Query) -> Result<Value, ApiError> {
+        self.send_with(path, method, data, &RequestOptions::new().query(query.clone()))
+    }
+
+    pub fn send_with(&self, path: &str, method: Method, data: Value, options: &RequestOptions) -> Result<Value, ApiError> {
+        let _call = self.enter_call(options);
+        let request = options.apply(self.with_body(path, method, data)?);
+        Ok(self.execute_json(self.build_request(request)?)?)
+    }
+
+    fn with_body(&self, path: &str, method: Method, data: Value) -> Result<RequestBuilder, ApiError> {
+        let request = self.client.request(method.into(), format!("{}{}", self.base_url, path));
+        if method == Method::Head || (method.body_optional() && data.is_null()) {
+            return Ok(request);
+        }
+        let data = self.outgoing_body(path, data)?;
+        Ok(request.json(&data))
+    }
+
+    // Sends `fields` as an application/x-www-form-urlencoded body, in order;
+    // names may repeat.
+    pub fn send_form(&self, path: &str, method: Method, fields: &[(&str, &str)]) -> Result<Value, ApiError> {
+        let request = self.client.request(method.into(), format!("{}{}", self.base_url, path)).form(fields);
+        Ok(self.execute_json(self.build_request(request)?)?)
+    }
+
+    // POSTs `form` as multipart/form-data.
+    pub fn send_multipart(&self, path: &str, form: &Multipart) -> Result<Value, ApiError> {
+        let (content_type, body) = form.encode().map_err(|e| ApiError::Other(e.into()))?;
+        let request = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+        Ok(self.execute_json(self.build_request(request)?)?)
+    }
+
+    // Like `send`, but a 201 with a Location header returns the created resource
+    // (fetched with GET) and a 202 with one polls it until it's done.
+    pub fn send_and_follow(&self, path: &str, method: Method, data: Value, options: &PollOptions) -> Result<Value, ApiError> {
+        let request = self.build_request(self.with_body(path, method, data)?)?;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let request_path = request.url().path().to_string();
+        let response = self.execute(request)?;
+        match (response.status().as_u16(), response.headers().location(response.url())) {
+            (201, Some(location)) => self.fetch_url(location.as_str()),
+            (202, Some(location)) => self.poll_url(location.as_str(), options),
+            _ => Ok(self.read_json(&endpoint, &request_path, response)?),
+        }
+    }
+
+    // GETs `path` until it stops answering 202 Accepted and returns that body.
+    pub fn poll(&self, path: &str, options: &PollOptions) -> Result<Value, ApiError> {
+        self.poll_url(&format!("{}{}", self.base_url, path), options)
+    }
+
+    fn poll_url(&self, url: &str, options: &PollOptions) -> Result<Value, ApiError> {
+        let deadline = self.clock.now() + options.timeout;
+        let mut polls = 0;
+        loop {
+            let request = self.build_request(self.client.get(url))?;
+            let endpoint = format!("{} {}", request.method(), request.url().path());
+            let request_path = request.url().path().to_string();
+            let response = self.execute(request)?;
+            polls += 1;
+            if response.status() != reqwest::StatusCode::ACCEPTED {
+                return Ok(self.read_json(&endpoint, &request_path, response)?);
+            }
+            let interval = options.next_interval(response.headers());
+            let now = self.clock.now();
+            if now + interval > deadline {
+                return Err(ApiError::Timeout(PollError::TimedOut { url: url.to_string(), polls }.into()));
+            }
+            match &self.sleeper {
+                Some(sleeper) => sleeper.sleep(interval),
+                None => std::thread::sleep(interval),
+            }
+        }
+    }
+
+    // Both steps are idempotent, so bodies replayed from the offline queue
+    // pass through again unchanged.
+    fn outgoing_body(&self, path: &str, data: Value) -> Result<Value, Box<dyn std::error::Error>> {
+        let data = match &self.key_transform {
+            Some(transform) => {
+                let url = reqwest::Url::parse(&format!("{}{}", self.base_url, path))?;
+                transform.outgoing_body(url.path(), data)
+            }
+            None => data,
+        };
+        #[cfg(feature = "field-encryption")]
+        let data = match &self.field_encryption {
+            Some(fields) => {
+                let mut data = data;
+                fields.encrypt(&mut data).map_err(ApiError::Middleware)?;
+                data
+            }
+            None => data,
+        };
+        Ok(data)
+    }
+
+    // Sends `requests` as one multipart/mixed POST to `path` (typically
+    // `/$batch`) and returns one response per part. Failed parts don't fail the
+    // batch; check each part's status.
+    pub fn batch(&self, path: &str, requests: &[BatchRequest]) -> Result<Vec<BatchResponse>, ApiError> {
+        Ok(self.send_batch(path, requests)?)
+    }
+
+    fn send_batch(&self, path: &str, requests: &[BatchRequest]) -> Result<Vec<BatchResponse>, Box<dyn std::error::Error>> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut nonce = [0u8; 8];
+        getrandom::getrandom(&mut nonce)?;
+        let boundary = format!("batch_{}", signing::hex(&nonce));
+        let prefix = reqwest::Url::parse(&self.base_url)?.path().to_string();
+        let request = self
+            .client
+            .post(&url)
+            .header(CONTENT_TYPE, format!("multipart/mixed; boundary={}", boundary))
+            .body(batch::encode(requests, &prefix, &boundary)?);
+        let response = self.execute(self.build_request(request)?)?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let boundary = batch::boundary_of(&content_type).ok_or("Batch response is not multipart")?;
+        let body = self.read_body(&format!("POST {}", reqwest::Url::parse(&url)?.path()), response)?;
+        batch::decode(&String::from_utf8_lossy(&body), &boundary)
+    }
+
+    // For bulk endpoints answering 207 Multi-Status: one result per item,
+    // each with its own status, instead of one success for the whole call.
+    // Any other successful status comes back as a single item.
+    pub fn send_multi_status(&self, path: &str, method: Method, data: Value) -> Result<Vec<MultiStatusItem>, ApiError> {
+        let request = self.build_request(self.with_body(path, method, data)?)?;
+        Ok(self.multi_status(request)?)
+    }
+
+    // A WebDAV request such as PROPFIND or REPORT with an XML body. `depth` is
+    // sent as the Depth header when given (`0`, `1` or `infinity`).
+    pub fn dav(&self, method: &str, path: &str, depth: Option<&str>, xml: &str) -> Result<Vec<MultiStatusItem>, ApiError> {
+        let method = reqwest::Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| ApiError::Other(UnsupportedMethod(method.to_string()).into()))?;
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path))
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(xml.to_string());
+        if let Some(depth) = depth {
+            request = request.header("Depth", depth);
+        }
+        Ok(self.multi_status(self.build_request(request)?)?)
+    }
+
+    fn multi_status(&self, request: Request) -> Result<Vec<MultiStatusItem>, Box<dyn std::error::Error>> {
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let path = request.url().path().to_string();
+        let response = self.execute(request)?;
+        let (status, url) = (response.status(), response.url().clone());
+        let headers = response.headers().clone();
+        let body = self.read_body(&endpoint, response)?;
+        let is_xml = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<mime::Mime>().ok())
+            .is_some_and(|mime| mime.subtype() == mime::XML || mime.suffix() == Some(mime::XML));
+        let response = ApiResponse::new(status, url, headers, body);
+        match status.as_u16() {
+            207 if is_xml => multistatus::from_xml(&String::from_utf8_lossy(response.into_utf8()?.body())),
+            207 => multistatus::from_json(self.decode_json(&endpoint, &path, response)?),
+            status if is_xml || response.body().is_empty() => Ok(vec![MultiStatusItem::whole(&path, status, Value::Null)]),
+            status => Ok(vec![MultiStatusItem::whole(&path, status, self.decode_json(&endpoint, &path, response)?)]),
+        }
+    }
+
+    // POSTs `items` to `path` in chunks of `chunk_size`, four chunks at a time.
+    pub fn bulk(&self, path: &str, items: &[Value], chunk_size: usize) -> BulkReport {
+        self.bulk_with(path, items, &BulkOptions::new(chunk_size))
+    }
+
+    // A failing chunk doesn't stop the others; the report has one entry per
+    // item, in input order.
+    pub fn bulk_with(&self, path: &str, items: &[Value], options: &BulkOptions) -> BulkReport {
+        let chunks: Vec<&[Value]> = items.chunks(options.chunk_size()).collect();
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let outcomes = std::sync::Mutex::new(Vec::with_capacity(items.len()));
+        std::thread::scope(|scope| {
+            for _ in 0..options.workers(chunks.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let Some(chunk) = chunks.get(i) else {
+                        break;
+                    };
+                    let result = self
+                        .send_chunk(path, options.method_kind(), chunk)
+                        .map_err(|e| (e.to_string(), ApiError::find(e.as_ref()).and_then(ApiError::status)));
+                    let chunk_outcomes = bulk::outcomes(i * options.chunk_size(), chunk.len(), result);
+                    outcomes.lock().unwrap().extend(chunk_outcomes);
+                });
+            }
+        });
+        let mut items = outcomes.into_inner().unwrap();
+        items.sort_by_key(|item| item.index);
+        BulkReport { items }
+    }
+
+    // Fetches `paths` with at most `concurrency` requests in flight; the
+    // results line up with `paths`. `AggregateError::collect` turns them into
+    // one result. Errors come back from the worker threads with their kind and
+    // HTTP details, but with other sources reduced to a message.
+    pub fn fetch_many(&self, paths: &[&str], concurrency: usize) -> Vec<Result<Value, ApiError>> {
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let results = std::sync::Mutex::new(Vec::with_capacity(paths.len()));
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency.clamp(1, paths.len().max(1)) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let Some(path) = paths.get(i) else {
+                        break;
+                    };
+                    let result = self.fetch(path).map_err(DetachedError::from);
+                    results.lock().unwrap().push((i, result));
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result.map_err(ApiError::from)).collect()
+    }
+
+    fn send_chunk(&self, path: &str, method: Method, chunk: &[Value]) -> Result<Value, Box<dyn std::error::Error>> {
+        let request = self.with_body(path, method, Value::Array(chunk.to_vec()))?;
+        self.execute_json(self.build_request(request)?)
+    }
+
+    fn api_error(&self, endpoint: &str, response: Response) -> ApiError {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = self.read_body(endpoint, response).unwrap_or_default();
+        let body = charset::to_utf8(&headers, body.clone()).map_or(body, |(body, _)| body);
+        ApiError::http(status, String::from_utf8_lossy(&body).into_owned(), self.error_translator.as_ref())
+    }
+
+    fn read_body(&self, endpoint: &str, response: Response) -> Result<Bytes, Box<dyn std::error::Error>> {
+        let method = endpoint.split(' ').next().unwrap_or_default().to_string();
+        let slot = response.extensions().get::<CaptureSlot>().copied();
+        let (body, received) = encoding::read_body(response, &self.decompression_limits)?;
+        self.record_received(endpoint, &method, received, body.len() as u64);
+        if let Some(slot) = slot {
+            self.capture.record_response_body(slot, &body);
+        }
+        Ok(body)
+    }
+
+    fn record_received(&self, endpoint: &str, method: &str, received: u64, decoded: u64) {
+        self.size_stats.received_body(endpoint, received, decoded);
+        if let Some(statsd) = &self.statsd {
+            statsd.record_bytes(method, "received", received);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.body(&BodyMetrics {
+                method,
+                endpoint,
+                bytes: received,
+                decoded_bytes: decoded,
+            });
+        }
+    }
+
+    fn build_request(&self, request: RequestBuilder) -> Result<Request, Box<dyn std::error::Error>> {
+        self.build_versioned(request, self.version.as_ref())
+    }
+
+    fn build_versioned(&self, request: RequestBuilder, version: Option<&ApiVersion>) -> Result<Request, Box<dyn std::error::Error>> {
+        let mut request = request.build()?;
+        for (name, value) in &self.default_headers {
+            if !request.headers().contains_key(name) {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
+        let mut request = self.apply_middleware(Phase::PreAuth, request);
+        self.apply_auth(&mut request)?;
+        if let Some(version) = version {
+            version.apply(&mut request, &self.base_url).map_err(ApiError::Middleware)?;
+        }
+        if let Some(locale) = &self.locale {
+            locale.apply(&mut request);
+        }
+        self.apply_default_query(&mut request);
+        let mut request = self.apply_middleware(Phase::PostAuth, request);
+        self.apply_traffic_split(&mut request)?;
+        self.apply_region(&mut request)?;
+        Ok(self.apply_middleware(Phase::PreSend, request))
+    }
+
+    fn apply_default_query(&self, request: &mut Request) {
+        let missing: Vec<&(String, String)> = self
+            .default_query
+            .iter()
+            .filter(|(name, _)| !request.url().query_pairs().any(|(present, _)| present == name.as_str()))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let mut pairs = request.url_mut().query_pairs_mut();
+        for (name, value) in missing {
+            pairs.append_pair(name, value);
+        }
+    }
+
+    // Requests still aimed at the client's base URL go to the selected region.
+    fn apply_region(&self, request: &mut Request) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(regions) = &self.regions else {
+            return Ok(());
+        };
+        let Some(rest) = request.url().as_str().strip_prefix(self.base_url.as_str()) else {
+            return Ok(());
+        };
+        let url = reqwest::Url::parse(&format!("{}{}", regions.base_url(), rest))?;
+        *request.url_mut() = url;
+        Ok(())
+    }
+
+    fn apply_traffic_split(&self, request: &mut Request) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(rest) = request.url().as_str().strip_prefix(self.base_url.as_str()) else {
+            return Ok(());
+        };
+        let path = rest.split(['?', '#']).next().unwrap_or_default();
+        for split in &self.traffic_splits {
+            if split.selects(path, request.headers())? {
+                let url = reqwest::Url::parse(&format!("{}{}", split.base_url(), rest))?;
+                *request.url_mut() = url;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&self, mut request: Request) -> Result<Response, Box<dyn std::error::Error>> {
+        let _in_flight = self.lifecycle.enter()?;
+        if scope::cancelled() {
+            return Err(ScopeError::Cancelled.into());
+        }
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        // A successful unsafe request makes the cached response for its URL stale.
+        let invalidates = (!request.method().is_safe()).then(|| request.url().to_string());
+        let requested = match (&self.version, &self.version_hook) {
+            (Some(version), Some(_)) => version.requested(&request, &self.base_url),
+            _ => None,
+        };
+        let body_decoded = self.prepare(&mut request)?;
+        let result = match &self.locale_fallback {
+            Some(fallback) => {
+                let retry = request.try_clone();
+                match (self.send_authorized(request, body_decoded), retry) {
+                    (Err(e), Some(mut retry)) if report::final_status(e.as_ref()) == Some(406) => {
+                        fallback.replace(&mut retry);
+                        self.send_authorized(retry, body_decoded)
+                    }
+                    (result, _) => result,
+                }
+            }
+            None => self.send_authorized(request, body_decoded),
+        };
+        if let Ok(response) = &result {
+            self.inspect_response(&endpoint, &host, requested, response.headers());
+            if let (Some(cache), Some(url)) = (&self.http_cache, &invalidates) {
+                cache.invalidate(url);
+            }
+        }
+        result
+    }
+
+    // With OAuth2, a 401 drops the cached token and the request goes out once
+    // more with a fresh one.
+    fn send_authorized(&self, request: Request, body_decoded: u64) -> Result<Response, Box<dyn std::error::Error>> {
+        let auth = self.auth();
+        let Some(Auth::OAuth2(oauth)) = auth.as_deref() else {
+            return self.send_with_retry(request, body_decoded);
+        };
+        let retry = request.try_clone();
+        match (self.send_with_retry(request, body_decoded), retry) {
+            (Err(e), Some(mut retry)) if report::final_status(e.as_ref()) == Some(401) => {
+                oauth.invalidate();
+                self.apply_auth(&mut retry)?;
+                self.send_with_retry(retry, body_decoded)
+            }
+            (result, _) => result,
+        }
+    }
+
+    // Egress checks and body encoding, done once per call before any attempt.
+    // Returns the body size before compression.
+    fn prepare(&self, request: &mut Request) -> Result<u64, Box<dyn std::error::Error>> {
+        if let Some(egress) = &self.egress {
+            egress.check(request.url())?;
+        }
+        if let Some(quota) = &self.quota {
+            quota.check(request.url().host_str().unwrap_or_default(), self.clock.system_time())?;
+        }
+        request
+            .headers_mut()
+            .entry(ACCEPT_ENCODING)
+            .or_insert(HeaderValue::from_static(encoding::accept_encoding()));
+        #[cfg(feature = "jwe")]
+        if let Some(jwe) = &self.jwe {
+            jwe.encrypt_request(request).map_err(ApiError::Middleware)?;
+        }
+        let body_decoded = request.body().and_then(|b| b.as_bytes()).map_or(0, |b| b.len() as u64);
+        #[cfg(feature = "zstd")]
+        if self.compress_requests {
+            encoding::compress_request(request)?;
+        }
+        if let Some(threshold) = self.expect_continue_threshold {
+            let len = request.body().and_then(|b| b.as_bytes()).map_or(0, |b| b.len() as u64);
+            if len >= threshold {
+                request.headers_mut().insert(EXPECT, HeaderValue::from_static("100-continue"));
+            }
+        }
+        Ok(body_decoded)
+    }
+
+    fn inspect_response(&self, endpoint: &str, host: &str, requested: Option<String>, headers: &HeaderMap) {
+        if let Some(hook) = &self.version_hook {
+            if let Some(info) = version::inspect(endpoint, self.version.as_ref(), requested, headers) {
+                hook(&info);
+            }
+        }
+        if self.monitor_deprecations {
+            let (method, path) = endpoint.split_once(' ').unwrap_or_default();
+            let endpoint = format!("{} {}{}", method, host, path);
+            deprecation::check(&endpoint, headers, self.deprecation_hook.as_ref());
+        }
+    }
+
+    fn execute_json(&self, request: Request) -> Result<Value, Box<dyn std::error::Error>> {
+        self.with_body_rules(request, |request| self.send_json(request))
+    }
+
+    // Runs `send` again while the body matches a retry rule and the policy
+    // allows another attempt.
+    fn with_body_rules<F>(&self, mut request: Request, send: F) -> Result<Value, Box<dyn std::error::Error>>
+    where
+        F: Fn(Request) -> Result<Value, Box<dyn std::error::Error>>,
+    {
+        if self.body_rules.is_empty() {
+            return send(request);
+        }
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let policy = self.retry_policy_for(&request).clone();
+        let started = self.clock.now();
+        let mut backed_off = Duration::ZERO;
+        let mut attempt = 1;
+        loop {
+            let next = request.try_clone();
+            let body = send(request)?;
+            let Some((rule, error)) = body_rules::matching(&self.body_rules, &body) else {
+                return Ok(body);
+            };
+            let next = match next {
+                Some(next) if rule.action() == BodyAction::Retry && policy.should_retry(attempt, next.method(), Some(429)) => next,
+                _ => return Err(ApiError::Other(error.into()).into()),
+            };
+            let delay = policy.delay(attempt, None, None);
+            let elapsed = self.clock.now().saturating_duration_since(started);
+            if let Some(limit) = policy.exhausted(elapsed, backed_off, delay) {
+                return Err(limit.into());
+            }
+            if !self.backoff(&endpoint, "body_rule", delay) {
+                return Err(ApiError::Other(error.into()).into());
+            }
+            backed_off += delay;
+            attempt += 1;
+            request = next;
+        }
+    }
+
+    fn send_json(&self, request: Request) -> Result<Value, Box<dyn std::error::Error>> {
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        let path = request.url().path().to_string();
+        let bodyless = request.method() == reqwest::Method::HEAD;
+        let response = self.execute(request)?;
+        self.response_json(&endpoint, &path, bodyless, response)
+    }
+
+    // HEAD, 204 and 304 responses come back as null.
+    fn response_json(&self, endpoint: &str, path: &str, bodyless: bool, response: Response) -> Result<Value, Box<dyn std::error::Error>> {
+        if bodyless || matches!(response.status(), reqwest::StatusCode::NO_CONTENT | reqwest::StatusCode::NOT_MODIFIED) {
+            let (status, url, headers) = (response.status(), response.url().clone(), response.headers().clone());
+            self.read_body(endpoint, response)?;
+            self.intercept(endpoint, ApiResponse::new(status, url, headers, Bytes::new()))?;
+            return Ok(Value::Null);
+        }
+        self.read_json(endpoint, path, response)
+    }
+
+    fn read_json(&self, endpoint: &str, path: &str, response: Response) -> Result<Value, Box<dyn std::error::Error>> {
+        let (status, url, headers) = (response.status(), response.url().clone(), response.headers().clone());
+        let body = self.read_body(endpoint, response)?;
+        self.decode_json(endpoint, path, ApiResponse::new(status, url, headers, body))
+    }
+
+    // Everything after the body is read: signature check, decryption,
+    // interceptors, parsing and the response-side pipeline.
+    fn decode_json(&self, endpoint: &str, path: &str, response: ApiResponse) -> Result<Value, Box<dyn std::error::Error>> {
+        let signature = match &self.response_verifier {
+            Some(verifier) => Some(verifier.signature(response.headers()).map_err(|e| ApiError::Middleware(e.into()))?),
+            None => None,
+        };
+        if let (Some(verifier), Some(signature)) = (&self.response_verifier, signature) {
+            verifier.verify(&signature, response.body()).map_err(|e| ApiError::Middleware(e.into()))?;
+        }
+        #[cfg(feature = "jwe")]
+        let response = match self.jwe.as_ref().and_then(|jwe| jwe.key_for(path)) {
+            Some(key) => {
+                let mut response = response;
+                response.set_body(key.decrypt(response.body()).map_err(|e| ApiError::Middleware(e.into()))?);
+                response
+            }
+            None => response,
+        };
+        let (_, body) = self.intercept(endpoint, response.into_utf8()?)?.into_parts();
+        let json = encoding::parse_json(body)?;
+        let json = self.apply_response_middleware(endpoint, json);
+        if let Some(tracker) = &self.schema_tracker {
+            tracker.check(endpoint, &json);
+        }
+        #[cfg(feature = "field-encryption")]
+        let json = match &self.field_encryption {
+            Some(fields) => {
+                let mut json = json;
+                fields.decrypt(&mut json).map_err(ApiError::Middleware)?;
+                json
+            }
+            None => json,
+        };
+        Ok(match &self.key_transform {
+            Some(transform) => transform.incoming_body(path, json),
+            None => json,
+        })
+    }
+
+    // Fetches `path` from this client's base URL and from `other_base_url` with the
+    // same auth and middleware, and diffs the two responses.
+    pub fn compare_with(&self, other_base_url: &str, path: &str, options: &DiffOptions) -> Result<Vec<Difference>, Box<dyn std::error::Error>> {
+        let left = self.fetch(path)?;
+        let right = self.fetch_url(&format!("{}{}", other_base_url, path))?;
+        Ok(diff(&left, &right, options))
+    }
+
+    // Runs `body` with a scope for spawning requests in parallel; all tasks are
+    // joined before this returns. If any task or `body` itself fails, the
+    // siblings are cancelled and the first failure is returned.
+    pub fn scope<'env, T, F>(&'env self, body: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> Result<T, Box<dyn std::error::Error>>,
+    {
+        scope::run(self, body)
+    }
+
+    // Fetches `path` repeatedly through the full request pipeline (auth,
+    // middleware, retries) and reports latency and error statistics.
+    #[cfg(feature = "bench")]
+    pub fn bench(&self, path: &str, options: &BenchOptions) -> BenchReport {
+        bench::run(options, || self.fetch(path))
+    }
+
+    #[cfg(feature = "bench")]
+    pub fn load(&self, path: &str, options: &LoadOptions) -> BenchReport {
+        bench::run_load(options, || self.fetch(path))
+    }
+
+    fn send_with_retry(&self, request: Request, body_decoded: u64) -> Result<Response, Box<dyn std::error::Error>> {
+        let mut history = Vec::new();
+        let mut response_id = None;
+        let endpoint = format!("{} {}", request.method(), request.url().path());
+        self.retry_stats.request(&endpoint);
+        #[cfg(feature = "tracing")]
+        let span = self.tracing.then(|| trace::request_span(&request, &self.capture, self.trace_redactor.as_ref()));
+        #[cfg(feature = "tracing")]
+        let entered = span.as_ref().map(|span| span.enter());
+        let started = self.clock.now();
+        let mut result = self.send_attempts(&request, &endpoint, body_decoded, &mut history, &mut response_id);
+        // Whatever the last attempt ran into, the call ended because of it.
+        if result.is_err() && cancel::cancelled() {
+            result = Err(Cancelled.into());
+        }
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &span {
+            drop(entered);
+            let elapsed = self.clock.now().saturating_duration_since(started);
+            let status = history.last().and_then(|attempt| attempt.status);
+            trace::finish(span, status, history.len(), elapsed, result.as_ref().err().map(|e| e.as_ref()));
+        }
+        let slot = self.report_call(
+            &request,
+            started,
+            &history,
+            response_id,
+            result.as_ref().ok().map(|r| r.headers()),
+            result.as_ref().err().map(|e| e.as_ref()),
+        );
+        if let Some(metrics) = &self.metrics {
+            metrics.request(&RequestMetrics {
+                method: request.method().as_str(),
+                endpoint: &endpoint,
+                status: history.last().and_then(|attempt| attempt.status),
+                attempts: history.len(),
+                elapsed: self.clock.now().saturating_duration_since(started),
+                error: result.as_ref().err().map(|e| e.as_ref()),
+            });
+        }
+        let mut response = match result {
+            Ok(response) => response,
+            #[cfg(feature = "revocation")]
+            Err(error) if error.is::<RevocationError>() => return Err(error),
+            Err(error) => return Err(RetryError { attempts: history, error }.into()),
+        };
+        let earlier = history[..history.len() - 1].to_vec();
+        response.extensions_mut().insert(FinalAttempt {
+            attempt: history.len(),
+            earlier,
+        });
+        if let Some(slot) = slot {
+            response.extensions_mut().insert(slot);
+        }
+        Ok(response)
+    }
+
+    // Feeds a finished call to the capture and, if it failed, the error hook.
+    // Returns where the capture wants the response body, if it does.
+    fn report_call(
+        &self,
+        request: &Request,
+        started: Instant,
+        history: &[Attempt],
+        response_id: Option<String>,
+        response_headers: Option<&HeaderMap>,
+        error: Option<&dyn std::error::Error>,
+    ) -> Option<CaptureSlot> {
+        let slot = self.capture.record(
+            self.clock.now(),
+            Exchange {
+                at: self.clock.system_time(),
+                method: request.method().as_str(),
+                url: request.url(),
+                request_headers: request.headers(),
+                request_body: request.body().and_then(|body| body.as_bytes()),
+                status: history.last().and_then(|attempt| attempt.status),
+                response_headers,
+                attempts: history.len(),
+                elapsed: self.clock.now().saturating_duration_since(started),
+                request_id: response_id.clone(),
+                error: error.map(|e| e.to_string()),
+            },
+        );
+        if let (Some(e), Some(hook)) = (error, &self.error_hook) {
+            let request_id = request
+                .headers()
+                .get(REQUEST_ID)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+                .or(response_id);
+            hook(&ErrorReport {
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                request_id,
+                attempts: history.to_vec(),
+                error: e.to_string(),
+            });
+        }
+        slot
+    }
+
+    fn store_failed(&self, store: Store, key: &str, error: &dyn std::error::Error) {
+        if let Some(hook) = &self.store_error_hook {
+            hook(&StoreError {
+                store,
+                key: key.to_string(),
+                error: error.to_string(),
+            });
+        }
+    }
+
+    // Waits out the backoff for `attempt`, cut short by a reconnect, the
+    // shutdown deadline or a cancelled scope. Returns false if the retry should
+    // be abandoned.
+    fn backoff(&self, endpoint: &str, reason: &str, delay: Duration) -> bool {
+        let started = self.clock.now();
+        match &self.sleeper {
+            Some(sleeper) => sleeper.sleep(delay),
+            None => {
+                let until = Instant::now() + delay;
+                loop {
+                    let end = self.lifecycle.cancel_at().map_or(until, |at| at.min(until));
+                    let now = Instant::now();
+                    if now >= end || self.connectivity.sleep(end - now) || scope::cancelled() || cancel::cancelled() {
+                        break;
+                    }
+                }
+            }
+        }
+        let slept = self.clock.now().saturating_duration_since(started);
+        self.retry_stats.retry(endpoint, reason, slept);
+        #[cfg(feature = "tracing")]
+        if self.tracing {
+            tracing::debug!(reason, slept_ms = slept.as_millis() as u64, "retrying");
+        }
+        !self.lifecycle.cancelled() && !scope::cancelled() && !cancel::cancelled()
+    }
+
+    // How long the client's and the request profile's rate limits hold it
+    // back. Takes the slots either way, so call it once per attempt.
+    fn throttle_delay(&self, request: &Request) -> Duration {
+        let now = self.clock.now();
+        let client = self.rate_limit.as_ref().map_or(Duration::ZERO, |bucket| bucket.acquire(now));
+        let profile = self.active_profile(request);
+        let delay = client.max(profile.map_or(Duration::ZERO, |profile| profile.throttle(now)));
+        if let Some(bucket) = &self.rate_limit {
+            self.save_bucket("client", bucket);
+        }
+        if let Some((key, bucket)) = profile.and_then(ActiveProfile::bucket) {
+            self.save_bucket(key, bucket);
+        }
+        delay
+    }
+
+    fn wait(&self, delay: Duration) {
+        if delay.is_zero() {
+            return;
+        }
+        match &self.sleeper {
+            Some(sleeper) => sleeper.sleep(delay),
+            None => std::thread::sleep(delay),
+        }
+    }
+
+    // A fresh, freshly signed copy of `request` for one attempt.
+    fn attempt_request(&self, request: &Request, body_decoded: u64) -> Result<(Request, TransferSize), Box<dyn std::error::Error>> {
+        let mut attempt_request = request.try_clone().ok_or("Request body can't be replayed")?;
+        // Not copied by `try_clone`.
+        *attempt_request.timeout_mut() = request.timeout().copied();
+        if let Some(signer) = &self.signer {
+            signer.sign(&mut attempt_request, self.clock.system_time())?;
+        }
+        let sent = TransferSize {
+            headers: size_stats::header_bytes(attempt_request.headers()),
+            body: attempt_request.body().and_then(|b| b.as_bytes()).map_or(0, |b| b.len() as u64),
+            body_decoded,
+        };
+        Ok((attempt_request, sent))
+    }
+
+    // Shared by the blocking and async retry loops, before and after each attempt.
+    fn check_maintenance(&self) -> Result<(), MaintenanceError> {
+        match &self.maintenance {
+            Some(maintenance) => maintenance.check(self.clock.now(), self.maintenance_hook.as_ref()),
+            None => Ok(()),
+        }
+    }
+
+    // The pause a failed response starts, if it's a maintenance one.
+    fn observe_maintenance(&self, headers: &HeaderMap, error: &ApiError, retry_after: Option<Duration>) -> Option<MaintenanceError> {
+        let (Some(maintenance), ApiError::Http { status, body, .. }) = (&self.maintenance, error) else {
+            return None;
+        };
+        maintenance.observe(*status, headers, body, retry_after, self.clock.now(), self.maintenance_hook.as_ref())
+    }
+
+    fn check_breaker(&self, host: &str) -> Result<(), CircuitOpen> {
+        match &self.breakers {
+            Some(breakers) => breakers.check(host, self.clock.now()),
+            None => Ok(()),
+        }
+    }
+
+    fn record_breaker(&self, host: &str, result: Result<reqwest::StatusCode, &reqwest::Error>) {
+        if let Some(breakers) = &self.breakers {
+            let failed = match result {
+                Ok(status) => status.is_server_error(),
+                // Refused locally, so it says nothing about the host.
+                Err(e) => egress::find(e).is_none(),
+            };
+            breakers.record(host, failed, self.clock.now());
+        }
+    }
+
+    // Size and StatsD accounting for one attempt; `outcome` is the response's
+    // status and headers, or the transport error.
+    fn record_attempt(
+        &self,
+        endpoint: &str,
+        request: &Request,
+        sent: TransferSize,
+        outcome: Result<(reqwest::StatusCode, &HeaderMap), String>,
+        elapsed: Duration,
+        response_id: &mut Option<String>,
+    ) -> Attempt {
+        let received_headers = outcome.as_ref().map_or(0, |(_, headers)| size_stats::header_bytes(headers));
+        self.size_stats.attempt(endpoint, sent, received_headers);
+        let method = request.method().as_str();
+        let attempt = match outcome {
+            Ok((status, headers)) => {
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    let now = self.clock.now();
+                    if let Some(bucket) = &self.rate_limit {
+                        bucket.penalize(now, headers.retry_after());
+                        self.save_bucket("client", bucket);
+                    }
+                    if let Some(profile) = self.active_profile(request) {
+                        profile.penalize(now, headers.retry_after());
+                        if let Some((key, bucket)) = profile.bucket() {
+                            self.save_bucket(key, bucket);
+                        }
+                    }
+                }
+                if let Some(quota) = &self.quota {
+                    let host = request.url().host_str().unwrap_or_default();
+                    if let Err(e) = quota.observe(host, status.as_u16(), headers, self.clock.system_time()) {
+                        self.store_failed(Store::Quota, host, e.as_ref());
+                    }
+                }
+                if let Some(id) = headers.get(REQUEST_ID).and_then(|v| v.to_str().ok()) {
+                    *response_id = Some(id.to_string());
+                }
+                Attempt {
+                    status: Some(status.as_u16()),
+                    error: (!status.is_success()).then(|| format!("HTTP error: {}", status)),
+                    elapsed,
+                }
+            }
+            Err(error) => Attempt {
+                status: None,
+                error: Some(error),
+                elapsed,
+            },
+        };
+        if let Some(statsd) = &self.statsd {
+            let status = attempt.status.map_or("error".to_string(), |s| s.to_string());
+            statsd.record_attempt(method, &status, elapsed);
+            statsd.record_bytes(method, "sent", sent.total());
+            statsd.record_bytes(method, "received", received_headers);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.attempt(&AttemptMetrics {
+                method,
+                endpoint,
+                status: attempt.status,
+                elapsed,
+                bytes_sent: sent.total(),
+                header_bytes_received: received_headers,
+            });
+        }
+        attempt
+    }
+
+    fn send_attempts(
+        &self,
+        request: &Request,
+        endpoint: &str,
+        body_decoded: u64,
+        history: &mut Vec<Attempt>,
+        response_id: &mut Option<String>,
+    ) -> Result<Response, Box<dyn std::error::Error>> {
+        let mut attempts = 0;
+        let mut stale_retried = false;
+        let call_started = self.clock.now();
+        let mut backed_off = Duration::ZERO;
+        let limits = options::limits();
+        let policy = match limits.deadline {
+            Some(deadline) => &self.retry_policy_for(request).clone().deadline(deadline),
+            None => self.retry_policy_for(request),
+        };
+        let client = self.client_for(limits.connect_timeout)?;
+
+        loop {
+            // Request IDs describe the last response only.
+            *response_id = None;
+            self.wait(self.throttle_delay(request));
+            let (mut attempt_request, sent) = self.attempt_request(request, body_decoded)?;
+            self.limit_to_deadline(policy, &mut attempt_request, call_started)?;
+            let host = attempt_request.url().host_str().unwrap_or_default().to_string();
+            self.check_maintenance()?;
+            self.check_breaker(&host)?;
+            let started = Instant::now();
+            let Some(result) = cancel::execute(&client, attempt_request) else {
+                return Err(Cancelled.into());
+            };
+            let elapsed = started.elapsed();
+            self.record_breaker(&host, result.as_ref().map(Response::status));
+            let outcome = match &result {
+                Ok(response) => Ok((response.status(), response.headers())),
+                Err(e) => Err(e.to_string()),
+            };
+            let attempt = self.record_attempt(endpoint, request, sent, outcome, elapsed, response_id);
+            history.push(attempt);
+            let call_elapsed = self.clock.now().saturating_duration_since(call_started);
+            match result {
+                Ok(response) => {
+                    let not_modified = response.status() == reqwest::StatusCode::NOT_MODIFIED && http_cache::is_conditional(request.headers());
+                    if response.status().is_success() || not_modified {
+                        return Ok(response);
+                    }
+                    let status = response.status().as_u16();
+                    let retry = policy.should_retry(attempts + 1, request.method(), Some(status));
+                    let retry_after = response.headers().retry_after();
+                    let delay = policy.delay(attempts + 1, Some(status), retry_after);
+                    let headers = self.maintenance.as_ref().map(|_| response.headers().clone());
+                    // Read and drop the failed response before backing off, so
+                    // nothing from this attempt leaks into the next one.
+                    let error = self.api_error(endpoint, response);
+                    if let Some(paused) = headers.and_then(|headers| self.observe_maintenance(&headers, &error, retry_after)) {
+                        return Err(paused.into());
+                    }
+                    if retry {
+                        attempts += 1;
+                        if let Some(limit) = policy.exhausted(call_elapsed, backed_off, delay) {
+                            return Err(limit.into());
+                        }
+                        if self.backoff(endpoint, &format!("status {}", status), delay) {
+                            backed_off += delay;
+                            continue;
+                        }
+                    }
+                    return Err(error.into());
+                }
+                Err(e) => {
+                    #[cfg(feature = "revocation")]
+                    if let Some(revocation) = revocation::find(&e) {
+                        return Err(revocation.into());
+                    }
+                    if let Some(egress) = egress::find(&e) {
+                        return Err(egress.into());
+                    }
+                    let timeout = TimeoutError::from_transport(&e, call_elapsed, elapsed, policy.deadline_limit());
+                    if let Some(deadline @ TimeoutError::Deadline { .. }) = timeout {
+                        return Err(deadline.into());
+                    }
+                    // Retried straight away and outside the attempt budget: the
+                    // request never reached a live connection.
+                    if !stale_retried && request.method().is_idempotent() && retry_stats::is_stale_connection(&e) {
+                        stale_retried = true;
+                        self.retry_stats.retry(endpoint, "stale_connection", Duration::ZERO);
+                        continue;
+                    }
+                    if policy.should_retry(attempts + 1, request.method(), None) {
+                        attempts += 1;
+                        let delay = policy.delay(attempts, None, None);
+                        if let Some(limit) = policy.exhausted(call_elapsed, backed_off, delay) {
+                            return Err(limit.into());
+                        }
+                        if self.backoff(endpoint, retry_stats::reason(&e), delay) {
+                            backed_off += delay;
+                            continue;
+                        }
+                    }
+                    return Err(match timeout {
+                        Some(timeout) => timeout.into(),
+                        None => e.into(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Caps an attempt's timeout at what's left of the retry deadline.
+    fn limit_to_deadline(&self, policy: &RetryPolicy, request: &mut Request, call_started: Instant) -> Result<(), TimeoutError> {
+        let Some(deadline) = policy.deadline_limit() else {
+            return Ok(());
+        };
+        let elapsed = self.clock.now().saturating_duration_since(call_started);
+        let remaining = deadline.checked_sub(elapsed).filter(|left| !left.is_zero()).ok_or(TimeoutError::Deadline { elapsed, deadline })?;
+        let timeout = request.timeout().copied().or(self.client_config.timeout);
+        *request.timeout_mut() = Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_new(base_url: *const c_char) -> *mut RustCore {
+    let c_str = unsafe { CStr::from_ptr(base_url) };
+    let base_url = c_str.to_str().unwrap();
+    Box::into_raw(Box::new(RustCore::new(base_url)))
+}
+
+// Frees a handle from `rust_core_new`, closing its connection pool. Null is
+// ignored. The handle must not be used again, and no other call may be using
+// it at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_destroy(core: *mut RustCore) {
+    if core.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(core) });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pool_new() -> *mut ClientPool {
+    match ClientPool::builder().build() {
+        Ok(pool) => Box::into_raw(Box::new(pool)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// A core drawing connections from `pool`. The pool may be destroyed before
+// the cores using it; they keep its connections alive.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_new_pooled(pool: *mut ClientPool, base_url: *const c_char) -> *mut RustCore {
+    let pool = unsafe { &*pool };
+    let c_str = unsafe { CStr::from_ptr(base_url) };
+    let base_url = c_str.to_str().unwrap();
+    match RustCore::builder(base_url).pool(pool).build() {
+        Ok(core) => Box::into_raw(Box::new(core)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// A core with the proxies in `proxy`, as described on
+// `RustCoreBuilder::proxy_json`. Null if they can't be parsed or used.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_new_with_proxy(base_url: *const c_char, proxy: *const c_char) -> *mut RustCore {
+    let c_str = unsafe { CStr::from_ptr(base_url) };
+    let base_url = c_str.to_str().unwrap();
+    let c_proxy = unsafe { CStr::from_ptr(proxy) };
+    let Some(builder) = serde_json::from_str(c_proxy.to_str().unwrap()).ok().and_then(|proxy| RustCore::builder(base_url).proxy_json(&proxy)) else {
+        last_error::invalid("Invalid proxy settings".to_string());
+        return std::ptr::null_mut();
+    };
+    match builder.build() {
+        Ok(core) => Box::into_raw(Box::new(core)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pool_destroy(pool: *mut ClientPool) {
+    if pool.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(pool) });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch(core: *mut RustCore, path: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_str = unsafe { CStr::from_ptr(path) };
+    let path = c_str.to_str().unwrap();
+    
+    match core.fetch(path) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_memoized(core: *mut RustCore, path: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_str = unsafe { CStr::from_ptr(path) };
+    let path = c_str.to_str().unwrap();
+
+    match core.memoized(path) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// A null `path` drops every memoized entry.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_invalidate_memo(core: *mut RustCore, path: *const c_char) {
+    let core = unsafe { &*core };
+    if path.is_null() {
+        core.clear_memo();
+        return;
+    }
+    let c_str = unsafe { CStr::from_ptr(path) };
+    core.invalidate_memo(c_str.to_str().unwrap());
+}
+
+// Returns `{"status": ..., "headers": {...}}` as JSON, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_head(core: *mut RustCore, path: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_str = unsafe { CStr::from_ptr(path) };
+    let path = c_str.to_str().unwrap();
+
+    match core.head(path) {
+        Ok(info) => {
+            let headers: serde_json::Map<String, Value> = info
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| Some((name.to_string(), Value::from(value.to_str().ok()?))))
+                .collect();
+            let json = serde_json::json!({ "status": info.status().as_u16(), "headers": headers });
+            CString::new(json.to_string()).unwrap().into_raw()
+        }
+        Err(e) => last_error::null(e),
+    }
+}
+
+// Per-endpoint byte counts under `endpoints`, the client-wide sum under `total`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_size_stats(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    let json = serde_json::json!({ "endpoints": core.size_stats(), "total": core.total_size_stats() });
+    CString::new(json.to_string()).unwrap().into_raw()
+}
+
+// 1 if the resource exists, 0 if not, or a negative `ApiError::ffi_code`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_exists(core: *mut RustCore, path: *const c_char) -> c_int {
+    let core = unsafe { &*core };
+    let c_str = unsafe { CStr::from_ptr(path) };
+    let path = c_str.to_str().unwrap();
+
+    match core.exists(path) {
+        Ok(exists) => exists as c_int,
+        Err(e) => last_error::code(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send(core: *mut RustCore, path: *const c_char, method: *const c_char, data: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+    
+    match core.send(path, method, data) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_free(ptr: *mut c_char) {
+    unsafe {
+        if ptr.is_null() { return }
+        drop(CString::from_raw(ptr))
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_auth(core: *mut RustCore, auth_type: *const c_char, key: *const c_char, value: *const c_char) {
+    let core = unsafe { &*core };
+    let c_auth_type = unsafe { CStr::from_ptr(auth_type) };
+    let auth_type = c_auth_type.to_str().unwrap();
+    let c_key = unsafe { CStr::from_ptr(key) };
+    let key = c_key.to_str().unwrap();
+    let c_value = unsafe { CStr::from_ptr(value) };
+    let value = c_value.to_str().unwrap();
+
+    let auth = match auth_type {
+        "Bearer" => Auth::Bearer(value.to_string()),
+        "Basic" => Auth::Basic(key.to_string(), value.to_string()),
+        "Custom" => Auth::Custom(key.to_string(), value.to_string()),
+        _ => return,
+    };
+
+    core.set_auth(auth);
+}
+
+// Safe to call while other threads use the core.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_clear_auth(core: *mut RustCore) {
+    let core = unsafe { &*core };
+    core.clear_auth();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_response_hmac(core: *mut RustCore, key: *const c_char, header: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_key = unsafe { CStr::from_ptr(key) };
+    let c_header = unsafe { CStr::from_ptr(header) };
+    let header = c_header.to_str().unwrap();
+
+    match ResponseVerifier::hmac(c_key.to_bytes(), header) {
+        Ok(verifier) => {
+            core.set_response_verifier(verifier);
+            0
+        }
+        Err(e) => last_error::invalid(e.to_string()),
+    }
+}
+
+// `skew_window_ms` of 0 leaves the receive-window header out.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_hmac_signer(core: *mut RustCore, key: *const c_char, skew_window_ms: u64) {
+    let core = unsafe { &mut *core };
+    let c_key = unsafe { CStr::from_ptr(key) };
+
+    let mut signer = HmacSigner::new(c_key.to_bytes());
+    if skew_window_ms > 0 {
+        signer = signer.skew_window(Duration::from_millis(skew_window_ms), "x-recv-window");
+    }
+    core.set_signer(signer);
+}
+
+// `tags` is a comma-separated list of `key:value` pairs and may be empty.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_statsd(core: *mut RustCore, addr: *const c_char, dogstatsd: bool, tags: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_addr = unsafe { CStr::from_ptr(addr) };
+    let c_tags = unsafe { CStr::from_ptr(tags) };
+    let addr = c_addr.to_str().unwrap();
+    let tags = c_tags.to_str().unwrap();
+
+    let mut sink = match StatsdSink::new(addr) {
+        Ok(sink) => sink,
+        Err(e) => return last_error::fail(e),
+    };
+    if dogstatsd {
+        sink = sink.format(StatsdFormat::DogStatsd);
+    }
+    for tag in tags.split(',').filter(|t| !t.is_empty()) {
+        let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+        sink = sink.tag(key, value);
+    }
+    core.set_statsd(sink);
+    0
+}
+
+// The stream borrows `core`, which must outlive it. Read events with
+// `rust_core_sse_next` and free it with `rust_core_sse_close`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_sse_open(core: *mut RustCore, path: *const c_char) -> *mut EventStream<'static> {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+
+    match core.fetch_sse(path) {
+        Ok(stream) => Box::into_raw(Box::new(stream)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// `{"id": ..., "event": ..., "data": ...}` for the next event, the JSON `null`
+// once the stream has ended, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_sse_next(stream: *mut EventStream<'static>) -> *mut c_char {
+    let stream = unsafe { &mut *stream };
+    let json = match stream.next() {
+        Some(Ok(event)) => serde_json::json!({ "id": event.id, "event": event.event, "data": event.data }),
+        Some(Err(e)) => return last_error::null(e),
+        None => Value::Null,
+    };
+    CString::new(json.to_string()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_sse_close(stream: *mut EventStream<'static>) {
+    if stream.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(stream) });
+}
+
+// Pages fetched one at a time, for progress reporting. Like the event stream,
+// it borrows `core`. See `paginate::parse_strategy` for the format of
+// `strategy`; free it with `rust_core_pages_close`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pages_open(core: *mut RustCore, path: *const c_char, strategy: *const c_char) -> *mut Pages<'static> {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let c_strategy = unsafe { CStr::from_ptr(strategy) };
+
+    match paginate::parse_strategy(c_strategy.to_str().unwrap()) {
+        Ok(strategy) => Box::into_raw(Box::new(core.fetch_paginated(c_path.to_str().unwrap(), strategy))),
+        Err(e) => {
+            last_error::invalid(format!("Invalid pagination strategy: {}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// `{"url": ..., "items": [...], "meta": {"number": ..., "page_size": ...,
+// "total": ..., "cursor": ..., "has_more": ...}}` for the next page, the JSON
+// `null` after the last one, or null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pages_next(pages: *mut Pages<'static>) -> *mut c_char {
+    let pages = unsafe { &mut *pages };
+    let json = match pages.next() {
+        Some(Ok(page)) => {
+            let meta = page.meta();
+            serde_json::json!({
+                "url": page.url().as_str(),
+                "items": pages.items_of(&page),
+                "meta": {
+                    "number": meta.number,
+                    "page_size": meta.page_size,
+                    "total": meta.total,
+                    "cursor": meta.cursor,
+                    "has_more": meta.has_more,
+                },
+            })
+        }
+        Some(Err(e)) => return last_error::null(e),
+        None => Value::Null,
+    };
+    CString::new(json.to_string()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pages_close(pages: *mut Pages<'static>) {
+    if pages.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(pages) });
+}
+
+// Remembers delivery IDs for `ttl_ms`, and in the file at `path` unless it's
+// null. Free it with `rust_core_idempotency_destroy`; streams and calls using
+// it keep their own reference.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_idempotency_new(ttl_ms: u64, path: *const c_char) -> *mut IdempotencyTracker {
+    let ttl = Duration::from_millis(ttl_ms);
+    if path.is_null() {
+        return Box::into_raw(Box::new(IdempotencyTracker::new(ttl)));
+    }
+    let c_path = unsafe { CStr::from_ptr(path) };
+    match IdempotencyTracker::open(c_path.to_str().unwrap(), ttl) {
+        Ok(tracker) => Box::into_raw(Box::new(tracker)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// 1 the first time `id` is seen, 0 for a duplicate. For webhook handlers,
+// with the delivery ID the sender puts in a header.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_idempotency_first_seen(tracker: *mut IdempotencyTracker, id: *const c_char) -> c_int {
+    let tracker = unsafe { &*tracker };
+    let c_id = unsafe { CStr::from_ptr(id) };
+    c_int::from(tracker.first_seen(c_id.to_str().unwrap()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_idempotency_forget(tracker: *mut IdempotencyTracker, id: *const c_char) {
+    let tracker = unsafe { &*tracker };
+    let c_id = unsafe { CStr::from_ptr(id) };
+    tracker.forget(c_id.to_str().unwrap());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_idempotency_destroy(tracker: *mut IdempotencyTracker) {
+    if tracker.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(tracker) });
+}
+
+// Skips events from now on whose ID `tracker` has seen.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_sse_dedup(stream: *mut EventStream<'static>, tracker: *mut IdempotencyTracker) {
+    let stream = unsafe { &mut *stream };
+    let tracker = unsafe { &*tracker };
+    stream.dedup = Some(tracker.clone());
+}
+
+// Like `rust_core_fetch_paginated`, leaving out items whose ID, at JSON
+// pointer `id`, `tracker` has seen.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_paginated_dedup(
+    core: *mut RustCore,
+    path: *const c_char,
+    strategy: *const c_char,
+    tracker: *mut IdempotencyTracker,
+    id: *const c_char,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let c_strategy = unsafe { CStr::from_ptr(strategy) };
+    let c_id = unsafe { CStr::from_ptr(id) };
+    let tracker = unsafe { &*tracker };
+    let path = c_path.to_str().unwrap();
+
+    let strategy = match paginate::parse_strategy(c_strategy.to_str().unwrap()) {
+        Ok(strategy) => strategy,
+        Err(e) => return last_error::invalid_null(format!("Invalid pagination strategy: {}", e)),
+    };
+    let items = core.fetch_paginated(path, strategy).items().dedup(tracker, c_id.to_str().unwrap());
+    match items.collect::<Result<Vec<_>, _>>() {
+        Ok(items) => CString::new(Value::Array(items).to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// A token for `rust_core_fetch_cancellable` and `rust_core_send_cancellable`.
+// Free it with `rust_core_cancel_token_destroy` once no call uses it.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_cancel_token_new() -> *mut CancellationToken {
+    Box::into_raw(Box::new(CancellationToken::new()))
+}
+
+// Safe from any thread, e.g. a GUI's cancel button while another thread is
+// blocked in the call. The call fails with code -1 and "Request cancelled".
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_cancel(token: *mut CancellationToken) {
+    let token = unsafe { &*token };
+    token.cancel();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_cancel_token_destroy(token: *mut CancellationToken) {
+    if token.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(token) });
+}
+
+// `options` as in `rust_core_fetch_with_options`, or null.
+unsafe fn cancellable_options(options: *const c_char, token: *mut CancellationToken) -> Option<RequestOptions> {
+    let options = if options.is_null() {
+        RequestOptions::new()
+    } else {
+        let c_options = unsafe { CStr::from_ptr(options) };
+        RequestOptions::from_json(&serde_json::from_str(c_options.to_str().unwrap()).ok()?)?
+    };
+    Some(options.cancel_token(unsafe { &*token }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_cancellable(
+    core: *mut RustCore,
+    path: *const c_char,
+    options: *const c_char,
+    token: *mut CancellationToken,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let Some(options) = (unsafe { cancellable_options(options, token) }) else {
+        return last_error::invalid_null(format!("Invalid request options: {}", unsafe { CStr::from_ptr(options) }.to_string_lossy()));
+    };
+
+    match core.fetch_with(path, &options) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_cancellable(
+    core: *mut RustCore,
+    path: *const c_char,
+    method: *const c_char,
+    data: *const c_char,
+    options: *const c_char,
+    token: *mut CancellationToken,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+    let Some(options) = (unsafe { cancellable_options(options, token) }) else {
+        return last_error::invalid_null(format!("Invalid request options: {}", unsafe { CStr::from_ptr(options) }.to_string_lossy()));
+    };
+
+    match core.send_with(path, method, data, &options) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// `paths` is a JSON array of strings. Returns one entry per path, in order:
+// `{"ok": true, "response": ...}` or `{"ok": false, "error": ..., "code": ...,
+// "status": ...}`, with `code` as in `rust_core_last_error_code`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_many(core: *mut RustCore, paths: *const c_char, concurrency: usize) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_paths = unsafe { CStr::from_ptr(paths) };
+    let Ok(paths) = serde_json::from_str::<Vec<String>>(c_paths.to_str().unwrap()) else {
+        return last_error::invalid_null("Paths must be a JSON array of strings".to_string());
+    };
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+    let results: Vec<Value> = core
+        .fetch_many(&paths, concurrency)
+        .into_iter()
+        .map(|result| match result {
+            Ok(response) => serde_json::json!({"ok": true, "response": response}),
+            Err(e) => serde_json::json!({"ok": false, "error": e.to_string(), "code": e.ffi_code(), "status": e.status()}),
+        })
+        .collect();
+    CString::new(Value::Array(results).to_string()).unwrap().into_raw()
+}
+
+// Pauses the client on 503 responses mentioning maintenance, for
+// `Retry-After` or `default_pause_ms`. `callback`, which may be null, gets
+// `{"event": "started", "resume_in_ms": ..., "message": ...}` or
+// `{"event": "ended"}`; the string is only valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_maintenance(
+    core: *mut RustCore,
+    default_pause_ms: u64,
+    callback: Option<extern "C" fn(event: *const c_char)>,
+) {
+    let core = unsafe { &mut *core };
+    core.set_maintenance(MaintenancePolicy::new().default_pause(Duration::from_millis(default_pause_ms)));
+    if let Some(callback) = callback {
+        core.on_maintenance(move |event| {
+            let event = match event {
+                MaintenanceEvent::Started { resume_in, message } => serde_json::json!({
+                    "event": "started",
+                    "resume_in_ms": resume_in.as_millis() as u64,
+                    "message": message,
+                }),
+                MaintenanceEvent::Ended => serde_json::json!({"event": "ended"}),
+            };
+            let event = CString::new(event.to_string()).unwrap();
+            callback(event.as_ptr());
+        });
+    }
+}
+
+// `{"resume_in_ms": ..., "message": ...}` while paused, `null` otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_maintenance_state(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    let state = core.maintenance_state().map_or(Value::Null, |pause| {
+        serde_json::json!({"resume_in_ms": pause.resume_in.as_millis() as u64, "message": pause.message})
+    });
+    CString::new(state.to_string()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_resume_after_maintenance(core: *mut RustCore) {
+    let core = unsafe { &*core };
+    core.resume_after_maintenance();
+}
+
+// Hands each metric to a C callback as a JSON object with a `kind` of
+// "attempt", "request" or "body", and a final "flush" on shutdown.
+struct CallbackMetrics(extern "C" fn(event: *const c_char));
+
+impl CallbackMetrics {
+    fn emit(&self, event: Value) {
+        let event = CString::new(event.to_string()).unwrap();
+        (self.0)(event.as_ptr());
+    }
+}
+
+impl MetricsSink for CallbackMetrics {
+    fn attempt(&self, metrics: &AttemptMetrics<'_>) {
+        self.emit(serde_json::json!({
+            "kind": "attempt",
+            "method": metrics.method,
+            "endpoint": metrics.endpoint,
+            "status": metrics.status,
+            "elapsed_ms": metrics.elapsed.as_secs_f64() * 1000.0,
+            "bytes_sent": metrics.bytes_sent,
+            "header_bytes_received": metrics.header_bytes_received,
+        }));
+    }
+
+    fn request(&self, metrics: &RequestMetrics<'_>) {
+        self.emit(serde_json::json!({
+            "kind": "request",
+            "method": metrics.method,
+            "endpoint": metrics.endpoint,
+            "status": metrics.status,
+            "attempts": metrics.attempts,
+            "retries": metrics.retries(),
+            "elapsed_ms": metrics.elapsed.as_secs_f64() * 1000.0,
+            "error": metrics.error.map(|e| e.to_string()),
+        }));
+    }
+
+    fn body(&self, metrics: &BodyMetrics<'_>) {
+        self.emit(serde_json::json!({
+            "kind": "body",
+            "method": metrics.method,
+            "endpoint": metrics.endpoint,
+            "bytes": metrics.bytes,
+            "decoded_bytes": metrics.decoded_bytes,
+        }));
+    }
+
+    fn flush(&self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.emit(serde_json::json!({"kind": "flush", "timeout_ms": timeout.as_millis() as u64}));
+    }
+}
+
+// `callback` runs on the thread making the request, and its string is only
+// valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_metrics_callback(core: *mut RustCore, callback: extern "C" fn(event: *const c_char)) {
+    let core = unsafe { &mut *core };
+    core.set_metrics_sink(CallbackMetrics(callback));
+}
+
+// `values` is a JSON array; a response whose JSON at `path` equals one of
+// them is retried when `retry` is set, and fails otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_add_body_rule(core: *mut RustCore, path: *const c_char, values: *const c_char, retry: bool) -> c_int {
+    let core = unsafe { &mut *core };
+    let path = unsafe { CStr::from_ptr(path) }.to_str().unwrap();
+    let values = unsafe { CStr::from_ptr(values) }.to_str().unwrap();
+    let values: Vec<Value> = match serde_json::from_str(values) {
+        Ok(values) => values,
+        Err(e) => return last_error::invalid(format!("Invalid body rule values: {}", e)),
+    };
+    let action = if retry { BodyAction::Retry } else { BodyAction::Fail };
+    core.add_body_rule(BodyRule::new(path, values, action));
+    0
+}
+
+// A `failure_threshold` of 0 removes the circuit breaker.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_circuit_breaker(core: *mut RustCore, failure_threshold: u32, cooldown_ms: u64) {
+    let core = unsafe { &mut *core };
+    if failure_threshold == 0 {
+        core.clear_circuit_breaker();
+    } else {
+        core.set_circuit_breaker(CircuitBreaker::new(failure_threshold, Duration::from_millis(cooldown_ms)));
+    }
+}
+
+// `{"host": "open" | "half-open" | "closed"}`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_circuit_states(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    let states: serde_json::Map<String, Value> = core
+        .circuit_states()
+        .into_iter()
+        .map(|(host, state)| (host, Value::String(state.as_str().to_string())))
+        .collect();
+    CString::new(Value::Object(states).to_string()).unwrap().into_raw()
+}
+
+// Caches up to `capacity` GET responses in memory; 0 turns caching off.
+// Responses without caching headers stay fresh for `default_ttl_ms`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_cache(core: *mut RustCore, capacity: usize, default_ttl_ms: u64) {
+    let core = unsafe { &mut *core };
+    core.set_cache(CachePolicy::memory(capacity).default_ttl(Duration::from_millis(default_ttl_ms)));
+}
+
+// Returns the response envelope as JSON, `data` and `errors` both present.
+// `path` may be empty to post to the base URL; `variables` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_graphql(core: *mut RustCore, path: *const c_char, query: *const c_char, variables: *const c_char, persisted: bool) -> *mut c_char {
+    let core = unsafe { &*core };
+    let path = unsafe { CStr::from_ptr(path) }.to_str().unwrap();
+    let query = unsafe { CStr::from_ptr(query) }.to_str().unwrap();
+    let mut request = GraphQLRequest::new(query).path(path).persisted(persisted);
+    if !variables.is_null() {
+        match serde_json::from_str(unsafe { CStr::from_ptr(variables) }.to_str().unwrap()) {
+            Ok(variables) => request = request.variables(variables),
+            Err(e) => return last_error::invalid_null(format!("Invalid GraphQL variables: {}", e)),
+        }
+    }
+    match core.graphql_request(&request) {
+        Ok(response) => CString::new(serde_json::to_string(&response).unwrap()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// Follows every page and returns all their items as one JSON array. See
+// `paginate::parse_strategy` for the format of `strategy`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_paginated(core: *mut RustCore, path: *const c_char, strategy: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let c_strategy = unsafe { CStr::from_ptr(strategy) };
+    let path = c_path.to_str().unwrap();
+
+    let strategy = match paginate::parse_strategy(c_strategy.to_str().unwrap()) {
+        Ok(strategy) => strategy,
+        Err(e) => return last_error::invalid_null(format!("Invalid pagination strategy: {}", e)),
+    };
+    match core.fetch_paginated(path, strategy).items().collect::<Result<Vec<_>, _>>() {
+        Ok(items) => CString::new(Value::Array(items).to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// `per_second` of 0 or less removes the rate limit.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_rate_limit(core: *mut RustCore, per_second: f64, burst: u32) {
+    let core = unsafe { &mut *core };
+    if per_second > 0.0 {
+        core.set_rate_limit(per_second, burst);
+    } else {
+        core.clear_rate_limit();
+    }
+}
+
+// `route` may be null or empty to use the profile for the whole client.
+// `name` is built in ("aggressive", "conservative", "read-heavy") or comes from
+// the loaded config file.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_profile(core: *mut RustCore, name: *const c_char, route: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_name = unsafe { CStr::from_ptr(name) };
+    let profile = match core.profile(c_name.to_str().unwrap()) {
+        Ok(profile) => profile,
+        Err(e) => return last_error::invalid(e.to_string()),
+    };
+    let route = match route.is_null() {
+        true => "",
+        false => unsafe { CStr::from_ptr(route) }.to_str().unwrap(),
+    };
+    if route.is_empty() {
+        core.set_profile(profile);
+    } else {
+        core.set_profile_for(route, profile);
+    }
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_load_profiles(core: *mut RustCore, path: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+
+    match core.load_profiles(path) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// The message of the last failed call on this thread, or null if none has
+// failed since the last clear. Free it with `rust_core_free`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_last_error() -> *mut c_char {
+    match last_error::get() {
+        Some((_, message)) => CString::new(message.replace('\0', "")).unwrap().into_raw(),
+        None => std::ptr::null_mut(),
+    }
+}
+
+// 0 if none, otherwise one of the codes of `ApiError::ffi_code`, or -7 for an
+// argument that couldn't be parsed.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_last_error_code() -> c_int {
+    last_error::get().map_or(0, |(code, _)| code)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_clear_last_error() {
+    last_error::clear();
+}
+
+// `fields` is a JSON array of `[name, value]` pairs.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_form(core: *mut RustCore, path: *const c_char, method: *const c_char, fields: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_fields = unsafe { CStr::from_ptr(fields) };
+    let Ok(fields) = serde_json::from_str::<Vec<(String, String)>>(c_fields.to_str().unwrap()) else {
+        return last_error::invalid_null("Form fields must be a JSON array of [name, value] pairs".to_string());
+    };
+    let fields: Vec<(&str, &str)> = fields.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+
+    match core.send_form(path, method, &fields) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// `parts` is a JSON array as described on `Multipart::from_json`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_multipart(core: *mut RustCore, path: *const c_char, parts: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_parts = unsafe { CStr::from_ptr(parts) };
+    let parts: Value = match serde_json::from_str(c_parts.to_str().unwrap()) {
+        Ok(parts) => parts,
+        Err(e) => return last_error::invalid_null(format!("Invalid multipart parts: {}", e)),
+    };
+    let Some(form) = Multipart::from_json(&parts) else {
+        return last_error::invalid_null("Multipart parts need a name and one of value, path or base64".to_string());
+    };
+
+    match core.send_multipart(path, &form) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// 0 for either limit means no limit.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_decompression_limits(core: *mut RustCore, max_bytes: u64, max_ratio: u64) {
+    let core = unsafe { &mut *core };
+    let unlimited_if_zero = |limit: u64| if limit == 0 { u64::MAX } else { limit };
+    core.set_decompression_limits(DecompressionLimits::new(unlimited_if_zero(max_bytes), unlimited_if_zero(max_ratio)));
+}
+
+// `message` and the returned response are base64; null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_grpc_web(core: *mut RustCore, method: *const c_char, message: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let method = c_method.to_str().unwrap();
+    let c_message = unsafe { CStr::from_ptr(message) };
+    let Ok(message) = STANDARD.decode(c_message.to_bytes()) else {
+        return last_error::invalid_null("gRPC-Web message must be base64".to_string());
+    };
+
+    match core.grpc_web_unary(method, &message) {
+        Ok(response) => CString::new(STANDARD.encode(response)).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// Returns the number of bytes written, or -1. `progress` may be null; it gets
+// the bytes downloaded so far and the total, or -1 when the size is unknown.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_download(
+    core: *mut RustCore,
+    path: *const c_char,
+    dest: *const c_char,
+    progress: Option<extern "C" fn(downloaded: u64, total: i64)>,
+) -> i64 {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_dest = unsafe { CStr::from_ptr(dest) };
+    let dest = c_dest.to_str().unwrap();
+
+    let result = core.download_to_file_with_progress(path, dest, |downloaded, total| {
+        if let Some(progress) = progress {
+            progress(downloaded, total.map_or(-1, |total| total as i64));
+        }
+    });
+    match result {
+        Ok(written) => written as i64,
+        Err(e) => last_error::fail(e) as i64,
+    }
+}
+
+// Returns the manifest as JSON.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_export(core: *mut RustCore, path: *const c_char, dest_dir: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_dest_dir = unsafe { CStr::from_ptr(dest_dir) };
+    let dest_dir = c_dest_dir.to_str().unwrap();
+
+    match core.export(path, dest_dir) {
+        Ok(manifest) => CString::new(serde_json::to_string(&manifest).unwrap()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_enable_cache(core: *mut RustCore, dir: *const c_char, max_bytes: u64) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_dir = unsafe { CStr::from_ptr(dir) };
+    let dir = c_dir.to_str().unwrap();
+
+    match core.enable_disk_cache(dir, max_bytes) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_cache_clear(core: *mut RustCore) -> c_int {
+    let core = unsafe { &*core };
+    match core.clear_disk_cache() {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// `scopes` is space-separated and may be empty.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_oauth2(
+    core: *mut RustCore,
+    token_url: *const c_char,
+    client_id: *const c_char,
+    client_secret: *const c_char,
+    scopes: *const c_char,
+) {
+    let core = unsafe { &*core };
+    let c_token_url = unsafe { CStr::from_ptr(token_url) };
+    let token_url = c_token_url.to_str().unwrap();
+    let c_client_id = unsafe { CStr::from_ptr(client_id) };
+    let client_id = c_client_id.to_str().unwrap();
+    let c_client_secret = unsafe { CStr::from_ptr(client_secret) };
+    let client_secret = c_client_secret.to_str().unwrap();
+    let c_scopes = unsafe { CStr::from_ptr(scopes) };
+    let scopes: Vec<&str> = c_scopes.to_str().unwrap().split_whitespace().collect();
+
+    core.set_auth(Auth::OAuth2(OAuth2::new(token_url, client_id, client_secret).scopes(&scopes)));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_default_header(core: *mut RustCore, name: *const c_char, value: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_name = unsafe { CStr::from_ptr(name) };
+    let name = c_name.to_str().unwrap();
+    let c_value = unsafe { CStr::from_ptr(value) };
+    let value = c_value.to_str().unwrap();
+
+    match core.set_default_header(name, value) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// `options` is a JSON object with optional "params" (as for
+// `rust_core_fetch_with_params`) and "headers" (name to string value).
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_with_options(core: *mut RustCore, path: *const c_char, options: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_options = unsafe { CStr::from_ptr(options) };
+    let Some(options) = serde_json::from_str(c_options.to_str().unwrap()).ok().as_ref().and_then(RequestOptions::from_json) else {
+        return last_error::invalid_null(format!("Invalid request options: {}", c_options.to_string_lossy()));
+    };
+
+    match core.fetch_with(path, &options) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_with_options(
+    core: *mut RustCore,
+    path: *const c_char,
+    method: *const c_char,
+    data: *const c_char,
+    options: *const c_char,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+    let c_options = unsafe { CStr::from_ptr(options) };
+    let Some(options) = serde_json::from_str(c_options.to_str().unwrap()).ok().as_ref().and_then(RequestOptions::from_json) else {
+        return last_error::invalid_null(format!("Invalid request options: {}", c_options.to_string_lossy()));
+    };
+
+    match core.send_with(path, method, data, &options) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// Returns a JSON array of per-item results: href, status and body, plus
+// failed_props and description for WebDAV.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_multi_status(core: *mut RustCore, path: *const c_char, method: *const c_char, data: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+
+    match core.send_multi_status(path, method, data) {
+        Ok(items) => CString::new(serde_json::to_string(&items).unwrap()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
+}
+
+// `depth` may be null to leave the Depth header off.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_dav(
+    core: *mut RustCore,
+    method: *const c_char,
+    path: *const c_char,
+    depth: *const c_char,
+    xml: *const c_char,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let method = c_method.to_str().unwrap();
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let depth = (!depth.is_null()).then(|| unsafe { CStr::from_ptr(depth) }.to_str().unwrap());
+    let c_xml = unsafe { CStr::from_ptr(xml) };
+    let xml = c_xml.to_str().unwrap();
 
-pub struct RustCore {
-    client: Client,
-    base_url: String,
-    auth: Option<Auth>,
-    middleware: Vec<Arc<dyn Fn(Request) -> Request + Send + Sync>>,
+    match core.dav(method, path, depth, xml) {
+        Ok(items) => CString::new(serde_json::to_string(&items).unwrap()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
 }
 
-pub enum Auth {
-    Bearer(String),
-    Basic(String, String),
-    Custom(String, String),
+// `params` is a JSON object of strings, numbers, booleans or arrays of those;
+// arrays become repeated names.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_fetch_with_params(core: *mut RustCore, path: *const c_char, params: *const c_char) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_params = unsafe { CStr::from_ptr(params) };
+    let Some(query) = serde_json::from_str(c_params.to_str().unwrap()).ok().as_ref().and_then(Query::from_json) else {
+        return last_error::invalid_null(format!("Invalid query parameters: {}", c_params.to_string_lossy()));
+    };
+
+    match core.fetch_with_query(path, &query) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
+    }
 }
 
-impl RustCore {
-    pub fn new(base_url: &str) -> Self {
-        RustCore {
-            client: Client::builder()
-                .timeout(Duration::from_secs(30))
-                .build()
-                .unwrap(),
-            base_url: base_url.to_string(),
-            auth: None,
-            middleware: Vec::new(),
-        }
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_with_params(
+    core: *mut RustCore,
+    path: *const c_char,
+    method: *const c_char,
+    data: *const c_char,
+    params: *const c_char,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+    let c_params = unsafe { CStr::from_ptr(params) };
+    let Some(query) = serde_json::from_str(c_params.to_str().unwrap()).ok().as_ref().and_then(Query::from_json) else {
+        return last_error::invalid_null(format!("Invalid query parameters: {}", c_params.to_string_lossy()));
+    };
+
+    match core.send_with_query(path, method, data, &query) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
     }
+}
 
-    pub fn set_auth(&mut self, auth: Auth) {
-        self.auth = Some(auth);
+// `retry_non_idempotent` extends the default status rule to POST and PATCH.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_retry_policy(
+    core: *mut RustCore,
+    max_attempts: u32,
+    base_delay_ms: u64,
+    max_delay_ms: u64,
+    jitter: f64,
+    retry_non_idempotent: bool,
+) {
+    let core = unsafe { &mut *core };
+    let mut policy = RetryPolicy::new()
+        .max_attempts(max_attempts)
+        .base_delay(Duration::from_millis(base_delay_ms))
+        .max_delay(Duration::from_millis(max_delay_ms))
+        .jitter(jitter);
+    if retry_non_idempotent {
+        policy = policy.retry_if(|_, status| RetryPolicy::retryable_status(status));
     }
+    core.set_retry_policy(policy);
+}
 
-    pub fn add_middleware<F>(&mut self, middleware: F)
-    where
-        F: Fn(Request) -> Request + Send + Sync + 'static,
-    {
-        self.middleware.push(Arc::new(middleware));
+// Sets the deadline and backoff budget on the current retry policy; 0 removes
+// a limit.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_retry_limits(core: *mut RustCore, deadline_ms: u64, backoff_budget_ms: u64) {
+    let core = unsafe { &mut *core };
+    let limit = |ms: u64| (ms > 0).then(|| Duration::from_millis(ms));
+    let policy = core.retry_policy.clone().limits(limit(deadline_ms), limit(backoff_budget_ms));
+    core.set_retry_policy(policy);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_add_default_query(core: *mut RustCore, name: *const c_char, value: *const c_char) {
+    let core = unsafe { &mut *core };
+    let c_name = unsafe { CStr::from_ptr(name) };
+    let name = c_name.to_str().unwrap();
+    let c_value = unsafe { CStr::from_ptr(value) };
+    let value = c_value.to_str().unwrap();
+
+    core.add_default_query(name, value);
+}
+
+// `{"queued": n, "in_flight": n, "oldest_wait_ms": n | null}`
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pending(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    let pending = core.pending();
+    let json = serde_json::json!({
+        "queued": pending.queued,
+        "in_flight": pending.in_flight,
+        "oldest_wait_ms": pending.oldest_wait.map(|wait| wait.as_millis() as u64),
+    });
+    CString::new(json.to_string()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_quota_store(core: *mut RustCore, path: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+
+    match core.set_quota_store(path) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
     }
+}
 
-    fn apply_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        match &self.auth {
-            Some(Auth::Bearer(token)) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
-            Some(Auth::Basic(username, password)) => request.basic_auth(username, Some(password)),
-            Some(Auth::Custom(key, value)) => request.header(key, value),
-            None => request,
-        }
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_quota_state(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    CString::new(serde_json::to_string(&core.quota_state()).unwrap()).unwrap().into_raw()
+}
+
+// `names` and `base_urls` are parallel comma-separated lists.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_regions(
+    core: *mut RustCore,
+    names: *const c_char,
+    base_urls: *const c_char,
+    probe_path: *const c_char,
+    interval_ms: u64,
+) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_names = unsafe { CStr::from_ptr(names) };
+    let names: Vec<&str> = c_names.to_str().unwrap().split(',').map(str::trim).collect();
+    let c_base_urls = unsafe { CStr::from_ptr(base_urls) };
+    let base_urls: Vec<&str> = c_base_urls.to_str().unwrap().split(',').map(str::trim).collect();
+    let c_probe_path = unsafe { CStr::from_ptr(probe_path) };
+    let probe_path = c_probe_path.to_str().unwrap();
+    if names.len() != base_urls.len() {
+        return last_error::invalid(format!("Got {} region names for {} base URLs", names.len(), base_urls.len()));
+    }
+    let mut regions = Regions::new().probe_path(probe_path).interval(Duration::from_millis(interval_ms));
+    for (name, base_url) in names.into_iter().zip(base_urls) {
+        regions = regions.region(name, base_url);
+    }
+
+    match core.set_regions(regions) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// An empty name unpins.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_pin_region(core: *mut RustCore, name: *const c_char) -> c_int {
+    let core = unsafe { &*core };
+    let c_name = unsafe { CStr::from_ptr(name) };
+    let name = c_name.to_str().unwrap();
+
+    match core.pin_region((!name.is_empty()).then_some(name)) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_region_status(core: *mut RustCore) -> *mut c_char {
+    let core = unsafe { &*core };
+    CString::new(serde_json::to_string(&core.region_status()).unwrap()).unwrap().into_raw()
+}
+
+// `max_count` > 0 keeps the last that many calls; otherwise the capture runs
+// for `duration_ms`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_start_capture(core: *mut RustCore, duration_ms: u64, max_count: usize) {
+    let core = unsafe { &*core };
+    let limit = match max_count {
+        0 => CaptureLimit::Duration(Duration::from_millis(duration_ms)),
+        count => CaptureLimit::Count(count),
+    };
+    core.start_capture(limit);
+}
+
+// Null if no capture was started. `stop` ends the capture after taking it.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_support_bundle(core: *mut RustCore, stop: bool) -> *mut c_char {
+    let core = unsafe { &*core };
+    let bundle = match stop {
+        true => core.stop_capture(),
+        false => core.support_bundle(),
+    };
+    match bundle {
+        Some(bundle) => CString::new(serde_json::to_string(&bundle).unwrap()).unwrap().into_raw(),
+        None => std::ptr::null_mut(),
     }
+}
 
-    fn apply_middleware(&self, mut request: Request) -> Request {
-        for middleware in &self.middleware {
-            request = middleware(request);
+// `callback` returns 0 retryable, 1 auth, 2 validation, 3 quota, or anything
+// else to fall back to the status default. `code` is null when the body has
+// no vendor code.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_error_translator(core: *mut RustCore, callback: extern "C" fn(status: u16, code: *const c_char) -> c_int) {
+    let core = unsafe { &mut *core };
+    core.set_error_translator(move |status, code| {
+        let code = code.map(|code| CString::new(code.replace('\0', "")).unwrap());
+        match callback(status, code.as_ref().map_or(std::ptr::null(), |code| code.as_ptr())) {
+            0 => Some(ErrorCategory::Retryable),
+            1 => Some(ErrorCategory::Auth),
+            2 => Some(ErrorCategory::Validation),
+            3 => Some(ErrorCategory::Quota),
+            _ => None,
         }
-        request
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_bulk(
+    core: *mut RustCore,
+    path: *const c_char,
+    method: *const c_char,
+    items: *const c_char,
+    chunk_size: usize,
+    concurrency: usize,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_items = unsafe { CStr::from_ptr(items) };
+    let Ok(items) = serde_json::from_str::<Vec<Value>>(c_items.to_str().unwrap()) else {
+        return last_error::invalid_null("Bulk items must be a JSON array".to_string());
+    };
+    let options = BulkOptions::new(chunk_size).method(method).concurrency(concurrency);
+
+    let report = core.bulk_with(path, &items, &options);
+    CString::new(serde_json::to_string(&report).unwrap()).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_send_and_follow(
+    core: *mut RustCore,
+    path: *const c_char,
+    method: *const c_char,
+    data: *const c_char,
+    interval_ms: u64,
+    timeout_ms: u64,
+) -> *mut c_char {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+    let c_method = unsafe { CStr::from_ptr(method) };
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid_null(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid_null(format!("Invalid JSON body: {}", e)),
+    };
+    let options = PollOptions {
+        interval: Duration::from_millis(interval_ms),
+        timeout: Duration::from_millis(timeout_ms),
+        ..PollOptions::default()
+    };
+
+    match core.send_and_follow(path, method, data, &options) {
+        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
+        Err(e) => last_error::null(e),
     }
+}
 
-    pub fn fetch(&self, path: &str) -> Result<Value, Box<dyn std::error::Error>> {
-        let url = format!("{}{}", self.base_url, path);
-        let request = self.client.get(&url);
-        let request = self.apply_auth(request);
-        let request = request.build()?;
-        let request = self.apply_middleware(request);
-        
-        let response = self.send_with_retry(request)?;
-        let json: Value = response.json()?;
-        Ok(json)
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_deprecation_monitoring(core: *mut RustCore, enabled: bool) {
+    let core = unsafe { &mut *core };
+    core.set_deprecation_monitoring(enabled);
+}
+
+// `strategy` is "media_type" (`name` is the vendor), "header" (`name` is the
+// header) or "path_prefix" (`name` is ignored).
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_api_version(core: *mut RustCore, strategy: *const c_char, name: *const c_char, version: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_strategy = unsafe { CStr::from_ptr(strategy) };
+    let c_name = unsafe { CStr::from_ptr(name) };
+    let c_version = unsafe { CStr::from_ptr(version) };
+    let name = c_name.to_str().unwrap();
+    let version = c_version.to_str().unwrap();
+
+    let version = match c_strategy.to_str().unwrap() {
+        "media_type" => ApiVersion::media_type(name, version),
+        "header" if HeaderName::try_from(name).is_ok() => ApiVersion::header(name, version),
+        "path_prefix" => ApiVersion::path_prefix(version),
+        other => return last_error::invalid(format!("Unsupported API version strategy or header: {} / {}", other, name)),
+    };
+    core.set_api_version(version);
+    0
+}
+
+// `tenant_header` may be empty; `tenants` is comma-separated.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_add_traffic_split(
+    core: *mut RustCore,
+    path: *const c_char,
+    base_url: *const c_char,
+    percent: f64,
+    tenant_header: *const c_char,
+    tenants: *const c_char,
+) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let c_base_url = unsafe { CStr::from_ptr(base_url) };
+    let c_tenant_header = unsafe { CStr::from_ptr(tenant_header) };
+    let c_tenants = unsafe { CStr::from_ptr(tenants) };
+    let tenant_header = c_tenant_header.to_str().unwrap();
+    let tenants = c_tenants.to_str().unwrap();
+
+    let mut split = TrafficSplit::new(c_path.to_str().unwrap(), c_base_url.to_str().unwrap()).percent(percent);
+    if !tenant_header.is_empty() {
+        if HeaderName::try_from(tenant_header).is_err() {
+            return last_error::invalid(format!("Invalid header name: {}", tenant_header));
+        }
+        split = split.tenant_header(tenant_header);
     }
+    for tenant in tenants.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        split = split.tenant(tenant);
+    }
+    core.add_traffic_split(split);
+    0
+}
 
-    pub fn send(&self, path: &str, method: &str, data: Value) -> Result<Value, Box<dyn std::error::Error>> {
-        let url = format!("{}{}", self.base_url, path);
-        let request = match method {
-            "POST" => self.client.post(&url),
-            "PUT" => self.client.put(&url),
-            _ => return Err("Unsupported method".into()),
+// `hosts` and `cidrs` are comma-separated; both empty allows any public host.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_egress_policy(core: *mut RustCore, hosts: *const c_char, cidrs: *const c_char, block_private: bool) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_hosts = unsafe { CStr::from_ptr(hosts) };
+    let c_cidrs = unsafe { CStr::from_ptr(cidrs) };
+    let hosts = c_hosts.to_str().unwrap();
+    let cidrs = c_cidrs.to_str().unwrap();
+
+    let mut policy = EgressPolicy::new().block_private(block_private);
+    for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+        policy = policy.allow_host(host);
+    }
+    for cidr in cidrs.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        policy = match policy.allow_cidr(cidr) {
+            Ok(policy) => policy,
+            Err(e) => return last_error::fail(e),
         };
-        let request = self.apply_auth(request);
-        let request = request.json(&data).build()?;
-        let request = self.apply_middleware(request);
-        
-        let response = self.send_with_retry(request)?;
-        let json: Value = response.json()?;
-        Ok(json)
     }
+    match core.set_egress_policy(policy) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
 
-    fn send_with_retry(&self, request: Request) -> Result<Response, Box<dyn std::error::Error>> {
-        let mut attempts = 0;
-        let max_attempts = 3;
-        
-        loop {
-            match self.client.execute(request.try_clone().unwrap()) {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        return Ok(response);
-                    } else if response.status().is_server_error() && attempts < max_attempts {
-                        attempts += 1;
-                        std::thread::sleep(Duration::from_secs(2u64.pow(attempts)));
-                        continue;
-                    } else {
-                        return Err(format!("HTTP error: {}", response.status()).into());
-                    }
-                }
-                Err(e) if attempts < max_attempts => {
-                    attempts += 1;
-                    std::thread::sleep(Duration::from_secs(2u64.pow(attempts)));
-                    continue;
-                }
-                Err(e) => return Err(e.into()),
-            }
-        }
+// `outgoing` and `incoming` are "snake", "camel" or "" for no conversion.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_key_transform(core: *mut RustCore, outgoing: *const c_char, incoming: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_outgoing = unsafe { CStr::from_ptr(outgoing) };
+    let c_incoming = unsafe { CStr::from_ptr(incoming) };
+    let parse = |case: &str| match case {
+        "snake" => Ok(Some(KeyCase::Snake)),
+        "camel" => Ok(Some(KeyCase::Camel)),
+        "" => Ok(None),
+        _ => Err(()),
+    };
+    let (Ok(outgoing), Ok(incoming)) = (parse(c_outgoing.to_str().unwrap()), parse(c_incoming.to_str().unwrap())) else {
+        return last_error::invalid(format!("Unsupported key case: {} / {}", c_outgoing.to_string_lossy(), c_incoming.to_string_lossy()));
+    };
+
+    let mut transform = KeyTransform::new();
+    if let Some(case) = outgoing {
+        transform = transform.outgoing(case);
+    }
+    if let Some(case) = incoming {
+        transform = transform.incoming(case);
     }
+    core.set_key_transform(transform);
+    0
 }
 
+// `languages` and `fallback` are comma-separated, most preferred first; an
+// empty `fallback` disables the 406 retry.
 #[no_mangle]
-pub extern "C" fn rust_core_new(base_url: *const c_char) -> *mut RustCore {
-    let c_str = unsafe { CStr::from_ptr(base_url) };
-    let base_url = c_str.to_str().unwrap();
-    Box::into_raw(Box::new(RustCore::new(base_url)))
+pub unsafe extern "C" fn rust_core_set_locale(core: *mut RustCore, languages: *const c_char, fallback: *const c_char) {
+    let core = unsafe { &mut *core };
+    let c_languages = unsafe { CStr::from_ptr(languages) };
+    let c_fallback = unsafe { CStr::from_ptr(fallback) };
+    let parse = |list: &str| -> Vec<String> {
+        list.split(',').map(str::trim).filter(|l| !l.is_empty()).map(str::to_string).collect()
+    };
+    let languages = parse(c_languages.to_str().unwrap());
+    let fallback = parse(c_fallback.to_str().unwrap());
+
+    core.set_locale(Locale::new(&languages.iter().map(String::as_str).collect::<Vec<_>>()));
+    if fallback.is_empty() {
+        core.clear_locale_fallback();
+    } else {
+        core.set_locale_fallback(Locale::new(&fallback.iter().map(String::as_str).collect::<Vec<_>>()));
+    }
 }
 
+// Returns 0 once drained, -1 if requests were still in flight at the deadline.
 #[no_mangle]
-pub extern "C" fn rust_core_fetch(core: *mut RustCore, path: *const c_char) -> *mut c_char {
+pub unsafe extern "C" fn rust_core_shutdown(core: *mut RustCore, timeout_ms: u64) -> c_int {
     let core = unsafe { &*core };
-    let c_str = unsafe { CStr::from_ptr(path) };
-    let path = c_str.to_str().unwrap();
-    
-    match core.fetch(path) {
-        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
-        Err(_) => std::ptr::null_mut(),
+    if core.shutdown(Duration::from_millis(timeout_ms)) {
+        0
+    } else {
+        last_error::fail(format!("Requests were still in flight after {}ms", timeout_ms))
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_notify_connectivity(core: *mut RustCore, online: bool) {
+    let core = unsafe { &*core };
+    core.notify_connectivity(online);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_enable_offline_queue(core: *mut RustCore, journal_path: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_path = unsafe { CStr::from_ptr(journal_path) };
+    let path = c_path.to_str().unwrap();
+
+    match core.enable_offline_queue(path) {
+        Ok(delivered) => delivered as c_int,
+        Err(e) => last_error::fail(e),
     }
 }
 
+// The callback receives the failed item as JSON and the error message; both
+// strings are only valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_set_queue_failure_callback(
+    core: *mut RustCore,
+    callback: extern "C" fn(item: *const c_char, error: *const c_char),
+) {
+    let core = unsafe { &mut *core };
+    core.set_queue_failure_handler(move |item, error| {
+        let item = CString::new(serde_json::to_string(item).unwrap()).unwrap();
+        let error = CString::new(error.to_string().replace('\0', "")).unwrap();
+        callback(item.as_ptr(), error.as_ptr());
+    });
+}
+
 #[no_mangle]
-pub extern "C" fn rust_core_send(core: *mut RustCore, path: *const c_char, method: *const c_char, data: *const c_char) -> *mut c_char {
+pub unsafe extern "C" fn rust_core_enqueue(core: *mut RustCore, path: *const c_char, method: *const c_char, data: *const c_char) -> c_int {
     let core = unsafe { &*core };
     let c_path = unsafe { CStr::from_ptr(path) };
     let path = c_path.to_str().unwrap();
     let c_method = unsafe { CStr::from_ptr(method) };
-    let method = c_method.to_str().unwrap();
+    let Ok(method) = c_method.to_str().unwrap().parse::<Method>() else {
+        return last_error::invalid(format!("Unsupported method: {}", c_method.to_string_lossy()));
+    };
     let c_data = unsafe { CStr::from_ptr(data) };
-    let data: Value = serde_json::from_str(c_data.to_str().unwrap()).unwrap();
-    
-    match core.send(path, method, data) {
-        Ok(json) => CString::new(json.to_string()).unwrap().into_raw(),
-        Err(_) => std::ptr::null_mut(),
+    let data: Value = match serde_json::from_str(c_data.to_str().unwrap()) {
+        Ok(data) => data,
+        Err(e) => return last_error::invalid(format!("Invalid JSON body: {}", e)),
+    };
+
+    match core.enqueue(path, method, data) {
+        Ok(_) => 0,
+        Err(e) => last_error::fail(e),
     }
 }
 
 #[no_mangle]
-pub extern "C" fn rust_core_free(ptr: *mut c_char) {
-    unsafe {
-        if ptr.is_null() { return }
-        CString::from_raw(ptr)
-    };
+pub unsafe extern "C" fn rust_core_flush_queue(core: *mut RustCore) -> c_int {
+    let core = unsafe { &*core };
+    match core.flush_queue() {
+        Ok(delivered) => delivered as c_int,
+        Err(e) => last_error::fail(e),
+    }
 }
 
+#[cfg(feature = "scripting")]
 #[no_mangle]
-pub extern "C" fn rust_core_set_auth(core: *mut RustCore, auth_type: *const c_char, key: *const c_char, value: *const c_char) {
+pub unsafe extern "C" fn rust_core_load_script(core: *mut RustCore, path: *const c_char) -> c_int {
     let core = unsafe { &mut *core };
-    let c_auth_type = unsafe { CStr::from_ptr(auth_type) };
-    let auth_type = c_auth_type.to_str().unwrap();
-    let c_key = unsafe { CStr::from_ptr(key) };
-    let key = c_key.to_str().unwrap();
-    let c_value = unsafe { CStr::from_ptr(value) };
-    let value = c_value.to_str().unwrap();
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
 
-    let auth = match auth_type {
-        "Bearer" => Auth::Bearer(value.to_string()),
-        "Basic" => Auth::Basic(key.to_string(), value.to_string()),
-        "Custom" => Auth::Custom(key.to_string(), value.to_string()),
-        _ => return,
+    match ScriptMiddleware::from_file(path) {
+        Ok(script) => {
+            core.add_script(script);
+            0
+        }
+        Err(e) => last_error::fail(e),
+    }
+}
+
+#[cfg(feature = "scripting")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_reload_scripts(core: *mut RustCore) -> c_int {
+    let core = unsafe { &*core };
+    match core.reload_scripts() {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_load_plugin(core: *mut RustCore, path: *const c_char) -> c_int {
+    let core = unsafe { &mut *core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+
+    match WasmMiddleware::from_file(path) {
+        Ok(plugin) => {
+            core.add_plugin(plugin);
+            0
+        }
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// The socket borrows `core`, which must outlive it. Close it with
+// `rust_core_ws_close`.
+#[cfg(feature = "websocket")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_ws_connect(core: *mut RustCore, path: *const c_char) -> *mut WebSocket<'static> {
+    let core = unsafe { &*core };
+    let c_path = unsafe { CStr::from_ptr(path) };
+    let path = c_path.to_str().unwrap();
+
+    match core.connect_ws(path) {
+        Ok(socket) => Box::into_raw(Box::new(socket)),
+        Err(e) => {
+            last_error::code(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// `data` is the text, or base64 when `binary` is set.
+#[cfg(feature = "websocket")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_ws_send(socket: *mut WebSocket<'static>, data: *const c_char, binary: bool) -> c_int {
+    let socket = unsafe { &mut *socket };
+    let c_data = unsafe { CStr::from_ptr(data) };
+    let message = match binary {
+        true => match STANDARD.decode(c_data.to_bytes()) {
+            Ok(data) => Message::Binary(data),
+            Err(_) => return last_error::invalid("Binary WebSocket messages must be base64".to_string()),
+        },
+        false => Message::Text(c_data.to_str().unwrap().to_string()),
+    };
+    match socket.send(message) {
+        Ok(()) => 0,
+        Err(e) => last_error::fail(e),
+    }
+}
+
+// `{"type": "text", "data": "..."}`, `{"type": "binary", "data": "<base64>"}`
+// or `{"type": "close"}` once the server closed the connection.
+#[cfg(feature = "websocket")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_ws_receive(socket: *mut WebSocket<'static>) -> *mut c_char {
+    let socket = unsafe { &mut *socket };
+    let message = match socket.receive() {
+        Ok(Some(Message::Text(text))) => serde_json::json!({ "type": "text", "data": text }),
+        Ok(Some(Message::Binary(data))) => serde_json::json!({ "type": "binary", "data": STANDARD.encode(data) }),
+        Ok(None) => serde_json::json!({ "type": "close" }),
+        Err(e) => return last_error::null(e),
     };
+    CString::new(message.to_string()).unwrap().into_raw()
+}
 
-    core.set_auth(auth);
+// Closes the connection and frees the socket; null is ignored.
+#[cfg(feature = "websocket")]
+#[no_mangle]
+pub unsafe extern "C" fn rust_core_ws_close(socket: *mut WebSocket<'static>) {
+    if socket.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(socket) });
 }
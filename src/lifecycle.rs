@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// What the client is still working on, for progress displays and stuck
+// pipeline checks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pending {
+    // Items waiting in the offline queue.
+    pub queued: usize,
+    pub in_flight: usize,
+    // Age of the oldest queued item or in-flight request, whichever is older.
+    pub oldest_wait: Option<Duration>,
+}
+
+struct State {
+    accepting: bool,
+    // Start time of each in-flight request, by entry number.
+    in_flight: BTreeMap<u64, Instant>,
+    next_id: u64,
+    // Retries still waiting once this passes are abandoned.
+    cancel_at: Option<Instant>,
+}
+
+// Tracks in-flight requests so `shutdown` can drain them.
+pub(crate) struct Lifecycle {
+    state: Mutex<State>,
+    idle: Condvar,
+}
+
+pub(crate) struct InFlight<'a> {
+    lifecycle: &'a Lifecycle,
+    id: u64,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut state = self.lifecycle.state.lock().unwrap();
+        state.in_flight.remove(&self.id);
+        if state.in_flight.is_empty() {
+            self.lifecycle.idle.notify_all();
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Lifecycle {
+            state: Mutex::new(State {
+                accepting: true,
+                in_flight: BTreeMap::new(),
+                next_id: 0,
+                cancel_at: None,
+            }),
+            idle: Condvar::new(),
+        }
+    }
+
+    pub fn enter(&self) -> Result<InFlight<'_>, Box<dyn std::error::Error>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.accepting {
+            return Err("Client is shutting down".into());
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.in_flight.insert(id, Instant::now());
+        Ok(InFlight { lifecycle: self, id })
+    }
+
+    // Count and age of the oldest request; ids only grow, so it's the first.
+    pub fn in_flight(&self) -> (usize, Option<Duration>) {
+        let state = self.state.lock().unwrap();
+        let oldest = state.in_flight.values().next().map(Instant::elapsed);
+        (state.in_flight.len(), oldest)
+    }
+
+    pub fn begin_shutdown(&self, deadline: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.accepting = false;
+        state.cancel_at = Some(state.cancel_at.map_or(deadline, |at| at.min(deadline)));
+    }
+
+    pub fn cancel_at(&self) -> Option<Instant> {
+        self.state.lock().unwrap().cancel_at
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancel_at().is_some_and(|at| Instant::now() >= at)
+    }
+
+    // Returns how many requests were still in flight at the deadline.
+    pub fn wait_idle(&self, deadline: Instant) -> usize {
+        let mut state = self.state.lock().unwrap();
+        while !state.in_flight.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.idle.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        state.in_flight.len()
+    }
+}
@@ -0,0 +1,60 @@
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderValue, ACCEPT_LANGUAGE, CONTENT_LANGUAGE};
+
+// Ordered language preferences, most preferred first (BCP 47 tags such as
+// `de-CH`). Sent as Accept-Language with descending q-values, and as
+// Content-Language (the first tag) on requests that carry a body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locale {
+    languages: Vec<String>,
+}
+
+impl Locale {
+    pub fn new(languages: &[&str]) -> Self {
+        Locale {
+            languages: languages.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    pub fn languages(&self) -> &[String] {
+        &self.languages
+    }
+
+    // `de-CH, de;q=0.9, en;q=0.8`, bottoming out at q=0.1.
+    pub fn accept_language(&self) -> String {
+        self.languages
+            .iter()
+            .enumerate()
+            .map(|(i, language)| match i {
+                0 => language.clone(),
+                _ => format!("{};q={:.1}", language, (10 - i.min(9)) as f32 / 10.0),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // Headers already present on the request win, so per-request locales and
+    // middleware can override the client's.
+    pub(crate) fn apply(&self, request: &mut Request) {
+        if self.languages.is_empty() {
+            return;
+        }
+        let has_body = request.body().is_some();
+        let headers = request.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&self.accept_language()) {
+            headers.entry(ACCEPT_LANGUAGE).or_insert(value);
+        }
+        if has_body {
+            if let Ok(value) = HeaderValue::from_str(&self.languages[0]) {
+                headers.entry(CONTENT_LANGUAGE).or_insert(value);
+            }
+        }
+    }
+
+    // Replaces whatever locale headers the request had.
+    pub(crate) fn replace(&self, request: &mut Request) {
+        request.headers_mut().remove(ACCEPT_LANGUAGE);
+        request.headers_mut().remove(CONTENT_LANGUAGE);
+        self.apply(request);
+    }
+}
@@ -0,0 +1,184 @@
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Matcher = Arc<dyn Fn(u16, &HeaderMap, &str) -> bool + Send + Sync>;
+
+pub(crate) type MaintenanceHook = Arc<dyn Fn(&MaintenanceEvent) + Send + Sync>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MaintenanceEvent {
+    // A maintenance response paused the client; `message` is the body's
+    // `message` field, if it has one.
+    Started { resume_in: Duration, message: Option<String> },
+    // The scheduled resume came; the next request is sent again.
+    Ended,
+}
+
+// A request refused without being sent while the client is paused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaintenanceError {
+    pub resume_in: Duration,
+    pub message: Option<String>,
+}
+
+impl fmt::Display for MaintenanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API is down for maintenance; resuming in {}s", self.resume_in.as_secs_f64().ceil() as u64)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MaintenanceError {}
+
+// Recognizes maintenance responses and pauses the whole client until the
+// API is expected back, instead of every caller retrying into it. The pause
+// lasts as long as `Retry-After` asks, or `default_pause` without one, capped
+// at `max_pause`. By default a 503 whose body mentions "maintenance" counts.
+#[derive(Clone)]
+pub struct MaintenancePolicy {
+    matcher: Matcher,
+    default_pause: Duration,
+    max_pause: Duration,
+}
+
+impl MaintenancePolicy {
+    pub fn new() -> Self {
+        MaintenancePolicy {
+            matcher: Arc::new(|status, _, body| status == 503 && body.to_ascii_lowercase().contains("maintenance")),
+            default_pause: Duration::from_secs(60),
+            max_pause: Duration::from_secs(3600),
+        }
+    }
+
+    // Called with the status, headers and body of every failed response.
+    pub fn matcher<F>(mut self, matcher: F) -> Self
+    where
+        F: Fn(u16, &HeaderMap, &str) -> bool + Send + Sync + 'static,
+    {
+        self.matcher = Arc::new(matcher);
+        self
+    }
+
+    pub fn default_pause(mut self, pause: Duration) -> Self {
+        self.default_pause = pause;
+        self
+    }
+
+    pub fn max_pause(mut self, pause: Duration) -> Self {
+        self.max_pause = pause;
+        self
+    }
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MaintenancePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaintenancePolicy")
+            .field("default_pause", &self.default_pause)
+            .field("max_pause", &self.max_pause)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Pause {
+    until: Instant,
+    message: Option<String>,
+}
+
+pub(crate) struct Maintenance {
+    policy: MaintenancePolicy,
+    pause: Mutex<Option<Pause>>,
+}
+
+impl Maintenance {
+    pub fn new(policy: MaintenancePolicy) -> Self {
+        Maintenance {
+            policy,
+            pause: Mutex::new(None),
+        }
+    }
+
+    // Called before every attempt; ends a pause whose time is up.
+    pub fn check(&self, now: Instant, hook: Option<&MaintenanceHook>) -> Result<(), MaintenanceError> {
+        let mut pause = self.pause.lock().unwrap();
+        match pause.as_ref() {
+            None => return Ok(()),
+            Some(current) if now < current.until => {
+                return Err(MaintenanceError {
+                    resume_in: current.until - now,
+                    message: current.message.clone(),
+                })
+            }
+            Some(_) => *pause = None,
+        }
+        drop(pause);
+        if let Some(hook) = hook {
+            hook(&MaintenanceEvent::Ended);
+        }
+        Ok(())
+    }
+
+    // Pauses the client if the failed response is a maintenance one.
+    pub fn observe(
+        &self,
+        status: u16,
+        headers: &HeaderMap,
+        body: &str,
+        retry_after: Option<Duration>,
+        now: Instant,
+        hook: Option<&MaintenanceHook>,
+    ) -> Option<MaintenanceError> {
+        if !(self.policy.matcher)(status, headers, body) {
+            return None;
+        }
+        let resume_in = retry_after.unwrap_or(self.policy.default_pause).min(self.policy.max_pause);
+        let message = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|body| body.get("message")?.as_str().map(str::to_string));
+        let mut pause = self.pause.lock().unwrap();
+        // Requests that were already in flight report the same window again.
+        let started = pause.is_none();
+        let until = pause.as_ref().map_or(now + resume_in, |current| current.until.max(now + resume_in));
+        *pause = Some(Pause {
+            until,
+            message: message.clone(),
+        });
+        drop(pause);
+        let resume_in = until - now;
+        if let (true, Some(hook)) = (started, hook) {
+            hook(&MaintenanceEvent::Started {
+                resume_in,
+                message: message.clone(),
+            });
+        }
+        Some(MaintenanceError { resume_in, message })
+    }
+
+    pub fn state(&self, now: Instant) -> Option<MaintenanceError> {
+        let pause = self.pause.lock().unwrap();
+        let pause = pause.as_ref().filter(|pause| now < pause.until)?;
+        Some(MaintenanceError {
+            resume_in: pause.until - now,
+            message: pause.message.clone(),
+        })
+    }
+
+    pub fn resume(&self, hook: Option<&MaintenanceHook>) {
+        if self.pause.lock().unwrap().take().is_some() {
+            if let Some(hook) = hook {
+                hook(&MaintenanceEvent::Ended);
+            }
+        }
+    }
+}
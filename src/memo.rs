@@ -0,0 +1,123 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+pub(crate) const DEFAULT_MEMO_CAPACITY: usize = 256;
+
+// Serialized size from which entries are stored gzipped, and the share of
+// that size the compressed bytes must stay under to be worth the decode on
+// every read.
+const COMPRESS_THRESHOLD: usize = 16 * 1024;
+const MAX_COMPRESSED_RATIO: f64 = 0.8;
+
+#[derive(Clone)]
+enum Stored {
+    Plain(Value),
+    // Gzipped JSON, decoded on each read.
+    Compressed(Arc<[u8]>),
+}
+
+impl Stored {
+    fn new(value: Value) -> Self {
+        let Ok(json) = serde_json::to_vec(&value) else {
+            return Stored::Plain(value);
+        };
+        if json.len() < COMPRESS_THRESHOLD {
+            return Stored::Plain(value);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        match encoder.write_all(&json).and_then(|_| encoder.finish()) {
+            Ok(gzipped) if (gzipped.len() as f64) < json.len() as f64 * MAX_COMPRESSED_RATIO => Stored::Compressed(gzipped.into()),
+            _ => Stored::Plain(value),
+        }
+    }
+
+    fn value(self) -> Option<Value> {
+        match self {
+            Stored::Plain(value) => Some(value),
+            Stored::Compressed(gzipped) => {
+                let mut json = Vec::new();
+                GzDecoder::new(&gzipped[..]).read_to_end(&mut json).ok()?;
+                serde_json::from_slice(&json).ok()
+            }
+        }
+    }
+}
+
+// Responses kept until explicitly invalidated, regardless of Cache-Control.
+// When full, the least recently read entry is evicted.
+pub(crate) struct Memo {
+    entries: Mutex<MemoEntries>,
+}
+
+struct MemoEntries {
+    capacity: usize,
+    tick: u64,
+    values: HashMap<String, (Stored, u64)>,
+}
+
+impl Memo {
+    pub fn new(capacity: usize) -> Self {
+        Memo {
+            entries: Mutex::new(MemoEntries {
+                capacity,
+                tick: 0,
+                values: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Value> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let (stored, last_read) = entries.values.get_mut(path)?;
+        *last_read = tick;
+        let stored = stored.clone();
+        drop(entries);
+        stored.value()
+    }
+
+    pub fn insert(&self, path: &str, value: Value) {
+        let stored = Stored::new(value);
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.values.insert(path.to_string(), (stored, tick));
+        entries.evict();
+    }
+
+    pub fn invalidate(&self, path: &str) -> bool {
+        self.entries.lock().unwrap().values.remove(path).is_some()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().values.clear();
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.capacity = capacity;
+        entries.evict();
+    }
+}
+
+impl MemoEntries {
+    fn evict(&mut self) {
+        while self.values.len() > self.capacity {
+            let Some(oldest) = self
+                .values
+                .iter()
+                .min_by_key(|(_, (_, last_read))| *last_read)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.values.remove(&oldest);
+        }
+    }
+}
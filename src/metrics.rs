@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+// One HTTP attempt: a response, or a transport error (`status` None).
+// Byte counts are on the wire; the response body is reported separately,
+// once it's read.
+#[derive(Clone, Copy, Debug)]
+pub struct AttemptMetrics<'a> {
+    pub method: &'a str,
+    // `METHOD /path`, without the query.
+    pub endpoint: &'a str,
+    pub status: Option<u16>,
+    pub elapsed: Duration,
+    pub bytes_sent: u64,
+    pub header_bytes_received: u64,
+}
+
+// A call after all its attempts and backoff.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestMetrics<'a> {
+    pub method: &'a str,
+    pub endpoint: &'a str,
+    // Of the last attempt.
+    pub status: Option<u16>,
+    pub attempts: usize,
+    pub elapsed: Duration,
+    pub error: Option<&'a dyn std::error::Error>,
+}
+
+impl RequestMetrics<'_> {
+    pub fn retries(&self) -> usize {
+        self.attempts.saturating_sub(1)
+    }
+}
+
+// A response body as read, for failed attempts as well as the final one.
+#[derive(Clone, Copy, Debug)]
+pub struct BodyMetrics<'a> {
+    pub method: &'a str,
+    pub endpoint: &'a str,
+    pub bytes: u64,
+    // After decompression.
+    pub decoded_bytes: u64,
+}
+
+// Receives timings and sizes from every call a `RustCore` makes, e.g. to
+// feed Prometheus histograms. Called on the request's thread, so
+// implementations should be quick and must not block.
+pub trait MetricsSink: Send + Sync {
+    fn attempt(&self, _metrics: &AttemptMetrics<'_>) {}
+
+    fn request(&self, _metrics: &RequestMetrics<'_>) {}
+
+    fn body(&self, _metrics: &BodyMetrics<'_>) {}
+
+    // Called once from `RustCore::shutdown`; sinks that buffer should send
+    // what they hold before `deadline`.
+    fn flush(&self, _deadline: Instant) {}
+}
@@ -0,0 +1,282 @@
+use crate::charset;
+use crate::error::ApiError;
+use bytes::Bytes;
+use reqwest::blocking::Request;
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+pub(crate) type RequestMiddleware = dyn Fn(Request) -> Request + Send + Sync;
+pub(crate) type ResponseMiddleware = dyn Fn(Value) -> Value + Send + Sync;
+pub(crate) type ResponseInterceptor = dyn Fn(ApiResponse) -> Result<ApiResponse, ApiError> + Send + Sync;
+
+// A successful response as response interceptors see it: after signature
+// verification, decryption and transcoding to UTF-8, before the body is
+// parsed as JSON.
+#[derive(Clone, Debug)]
+pub struct ApiResponse {
+    status: StatusCode,
+    url: Url,
+    headers: HeaderMap,
+    body: Bytes,
+    charset: Option<&'static str>,
+}
+
+impl ApiResponse {
+    pub(crate) fn new(status: StatusCode, url: Url, headers: HeaderMap, body: Bytes) -> Self {
+        ApiResponse { status, url, headers, body, charset: None }
+    }
+
+    // Decodes the body from the Content-Type's charset.
+    pub(crate) fn into_utf8(mut self) -> Result<Self, ApiError> {
+        let body = std::mem::take(&mut self.body);
+        let (body, charset) = charset::to_utf8(&self.headers, body).map_err(|e| ApiError::Decode(e.into()))?;
+        self.body = body;
+        self.charset = charset;
+        Ok(self)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    // Empty for HEAD and 204 responses.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    // The encoding the body arrived in, e.g. "windows-1252" for Latin-1,
+    // when it was transcoded to UTF-8. None if it was UTF-8 already.
+    pub fn charset(&self) -> Option<&str> {
+        self.charset
+    }
+
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.body = Bytes::from(body);
+    }
+
+    pub fn json(&self) -> Result<Value, ApiError> {
+        serde_json::from_slice(&self.body).map_err(|e| ApiError::Decode(e.into()))
+    }
+
+    pub fn set_json(&mut self, json: &Value) {
+        self.body = Bytes::from(json.to_string());
+    }
+
+    pub(crate) fn into_parts(self) -> (HeaderMap, Bytes) {
+        (self.headers, self.body)
+    }
+}
+
+// Which kind of auth a client uses, without the credentials.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthKind {
+    Bearer,
+    Basic,
+    // The name of the header the key goes in.
+    Custom(String),
+    OAuth2,
+}
+
+impl AuthKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthKind::Bearer => "bearer",
+            AuthKind::Basic => "basic",
+            AuthKind::Custom(_) => "custom",
+            AuthKind::OAuth2 => "oauth2",
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<RequestContext>> = const { RefCell::new(None) };
+}
+
+// What the client resolved for the request a middleware is running on, so
+// audit or signing layers don't have to work it out again. reqwest's blocking
+// requests have no extensions to carry it, so middleware asks for it with
+// `current`:
+//
+//     core.add_middleware(|request| {
+//         if let Some(context) = RequestContext::current() {
+//             audit(context.host(), context.auth());
+//         }
+//         request
+//     });
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    auth: Option<AuthKind>,
+    host: String,
+    phase: Phase,
+}
+
+impl RequestContext {
+    pub(crate) fn new(auth: Option<AuthKind>, host: String, phase: Phase) -> Self {
+        RequestContext { auth, host, phase }
+    }
+
+    // The context of the request whose middleware is running on this thread,
+    // or None outside middleware.
+    pub fn current() -> Option<RequestContext> {
+        CONTEXT.with(|context| context.borrow().clone())
+    }
+
+    // The auth that is or will be applied; None if the client has none.
+    pub fn auth(&self) -> Option<&AuthKind> {
+        self.auth.as_ref()
+    }
+
+    // Where the request is addressed as this phase runs. Traffic splits and
+    // regions can still move it before `PreSend`.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    // Makes this the current context until `f` returns. Requests that
+    // middleware sends itself get their own and restore this one afterwards.
+    pub(crate) fn run<T>(self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<RequestContext>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CONTEXT.with(|context| *context.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(CONTEXT.with(|context| context.borrow_mut().replace(self)));
+        f()
+    }
+}
+
+// Where a request middleware runs while a request is built.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    // Before the Authorization header (or custom auth header) is set.
+    PreAuth,
+    // After auth, versioning, locale and default query parameters. Where
+    // `add_middleware` puts middleware.
+    #[default]
+    PostAuth,
+    // After traffic splitting and region routing picked the final URL.
+    PreSend,
+}
+
+// Which requests a scoped middleware runs on: `/admin/*` or `POST /users`.
+// `*` stands for one path segment, or for any number of them (including
+// none) at the end. Paths are relative to the base URL.
+#[derive(Clone, Debug)]
+pub(crate) struct Route {
+    method: Option<String>,
+    segments: Vec<String>,
+}
+
+impl Route {
+    pub fn parse(route: &str) -> Self {
+        let (method, path) = match route.trim().split_once(char::is_whitespace) {
+            Some((method, path)) if !method.starts_with('/') => (Some(method.to_ascii_uppercase()), path.trim()),
+            _ => (None, route.trim()),
+        };
+        Route {
+            method,
+            segments: split(path).map(str::to_string).collect(),
+        }
+    }
+
+    pub fn matches(&self, method: &str, path: &str) -> bool {
+        if self.method.as_deref().is_some_and(|m| !m.eq_ignore_ascii_case(method)) {
+            return false;
+        }
+        let mut path = split(path);
+        for (i, segment) in self.segments.iter().enumerate() {
+            if segment == "*" && i == self.segments.len() - 1 {
+                return true;
+            }
+            match path.next() {
+                Some(actual) if segment == "*" || segment == actual => {}
+                _ => return false,
+            }
+        }
+        path.next().is_none()
+    }
+}
+
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+struct Entry<P, T: ?Sized> {
+    name: Option<String>,
+    phase: P,
+    order: i32,
+    seq: u64,
+    route: Option<Route>,
+    middleware: Arc<T>,
+}
+
+// Middleware kept sorted by phase, then `order`, then registration. Named
+// entries are unique: registering a name again replaces the old entry.
+pub(crate) struct Chain<P, T: ?Sized> {
+    entries: Vec<Entry<P, T>>,
+    next_seq: u64,
+}
+
+impl<P: Copy + Ord, T: ?Sized> Chain<P, T> {
+    pub fn new() -> Self {
+        Chain {
+            entries: Vec::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn add(&mut self, name: Option<&str>, phase: P, order: i32, route: Option<Route>, middleware: Arc<T>) {
+        if let Some(name) = name {
+            self.remove(name);
+        }
+        self.entries.push(Entry {
+            name: name.map(str::to_string),
+            phase,
+            order,
+            seq: self.next_seq,
+            route,
+            middleware,
+        });
+        self.next_seq += 1;
+        self.entries.sort_by_key(|entry| (entry.phase, entry.order, entry.seq));
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.name.as_deref() != Some(name));
+        self.entries.len() != before
+    }
+
+    // Middleware for `phase` whose route, if any, matches the request.
+    pub fn matching<'a>(&'a self, phase: P, method: &'a str, path: &'a str) -> impl Iterator<Item = &'a T> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.phase == phase)
+            .filter(move |entry| entry.route.as_ref().is_none_or(|route| route.matches(method, path)))
+            .map(|entry| &*entry.middleware)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries.iter().filter_map(|entry| entry.name.clone()).collect()
+    }
+}
@@ -0,0 +1,175 @@
+use crate::signing;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+// A multipart/form-data body for `send_multipart`. Files are read when the
+// request is built and the whole body is buffered, so uploads can be retried
+// like any other request.
+#[derive(Clone, Debug, Default)]
+pub struct Multipart {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    body: PartBody,
+}
+
+#[derive(Clone, Debug)]
+enum PartBody {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    pub fn file<P: AsRef<Path>>(self, name: &str, path: P) -> Self {
+        self.part(Part::file(name, path))
+    }
+
+    pub fn bytes(self, name: &str, filename: &str, bytes: Vec<u8>) -> Self {
+        self.part(Part::bytes(name, filename, bytes))
+    }
+
+    // `[{"name": "title", "value": "Q3"}, {"name": "report", "path": "q3.pdf"},
+    // {"name": "logo", "filename": "logo.png", "base64": "..."}]` for the C
+    // API. Any part may also set "content_type".
+    pub(crate) fn from_json(parts: &Value) -> Option<Self> {
+        let mut form = Multipart::new();
+        for part in parts.as_array()? {
+            let name = part.get("name")?.as_str()?;
+            let mut next = if let Some(value) = part.get("value") {
+                Part::text(name, value.as_str()?)
+            } else if let Some(path) = part.get("path") {
+                Part::file(name, path.as_str()?)
+            } else {
+                let bytes = STANDARD.decode(part.get("base64")?.as_str()?).ok()?;
+                Part::bytes(name, part.get("filename").and_then(Value::as_str).unwrap_or(name), bytes)
+            };
+            if let Some(content_type) = part.get("content_type").and_then(Value::as_str) {
+                next = next.content_type(content_type);
+            }
+            form = form.part(next);
+        }
+        Some(form)
+    }
+
+    // The Content-Type header, with its boundary, and the encoded body.
+    pub(crate) fn encode(&self) -> std::io::Result<(String, Vec<u8>)> {
+        let mut random = [0u8; 12];
+        getrandom::getrandom(&mut random)?;
+        let boundary = format!("easier-apis-{}", signing::hex(&random));
+        let mut body = Vec::new();
+        for part in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", escape(&part.name));
+            if let Some(filename) = &part.filename {
+                disposition.push_str(&format!("; filename=\"{}\"", escape(filename)));
+            }
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(b"\r\n");
+            if let Some(content_type) = part.effective_content_type() {
+                body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            match &part.body {
+                PartBody::Bytes(bytes) => body.extend_from_slice(bytes),
+                PartBody::File(path) => body.extend_from_slice(&std::fs::read(path)?),
+            }
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        Ok((format!("multipart/form-data; boundary={}", boundary), body))
+    }
+}
+
+impl Part {
+    pub fn text(name: &str, value: &str) -> Self {
+        Part {
+            name: name.to_string(),
+            filename: None,
+            content_type: None,
+            body: PartBody::Bytes(value.as_bytes().to_vec()),
+        }
+    }
+
+    // Sent under the file's own name, with a content type guessed from its
+    // extension unless one is set.
+    pub fn file<P: AsRef<Path>>(name: &str, path: P) -> Self {
+        let path = path.as_ref();
+        Part {
+            name: name.to_string(),
+            filename: path.file_name().map(|f| f.to_string_lossy().into_owned()),
+            content_type: None,
+            body: PartBody::File(path.to_path_buf()),
+        }
+    }
+
+    pub fn bytes(name: &str, filename: &str, bytes: Vec<u8>) -> Self {
+        Part {
+            name: name.to_string(),
+            filename: Some(filename.to_string()),
+            content_type: None,
+            body: PartBody::Bytes(bytes),
+        }
+    }
+
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    // Text fields go without a Content-Type, which means text/plain.
+    fn effective_content_type(&self) -> Option<&str> {
+        match (&self.content_type, &self.filename) {
+            (Some(content_type), _) => Some(content_type),
+            (None, Some(filename)) => Some(guess_content_type(filename)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn guess_content_type(filename: &str) -> &'static str {
+    let extension = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+// Quotes and line breaks in names, percent-encoded as browsers do.
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
@@ -0,0 +1,290 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+// One resource's outcome in a 207 Multi-Status response. For WebDAV, `body`
+// holds the properties that came back with a 2xx status, keyed by local name,
+// and `failed_props` the status of each one that didn't.
+#[derive(Clone, Debug, Serialize)]
+pub struct MultiStatusItem {
+    pub href: Option<String>,
+    pub status: u16,
+    pub body: Value,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub failed_props: BTreeMap<String, u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl MultiStatusItem {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    // Anything other than a 207 describes a single resource.
+    pub(crate) fn whole(href: &str, status: u16, body: Value) -> Self {
+        MultiStatusItem {
+            href: Some(href.to_string()),
+            status,
+            body,
+            failed_props: BTreeMap::new(),
+            description: None,
+        }
+    }
+}
+
+// Keys bulk APIs put their per-item array under when it isn't the top level.
+const ITEM_KEYS: [&str; 4] = ["responses", "results", "items", "multistatus"];
+
+// JSON multi-status: an array of items, or an object holding one under a
+// key from `ITEM_KEYS`. Each item carries its own `status` (or `statusCode`
+// or `code`), as a number or an HTTP status line.
+pub(crate) fn from_json(json: Value) -> Result<Vec<MultiStatusItem>, Box<dyn std::error::Error>> {
+    let items = match json {
+        Value::Array(items) => items,
+        Value::Object(mut object) => ITEM_KEYS
+            .iter()
+            .find_map(|key| match object.remove(*key) {
+                Some(Value::Array(items)) => Some(items),
+                _ => None,
+            })
+            .ok_or("207 response has no item array")?,
+        _ => return Err("207 response has no item array".into()),
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let status = ["status", "statusCode", "code"]
+                .iter()
+                .find_map(|key| match item.get(*key)? {
+                    Value::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+                    Value::String(s) => status_code(s),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("207 item has no status: {}", item))?;
+            let href = ["href", "id"].iter().find_map(|key| match item.get(*key)? {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            });
+            Ok(MultiStatusItem {
+                href,
+                status,
+                body: item,
+                failed_props: BTreeMap::new(),
+                description: None,
+            })
+        })
+        .collect()
+}
+
+// A DAV:multistatus document (RFC 4918 section 13), whatever the namespace
+// prefix.
+pub(crate) fn from_xml(xml: &str) -> Result<Vec<MultiStatusItem>, Box<dyn std::error::Error>> {
+    let root = parse(xml)?;
+    if root.name != "multistatus" {
+        return Err(format!("Expected a multistatus document, got <{}>", root.name).into());
+    }
+    let mut items = Vec::new();
+    for response in root.children_named("response") {
+        let hrefs: Vec<String> = response.children_named("href").map(|href| href.text.trim().to_string()).collect();
+        let description = response.child("responsedescription").map(|d| d.text.trim().to_string());
+        let mut props = Map::new();
+        let mut failed_props = BTreeMap::new();
+        let mut propstat_statuses = Vec::new();
+        for propstat in response.children_named("propstat") {
+            let status = propstat.child("status").and_then(|s| status_code(&s.text)).unwrap_or(200);
+            propstat_statuses.push(status);
+            for prop in propstat.child("prop").into_iter().flat_map(|p| &p.children) {
+                if (200..300).contains(&status) {
+                    insert(&mut props, &prop.name, prop.to_json());
+                } else {
+                    failed_props.insert(prop.name.clone(), status);
+                }
+            }
+        }
+        let status = match response.child("status") {
+            Some(status) => status_code(&status.text).ok_or_else(|| format!("Invalid status: {}", status.text.trim()))?,
+            None => propstat_statuses
+                .iter()
+                .copied()
+                .find(|s| (200..300).contains(s))
+                .or_else(|| propstat_statuses.first().copied())
+                .ok_or("multistatus response has neither status nor propstat")?,
+        };
+        // A response-level status may cover several hrefs.
+        let hrefs = if hrefs.is_empty() { vec![None] } else { hrefs.into_iter().map(Some).collect() };
+        for href in hrefs {
+            items.push(MultiStatusItem {
+                href,
+                status,
+                body: Value::Object(props.clone()),
+                failed_props: failed_props.clone(),
+                description: description.clone(),
+            });
+        }
+    }
+    Ok(items)
+}
+
+// `HTTP/1.1 404 Not Found` or just `404`.
+fn status_code(text: &str) -> Option<u16> {
+    text.split_whitespace().find_map(|token| token.parse().ok().filter(|code| (100..600).contains(code)))
+}
+
+// Repeated names collect into an array.
+fn insert(object: &mut Map<String, Value>, name: &str, value: Value) {
+    match object.get_mut(name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            object.insert(name.to_string(), value);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    // Leaf elements become their text (null when empty, like `<collection/>`),
+    // others an object of their children.
+    fn to_json(&self) -> Value {
+        if self.children.is_empty() {
+            let text = self.text.trim();
+            return if text.is_empty() { Value::Null } else { Value::String(text.to_string()) };
+        }
+        let mut object = Map::new();
+        for child in &self.children {
+            insert(&mut object, &child.name, child.to_json());
+        }
+        Value::Object(object)
+    }
+}
+
+// Just enough XML for multistatus bodies: elements by local name, text and
+// CDATA. Attributes, comments, processing instructions and doctypes are
+// skipped.
+fn parse(xml: &str) -> Result<Element, Box<dyn std::error::Error>> {
+    let mut stack = vec![Element::default()];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        if let Some(open) = stack.last_mut() {
+            open.text.push_str(&unescape(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").ok_or("Unterminated CDATA section")?;
+            if let Some(open) = stack.last_mut() {
+                open.text.push_str(&after[..end]);
+            }
+            rest = &after[end + 3..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = &after[after.find("-->").ok_or("Unterminated comment")? + 3..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or("Unterminated tag")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().filter(|_| !stack.is_empty()).ok_or("Unbalanced closing tag")?;
+            if element.name != local_name(name.trim()) {
+                return Err(format!("Mismatched closing tag </{}>", name.trim()).into());
+            }
+            stack.last_mut().ok_or("Unbalanced closing tag")?.children.push(element);
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let name = tag.trim_end_matches('/').split_whitespace().next().ok_or("Empty tag")?;
+        let element = Element {
+            name: local_name(name).to_string(),
+            ..Element::default()
+        };
+        if self_closing {
+            stack.last_mut().ok_or("Unbalanced tag")?.children.push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+    if stack.len() != 1 {
+        return Err("Unclosed element".into());
+    }
+    stack.pop().and_then(|document| document.children.into_iter().next()).ok_or_else(|| "Empty XML document".into())
+}
+
+// Index of the `>` closing the tag at the start of `text`, skipping quoted
+// attribute values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
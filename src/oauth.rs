@@ -0,0 +1,166 @@
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+// Tokens are refreshed this long before they expire, so one doesn't lapse
+// between being attached and reaching the server.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+// OAuth2 client credentials grant (RFC 6749 section 4.4). The access token is
+// fetched on first use, cached until shortly before `expires_in` runs out and
+// refetched after a 401.
+pub struct OAuth2 {
+    token_url: String,
+    client_id: String,
+    client_secret: Zeroizing<String>,
+    scopes: Vec<String>,
+    credentials_in_body: bool,
+    token: Mutex<Option<Token>>,
+}
+
+struct Token {
+    access_token: Zeroizing<String>,
+    // None when the server didn't say; such tokens are kept until a 401.
+    expires_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl OAuth2 {
+    pub fn new(token_url: &str, client_id: &str, client_secret: &str) -> Self {
+        OAuth2 {
+            token_url: token_url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: Zeroizing::new(client_secret.to_string()),
+            scopes: Vec::new(),
+            credentials_in_body: false,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_string());
+        self
+    }
+
+    pub fn scopes<S: AsRef<str>>(mut self, scopes: &[S]) -> Self {
+        self.scopes.extend(scopes.iter().map(|s| s.as_ref().to_string()));
+        self
+    }
+
+    // Send client_id and client_secret as form fields instead of HTTP Basic,
+    // for servers that only accept `client_secret_post`.
+    pub fn credentials_in_body(mut self, in_body: bool) -> Self {
+        self.credentials_in_body = in_body;
+        self
+    }
+
+    // A valid access token, fetching a new one if there's none yet or it's
+    // about to expire. Concurrent callers wait for a single fetch.
+    pub(crate) fn access_token(&self, client: &Client, now: Instant) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+        let mut token = self.token.lock().unwrap();
+        if let Some(current) = token.as_ref().filter(|t| t.valid_at(now)) {
+            return Ok(current.access_token.clone());
+        }
+        let mut request = client.post(&self.token_url).form(&self.form());
+        if !self.credentials_in_body {
+            request = request.basic_auth(&self.client_id, Some(self.client_secret.as_str()));
+        }
+        let response = request.send()?;
+        let status = response.status().as_u16();
+        let fresh = Self::parse(status, &Zeroizing::new(response.text()?), now)?;
+        let access_token = fresh.access_token.clone();
+        *token = Some(fresh);
+        Ok(access_token)
+    }
+
+    // Makes sure a valid token is cached, so building the request afterwards
+    // doesn't block. Concurrent callers may each fetch one.
+    #[cfg(feature = "async")]
+    pub(crate) async fn refresh(&self, client: &reqwest::Client, now: Instant) -> Result<(), Box<dyn std::error::Error>> {
+        if self.token.lock().unwrap().as_ref().is_some_and(|t| t.valid_at(now)) {
+            return Ok(());
+        }
+        let mut request = client.post(&self.token_url).form(&self.form());
+        if !self.credentials_in_body {
+            request = request.basic_auth(&self.client_id, Some(self.client_secret.as_str()));
+        }
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let fresh = Self::parse(status, &Zeroizing::new(response.text().await?), now)?;
+        *self.token.lock().unwrap() = Some(fresh);
+        Ok(())
+    }
+
+    // Drops the cached token after the server rejected it.
+    pub(crate) fn invalidate(&self) {
+        *self.token.lock().unwrap() = None;
+    }
+
+    // Borrows the secret rather than copying it.
+    fn form(&self) -> Vec<(&'static str, Cow<'_, str>)> {
+        let mut form = vec![("grant_type", Cow::Borrowed("client_credentials"))];
+        if !self.scopes.is_empty() {
+            form.push(("scope", Cow::Owned(self.scopes.join(" "))));
+        }
+        if self.credentials_in_body {
+            form.push(("client_id", Cow::Borrowed(self.client_id.as_str())));
+            form.push(("client_secret", Cow::Borrowed(self.client_secret.as_str())));
+        }
+        form
+    }
+
+    fn parse(status: u16, body: &str, now: Instant) -> Result<Token, Box<dyn std::error::Error>> {
+        if !(200..300).contains(&status) {
+            return Err(OAuth2Error {
+                status,
+                body: body.to_string(),
+            }
+            .into());
+        }
+        let token: TokenResponse = serde_json::from_str(body)?;
+        Ok(Token {
+            access_token: Zeroizing::new(token.access_token),
+            expires_at: token.expires_in.map(|secs| now + Duration::from_secs(secs)),
+        })
+    }
+}
+
+impl Token {
+    fn valid_at(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|expires_at| now + EXPIRY_MARGIN < expires_at)
+    }
+}
+
+impl fmt::Debug for OAuth2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuth2")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.client_id)
+            .field("scopes", &self.scopes)
+            .finish_non_exhaustive()
+    }
+}
+
+// The token endpoint refused to issue a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuth2Error {
+    pub status: u16,
+    pub body: String,
+}
+
+impl fmt::Display for OAuth2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Token request failed with status {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for OAuth2Error {}
@@ -0,0 +1,154 @@
+use crate::{CancellationToken, Query};
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::cell::Cell;
+use std::time::Duration;
+
+// Settings for a single call, for `fetch_with` and `send_with`. Headers set
+// here win over the client's default headers; auth is still applied on top.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    query: Query,
+    headers: HeaderMap,
+    // Kept as text so the builder stays infallible; invalid names or values
+    // fail the request when it's built.
+    raw_headers: Vec<(String, String)>,
+    cancellation: Option<CancellationToken>,
+    timeout: Option<Duration>,
+    limits: CallLimits,
+}
+
+// The parts of `RequestOptions` the retry loop reads, which can't travel on
+// the request itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CallLimits {
+    pub connect_timeout: Option<Duration>,
+    pub deadline: Option<Duration>,
+}
+
+thread_local! {
+    static LIMITS: Cell<CallLimits> = const {
+        Cell::new(CallLimits {
+            connect_timeout: None,
+            deadline: None,
+        })
+    };
+}
+
+// The limits of the call running on this thread, set for the guard's lifetime.
+pub(crate) struct Entered {
+    previous: CallLimits,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        LIMITS.with(|limits| limits.set(self.previous));
+    }
+}
+
+pub(crate) fn limits() -> CallLimits {
+    LIMITS.with(Cell::get)
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    // Replaces earlier values for the same name.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.raw_headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers.remove(name);
+        self.raw_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.raw_headers.retain(|(name, _)| !headers.contains_key(name.as_str()));
+        self.headers.extend(headers);
+        self
+    }
+
+    // Lets the call be aborted from another thread.
+    pub fn cancel_token(mut self, token: &CancellationToken) -> Self {
+        self.cancellation = Some(token.clone());
+        self
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    // Replaces the client's timeout for each attempt of this call.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Replaces the client's connect timeout. The connect timeout is fixed per
+    // HTTP client, so these calls go through one built for it and don't reuse
+    // the main client's connections.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.connect_timeout = Some(timeout);
+        self
+    }
+
+    // Replaces the retry policy's deadline: the whole call, every attempt and
+    // backoff included, fails with `TimeoutError::Deadline` once it runs out.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.limits.deadline = Some(deadline);
+        self
+    }
+
+    pub(crate) fn enter(&self) -> Entered {
+        Entered {
+            previous: LIMITS.with(|limits| limits.replace(self.limits)),
+        }
+    }
+
+    // `{"params": {...}, "headers": {"Accept": "text/csv"}, "timeout_ms": ...,
+    // "connect_timeout_ms": ..., "deadline_ms": ...}` for the C API; all keys
+    // are optional.
+    pub(crate) fn from_json(options: &Value) -> Option<Self> {
+        let mut out = RequestOptions::new();
+        if let Some(params) = options.get("params").filter(|p| !p.is_null()) {
+            out = out.query(Query::from_json(params)?);
+        }
+        if let Some(headers) = options.get("headers").filter(|h| !h.is_null()) {
+            for (name, value) in headers.as_object()? {
+                out = out.header(name, value.as_str()?);
+            }
+        }
+        let millis = |key: &str| match options.get(key) {
+            None | Some(Value::Null) => Some(None),
+            Some(value) => value.as_u64().map(|ms| Some(Duration::from_millis(ms))),
+        };
+        if let Some(timeout) = millis("timeout_ms")? {
+            out = out.timeout(timeout);
+        }
+        if let Some(timeout) = millis("connect_timeout_ms")? {
+            out = out.connect_timeout(timeout);
+        }
+        if let Some(deadline) = millis("deadline_ms")? {
+            out = out.deadline(deadline);
+        }
+        Some(out)
+    }
+
+    pub(crate) fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let mut request = request.query(&self.query.pairs()).headers(self.headers.clone());
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        for (name, value) in &self.raw_headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+}
@@ -0,0 +1,457 @@
+use crate::export;
+use crate::{ApiError, IdempotencyTracker, RustCore};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+
+// What a page says about where it is in the listing, for progress and
+// sanity checks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PageMeta {
+    // 1 for the first page fetched.
+    pub number: u64,
+    // Items on this page.
+    pub page_size: usize,
+    // Items in the whole listing, if the API says.
+    pub total: Option<u64>,
+    // The cursor this page was fetched with; None for the first page and for
+    // strategies without cursors.
+    pub cursor: Option<String>,
+    pub has_more: bool,
+}
+
+// One page as fetched, after the response pipeline.
+#[derive(Clone, Debug)]
+pub struct Page {
+    url: Url,
+    headers: HeaderMap,
+    body: Value,
+    meta: PageMeta,
+}
+
+impl Page {
+    pub(crate) fn new(url: Url, headers: HeaderMap, body: Value) -> Self {
+        Page {
+            url,
+            headers,
+            body,
+            meta: PageMeta::default(),
+        }
+    }
+
+    // Filled in by `Pages`; an empty default on pages built elsewhere.
+    pub fn meta(&self) -> &PageMeta {
+        &self.meta
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &Value {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Value {
+        self.body
+    }
+
+    // This page's URL with query parameter `name` set to `value`, replacing
+    // any earlier value.
+    pub fn url_with(&self, name: &str, value: &str) -> Url {
+        with_param(&self.url, name, value)
+    }
+
+    // A query parameter of this page's URL.
+    pub fn param(&self, name: &str) -> Option<String> {
+        self.url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned())
+    }
+}
+
+// Where the pages of a paginated endpoint are. Implement it for APIs that
+// none of the built-in strategies fit.
+pub trait PaginationStrategy {
+    // The URL of the first page, given the one built from the path.
+    fn first(&self, url: Url) -> Url {
+        url
+    }
+
+    // The URL of the page after `page`, or None if it was the last.
+    fn next(&self, page: &Page) -> Option<Url>;
+
+    // The items on `page`. By default the elements of an array body, or the
+    // body itself otherwise.
+    fn items(&self, page: &Page) -> Vec<Value> {
+        items_at(page.body(), None)
+    }
+
+    // How many items the whole listing has. By default an `X-Total-Count`
+    // header, or a number at `/total`, `/total_count`, `/meta/total` or
+    // `/meta/total_count` in the body.
+    fn total(&self, page: &Page) -> Option<u64> {
+        default_total(page)
+    }
+
+    // The cursor `page` was fetched with, if the strategy uses cursors.
+    fn cursor(&self, _page: &Page) -> Option<String> {
+        None
+    }
+}
+
+impl<S: PaginationStrategy + ?Sized> PaginationStrategy for Box<S> {
+    fn first(&self, url: Url) -> Url {
+        (**self).first(url)
+    }
+
+    fn next(&self, page: &Page) -> Option<Url> {
+        (**self).next(page)
+    }
+
+    fn items(&self, page: &Page) -> Vec<Value> {
+        (**self).items(page)
+    }
+
+    fn total(&self, page: &Page) -> Option<u64> {
+        (**self).total(page)
+    }
+
+    fn cursor(&self, page: &Page) -> Option<String> {
+        (**self).cursor(page)
+    }
+}
+
+// RFC 8288 (formerly 5988) `Link: <...>; rel="next"` headers.
+#[derive(Clone, Debug, Default)]
+pub struct LinkHeader {
+    items: Option<String>,
+}
+
+impl LinkHeader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // JSON pointer to the array of items, e.g. "/data".
+    pub fn items(mut self, pointer: &str) -> Self {
+        self.items = Some(pointer.to_string());
+        self
+    }
+}
+
+impl PaginationStrategy for LinkHeader {
+    fn next(&self, page: &Page) -> Option<Url> {
+        export::next_link(page.headers(), page.url())
+    }
+
+    fn items(&self, page: &Page) -> Vec<Value> {
+        items_at(page.body(), self.items.as_deref())
+    }
+}
+
+// The body names the next page's cursor, which goes back in a query
+// parameter; a missing, null or empty cursor ends it.
+#[derive(Clone, Debug)]
+pub struct Cursor {
+    param: String,
+    cursor: String,
+    items: Option<String>,
+}
+
+impl Cursor {
+    // `param` is the query parameter, `cursor` a JSON pointer to the next
+    // cursor in the body, e.g. `Cursor::new("cursor", "/meta/next_cursor")`.
+    pub fn new(param: &str, cursor: &str) -> Self {
+        Cursor {
+            param: param.to_string(),
+            cursor: cursor.to_string(),
+            items: None,
+        }
+    }
+
+    pub fn items(mut self, pointer: &str) -> Self {
+        self.items = Some(pointer.to_string());
+        self
+    }
+}
+
+impl PaginationStrategy for Cursor {
+    fn next(&self, page: &Page) -> Option<Url> {
+        let cursor = match page.body().pointer(&self.cursor)? {
+            Value::String(cursor) if !cursor.is_empty() => cursor.clone(),
+            Value::Number(cursor) => cursor.to_string(),
+            _ => return None,
+        };
+        Some(page.url_with(&self.param, &cursor))
+    }
+
+    fn items(&self, page: &Page) -> Vec<Value> {
+        items_at(page.body(), self.items.as_deref())
+    }
+
+    fn cursor(&self, page: &Page) -> Option<String> {
+        page.param(&self.param)
+    }
+}
+
+// `?offset=0&limit=100`, then `offset=100` and so on until a page comes back
+// with fewer than `limit` items.
+#[derive(Clone, Debug)]
+pub struct OffsetLimit {
+    limit: u64,
+    offset_param: String,
+    limit_param: String,
+    items: Option<String>,
+}
+
+impl OffsetLimit {
+    pub fn new(limit: u64) -> Self {
+        OffsetLimit {
+            limit: limit.max(1),
+            offset_param: "offset".to_string(),
+            limit_param: "limit".to_string(),
+            items: None,
+        }
+    }
+
+    // Names of the query parameters, for APIs that use e.g. "skip" and "take".
+    pub fn params(mut self, offset: &str, limit: &str) -> Self {
+        self.offset_param = offset.to_string();
+        self.limit_param = limit.to_string();
+        self
+    }
+
+    pub fn items(mut self, pointer: &str) -> Self {
+        self.items = Some(pointer.to_string());
+        self
+    }
+}
+
+impl PaginationStrategy for OffsetLimit {
+    fn first(&self, url: Url) -> Url {
+        let url = with_param(&url, &self.limit_param, &self.limit.to_string());
+        match url.query_pairs().any(|(key, _)| key == self.offset_param) {
+            true => url,
+            false => with_param(&url, &self.offset_param, "0"),
+        }
+    }
+
+    fn next(&self, page: &Page) -> Option<Url> {
+        let count = self.items(page).len() as u64;
+        if count < self.limit {
+            return None;
+        }
+        let offset: u64 = page.param(&self.offset_param).and_then(|v| v.parse().ok()).unwrap_or(0);
+        Some(page.url_with(&self.offset_param, &(offset + count).to_string()))
+    }
+
+    fn items(&self, page: &Page) -> Vec<Value> {
+        items_at(page.body(), self.items.as_deref())
+    }
+}
+
+// A built-in strategy described in JSON, for the C API:
+// `{"type": "link"}`, `{"type": "cursor", "param": "cursor", "cursor": "/next"}`
+// or `{"type": "offset", "limit": 100}`, each with an optional "items" pointer.
+// Offsets also take "offset_param" and "limit_param".
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum StrategyConfig {
+    Link {
+        items: Option<String>,
+    },
+    Cursor {
+        param: String,
+        cursor: String,
+        items: Option<String>,
+    },
+    Offset {
+        limit: u64,
+        offset_param: Option<String>,
+        limit_param: Option<String>,
+        items: Option<String>,
+    },
+}
+
+pub(crate) fn parse_strategy(config: &str) -> Result<Box<dyn PaginationStrategy>, serde_json::Error> {
+    let strategy: Box<dyn PaginationStrategy> = match serde_json::from_str(config)? {
+        StrategyConfig::Link { items } => {
+            let strategy = LinkHeader::new();
+            Box::new(match items {
+                Some(pointer) => strategy.items(&pointer),
+                None => strategy,
+            })
+        }
+        StrategyConfig::Cursor { param, cursor, items } => {
+            let strategy = Cursor::new(&param, &cursor);
+            Box::new(match items {
+                Some(pointer) => strategy.items(&pointer),
+                None => strategy,
+            })
+        }
+        StrategyConfig::Offset {
+            limit,
+            offset_param,
+            limit_param,
+            items,
+        } => {
+            let strategy = OffsetLimit::new(limit).params(
+                offset_param.as_deref().unwrap_or("offset"),
+                limit_param.as_deref().unwrap_or("limit"),
+            );
+            Box::new(match items {
+                Some(pointer) => strategy.items(&pointer),
+                None => strategy,
+            })
+        }
+    };
+    Ok(strategy)
+}
+
+// Pages from `RustCore::fetch_paginated`, fetched as the iterator advances.
+// Stops after the first error, and when a page points back at one already
+// fetched.
+pub struct Pages<'a> {
+    core: &'a RustCore,
+    strategy: Box<dyn PaginationStrategy + 'a>,
+    next: Option<Result<Url, ApiError>>,
+    seen: HashSet<Url>,
+    fetched: u64,
+}
+
+impl<'a> Pages<'a> {
+    pub(crate) fn new(core: &'a RustCore, url: Result<Url, ApiError>, strategy: Box<dyn PaginationStrategy + 'a>) -> Self {
+        Pages {
+            core,
+            next: Some(url.map(|url| strategy.first(url))),
+            strategy,
+            seen: HashSet::new(),
+            fetched: 0,
+        }
+    }
+
+    // The items on `page`, as the strategy finds them.
+    pub fn items_of(&self, page: &Page) -> Vec<Value> {
+        self.strategy.items(page)
+    }
+
+    // The items of every page, one at a time.
+    pub fn items(self) -> Items<'a> {
+        Items {
+            pages: self,
+            buffered: VecDeque::new(),
+            dedup: None,
+        }
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Page, ApiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let url = match self.next.take()? {
+            Ok(url) => url,
+            Err(error) => return Some(Err(error)),
+        };
+        if !self.seen.insert(url.clone()) {
+            return None;
+        }
+        match self.core.fetch_page(url) {
+            Ok(mut page) => {
+                let next = self.strategy.next(&page);
+                self.fetched += 1;
+                page.meta = PageMeta {
+                    number: self.fetched,
+                    page_size: self.strategy.items(&page).len(),
+                    total: self.strategy.total(&page),
+                    cursor: self.strategy.cursor(&page),
+                    // A link back to a page already fetched ends it as well.
+                    has_more: next.as_ref().is_some_and(|url| !self.seen.contains(url)),
+                };
+                self.next = next.map(Ok);
+                Some(Ok(page))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+pub struct Items<'a> {
+    pages: Pages<'a>,
+    buffered: VecDeque<Value>,
+    dedup: Option<(IdempotencyTracker, String)>,
+}
+
+impl Items<'_> {
+    // Skips items whose ID, the string or number at JSON pointer `id`, the
+    // tracker has seen, so polling a feed again only yields what's new.
+    // Items without one always get through.
+    pub fn dedup(mut self, tracker: &IdempotencyTracker, id: &str) -> Self {
+        self.dedup = Some((tracker.clone(), id.to_string()));
+        self
+    }
+
+    fn is_duplicate(&self, item: &Value) -> bool {
+        let Some((tracker, pointer)) = &self.dedup else {
+            return false;
+        };
+        match item.pointer(pointer) {
+            Some(Value::String(id)) => !tracker.first_seen(id),
+            Some(Value::Number(id)) => !tracker.first_seen(&id.to_string()),
+            _ => false,
+        }
+    }
+}
+
+impl Iterator for Items<'_> {
+    type Item = Result<Value, ApiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffered.pop_front() {
+                if self.is_duplicate(&item) {
+                    continue;
+                }
+                return Some(Ok(item));
+            }
+            match self.pages.next()? {
+                Ok(page) => self.buffered.extend(self.pages.strategy.items(&page)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn items_at(body: &Value, pointer: Option<&str>) -> Vec<Value> {
+    let items = match pointer {
+        Some(pointer) => body.pointer(pointer),
+        None => Some(body),
+    };
+    match items {
+        Some(Value::Array(items)) => items.clone(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(item) if pointer.is_none() => vec![item.clone()],
+        Some(_) => Vec::new(),
+    }
+}
+
+fn default_total(page: &Page) -> Option<u64> {
+    if let Some(total) = page.headers().get("x-total-count").and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse().ok()) {
+        return Some(total);
+    }
+    ["/total", "/total_count", "/meta/total", "/meta/total_count"]
+        .iter()
+        .find_map(|pointer| page.body().pointer(pointer)?.as_u64())
+}
+
+fn with_param(url: &Url, name: &str, value: &str) -> Url {
+    let pairs: Vec<(String, String)> = url.query_pairs().filter(|(key, _)| key != name).map(|(k, v)| (k.into_owned(), v.into_owned())).collect();
+    let mut url = url.clone();
+    url.query_pairs_mut().clear().extend_pairs(pairs).append_pair(name, value);
+    url
+}
@@ -0,0 +1,144 @@
+use crate::snapshot::RequestSnapshot;
+use reqwest::blocking::Request;
+use serde_json::Value;
+use std::path::Path;
+use wasmi::{Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+// Host interface, version 1. A plugin module must export:
+//
+//   memory                              linear memory used for all buffers
+//   alloc(len: i32) -> i32              returns a buffer of `len` bytes
+//
+// and may export either of:
+//
+//   on_request(ptr: i32, len: i32) -> i64
+//   on_response(ptr: i32, len: i32) -> i64
+//
+// Inputs are UTF-8 JSON written into a buffer obtained from `alloc`: a request
+// object (`method`, `url`, `headers`, `body`; a repeated header's value is an
+// array of strings) or the decoded response body. The result packs the output
+// buffer as `(ptr << 32) | len`; returning 0 leaves the request/response
+// unchanged. An optional `abi_version() -> i32` export must return
+// `PLUGIN_ABI_VERSION` when present. No host functions are imported, so
+// plugins only see the data they are handed.
+pub const PLUGIN_ABI_VERSION: i32 = 1;
+
+const DEFAULT_FUEL: u64 = 10_000_000;
+const DEFAULT_MAX_MEMORY: usize = 64 * 1024 * 1024;
+// Largest output a hook may hand back.
+const MAX_OUTPUT: usize = 16 * 1024 * 1024;
+
+pub struct WasmMiddleware {
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    max_memory: usize,
+}
+
+impl WasmMiddleware {
+    pub fn from_bytes(wasm: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm)?;
+        let plugin = WasmMiddleware {
+            engine,
+            module,
+            fuel: DEFAULT_FUEL,
+            max_memory: DEFAULT_MAX_MEMORY,
+        };
+        let (mut store, instance) = plugin.instantiate()?;
+        if let Ok(abi_version) = instance.get_typed_func::<(), i32>(&store, "abi_version") {
+            let version = abi_version.call(&mut store, ())?;
+            if version != PLUGIN_ABI_VERSION {
+                return Err(format!("Unsupported plugin ABI version: {}", version).into());
+            }
+        }
+        Ok(plugin)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    // Upper bound on instructions a single hook invocation may execute.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    // Upper bound on a plugin's linear memory, in bytes; growing past it fails.
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    // Every call gets a fresh instance so plugins can't carry state between requests.
+    fn instantiate(&self) -> Result<(Store<StoreLimits>, Instance), Box<dyn std::error::Error>> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.max_memory).instances(1).memories(1).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel)?;
+        let linker = Linker::<StoreLimits>::new(&self.engine);
+        let instance = linker.instantiate_and_start(&mut store, &self.module)?;
+        Ok((store, instance))
+    }
+
+    fn has_export(&self, name: &str) -> bool {
+        self.module.exports().any(|export| export.name() == name)
+    }
+
+    fn call(&self, name: &str, input: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let (mut store, instance) = self.instantiate()?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("Plugin does not export memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+        let hook = instance.get_typed_func::<(i32, i32), i64>(&store, name)?;
+
+        let len = i32::try_from(input.len())?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, usize::try_from(ptr)?, input)?;
+
+        let packed = hook.call(&mut store, (ptr, len))?;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let out_ptr = (packed as u64 >> 32) as usize;
+        let out_len = (packed as u64 & 0xffff_ffff) as usize;
+        if out_len > MAX_OUTPUT {
+            return Err(format!("Plugin output of {} bytes is over the {} byte limit", out_len, MAX_OUTPUT).into());
+        }
+        if out_ptr.checked_add(out_len).is_none_or(|end| end > memory.data_size(&store)) {
+            return Err("Plugin output lies outside its memory".into());
+        }
+        let mut output = vec![0; out_len];
+        memory.read(&store, out_ptr, &mut output)?;
+        Ok(Some(output))
+    }
+
+    pub fn on_request(&self, request: Request) -> Request {
+        if !self.has_export("on_request") {
+            return request;
+        }
+        self.rewrite_request(&request).unwrap_or(request)
+    }
+
+    fn rewrite_request(&self, original: &Request) -> Option<Request> {
+        let input = serde_json::to_vec(&RequestSnapshot::capture(original)).ok()?;
+        let output = self.call("on_request", &input).ok()??;
+        let modified: RequestSnapshot = serde_json::from_slice(&output).ok()?;
+        modified.apply(original)
+    }
+
+    pub fn on_response(&self, body: Value) -> Value {
+        if !self.has_export("on_response") {
+            return body;
+        }
+        let modified = serde_json::to_vec(&body)
+            .ok()
+            .and_then(|input| self.call("on_response", &input).ok().flatten())
+            .and_then(|output| serde_json::from_slice(&output).ok());
+        modified.unwrap_or(body)
+    }
+}
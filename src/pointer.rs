@@ -0,0 +1,24 @@
+// JSON pointer helpers shared by the diff, schema and anonymization layers.
+
+pub(crate) fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+pub(crate) fn unescape(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+pub(crate) fn to_pointer(path: &[String]) -> String {
+    path.iter().map(|segment| format!("/{}", escape(segment))).collect()
+}
+
+// True if `pattern` (a JSON pointer where `*` matches any single segment) names
+// `path` or one of its ancestors.
+pub(crate) fn matches(pattern: &str, path: &[String]) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').skip(1).collect();
+    pattern.len() <= path.len()
+        && pattern
+            .iter()
+            .zip(path)
+            .all(|(p, segment)| *p == "*" || unescape(p) == *segment)
+}
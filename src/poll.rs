@@ -0,0 +1,56 @@
+use crate::typed_headers::TypedHeaders;
+use reqwest::header::HeaderMap;
+use std::fmt;
+use std::time::Duration;
+
+// How `poll` waits on a resource that answers 202 Accepted.
+#[derive(Clone, Copy, Debug)]
+pub struct PollOptions {
+    pub interval: Duration,
+    // Overall limit, counted from the first poll.
+    pub timeout: Duration,
+    // Whether `X-Poll-Interval` or `Retry-After` on a pending response sets
+    // the wait before the next poll instead of `interval`.
+    pub server_hints: bool,
+    // Longest wait a server hint can ask for.
+    pub max_interval: Duration,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        PollOptions {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(60),
+            server_hints: true,
+            max_interval: Duration::from_secs(300),
+        }
+    }
+}
+
+impl PollOptions {
+    // The wait after a pending response with these headers.
+    pub(crate) fn next_interval(&self, headers: &HeaderMap) -> Duration {
+        if !self.server_hints {
+            return self.interval;
+        }
+        match headers.poll_interval().or_else(|| headers.retry_after()) {
+            Some(hint) => hint.min(self.max_interval),
+            None => self.interval,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PollError {
+    TimedOut { url: String, polls: u32 },
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::TimedOut { url, polls } => write!(f, "{} was still pending after {} polls", url, polls),
+        }
+    }
+}
+
+impl std::error::Error for PollError {}
@@ -0,0 +1,109 @@
+use crate::build_client;
+use crate::builder::ClientConfig;
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+// One HTTP client, and so one set of pooled connections, shared by several
+// `RustCore`s with their own base URLs and auth. Cores on the same host reuse
+// each other's connections and TLS sessions. Clones share the pool.
+//
+//     let pool = ClientPool::new();
+//     let billing = RustCore::builder("https://billing.example.com").pool(&pool).build()?;
+//     let crm = RustCore::builder("https://crm.example.com").pool(&pool).build()?;
+#[derive(Clone, Debug)]
+pub struct ClientPool {
+    client: Client,
+    config: ClientConfig,
+}
+
+impl ClientPool {
+    // Panics if the TLS backend can't be initialized; `builder` reports that
+    // as an error instead.
+    pub fn new() -> Self {
+        Self::builder().build().unwrap()
+    }
+
+    pub fn builder() -> ClientPoolBuilder {
+        ClientPoolBuilder::new()
+    }
+
+    pub(crate) fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    pub(crate) fn config(&self) -> ClientConfig {
+        self.config.clone()
+    }
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Client settings for every core drawing from the pool.
+#[derive(Clone, Debug)]
+pub struct ClientPoolBuilder {
+    config: ClientConfig,
+    max_idle_per_host: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl ClientPoolBuilder {
+    pub fn new() -> Self {
+        ClientPoolBuilder {
+            config: ClientConfig::default(),
+            max_idle_per_host: None,
+            idle_timeout: None,
+        }
+    }
+
+    // For the whole request, including reading the body. Defaults to 30s.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.config.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    // Idle connections kept open per host. Unlimited by default.
+    pub fn max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = Some(max);
+        self
+    }
+
+    // How long an idle connection is kept before it's closed. Defaults to 90s.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<ClientPool, Box<dyn std::error::Error>> {
+        let mut builder = self.config.blocking();
+        if let Some(max) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        Ok(ClientPool {
+            client: build_client(builder)?,
+            config: self.config,
+        })
+    }
+}
+
+impl Default for ClientPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,349 @@
+use crate::middleware::Route;
+use crate::retry::RetryPolicy;
+use crate::throttle::TokenBucket;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Retry, rate limit and cache settings that go together, for the whole client
+// or a group of routes. Built-in profiles are "aggressive", "conservative" and
+// "read-heavy"; `RustCore::load_profiles` adds more from a config file.
+#[derive(Clone, Debug)]
+pub struct PolicyProfile {
+    retry: RetryPolicy,
+    rate_limit: Option<(f64, u32)>,
+    cache_ttl: Option<Duration>,
+}
+
+impl Default for PolicyProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PolicyProfile {
+    // The default retry policy, no rate limit and no caching.
+    pub fn new() -> Self {
+        PolicyProfile {
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            cache_ttl: None,
+        }
+    }
+
+    // Many quick retries and no throttling, for flaky but generous APIs.
+    pub fn aggressive() -> Self {
+        Self::new().retry(
+            RetryPolicy::new()
+                .max_attempts(6)
+                .base_delay(Duration::from_millis(250))
+                .max_delay(Duration::from_secs(10))
+                .jitter(0.5),
+        )
+    }
+
+    // Few, slow retries and at most 5 requests a second, for strict quotas.
+    pub fn conservative() -> Self {
+        Self::new()
+            .retry(
+                RetryPolicy::new()
+                    .max_attempts(2)
+                    .base_delay(Duration::from_secs(2))
+                    .max_delay(Duration::from_secs(30))
+                    .jitter(0.2),
+            )
+            .rate_limit(5.0, 10)
+    }
+
+    // Default retries, 20 requests a second and GET responses cached for 30s.
+    pub fn read_heavy() -> Self {
+        Self::new().rate_limit(20.0, 40).cache_ttl(Duration::from_secs(30))
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "aggressive" => Some(Self::aggressive()),
+            "conservative" => Some(Self::conservative()),
+            "read-heavy" => Some(Self::read_heavy()),
+            _ => None,
+        }
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    // At most `burst` requests at once and `per_second` on average. Every
+    // attempt counts, retries included.
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> Self {
+        self.rate_limit = Some((per_second, burst));
+        self
+    }
+
+    pub fn no_rate_limit(mut self) -> Self {
+        self.rate_limit = None;
+        self
+    }
+
+    // How long successful GET responses are served without asking again.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    pub fn no_cache(mut self) -> Self {
+        self.cache_ttl = None;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    Unknown(String),
+    Cycle(String),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Unknown(name) => write!(f, "Unknown policy profile: {}", name),
+            ProfileError::Cycle(name) => write!(f, "Policy profile {} extends itself, directly or through others", name),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+// A profile in use, with the rate limiter and cache it shares between all the
+// requests it applies to.
+pub(crate) struct ActiveProfile {
+    // Its rate limit's name in the quota store.
+    key: String,
+    profile: PolicyProfile,
+    bucket: Option<TokenBucket>,
+    cache: Mutex<HashMap<String, (Value, Instant)>>,
+}
+
+impl ActiveProfile {
+    fn new(key: String, profile: PolicyProfile, now: Instant) -> Self {
+        ActiveProfile {
+            key,
+            bucket: profile.rate_limit.map(|(per_second, burst)| TokenBucket::new(per_second, burst, now)),
+            profile,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.profile.retry
+    }
+
+    // How long to wait before the next attempt may be sent.
+    pub fn throttle(&self, now: Instant) -> Duration {
+        self.bucket.as_ref().map_or(Duration::ZERO, |bucket| bucket.acquire(now))
+    }
+
+    pub fn bucket(&self) -> Option<(&str, &TokenBucket)> {
+        Some((&self.key, self.bucket.as_ref()?))
+    }
+
+    pub fn penalize(&self, now: Instant, retry_after: Option<Duration>) {
+        if let Some(bucket) = &self.bucket {
+            bucket.penalize(now, retry_after);
+        }
+    }
+
+    pub fn cached(&self, url: &str, now: Instant) -> Option<Value> {
+        self.profile.cache_ttl?;
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(url) {
+            Some((value, expires)) if now < *expires => Some(value.clone()),
+            Some(_) => {
+                cache.remove(url);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn store(&self, url: &str, value: &Value, now: Instant) {
+        let Some(ttl) = self.profile.cache_ttl else {
+            return;
+        };
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (_, expires)| now < *expires);
+        cache.insert(url.to_string(), (value.clone(), now + ttl));
+    }
+}
+
+// The profiles a client uses: one for everything and one per route group,
+// matched in the order they were added.
+#[derive(Default)]
+pub(crate) struct Profiles {
+    default: Option<ActiveProfile>,
+    routes: Vec<(String, Route, ActiveProfile)>,
+    // Loaded from a config file, looked up before the built-ins.
+    named: BTreeMap<String, PolicyProfile>,
+}
+
+impl Profiles {
+    pub fn set_default(&mut self, profile: PolicyProfile, now: Instant) {
+        self.default = Some(ActiveProfile::new("profile:default".to_string(), profile, now));
+    }
+
+    // Replaces the profile of an existing group with the same route.
+    pub fn set_route(&mut self, route: &str, profile: PolicyProfile, now: Instant) {
+        let route = route.trim();
+        let active = ActiveProfile::new(format!("profile:{}", route), profile, now);
+        match self.routes.iter_mut().find(|(existing, _, _)| existing == route) {
+            Some(entry) => entry.2 = active,
+            None => self.routes.push((route.to_string(), Route::parse(route), active)),
+        }
+    }
+
+    pub fn named(&self, name: &str) -> Result<PolicyProfile, ProfileError> {
+        self.named.get(name).cloned().or_else(|| PolicyProfile::named(name)).ok_or_else(|| ProfileError::Unknown(name.to_string()))
+    }
+
+    pub fn matching(&self, method: &str, path: &str) -> Option<&ActiveProfile> {
+        self.routes
+            .iter()
+            .find(|(_, route, _)| route.matches(method, path))
+            .map(|(_, _, active)| active)
+            .or(self.default.as_ref())
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = (&str, &TokenBucket)> {
+        self.default.iter().chain(self.routes.iter().map(|(_, _, active)| active)).filter_map(ActiveProfile::bucket)
+    }
+
+    // Replaces everything with what's in the config file.
+    pub fn load(&mut self, config: &Value, now: Instant) -> Result<(), Box<dyn std::error::Error>> {
+        let config = ProfilesConfig::deserialize(config)?;
+        let mut named = BTreeMap::new();
+        for name in config.profiles.keys() {
+            resolve(name, &config.profiles, &mut named, &mut Vec::new())?;
+        }
+        let mut loaded = Profiles {
+            named,
+            ..Profiles::default()
+        };
+        if let Some(name) = &config.default {
+            loaded.set_default(loaded.named(name)?, now);
+        }
+        for entry in &config.routes {
+            loaded.set_route(&entry.route, loaded.named(&entry.profile)?, now);
+        }
+        *self = loaded;
+        Ok(())
+    }
+}
+
+// `{"profiles": {"reports": {"extends": "conservative", "max_attempts": 3,
+// "rate_limit": {"per_second": 2, "burst": 4}, "cache_ttl_ms": 60000}},
+// "default": "read-heavy", "routes": [{"route": "GET /reports/*", "profile":
+// "reports"}]}`. A profile without "extends" starts from the defaults; a
+// null "rate_limit" or "cache_ttl_ms" turns that part off.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilesConfig {
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileConfig>,
+    default: Option<String>,
+    #[serde(default)]
+    routes: Vec<RouteConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteConfig {
+    route: String,
+    profile: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileConfig {
+    extends: Option<String>,
+    max_attempts: Option<u32>,
+    base_delay_ms: Option<u64>,
+    max_delay_ms: Option<u64>,
+    jitter: Option<f64>,
+    #[serde(default, deserialize_with = "present")]
+    rate_limit: Option<Option<RateLimitConfig>>,
+    #[serde(default, deserialize_with = "present")]
+    cache_ttl_ms: Option<Option<u64>>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitConfig {
+    per_second: f64,
+    burst: u32,
+}
+
+// Tells a null value (Some(None)) apart from a missing one (None).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+fn resolve(
+    name: &str,
+    configs: &BTreeMap<String, ProfileConfig>,
+    resolved: &mut BTreeMap<String, PolicyProfile>,
+    stack: &mut Vec<String>,
+) -> Result<PolicyProfile, ProfileError> {
+    if let Some(profile) = resolved.get(name) {
+        return Ok(profile.clone());
+    }
+    let Some(config) = configs.get(name) else {
+        return PolicyProfile::named(name).ok_or_else(|| ProfileError::Unknown(name.to_string()));
+    };
+    if stack.iter().any(|seen| seen == name) {
+        return Err(ProfileError::Cycle(name.to_string()));
+    }
+    stack.push(name.to_string());
+    let mut profile = match &config.extends {
+        Some(parent) => resolve(parent, configs, resolved, stack)?,
+        None => PolicyProfile::new(),
+    };
+    stack.pop();
+    let mut retry = profile.retry.clone();
+    if let Some(attempts) = config.max_attempts {
+        retry = retry.max_attempts(attempts);
+    }
+    if let Some(delay) = config.base_delay_ms {
+        retry = retry.base_delay(Duration::from_millis(delay));
+    }
+    if let Some(delay) = config.max_delay_ms {
+        retry = retry.max_delay(Duration::from_millis(delay));
+    }
+    if let Some(jitter) = config.jitter {
+        retry = retry.jitter(jitter);
+    }
+    profile = profile.retry(retry);
+    match config.rate_limit {
+        Some(Some(limit)) => profile = profile.rate_limit(limit.per_second, limit.burst),
+        Some(None) => profile = profile.no_rate_limit(),
+        None => {}
+    }
+    match config.cache_ttl_ms {
+        Some(Some(ttl)) => profile = profile.cache_ttl(Duration::from_millis(ttl)),
+        Some(None) => profile = profile.no_cache(),
+        None => {}
+    }
+    resolved.insert(name.to_string(), profile.clone());
+    Ok(profile)
+}
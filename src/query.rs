@@ -0,0 +1,95 @@
+use serde_json::Value;
+
+// How `Query::array` spells a list of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArrayStyle {
+    // ids=1&ids=2
+    #[default]
+    Repeat,
+    // ids[]=1&ids[]=2
+    Brackets,
+    // ids=1,2
+    Comma,
+}
+
+#[derive(Clone, Debug)]
+enum Param {
+    Single(String, String),
+    Array(String, Vec<String>),
+}
+
+// Query string parameters for one request, appended to any already in the
+// path. Names and values are URL-encoded when sent; repeated names are kept.
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    params: Vec<Param>,
+    array_style: ArrayStyle,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push(Param::Single(name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn array<S: AsRef<str>>(mut self, name: &str, values: &[S]) -> Self {
+        let values = values.iter().map(|v| v.as_ref().to_string()).collect();
+        self.params.push(Param::Array(name.to_string(), values));
+        self
+    }
+
+    pub fn array_style(mut self, style: ArrayStyle) -> Self {
+        self.array_style = style;
+        self
+    }
+
+    pub(crate) fn pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        for param in &self.params {
+            match param {
+                Param::Single(name, value) => pairs.push((name.clone(), value.clone())),
+                Param::Array(name, values) => match self.array_style {
+                    ArrayStyle::Repeat => pairs.extend(values.iter().map(|v| (name.clone(), v.clone()))),
+                    ArrayStyle::Brackets => pairs.extend(values.iter().map(|v| (format!("{}[]", name), v.clone()))),
+                    ArrayStyle::Comma => pairs.push((name.clone(), values.join(","))),
+                },
+            }
+        }
+        pairs
+    }
+
+    // `{"q": "x", "page": 2, "ids": [1, 2]}` for the C API. Nulls are skipped.
+    pub(crate) fn from_json(params: &Value) -> Option<Self> {
+        let mut query = Query::new();
+        for (name, value) in params.as_object()? {
+            match value {
+                Value::Null => {}
+                Value::Array(values) => {
+                    let values: Vec<String> = values.iter().map(scalar).collect::<Option<_>>()?;
+                    query = query.array(name, &values);
+                }
+                value => query = query.param(name, &scalar(value)?),
+            }
+        }
+        Some(query)
+    }
+}
+
+impl From<&[(&str, &str)]> for Query {
+    fn from(params: &[(&str, &str)]) -> Self {
+        params.iter().fold(Query::new(), |query, (name, value)| query.param(name, value))
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
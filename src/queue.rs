@@ -0,0 +1,113 @@
+use crate::{ApiError, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedRequest {
+    pub id: u64,
+    pub method: Method,
+    pub path: String,
+    pub body: Value,
+    // Epoch milliseconds; 0 for items journalled before this was recorded.
+    #[serde(default)]
+    pub queued_at: u64,
+}
+
+pub(crate) type FailureHandler = Arc<dyn Fn(&QueuedRequest, &dyn std::error::Error) + Send + Sync>;
+
+// Mutations waiting for connectivity, journalled to disk so they survive restarts.
+pub(crate) struct OfflineQueue {
+    journal: PathBuf,
+    items: Mutex<VecDeque<QueuedRequest>>,
+    // Held for a whole flush so two flushes never send the same item.
+    flushing: Mutex<()>,
+}
+
+impl OfflineQueue {
+    pub fn open<P: AsRef<Path>>(journal: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let journal = journal.as_ref().to_path_buf();
+        let items = match std::fs::read_to_string(&journal) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(OfflineQueue {
+            journal,
+            items: Mutex::new(items),
+            flushing: Mutex::new(()),
+        })
+    }
+
+    pub fn push(&self, method: Method, path: &str, body: Value, now: SystemTime) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut items = self.items.lock().unwrap();
+        let id = items.back().map_or(1, |last| last.id + 1);
+        items.push_back(QueuedRequest {
+            id,
+            method,
+            path: path.to_string(),
+            body,
+            queued_at: now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+        });
+        // Not queued unless it's journalled, so a caller retrying the error
+        // doesn't send it twice.
+        if let Err(e) = self.persist(&items) {
+            items.pop_back();
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    // How long the front item has waited, if its enqueue time is known.
+    pub fn oldest_wait(&self, now: SystemTime) -> Option<Duration> {
+        let items = self.items.lock().unwrap();
+        let queued_at = items.front()?.queued_at;
+        if queued_at == 0 {
+            return None;
+        }
+        Some(now.duration_since(UNIX_EPOCH + Duration::from_millis(queued_at)).unwrap_or_default())
+    }
+
+    // Replays queued items in order through `send`. Connection errors and
+    // timeouts stop the flush and keep the item for next time; any other error
+    // drops the item and reports it to `on_failure`. Returns the number of items delivered.
+    pub fn flush<F>(&self, send: F, on_failure: Option<&FailureHandler>) -> Result<usize, Box<dyn std::error::Error>>
+    where
+        F: Fn(&QueuedRequest) -> Result<Value, ApiError>,
+    {
+        let _flushing = self.flushing.lock().unwrap();
+        let mut delivered = 0;
+        loop {
+            let Some(item) = self.items.lock().unwrap().front().cloned() else {
+                break;
+            };
+            match send(&item) {
+                Ok(_) => delivered += 1,
+                Err(ApiError::Connection(_) | ApiError::Timeout(_)) => break,
+                Err(e) => {
+                    if let Some(handler) = on_failure {
+                        handler(&item, &e);
+                    }
+                }
+            }
+            let mut items = self.items.lock().unwrap();
+            items.retain(|queued| queued.id != item.id);
+            self.persist(&items)?;
+        }
+        Ok(delivered)
+    }
+
+    fn persist(&self, items: &VecDeque<QueuedRequest>) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = self.journal.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(items)?)?;
+        std::fs::rename(&tmp, &self.journal)?;
+        Ok(())
+    }
+}
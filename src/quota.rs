@@ -0,0 +1,140 @@
+use crate::throttle::SavedBucket;
+use crate::typed_headers::TypedHeaders;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Last known quota for one host. `reset_at` is in epoch seconds so the state
+// stays meaningful across processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaState {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExhausted {
+    pub host: String,
+    pub reset_in: Duration,
+}
+
+impl fmt::Display for QuotaExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Quota for {} is exhausted; resets in {}s", self.host, self.reset_in.as_secs())
+    }
+}
+
+impl std::error::Error for QuotaExhausted {}
+
+// Vendor quota per host, learned from rate-limit and Retry-After headers and
+// written to disk after every change, so short-lived processes (cron jobs, CLI
+// runs) start from what the previous run saw. The client's own rate-limit
+// buckets are kept in the same file, so a new run doesn't get a fresh burst.
+pub(crate) struct QuotaStore {
+    path: PathBuf,
+    saved: Mutex<Saved>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    #[serde(default)]
+    hosts: BTreeMap<String, QuotaState>,
+    // "client" for the client's rate limit, "profile:<route>" (or
+    // "profile:default") for a profile's.
+    #[serde(default)]
+    buckets: BTreeMap<String, SavedBucket>,
+}
+
+impl QuotaStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let saved = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Saved::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(QuotaStore {
+            path,
+            saved: Mutex::new(saved),
+        })
+    }
+
+    // Fails while a host is known to have nothing left and hasn't reset yet.
+    pub fn check(&self, host: &str, now: SystemTime) -> Result<(), QuotaExhausted> {
+        let saved = self.saved.lock().unwrap();
+        let Some(state) = saved.hosts.get(host) else {
+            return Ok(());
+        };
+        match (state.remaining, state.reset_at) {
+            (Some(0), Some(reset_at)) => {
+                let reset = UNIX_EPOCH + Duration::from_secs(reset_at);
+                match reset.duration_since(now) {
+                    Ok(reset_in) if !reset_in.is_zero() => Err(QuotaExhausted {
+                        host: host.to_string(),
+                        reset_in,
+                    }),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Headers win when present. Otherwise a known count is decremented, since
+    // the request used up quota either way.
+    pub fn observe(&self, host: &str, status: u16, headers: &HeaderMap, now: SystemTime) -> Result<(), Box<dyn std::error::Error>> {
+        let epoch = |delay: Duration| (now + delay).duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let mut saved = self.saved.lock().unwrap();
+        let previous = saved.hosts.get(host).copied();
+        let state = match (headers.rate_limit(), headers.retry_after()) {
+            (Some(limit), _) => QuotaState {
+                limit: limit.limit,
+                remaining: limit.remaining,
+                reset_at: limit.reset.map(epoch),
+            },
+            (None, Some(retry_after)) if status == 429 => QuotaState {
+                limit: previous.and_then(|p| p.limit),
+                remaining: Some(0),
+                reset_at: Some(epoch(retry_after)),
+            },
+            _ => match previous {
+                Some(mut state) => {
+                    state.remaining = state.remaining.map(|r| r.saturating_sub(1));
+                    state
+                }
+                None => return Ok(()),
+            },
+        };
+        if previous == Some(state) {
+            return Ok(());
+        }
+        saved.hosts.insert(host.to_string(), state);
+        self.persist(&saved)
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, QuotaState> {
+        self.saved.lock().unwrap().hosts.clone()
+    }
+
+    pub fn bucket(&self, key: &str) -> Option<SavedBucket> {
+        self.saved.lock().unwrap().buckets.get(key).copied()
+    }
+
+    pub fn save_bucket(&self, key: &str, bucket: SavedBucket) -> Result<(), Box<dyn std::error::Error>> {
+        let mut saved = self.saved.lock().unwrap();
+        saved.buckets.insert(key.to_string(), bucket);
+        self.persist(&saved)
+    }
+
+    fn persist(&self, saved: &Saved) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(saved)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
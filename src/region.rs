@@ -0,0 +1,254 @@
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Weight given to the newest probe in the latency average.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+#[derive(Clone, Debug)]
+struct Region {
+    name: String,
+    base_url: String,
+    weight: f64,
+}
+
+// Regional base URLs serving the same API. A background thread probes each
+// one and requests go to the healthy region with the lowest latency divided by
+// weight. The current region is only abandoned for one that beats it by more
+// than `switch_margin`, so close races don't flip traffic back and forth.
+#[derive(Clone, Debug)]
+pub struct Regions {
+    regions: Vec<Region>,
+    probe_path: String,
+    interval: Duration,
+    timeout: Duration,
+    switch_margin: f64,
+}
+
+impl Regions {
+    pub fn new() -> Self {
+        Regions {
+            regions: Vec::new(),
+            probe_path: "/".to_string(),
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            switch_margin: 0.2,
+        }
+    }
+
+    pub fn region(self, name: &str, base_url: &str) -> Self {
+        self.weighted(name, base_url, 1.0)
+    }
+
+    // Latency is divided by `weight`, so a weight-2 region at 100ms ties a
+    // weight-1 region at 50ms.
+    pub fn weighted(mut self, name: &str, base_url: &str, weight: f64) -> Self {
+        self.regions.push(Region {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            weight: weight.max(f64::MIN_POSITIVE),
+        });
+        self
+    }
+
+    // GET relative to each region's base URL; any 2xx or 3xx counts as healthy.
+    pub fn probe_path(mut self, path: &str) -> Self {
+        self.probe_path = path.to_string();
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Fraction (0.2 = 20%) by which another region must beat the current one.
+    pub fn switch_margin(mut self, margin: f64) -> Self {
+        self.switch_margin = margin.max(0.0);
+        self
+    }
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RegionStatus {
+    pub name: String,
+    pub base_url: String,
+    // False until the first probe answers.
+    pub healthy: bool,
+    pub latency_ms: Option<f64>,
+    pub selected: bool,
+    pub pinned: bool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Probe {
+    healthy: bool,
+    latency: Option<f64>,
+}
+
+struct State {
+    probes: Vec<Probe>,
+    current: usize,
+    pinned: Option<usize>,
+    stopped: bool,
+}
+
+struct Shared {
+    config: Regions,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+// Owns the prober thread, which stops when this is dropped.
+pub(crate) struct RegionRouter {
+    shared: Arc<Shared>,
+}
+
+impl RegionRouter {
+    pub(crate) fn start(config: Regions) -> Result<Self, Box<dyn std::error::Error>> {
+        if config.regions.is_empty() {
+            return Err("No regions configured".into());
+        }
+        let client = Client::builder().timeout(config.timeout).build()?;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                probes: vec![Probe::default(); config.regions.len()],
+                current: 0,
+                pinned: None,
+                stopped: false,
+            }),
+            config,
+            wake: Condvar::new(),
+        });
+        let prober = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("easier_apis-region-probe".to_string())
+            .spawn(move || run(&prober, &client))?;
+        Ok(RegionRouter { shared })
+    }
+
+    pub(crate) fn base_url(&self) -> String {
+        let state = self.shared.state.lock().unwrap();
+        let index = state.pinned.unwrap_or(state.current);
+        self.shared.config.regions[index].base_url.clone()
+    }
+
+    pub(crate) fn pin(&self, name: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let index = match name {
+            Some(name) => Some(
+                self.shared
+                    .config
+                    .regions
+                    .iter()
+                    .position(|r| r.name == name)
+                    .ok_or_else(|| format!("Unknown region: {}", name))?,
+            ),
+            None => None,
+        };
+        self.shared.state.lock().unwrap().pinned = index;
+        Ok(())
+    }
+
+    pub(crate) fn status(&self) -> Vec<RegionStatus> {
+        let state = self.shared.state.lock().unwrap();
+        let selected = state.pinned.unwrap_or(state.current);
+        self.shared
+            .config
+            .regions
+            .iter()
+            .zip(&state.probes)
+            .enumerate()
+            .map(|(i, (region, probe))| RegionStatus {
+                name: region.name.clone(),
+                base_url: region.base_url.clone(),
+                healthy: probe.healthy,
+                latency_ms: probe.latency,
+                selected: i == selected,
+                pinned: state.pinned == Some(i),
+            })
+            .collect()
+    }
+}
+
+impl Drop for RegionRouter {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+fn run(shared: &Shared, client: &Client) {
+    loop {
+        let results: Vec<Option<f64>> = thread::scope(|scope| {
+            let probes: Vec<_> = shared
+                .config
+                .regions
+                .iter()
+                .map(|region| scope.spawn(|| probe(client, &format!("{}{}", region.base_url, shared.config.probe_path))))
+                .collect();
+            probes.into_iter().map(|p| p.join().unwrap_or(None)).collect()
+        });
+        let mut state = shared.state.lock().unwrap();
+        if state.stopped {
+            return;
+        }
+        for (probe, result) in state.probes.iter_mut().zip(results) {
+            probe.healthy = result.is_some();
+            if let Some(latency) = result {
+                probe.latency = Some(match probe.latency {
+                    Some(average) => average + LATENCY_SMOOTHING * (latency - average),
+                    None => latency,
+                });
+            }
+        }
+        state.current = select(&shared.config, &state.probes, state.current);
+        let deadline = Instant::now() + shared.config.interval;
+        while !state.stopped {
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            state = shared.wake.wait_timeout(state, left).unwrap().0;
+        }
+        if state.stopped {
+            return;
+        }
+    }
+}
+
+// Round trip in milliseconds, or None if the region is unhealthy.
+fn probe(client: &Client, url: &str) -> Option<f64> {
+    let started = Instant::now();
+    let response = client.get(url).send().ok()?;
+    let healthy = response.status().is_success() || response.status().is_redirection();
+    healthy.then(|| started.elapsed().as_secs_f64() * 1000.0)
+}
+
+fn select(config: &Regions, probes: &[Probe], current: usize) -> usize {
+    let score = |i: usize| {
+        let probe = &probes[i];
+        probe.healthy.then_some(probe.latency?).map(|latency| latency / config.regions[i].weight)
+    };
+    let best = (0..probes.len())
+        .filter_map(|i| score(i).map(|s| (i, s)))
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    match (best, score(current)) {
+        (Some((best, best_score)), Some(current_score)) if best_score * (1.0 + config.switch_margin) < current_score => best,
+        (Some(_), Some(_)) => current,
+        (Some((best, _)), None) => best,
+        // Nothing is healthy; stay put rather than guess.
+        (None, _) => current,
+    }
+}
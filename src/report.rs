@@ -0,0 +1,127 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+// One HTTP attempt of a failed call. `status` is None when no response arrived.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+// Handed to `on_error` hooks once a call has failed for good.
+#[derive(Clone, Debug)]
+pub struct ErrorReport {
+    pub method: String,
+    pub url: String,
+    // `X-Request-Id` from the request, or failing that the last response.
+    pub request_id: Option<String>,
+    pub attempts: Vec<Attempt>,
+    pub error: String,
+}
+
+// Which on-disk store a `StoreError` came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Store {
+    Quota,
+    DiskCache,
+}
+
+// Handed to `on_store_error` hooks when saving to one of the client's on-disk
+// stores fails. The call it happened during still succeeds; the store just
+// falls behind.
+#[derive(Clone, Debug)]
+pub struct StoreError {
+    pub store: Store,
+    // The host for quota state, the URL for a cached response.
+    pub key: String,
+    pub error: String,
+}
+
+pub(crate) type StoreErrorHook = Arc<dyn Fn(&StoreError) + Send + Sync>;
+
+// Final error of a call that went through the retry loop, carrying every
+// attempt made. `source()` is the error of the last attempt.
+#[derive(Debug)]
+pub struct RetryError {
+    pub attempts: Vec<Attempt>,
+    pub error: Box<dyn std::error::Error>,
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.attempts.len() {
+            0 | 1 => write!(f, "{}", self.error),
+            n => write!(f, "{} (after {} attempts)", self.error, n),
+        }
+    }
+}
+
+impl std::error::Error for RetryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+// Inserted into the extensions of the response a call returns: which attempt
+// (from 1) produced it, and what the attempts before it saw.
+#[derive(Clone, Debug)]
+pub struct FinalAttempt {
+    pub attempt: usize,
+    pub earlier: Vec<Attempt>,
+}
+
+// Status of the last response behind a `RetryError`, if any.
+pub(crate) fn final_status(error: &(dyn std::error::Error + 'static)) -> Option<u16> {
+    error.downcast_ref::<RetryError>()?.attempts.last()?.status
+}
+
+pub(crate) const REQUEST_ID: &str = "x-request-id";
+
+pub(crate) type ErrorHook = Arc<dyn Fn(&ErrorReport) + Send + Sync>;
+
+// Reports failed calls to the Sentry hub the host application initialised,
+// with one breadcrumb per attempt and the request ID as a tag:
+//
+//     core.on_error(easier_apis_core::sentry_reporter());
+#[cfg(feature = "sentry")]
+pub fn sentry_reporter() -> impl Fn(&ErrorReport) + Send + Sync + 'static {
+    use sentry_core::protocol::{Breadcrumb, Level, Map};
+
+    |report: &ErrorReport| {
+        sentry_core::with_scope(
+            |scope| {
+                scope.set_tag("http.method", &report.method);
+                scope.set_extra("url", report.url.clone().into());
+                scope.set_extra("attempts", report.attempts.len().into());
+                if let Some(request_id) = &report.request_id {
+                    scope.set_tag("request_id", request_id);
+                }
+            },
+            || {
+                // Breadcrumbs added here land on the pushed scope, so they stay
+                // attached to this event only.
+                for (i, attempt) in report.attempts.iter().enumerate() {
+                    let mut data = Map::new();
+                    data.insert("method".into(), report.method.clone().into());
+                    data.insert("url".into(), report.url.clone().into());
+                    data.insert("attempt".into(), (i + 1).into());
+                    data.insert("elapsed_ms".into(), (attempt.elapsed.as_millis() as u64).into());
+                    if let Some(status) = attempt.status {
+                        data.insert("status_code".into(), status.into());
+                    }
+                    sentry_core::add_breadcrumb(Breadcrumb {
+                        ty: "http".into(),
+                        category: Some("easier_apis.request".into()),
+                        message: attempt.error.clone(),
+                        level: if attempt.status.is_some() { Level::Warning } else { Level::Error },
+                        data,
+                        ..Default::default()
+                    });
+                }
+                sentry_core::capture_message(&report.error, Level::Error)
+            },
+        );
+    }
+}
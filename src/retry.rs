@@ -0,0 +1,181 @@
+use crate::error::TimeoutError;
+use crate::Method;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+type RetryPredicate = Arc<dyn Fn(Method, Option<u16>) -> bool + Send + Sync>;
+
+// When and how long to wait before sending a failed request again. The
+// default makes four attempts with exponential backoff (2s, 4s, 8s), retries
+// only idempotent methods and honors Retry-After on 429 and 503.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    retry_after: bool,
+    deadline: Option<Duration>,
+    backoff_budget: Option<Duration>,
+    predicate: RetryPredicate,
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.0,
+            retry_after: true,
+            deadline: None,
+            backoff_budget: None,
+            predicate: Arc::new(|method, status| method.is_idempotent() && Self::retryable_status(status)),
+        }
+    }
+
+    // Never retries.
+    pub fn none() -> Self {
+        Self::new().max_attempts(1)
+    }
+
+    // Counting the first one; 1 disables retries.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    // Retry `n` waits `base_delay * 2^n`, capped at `max_delay`.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    // Fraction (0.0 to 1.0) of each delay that may be randomly cut, so clients
+    // that failed together don't retry together.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    // Whether a Retry-After header on a 429 or 503 replaces the computed
+    // delay. It's still capped at `max_delay`.
+    pub fn honor_retry_after(mut self, honor: bool) -> Self {
+        self.retry_after = honor;
+        self
+    }
+
+    // Total time for a call, from the first attempt through every backoff and
+    // retry. Attempts are cut short when it runs out, and a retry that would
+    // start after it isn't made; both fail with `TimeoutError::Deadline`.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    // Total time a call may spend waiting between attempts. A retry whose
+    // backoff would go past it isn't made and the call fails with
+    // `TimeoutError::BackoffBudget`.
+    pub fn backoff_budget(mut self, budget: Duration) -> Self {
+        self.backoff_budget = Some(budget);
+        self
+    }
+
+    // Decides which failures are retried, given the method and the response
+    // status (None for transport errors).
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(Method, Option<u16>) -> bool + Send + Sync + 'static,
+    {
+        self.predicate = Arc::new(predicate);
+        self
+    }
+
+    // The default status rule: transport errors, 408, 429 and 5xx.
+    pub fn retryable_status(status: Option<u16>) -> bool {
+        match status {
+            None => true,
+            Some(status) => matches!(status, 408 | 429 | 500..=599),
+        }
+    }
+
+    // Whether attempt number `attempt` (from 1) may be followed by another.
+    pub(crate) fn should_retry(&self, attempt: u32, method: &reqwest::Method, status: Option<u16>) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match method.as_str().parse() {
+            Ok(method) => (self.predicate)(method, status),
+            Err(_) => false,
+        }
+    }
+
+    // Both limits at once, None removing one; for the C API.
+    pub(crate) fn limits(mut self, deadline: Option<Duration>, backoff_budget: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self.backoff_budget = backoff_budget;
+        self
+    }
+
+    pub(crate) fn deadline_limit(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    // Why a retry after waiting `delay` may not be made, `elapsed` into the
+    // call with `backed_off` already spent waiting.
+    pub(crate) fn exhausted(&self, elapsed: Duration, backed_off: Duration, delay: Duration) -> Option<TimeoutError> {
+        if let Some(budget) = self.backoff_budget.filter(|budget| backed_off + delay > *budget) {
+            return Some(TimeoutError::BackoffBudget { elapsed, budget });
+        }
+        self.deadline
+            .filter(|deadline| elapsed + delay >= *deadline)
+            .map(|deadline| TimeoutError::Deadline { elapsed, deadline })
+    }
+
+    // Wait before retry number `retry` (from 1).
+    pub(crate) fn delay(&self, retry: u32, status: Option<u16>, retry_after: Option<Duration>) -> Duration {
+        if let (true, Some(503 | 429), Some(retry_after)) = (self.retry_after, status, retry_after) {
+            return retry_after.min(self.max_delay);
+        }
+        let delay = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let mut random = [0u8; 8];
+        if getrandom::getrandom(&mut random).is_err() {
+            return delay;
+        }
+        let fraction = u64::from_le_bytes(random) as f64 / u64::MAX as f64;
+        delay.mul_f64(1.0 - self.jitter * fraction)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("retry_after", &self.retry_after)
+            .field("deadline", &self.deadline)
+            .field("backoff_budget", &self.backoff_budget)
+            .finish_non_exhaustive()
+    }
+}
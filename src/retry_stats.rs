@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct RetryStats {
+    // Calls that went through the retry loop.
+    pub requests: u64,
+    pub retries: u64,
+    // Why each retry happened: `status 503`, `timeout`, `connect`,
+    // `stale_connection`, `transport`.
+    pub reasons: BTreeMap<String, u64>,
+    // Time actually spent sleeping between attempts.
+    pub backoff: Duration,
+}
+
+// Counters keyed by endpoint (`METHOD /path`), kept for the client's lifetime.
+#[derive(Default)]
+pub(crate) struct RetryRecorder {
+    endpoints: Mutex<BTreeMap<String, RetryStats>>,
+}
+
+impl RetryRecorder {
+    pub fn request(&self, endpoint: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        endpoints.entry(endpoint.to_string()).or_default().requests += 1;
+    }
+
+    pub fn retry(&self, endpoint: &str, reason: &str, backoff: Duration) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint.to_string()).or_default();
+        stats.retries += 1;
+        *stats.reasons.entry(reason.to_string()).or_default() += 1;
+        stats.backoff += backoff;
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, RetryStats> {
+        self.endpoints.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        self.endpoints.lock().unwrap().clear();
+    }
+}
+
+pub(crate) fn reason(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connect"
+    } else if is_stale_connection(error) {
+        "stale_connection"
+    } else {
+        "transport"
+    }
+}
+
+// A pooled keep-alive connection the server closed just as we reused it:
+// hyper reports the request as sent but the response as never started.
+// These say nothing about the server's health.
+pub(crate) fn is_stale_connection(error: &reqwest::Error) -> bool {
+    if error.is_timeout() || error.is_connect() {
+        return false;
+    }
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(err) = current {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::BrokenPipe
+            ) {
+                return true;
+            }
+        }
+        // hyper's error types aren't reachable without depending on hyper.
+        let message = err.to_string();
+        if message.contains("connection closed before message completed") || message.contains("IncompleteMessage") {
+            return true;
+        }
+        current = err.source();
+    }
+    false
+}
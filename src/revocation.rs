@@ -0,0 +1,243 @@
+use der::asn1::ObjectIdentifier;
+use der::oid::db::{rfc5280::ID_KP_OCSP_SIGNING, rfc5912, rfc6960::ID_PKIX_OCSP_BASIC};
+use der::{Decode, Encode};
+use ring::digest;
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_ocsp::{BasicOcspResponse, CertStatus, OcspResponse, OcspResponseStatus};
+
+const CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevocationError {
+    Revoked,
+    StatusUnknown,
+    MissingStaple,
+    InvalidResponse(String),
+}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevocationError::Revoked => write!(f, "Server certificate has been revoked"),
+            RevocationError::StatusUnknown => write!(f, "OCSP responder does not know the server certificate"),
+            RevocationError::MissingStaple => write!(f, "Server did not staple an OCSP response"),
+            RevocationError::InvalidResponse(reason) => write!(f, "Invalid OCSP response: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RevocationError {}
+
+// Only stapled OCSP responses are checked; responders are never contacted and
+// CRLs are not consulted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RevocationPolicy {
+    // Fail handshakes that carry no staple instead of accepting them.
+    pub require_staple: bool,
+}
+
+pub(crate) fn tls_config(policy: RevocationPolicy) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(anchor.subject, anchor.spki, anchor.name_constraints)
+    }));
+    let verifier = OcspVerifier {
+        inner: WebPkiVerifier::new(roots, None),
+        policy,
+    };
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth()
+}
+
+// Digs a revocation failure out of a transport error. rustls hands it back
+// inside (possibly nested) io::Errors, whose `source()` skips the wrapped error,
+// so those are unwrapped with `get_ref()` instead.
+pub(crate) fn find(error: &(dyn std::error::Error + 'static)) -> Option<RevocationError> {
+    let mut current = Some(error);
+    while let Some(err) = current {
+        match err.downcast_ref::<rustls::Error>() {
+            Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) => {
+                return other.downcast_ref::<RevocationError>().cloned();
+            }
+            Some(rustls::Error::InvalidCertificate(CertificateError::Revoked)) => {
+                return Some(RevocationError::Revoked);
+            }
+            Some(_) => return None,
+            None => {}
+        }
+        current = match err.downcast_ref::<std::io::Error>() {
+            Some(io) => io.get_ref().map(|inner| inner as &(dyn std::error::Error + 'static)),
+            None => err.source(),
+        };
+    }
+    None
+}
+
+struct OcspVerifier {
+    inner: WebPkiVerifier,
+    policy: RevocationPolicy,
+}
+
+impl ServerCertVerifier for OcspVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        let result = if ocsp_response.is_empty() {
+            if self.policy.require_staple {
+                Err(RevocationError::MissingStaple)
+            } else {
+                Ok(())
+            }
+        } else {
+            match intermediates.first() {
+                Some(issuer) => check_staple(ocsp_response, &end_entity.0, &issuer.0, now),
+                None => Err(RevocationError::InvalidResponse("issuer certificate not sent".to_string())),
+            }
+        };
+        match result {
+            Ok(()) => Ok(verified),
+            Err(RevocationError::Revoked) => Err(rustls::Error::InvalidCertificate(CertificateError::Revoked)),
+            Err(e) => Err(rustls::Error::InvalidCertificate(CertificateError::Other(Arc::new(e)))),
+        }
+    }
+}
+
+fn invalid<E: fmt::Display>(e: E) -> RevocationError {
+    RevocationError::InvalidResponse(e.to_string())
+}
+
+pub(crate) fn check_staple(staple: &[u8], leaf: &[u8], issuer: &[u8], now: SystemTime) -> Result<(), RevocationError> {
+    let leaf = x509_cert::Certificate::from_der(leaf).map_err(invalid)?;
+    let issuer = x509_cert::Certificate::from_der(issuer).map_err(invalid)?;
+
+    let response = OcspResponse::from_der(staple).map_err(invalid)?;
+    if response.response_status != OcspResponseStatus::Successful {
+        return Err(invalid(format!("responder status {:?}", response.response_status)));
+    }
+    let bytes = response.response_bytes.ok_or_else(|| invalid("no response bytes"))?;
+    if bytes.response_type != ID_PKIX_OCSP_BASIC {
+        return Err(invalid("not a basic OCSP response"));
+    }
+    let basic = BasicOcspResponse::from_der(bytes.response.as_bytes()).map_err(invalid)?;
+    verify_responder(&basic, &issuer)?;
+
+    let issuer_spki = &issuer.tbs_certificate.subject_public_key_info;
+    let issuer_name = issuer.tbs_certificate.subject.to_der().map_err(invalid)?;
+    let single = basic
+        .tbs_response_data
+        .responses
+        .iter()
+        .find(|single| {
+            let id = &single.cert_id;
+            let Some(hash) = digest_for(&id.hash_algorithm.oid) else {
+                return false;
+            };
+            id.serial_number == leaf.tbs_certificate.serial_number
+                && digest::digest(hash, &issuer_name).as_ref() == id.issuer_name_hash.as_bytes()
+                && digest::digest(hash, issuer_spki.subject_public_key.raw_bytes()).as_ref() == id.issuer_key_hash.as_bytes()
+        })
+        .ok_or_else(|| invalid("no status for the server certificate"))?;
+
+    if single.this_update.0.to_system_time() > now + CLOCK_SKEW {
+        return Err(invalid("response is not yet valid"));
+    }
+    if let Some(next_update) = single.next_update {
+        if next_update.0.to_system_time() + CLOCK_SKEW < now {
+            return Err(invalid("response has expired"));
+        }
+    }
+    match single.cert_status {
+        CertStatus::Good(_) => Ok(()),
+        CertStatus::Revoked(_) => Err(RevocationError::Revoked),
+        CertStatus::Unknown(_) => Err(RevocationError::StatusUnknown),
+    }
+}
+
+// The response must be signed by the issuer itself or by a responder
+// certificate the issuer delegated OCSP signing to.
+fn verify_responder(basic: &BasicOcspResponse, issuer: &x509_cert::Certificate) -> Result<(), RevocationError> {
+    let tbs = basic.tbs_response_data.to_der().map_err(invalid)?;
+    let signature = basic.signature.raw_bytes();
+    let algorithm = &basic.signature_algorithm.oid;
+    let issuer_spki = &issuer.tbs_certificate.subject_public_key_info;
+    if verify_signature(issuer_spki, algorithm, &tbs, signature) {
+        return Ok(());
+    }
+    for delegate in basic.certs.iter().flatten() {
+        let delegate_tbs = delegate.tbs_certificate.to_der().map_err(invalid)?;
+        let issued_by_issuer = delegate.tbs_certificate.issuer == issuer.tbs_certificate.subject
+            && verify_signature(
+                issuer_spki,
+                &delegate.signature_algorithm.oid,
+                &delegate_tbs,
+                delegate.signature.raw_bytes(),
+            );
+        let may_sign_ocsp = delegate
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .filter(|ext| ext.extn_id == rfc5912::ID_CE_EXT_KEY_USAGE)
+            .filter_map(|ext| ExtendedKeyUsage::from_der(ext.extn_value.as_bytes()).ok())
+            .any(|eku| eku.0.contains(&ID_KP_OCSP_SIGNING));
+        if issued_by_issuer
+            && may_sign_ocsp
+            && verify_signature(&delegate.tbs_certificate.subject_public_key_info, algorithm, &tbs, signature)
+        {
+            return Ok(());
+        }
+    }
+    Err(invalid("signature does not verify against the issuer"))
+}
+
+fn digest_for(oid: &ObjectIdentifier) -> Option<&'static digest::Algorithm> {
+    match *oid {
+        rfc5912::ID_SHA_1 => Some(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        rfc5912::ID_SHA_256 => Some(&digest::SHA256),
+        rfc5912::ID_SHA_384 => Some(&digest::SHA384),
+        rfc5912::ID_SHA_512 => Some(&digest::SHA512),
+        _ => None,
+    }
+}
+
+fn verify_signature(spki: &SubjectPublicKeyInfoOwned, algorithm: &ObjectIdentifier, message: &[u8], sig: &[u8]) -> bool {
+    let curve = spki
+        .algorithm
+        .parameters
+        .as_ref()
+        .and_then(|params| params.decode_as::<ObjectIdentifier>().ok());
+    let p256 = curve == Some(rfc5912::SECP_256_R_1);
+    let p384 = curve == Some(rfc5912::SECP_384_R_1);
+    let verifier: &'static dyn VerificationAlgorithm = match *algorithm {
+        rfc5912::SHA_1_WITH_RSA_ENCRYPTION => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        rfc5912::SHA_256_WITH_RSA_ENCRYPTION => &signature::RSA_PKCS1_2048_8192_SHA256,
+        rfc5912::SHA_384_WITH_RSA_ENCRYPTION => &signature::RSA_PKCS1_2048_8192_SHA384,
+        rfc5912::SHA_512_WITH_RSA_ENCRYPTION => &signature::RSA_PKCS1_2048_8192_SHA512,
+        rfc5912::ECDSA_WITH_SHA_256 if p256 => &signature::ECDSA_P256_SHA256_ASN1,
+        rfc5912::ECDSA_WITH_SHA_256 if p384 => &signature::ECDSA_P384_SHA256_ASN1,
+        rfc5912::ECDSA_WITH_SHA_384 if p256 => &signature::ECDSA_P256_SHA384_ASN1,
+        rfc5912::ECDSA_WITH_SHA_384 if p384 => &signature::ECDSA_P384_SHA384_ASN1,
+        _ => return false,
+    };
+    UnparsedPublicKey::new(verifier, spki.subject_public_key.raw_bytes())
+        .verify(message, sig)
+        .is_ok()
+}
@@ -0,0 +1,162 @@
+use crate::pointer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Inferred shape of a JSON document: every JSON pointer (array elements as `*`)
+// mapped to the set of JSON types observed there.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    pub fields: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Schema {
+    pub fn infer(value: &Value) -> Self {
+        let mut schema = Schema::default();
+        schema.observe(String::new(), value);
+        schema
+    }
+
+    fn observe(&mut self, path: String, value: &Value) {
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(items) => {
+                for item in items {
+                    self.observe(format!("{}/*", path), item);
+                }
+                "array"
+            }
+            Value::Object(map) => {
+                for (key, item) in map {
+                    self.observe(format!("{}/{}", path, pointer::escape(key)), item);
+                }
+                "object"
+            }
+        };
+        self.fields.entry(path).or_default().insert(kind.to_string());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DriftKind {
+    FieldAdded,
+    FieldRemoved,
+    TypeChanged { expected: Vec<String>, found: Vec<String> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaDrift {
+    pub endpoint: String,
+    pub path: String,
+    pub kind: DriftKind,
+}
+
+type DriftHook = Arc<dyn Fn(&SchemaDrift) + Send + Sync>;
+
+// Learns a baseline per endpoint (`"GET /users"`) on first sight and reports
+// drift from it afterwards. Baselines can be persisted with `save`/`load`.
+#[derive(Default)]
+pub struct SchemaTracker {
+    baselines: Mutex<HashMap<String, Schema>>,
+    hook: Option<DriftHook>,
+}
+
+impl SchemaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let baselines = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(SchemaTracker {
+            baselines: Mutex::new(baselines),
+            hook: None,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let baselines = self.baselines.lock().unwrap();
+        std::fs::write(path, serde_json::to_string_pretty(&*baselines)?)?;
+        Ok(())
+    }
+
+    // Without a hook, drift is reported on stderr.
+    pub fn on_drift<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SchemaDrift) + Send + Sync + 'static,
+    {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn baseline(&self, endpoint: &str) -> Option<Schema> {
+        self.baselines.lock().unwrap().get(endpoint).cloned()
+    }
+
+    // Forgets the baseline so the next response for `endpoint` is recorded afresh.
+    pub fn reset(&self, endpoint: &str) {
+        self.baselines.lock().unwrap().remove(endpoint);
+    }
+
+    pub fn check(&self, endpoint: &str, body: &Value) -> Vec<SchemaDrift> {
+        let observed = Schema::infer(body);
+        let drift = {
+            let mut baselines = self.baselines.lock().unwrap();
+            match baselines.get(endpoint) {
+                Some(baseline) => compare(endpoint, baseline, &observed),
+                None => {
+                    baselines.insert(endpoint.to_string(), observed);
+                    Vec::new()
+                }
+            }
+        };
+        for item in &drift {
+            match &self.hook {
+                Some(hook) => hook(item),
+                None => eprintln!("easier_apis: schema drift on {} at {}: {:?}", item.endpoint, item.path, item.kind),
+            }
+        }
+        drift
+    }
+}
+
+fn compare(endpoint: &str, baseline: &Schema, observed: &Schema) -> Vec<SchemaDrift> {
+    let drift = |path: &str, kind| SchemaDrift {
+        endpoint: endpoint.to_string(),
+        path: path.to_string(),
+        kind,
+    };
+    // Only report fields whose parent was seen on both sides: a new object is one
+    // addition rather than one per descendant, and array elements appearing in
+    // or vanishing from an empty array are not drift.
+    let reportable = |path: &str, other: &Schema| {
+        let parent = &path[..path.rfind('/').unwrap_or(0)];
+        !path.is_empty() && !path.ends_with("/*") && other.fields.contains_key(parent)
+    };
+    let mut out = Vec::new();
+    for (path, found) in &observed.fields {
+        match baseline.fields.get(path) {
+            None if reportable(path, baseline) => out.push(drift(path, DriftKind::FieldAdded)),
+            None => {}
+            Some(expected) if !found.is_subset(expected) => out.push(drift(
+                path,
+                DriftKind::TypeChanged {
+                    expected: expected.iter().cloned().collect(),
+                    found: found.iter().cloned().collect(),
+                },
+            )),
+            Some(_) => {}
+        }
+    }
+    for path in baseline.fields.keys() {
+        if !observed.fields.contains_key(path) && reportable(path, observed) {
+            out.push(drift(path, DriftKind::FieldRemoved));
+        }
+    }
+    out
+}
@@ -0,0 +1,152 @@
+use crate::RustCore;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ScopedJoinHandle};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScopeError {
+    // A task failed; the message is its error's. Errors can't cross threads,
+    // so only the text survives.
+    Failed(String),
+    Cancelled,
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeError::Failed(message) => write!(f, "Scoped request failed: {}", message),
+            ScopeError::Cancelled => write!(f, "Request cancelled because a sibling in its scope failed"),
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+#[derive(Default)]
+struct Group {
+    cancelled: AtomicBool,
+    failure: Mutex<Option<String>>,
+}
+
+impl Group {
+    fn fail(&self, core: &RustCore, message: Option<String>) {
+        if let Some(message) = message {
+            self.failure.lock().unwrap().get_or_insert(message);
+        }
+        if !self.cancelled.swap(true, Ordering::SeqCst) {
+            // Wake siblings sleeping out a backoff.
+            core.connectivity.interrupt();
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Group>>> = const { RefCell::new(None) };
+}
+
+// True if the calling thread runs a scoped task whose group was cancelled.
+pub(crate) fn cancelled() -> bool {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|group| group.cancelled.load(Ordering::SeqCst))
+    })
+}
+
+// Spawns requests on their own threads. The first task to fail cancels the
+// rest: tasks not yet sending fail with `ScopeError::Cancelled` and retries
+// stop at their next backoff. Requests already on the wire can't be aborted
+// and run to their own timeout.
+pub struct Scope<'scope, 'env> {
+    scope: &'scope thread::Scope<'scope, 'env>,
+    core: &'env RustCore,
+    group: Arc<Group>,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    pub fn spawn<T, F>(&self, task: F) -> Task<'scope, T>
+    where
+        F: FnOnce(&RustCore) -> Result<T, Box<dyn std::error::Error>> + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let core = self.core;
+        let group = Arc::clone(&self.group);
+        let handle = self.scope.spawn(move || {
+            CURRENT.with(|current| *current.borrow_mut() = Some(Arc::clone(&group)));
+            match task(core) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    let message = (!cancelled()).then(|| e.to_string());
+                    group.fail(core, message);
+                    None
+                }
+            }
+        });
+        Task {
+            handle,
+            group: Arc::clone(&self.group),
+        }
+    }
+}
+
+pub struct Task<'scope, T> {
+    handle: ScopedJoinHandle<'scope, Option<T>>,
+    group: Arc<Group>,
+}
+
+impl<T> Task<'_, T> {
+    // Waits for the task. A failed task reports the scope's first failure.
+    pub fn join(self) -> Result<T, ScopeError> {
+        match self.handle.join() {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(match self.group.failure.lock().unwrap().clone() {
+                Some(message) => ScopeError::Failed(message),
+                None => ScopeError::Cancelled,
+            }),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+// Cancels the group if `body` unwinds, so siblings don't keep retrying while
+// the panic waits for them to be joined.
+struct CancelOnUnwind<'a> {
+    group: &'a Group,
+    core: &'a RustCore,
+}
+
+impl Drop for CancelOnUnwind<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.group.fail(self.core, None);
+        }
+    }
+}
+
+pub(crate) fn run<'env, T, F>(core: &'env RustCore, body: F) -> Result<T, Box<dyn std::error::Error>>
+where
+    F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> Result<T, Box<dyn std::error::Error>>,
+{
+    let group = Arc::new(Group::default());
+    let result = thread::scope(|scope| {
+        let _guard = CancelOnUnwind { group: &group, core };
+        let scope = Scope {
+            scope,
+            core,
+            group: Arc::clone(&group),
+        };
+        let result = body(&scope);
+        if let Err(e) = &result {
+            group.fail(core, Some(e.to_string()));
+        }
+        result
+    });
+    let failure = group.failure.lock().unwrap().clone();
+    match failure {
+        Some(message) if result.is_ok() => Err(ScopeError::Failed(message).into()),
+        _ => result,
+    }
+}
@@ -0,0 +1,98 @@
+use crate::snapshot::RequestSnapshot;
+use reqwest::blocking::Request;
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+
+// A rhai script that can define `on_request(req)` and/or `on_response(body)`.
+// `req` is a map of `method`, `url`, `headers` (a string per header, or an
+// array for repeated ones) and `body` (string or ()), and the function returns
+// the (possibly modified) map. `on_response` receives the decoded JSON body
+// and returns the value handed back to the caller.
+//
+// Script failures leave the request/response untouched so a broken hotfix
+// cannot take down every call. That includes running into the engine's limits,
+// which stop runaway loops and recursion.
+pub struct ScriptMiddleware {
+    engine: Engine,
+    ast: RwLock<AST>,
+    path: Option<PathBuf>,
+}
+
+impl ScriptMiddleware {
+    pub fn from_source(source: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(DEFAULT_MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+        let ast = engine.compile(source)?;
+        Ok(ScriptMiddleware {
+            engine,
+            ast: RwLock::new(ast),
+            path: None,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = std::fs::read_to_string(path.as_ref())?;
+        let mut script = Self::from_source(&source)?;
+        script.path = Some(path.as_ref().to_path_buf());
+        Ok(script)
+    }
+
+    // Upper bound on operations a single hook invocation may run.
+    pub fn with_max_operations(mut self, operations: u64) -> Self {
+        self.engine.set_max_operations(operations);
+        self
+    }
+
+    // Re-reads the script from disk; scripts built from source are left as-is.
+    pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = &self.path {
+            let source = std::fs::read_to_string(path)?;
+            let ast = self.engine.compile(&source)?;
+            *self.ast.write().unwrap() = ast;
+        }
+        Ok(())
+    }
+
+    fn has_fn(&self, name: &str) -> bool {
+        self.ast.read().unwrap().iter_functions().any(|f| f.name == name && f.params.len() == 1)
+    }
+
+    fn call(&self, name: &str, arg: Dynamic) -> Result<Dynamic, Box<dyn std::error::Error>> {
+        let ast = self.ast.read().unwrap();
+        let mut scope = Scope::new();
+        Ok(self.engine.call_fn::<Dynamic>(&mut scope, &ast, name, (arg,))?)
+    }
+
+    pub fn on_request(&self, request: Request) -> Request {
+        if !self.has_fn("on_request") {
+            return request;
+        }
+        self.rewrite_request(&request).unwrap_or(request)
+    }
+
+    fn rewrite_request(&self, original: &Request) -> Option<Request> {
+        let arg = rhai::serde::to_dynamic(RequestSnapshot::capture(original)).ok()?;
+        let result = self.call("on_request", arg).ok()?;
+        let modified: RequestSnapshot = rhai::serde::from_dynamic(&result).ok()?;
+        modified.apply(original)
+    }
+
+    pub fn on_response(&self, body: Value) -> Value {
+        if !self.has_fn("on_response") {
+            return body;
+        }
+        let modified = rhai::serde::to_dynamic(&body)
+            .ok()
+            .and_then(|arg| self.call("on_response", arg).ok())
+            .and_then(|result| rhai::serde::from_dynamic::<Value>(&result).ok());
+        modified.unwrap_or(body)
+    }
+}
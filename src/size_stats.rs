@@ -0,0 +1,94 @@
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::sync::Mutex;
+
+// Bytes in one direction. `body` is what went over the wire; `body_decoded`
+// is the same body without its content-encoding (zstd). Header sizes cover the
+// headers the client sees, not ones the transport adds itself (Host,
+// Content-Length).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TransferSize {
+    pub headers: u64,
+    pub body: u64,
+    pub body_decoded: u64,
+}
+
+impl TransferSize {
+    pub fn total(&self) -> u64 {
+        self.headers + self.body
+    }
+}
+
+impl AddAssign for TransferSize {
+    fn add_assign(&mut self, other: Self) {
+        self.headers += other.headers;
+        self.body += other.body;
+        self.body_decoded += other.body_decoded;
+    }
+}
+
+// Every attempt counts, so retries show up as extra egress. Streamed bodies
+// are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SizeStats {
+    pub attempts: u64,
+    pub sent: TransferSize,
+    pub received: TransferSize,
+}
+
+impl AddAssign for SizeStats {
+    fn add_assign(&mut self, other: Self) {
+        self.attempts += other.attempts;
+        self.sent += other.sent;
+        self.received += other.received;
+    }
+}
+
+// Keyed by endpoint (`METHOD /path`) like `RetryRecorder`.
+#[derive(Default)]
+pub(crate) struct SizeRecorder {
+    endpoints: Mutex<BTreeMap<String, SizeStats>>,
+}
+
+impl SizeRecorder {
+    pub fn attempt(&self, endpoint: &str, sent: TransferSize, received_headers: u64) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint.to_string()).or_default();
+        stats.attempts += 1;
+        stats.sent += sent;
+        stats.received.headers += received_headers;
+    }
+
+    pub fn received_body(&self, endpoint: &str, body: u64, body_decoded: u64) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let received = &mut endpoints.entry(endpoint.to_string()).or_default().received;
+        received.body += body;
+        received.body_decoded += body_decoded;
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, SizeStats> {
+        self.endpoints.lock().unwrap().clone()
+    }
+
+    pub fn total(&self) -> SizeStats {
+        let mut total = SizeStats::default();
+        for stats in self.endpoints.lock().unwrap().values() {
+            total += *stats;
+        }
+        total
+    }
+
+    pub fn reset(&self) {
+        self.endpoints.lock().unwrap().clear();
+    }
+}
+
+// `name: value\r\n` per header.
+pub(crate) fn header_bytes(headers: &HeaderMap) -> u64 {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().len() + value.len() + 4) as u64)
+        .sum()
+}
@@ -0,0 +1,100 @@
+use reqwest::blocking::{Body, Request};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Serializable view of an outgoing request handed to scripts and plugins.
+// Headers that aren't valid UTF-8 are left out of it, and kept as they are.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RequestSnapshot {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, HeaderValues>,
+    pub body: Option<String>,
+}
+
+// A string for a header sent once, a list for one that's repeated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HeaderValues {
+    fn as_slice(&self) -> &[String] {
+        match self {
+            HeaderValues::One(value) => std::slice::from_ref(value),
+            HeaderValues::Many(values) => values,
+        }
+    }
+}
+
+impl RequestSnapshot {
+    pub fn capture(request: &Request) -> Self {
+        RequestSnapshot {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: headers_of(request.headers()),
+            body: body_of(request),
+        }
+    }
+
+    // Builds a copy of `original` carrying this snapshot's values, or `None` if
+    // the request can't be cloned or the snapshot holds invalid values. Only
+    // headers whose values changed are rewritten, so the rest keep their
+    // repeats and sensitivity.
+    pub fn apply(self, original: &Request) -> Option<Request> {
+        let mut request = original.try_clone()?;
+        *request.method_mut() = Method::from_bytes(self.method.as_bytes()).ok()?;
+        *request.url_mut() = Url::parse(&self.url).ok()?;
+        let before = headers_of(original.headers());
+        let headers = request.headers_mut();
+        for name in before.keys().filter(|name| !self.headers.contains_key(*name)) {
+            headers.remove(name.as_str());
+        }
+        for (key, values) in &self.headers {
+            if before.get(key) == Some(values) {
+                continue;
+            }
+            let name = HeaderName::from_bytes(key.as_bytes()).ok()?;
+            let sensitive = headers.get_all(&name).iter().any(HeaderValue::is_sensitive);
+            headers.remove(&name);
+            for value in values.as_slice() {
+                let mut value = HeaderValue::from_str(value).ok()?;
+                value.set_sensitive(sensitive);
+                headers.append(&name, value);
+            }
+        }
+        if let Some(body) = self.body.filter(|body| Some(body) != body_of(original).as_ref()) {
+            *request.body_mut() = Some(Body::from(body));
+        }
+        Some(request)
+    }
+}
+
+fn headers_of(headers: &HeaderMap) -> BTreeMap<String, HeaderValues> {
+    let mut out = BTreeMap::new();
+    for name in headers.keys() {
+        let values: Vec<String> = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| Some(value.to_str().ok()?.to_string()))
+            .collect();
+        let values = match <[String; 1]>::try_from(values) {
+            Ok([value]) => HeaderValues::One(value),
+            Err(values) if values.is_empty() => continue,
+            Err(values) => HeaderValues::Many(values),
+        };
+        out.insert(name.to_string(), values);
+    }
+    out
+}
+
+fn body_of(request: &Request) -> Option<String> {
+    request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .map(|b| String::from_utf8_lossy(b).into_owned())
+}
@@ -0,0 +1,74 @@
+use reqwest::header::{HeaderMap, HeaderName};
+use sha2::{Digest, Sha256};
+
+// Sends part of an endpoint's traffic to another base URL. Listed tenants
+// always go; otherwise `percent` of requests do. With a tenant header the
+// choice is sticky per tenant (hashed), so one tenant doesn't flip between
+// backends; requests without one are picked at random.
+#[derive(Clone, Debug)]
+pub struct TrafficSplit {
+    path: String,
+    base_url: String,
+    percent: f64,
+    tenant_header: Option<HeaderName>,
+    tenants: Vec<String>,
+}
+
+impl TrafficSplit {
+    // `path` is a prefix of request paths relative to the client's base URL.
+    pub fn new(path: &str, base_url: &str) -> Self {
+        TrafficSplit {
+            path: path.to_string(),
+            base_url: base_url.to_string(),
+            percent: 0.0,
+            tenant_header: None,
+            tenants: Vec::new(),
+        }
+    }
+
+    pub fn percent(mut self, percent: f64) -> Self {
+        self.percent = percent.clamp(0.0, 100.0);
+        self
+    }
+
+    // Panics on an invalid header name, like `HeaderName::from_static`.
+    pub fn tenant_header(mut self, header: &str) -> Self {
+        self.tenant_header = Some(HeaderName::try_from(header).unwrap());
+        self
+    }
+
+    pub fn tenant(mut self, tenant: &str) -> Self {
+        self.tenants.push(tenant.to_string());
+        self
+    }
+
+    pub(crate) fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub(crate) fn selects(&self, path: &str, headers: &HeaderMap) -> Result<bool, getrandom::Error> {
+        if !path.starts_with(self.path.as_str()) {
+            return Ok(false);
+        }
+        let tenant = self
+            .tenant_header
+            .as_ref()
+            .and_then(|header| headers.get(header))
+            .and_then(|v| v.to_str().ok());
+        if tenant.is_some_and(|tenant| self.tenants.iter().any(|t| t == tenant)) {
+            return Ok(true);
+        }
+        if self.percent <= 0.0 {
+            return Ok(false);
+        }
+        let mut bucket = [0u8; 8];
+        match tenant {
+            Some(tenant) => {
+                let digest = Sha256::new().chain_update(self.path.as_bytes()).chain_update(tenant).finalize();
+                bucket.copy_from_slice(&digest[..8]);
+            }
+            None => getrandom::getrandom(&mut bucket)?,
+        }
+        Ok((u64::from_be_bytes(bucket) % 10_000) as f64 / 100.0 < self.percent)
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{ApiError, IdempotencyTracker, ResponseStream, RustCore};
+use reqwest::header::CONTENT_TYPE;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+// Blocking requests can't go without a timeout, and the client's would cut
+// long-lived streams off; a dropped stream reconnects anyway.
+pub(crate) const STREAM_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+// One event of a `text/event-stream`. `id` is the last ID the server set,
+// which may have come with an earlier event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: String,
+    pub data: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SseError {
+    // The response wasn't `text/event-stream`.
+    NotEventStream(String),
+}
+
+impl fmt::Display for SseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SseError::NotEventStream(content_type) => write!(f, "Expected text/event-stream, got {}", content_type),
+        }
+    }
+}
+
+impl std::error::Error for SseError {}
+
+// Events from `RustCore::fetch_sse`, read as the iterator advances. When the
+// connection drops, it reconnects after the server's `retry:` delay (or the
+// retry policy's backoff) and sends `Last-Event-ID` so the server can resume.
+// Ends when the server answers a reconnect with 204 No Content, or after the
+// first error it couldn't reconnect from.
+pub struct EventStream<'a> {
+    core: &'a RustCore,
+    path: String,
+    reader: Option<BufReader<ResponseStream>>,
+    last_event_id: Option<String>,
+    // Set by `id:` lines, taken over as the last event ID once the event
+    // they belong to is complete.
+    id_buffer: Option<String>,
+    retry: Option<Duration>,
+    reconnects: u32,
+    done: bool,
+    pub(crate) dedup: Option<IdempotencyTracker>,
+}
+
+impl<'a> EventStream<'a> {
+    pub(crate) fn connect(core: &'a RustCore, path: &str) -> Result<Self, ApiError> {
+        let mut stream = EventStream {
+            core,
+            path: path.to_string(),
+            reader: None,
+            last_event_id: None,
+            id_buffer: None,
+            retry: None,
+            reconnects: 0,
+            done: false,
+            dedup: None,
+        };
+        stream.reader = stream.open()?;
+        stream.done = stream.reader.is_none();
+        Ok(stream)
+    }
+
+    // Drops events whose own `id:` the tracker has seen, e.g. ones a server
+    // sends again after a reconnect. Events without an ID always get through.
+    pub fn dedup(mut self, tracker: &IdempotencyTracker) -> Self {
+        self.dedup = Some(tracker.clone());
+        self
+    }
+
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    // How many times the stream was re-established.
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects
+    }
+
+    // None for 204 No Content, which tells clients to stop reconnecting.
+    fn open(&self) -> Result<Option<BufReader<ResponseStream>>, ApiError> {
+        let stream = self.core.open_event_stream(&self.path, self.last_event_id.as_deref())?;
+        if stream.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let content_type = stream.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        if !content_type.trim().to_ascii_lowercase().starts_with("text/event-stream") {
+            return Err(ApiError::Decode(SseError::NotEventStream(content_type.to_string()).into()));
+        }
+        Ok(Some(BufReader::new(stream)))
+    }
+
+    fn reconnect(&mut self) -> Result<(), ApiError> {
+        self.reader = None;
+        let policy = self.core.retry_policy.clone();
+        let mut retry = 1;
+        loop {
+            self.core.wait(self.retry.unwrap_or_else(|| policy.delay(retry, None, None)));
+            match self.open() {
+                Ok(reader) => {
+                    self.done = reader.is_none();
+                    self.reader = reader;
+                    self.reconnects += 1;
+                    return Ok(());
+                }
+                Err(e) if !policy.should_retry(retry, &reqwest::Method::GET, e.status()) => return Err(e),
+                Err(_) => retry += 1,
+            }
+        }
+    }
+
+    // Parses lines up to the next complete event, and whether it set its own
+    // ID. None when the connection ended, or failed, before one.
+    fn read_event(&mut self) -> Option<(SseEvent, bool)> {
+        let reader = self.reader.as_mut()?;
+        let mut own_id = false;
+        let mut event = String::new();
+        let mut data = String::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => {}
+            }
+            let line = line.trim_end_matches(['\n', '\r']).trim_start_matches('\u{feff}');
+            if line.is_empty() {
+                self.last_event_id = self.id_buffer.clone();
+                if data.is_empty() {
+                    event.clear();
+                    own_id = false;
+                    continue;
+                }
+                data.pop();
+                let event = SseEvent {
+                    id: self.last_event_id.clone(),
+                    event: if event.is_empty() { "message".to_string() } else { event },
+                    data,
+                };
+                return Some((event, own_id));
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "data" => {
+                    data.push_str(value);
+                    data.push('\n');
+                }
+                "event" => event = value.to_string(),
+                "id" if !value.contains('\0') => {
+                    self.id_buffer = Some(value.to_string());
+                    own_id = !value.is_empty();
+                }
+                "retry" => {
+                    if let Ok(ms) = value.parse() {
+                        self.retry = Some(Duration::from_millis(ms));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Iterator for EventStream<'_> {
+    type Item = Result<SseEvent, ApiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some((event, own_id)) = self.read_event() {
+                if let (Some(tracker), Some(id), true) = (&self.dedup, &event.id, own_id) {
+                    if !tracker.first_seen(id) {
+                        continue;
+                    }
+                }
+                return Some(Ok(event));
+            }
+            if let Err(e) = self.reconnect() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,88 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsdFormat {
+    // Plain StatsD has no tag syntax, so tags are dropped.
+    Statsd,
+    // `name:value|type|#key:value,...`
+    DogStatsd,
+}
+
+// Fire-and-forget UDP metrics. Send failures are ignored so a missing or
+// unreachable agent never affects requests.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    format: StatsdFormat,
+    tags: Vec<(String, String)>,
+}
+
+impl StatsdSink {
+    pub fn new<A: ToSocketAddrs>(addr: A) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(StatsdSink {
+            socket,
+            prefix: "easier_apis.".to_string(),
+            format: StatsdFormat::Statsd,
+            tags: Vec::new(),
+        })
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn format(mut self, format: StatsdFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    // Tag attached to every metric this sink sends.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn count(&self, name: &str, value: i64, tags: &[(&str, &str)]) {
+        self.send(name, &value.to_string(), "c", tags);
+    }
+
+    pub fn timing(&self, name: &str, duration: Duration, tags: &[(&str, &str)]) {
+        self.send(name, &format!("{:.3}", duration.as_secs_f64() * 1000.0), "ms", tags);
+    }
+
+    fn send(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let mut line = format!("{}{}:{}|{}", self.prefix, name, value, kind);
+        if self.format == StatsdFormat::DogStatsd {
+            let tags: Vec<String> = self
+                .tags
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .chain(tags.iter().copied())
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect();
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags.join(","));
+            }
+        }
+        let _ = self.socket.send(line.as_bytes());
+    }
+
+    // One timing and one count per HTTP attempt, tagged with method and status
+    // ("error" when no response arrived).
+    pub(crate) fn record_attempt(&self, method: &str, status: &str, duration: Duration) {
+        let tags = [("method", method), ("status", status)];
+        self.timing("request.duration", duration, &tags);
+        self.count("request.count", 1, &tags);
+    }
+
+    // Wire bytes (headers and body), tagged `direction:sent` or `received`.
+    pub(crate) fn record_bytes(&self, method: &str, direction: &str, bytes: u64) {
+        self.count("request.bytes", bytes as i64, &[("method", method), ("direction", direction)]);
+    }
+}
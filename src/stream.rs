@@ -0,0 +1,132 @@
+use crate::encoding::{self, DecompressionLimits};
+use crate::report::FinalAttempt;
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH};
+use reqwest::{StatusCode, Url};
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// Chunk size for `fetch_bytes_stream` and downloads.
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+// A successful response whose body is read incrementally instead of being
+// buffered and parsed.
+//
+// HTTP trailers are not available here: reqwest 0.11 discards trailer frames
+// before they reach the response, so exposing them needs a transport change.
+pub struct ResponseStream {
+    status: StatusCode,
+    url: Url,
+    headers: HeaderMap,
+    final_attempt: Option<FinalAttempt>,
+    body: Box<dyn Read + Send>,
+}
+
+impl ResponseStream {
+    pub(crate) fn new(response: Response, limits: &DecompressionLimits) -> Result<Self, Box<dyn std::error::Error>> {
+        let status = response.status();
+        let url = response.url().clone();
+        let final_attempt = response.extensions().get::<FinalAttempt>().cloned();
+        let mut headers = response.headers().clone();
+        let body = encoding::body_reader(response, &mut headers, limits)?;
+        Ok(ResponseStream {
+            status,
+            url,
+            headers,
+            final_attempt,
+            body,
+        })
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    // Which retry attempt answered.
+    pub fn final_attempt(&self) -> Option<&FinalAttempt> {
+        self.final_attempt.as_ref()
+    }
+
+    // The size of the body as it will be read, if the server sent one.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+    }
+
+    pub fn chunks(self, chunk_size: usize) -> Chunks {
+        Chunks {
+            stream: self,
+            chunk_size,
+        }
+    }
+}
+
+impl Read for ResponseStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+// Iterator over body chunks of at most `chunk_size` bytes.
+pub struct Chunks {
+    stream: ResponseStream,
+    chunk_size: usize,
+}
+
+impl Iterator for Chunks {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = vec![0; self.chunk_size];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => None,
+            Ok(n) => {
+                chunk.truncate(n);
+                Some(Ok(chunk))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// Writes the body to `dest` through `dest.part`, renamed into place once it's
+// complete so a failed download never leaves a truncated file under the
+// final name. Returns the number of bytes written.
+pub(crate) fn download<F>(stream: ResponseStream, dest: &Path, mut progress: F) -> Result<u64, Box<dyn std::error::Error>>
+where
+    F: FnMut(u64, Option<u64>),
+{
+    let total = stream.content_length();
+    let partial = partial_path(dest);
+    let mut out = io::BufWriter::new(std::fs::File::create(&partial)?);
+    let mut written = 0;
+    let copied = stream.chunks(DEFAULT_CHUNK_SIZE).try_for_each(|chunk| {
+        let chunk = chunk?;
+        out.write_all(&chunk)?;
+        written += chunk.len() as u64;
+        progress(written, total);
+        Ok(())
+    });
+    if let Err(e) = copied.and_then(|()| out.flush()) {
+        drop(out);
+        let _ = std::fs::remove_file(&partial);
+        return Err(encoding::io_error(e));
+    }
+    drop(out);
+    std::fs::rename(&partial, dest)?;
+    Ok(written)
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(dest.as_os_str());
+    name.push(".part");
+    PathBuf::from(name)
+}
@@ -0,0 +1,172 @@
+use crate::RustCore;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Clone)]
+struct StubResponse {
+    status: u16,
+    body: Value,
+}
+
+// Local HTTP server answering declared routes with canned JSON, for hermetic
+// examples and tests. Routes match on method and path (the query string is
+// ignored unless the route contains one); anything else gets a 404.
+#[derive(Default)]
+pub struct StubServer {
+    routes: HashMap<(String, String), StubResponse>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    addr: Option<SocketAddr>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StubServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, method: &str, path: &str, status: u16, body: Value) -> Self {
+        self.routes
+            .insert((method.to_ascii_uppercase(), path.to_string()), StubResponse { status, body });
+        self
+    }
+
+    pub fn start(mut self) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        self.addr = Some(listener.local_addr()?);
+        let routes = Arc::new(std::mem::take(&mut self.routes));
+        let requests = Arc::clone(&self.requests);
+        let shutdown = Arc::clone(&self.shutdown);
+        self.handle = Some(std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let _ = serve(stream, &routes, &requests);
+                }
+            }
+        }));
+        Ok(self)
+    }
+
+    pub fn url(&self) -> String {
+        self.addr.map(|addr| format!("http://{}", addr)).unwrap_or_default()
+    }
+
+    pub fn core(&self) -> RustCore {
+        RustCore::new(&self.url())
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(addr) = self.addr {
+            // Wake the accept loop so it sees the shutdown flag.
+            let _ = TcpStream::connect(addr);
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    routes: &HashMap<(String, String), StubResponse>,
+    requests: &Mutex<Vec<RecordedRequest>>,
+) -> std::io::Result<()> {
+    let request = read_request(&stream)?;
+    let path = request.path.split('?').next().unwrap_or_default().to_string();
+    let response = routes
+        .get(&(request.method.clone(), request.path.clone()))
+        .or_else(|| routes.get(&(request.method.clone(), path)))
+        .cloned()
+        .unwrap_or_else(|| StubResponse {
+            status: 404,
+            body: serde_json::json!({ "error": format!("no stub for {} {}", request.method, request.path) }),
+        });
+    requests.lock().unwrap().push(request);
+    write_response(stream, response.status, &[], &response.body)
+}
+
+// Reads one request; `path` keeps its query string.
+pub(crate) fn read_request(stream: &TcpStream) -> std::io::Result<RecordedRequest> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(RecordedRequest {
+        method,
+        path: target,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+pub(crate) fn write_response(mut stream: TcpStream, status: u16, headers: &[(&str, &str)], body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    let extra: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    write!(
+        stream,
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        extra,
+        body
+    )?;
+    stream.flush()
+}
+
+// Starts a `StubServer` for the declared routes and returns it together with a
+// `RustCore` pointed at it; keep the server alive for as long as it's needed.
+//
+//     let (server, core) = stub! {
+//         GET "/users/1" => 200, { "id": 1, "name": "Ada" },
+//         POST "/users" => 201, { "id": 2 },
+//     };
+#[macro_export]
+macro_rules! stub {
+    ($($method:ident $path:literal => $status:literal, $body:tt),* $(,)?) => {{
+        let server = $crate::StubServer::new()
+            $(.route(stringify!($method), $path, $status, $crate::__stub_json!($body)))*
+            .start()
+            .expect("failed to start stub server");
+        let core = server.core();
+        (server, core)
+    }};
+}
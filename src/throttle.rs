@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Allows `burst` requests at once, refilled at `per_second`. Callers that find
+// it empty still take a token, going into debt, so concurrent requests queue
+// up behind each other instead of all waking at the same moment.
+pub(crate) struct TokenBucket {
+    per_second: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// A bucket as written to the quota store, with the refill time in epoch
+// milliseconds so it still means something to the next process.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedBucket {
+    pub tokens: f64,
+    pub refilled_at: u64,
+}
+
+impl TokenBucket {
+    pub fn new(per_second: f64, burst: u32, now: Instant) -> Self {
+        let burst = f64::from(burst.max(1));
+        TokenBucket {
+            per_second: per_second.max(f64::MIN_POSITIVE),
+            burst,
+            state: Mutex::new(BucketState { tokens: burst, refilled_at: now }),
+        }
+    }
+
+    // Takes a token and returns how long to wait before using it.
+    pub fn acquire(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let refill = now.saturating_duration_since(state.refilled_at).as_secs_f64() * self.per_second;
+        state.tokens = (state.tokens + refill).min(self.burst);
+        state.refilled_at = now.max(state.refilled_at);
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-state.tokens / self.per_second)
+    }
+
+    // After a 429: whatever was left is forfeit, and with a Retry-After the
+    // next request waits until it has passed.
+    pub fn penalize(&self, now: Instant, retry_after: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        let owed = retry_after.map_or(0.0, |delay| (delay.as_secs_f64() * self.per_second - 1.0).max(0.0));
+        state.tokens = state.tokens.min(0.0).min(-owed);
+        state.refilled_at = now.max(state.refilled_at);
+    }
+
+    pub fn save(&self, now: Instant, now_at: SystemTime) -> SavedBucket {
+        let state = self.state.lock().unwrap();
+        let refilled_at = now_at.checked_sub(now.saturating_duration_since(state.refilled_at)).unwrap_or(now_at);
+        SavedBucket {
+            tokens: state.tokens,
+            refilled_at: refilled_at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+        }
+    }
+
+    // Picks up where a saved bucket left off, refilled for the time since.
+    pub fn restore(&self, saved: SavedBucket, now: Instant, now_at: SystemTime) {
+        let saved_at = UNIX_EPOCH + Duration::from_millis(saved.refilled_at);
+        let refill = now_at.duration_since(saved_at).unwrap_or_default().as_secs_f64() * self.per_second;
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (saved.tokens + refill).min(self.burst);
+        state.refilled_at = now;
+    }
+}
@@ -0,0 +1,211 @@
+use crate::stub::{read_request, write_response};
+use crate::{Auth, Clock, OAuth2, RecordedRequest, RustCore, SystemClock, VirtualClock};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const TOKEN_PATH: &str = "/oauth/token";
+
+// A local OAuth2 token endpoint (client credentials) in front of an API where
+// every other path needs a valid bearer token, for testing token refresh
+// without a real identity provider. Expiry is checked against the server's
+// clock: with a shared `VirtualClock`, tests jump past it instantly.
+//
+//     let clock = Arc::new(VirtualClock::new());
+//     let server = TokenServer::new().expires_in(Duration::from_secs(300)).clock(clock.clone()).start()?;
+//     let core = server.core();
+//     core.fetch("/me")?;
+//     clock.advance(Duration::from_secs(600));
+//     core.fetch("/me")?;
+//     assert_eq!(server.tokens_issued(), 2);
+pub struct TokenServer {
+    expires_in: Option<Duration>,
+    token_delay: Duration,
+    clock: Option<Arc<VirtualClock>>,
+    state: Arc<TokenState>,
+    addr: Option<SocketAddr>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct TokenState {
+    // Access token to when it expires, None for tokens that don't.
+    tokens: Mutex<HashMap<String, Option<Instant>>>,
+    issued: AtomicU32,
+    reject_next: AtomicU32,
+    rejected: AtomicU32,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl Default for TokenServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenServer {
+    pub fn new() -> Self {
+        TokenServer {
+            expires_in: Some(Duration::from_secs(3600)),
+            token_delay: Duration::ZERO,
+            clock: None,
+            state: Arc::default(),
+            addr: None,
+            shutdown: Arc::default(),
+            handle: None,
+        }
+    }
+
+    // Lifetime of issued tokens, sent as `expires_in`. Defaults to an hour.
+    pub fn expires_in(mut self, lifetime: Duration) -> Self {
+        self.expires_in = Some(lifetime);
+        self
+    }
+
+    // Issues tokens without `expires_in` that stay valid until revoked.
+    pub fn never_expires(mut self) -> Self {
+        self.expires_in = None;
+        self
+    }
+
+    // How long the token endpoint takes to answer, in real time. Widens the
+    // window in which concurrent requests race to refresh.
+    pub fn token_delay(mut self, delay: Duration) -> Self {
+        self.token_delay = delay;
+        self
+    }
+
+    // Checks expiry against `clock`, which `core` also hands to the client.
+    pub fn clock(mut self, clock: Arc<VirtualClock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn start(mut self) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        self.addr = Some(listener.local_addr()?);
+        let state = Arc::clone(&self.state);
+        let shutdown = Arc::clone(&self.shutdown);
+        let clock: Arc<dyn Clock> = match &self.clock {
+            Some(clock) => clock.clone(),
+            None => Arc::new(SystemClock),
+        };
+        let (expires_in, token_delay) = (self.expires_in, self.token_delay);
+        self.handle = Some(std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                // One thread per connection, so refreshes can overlap.
+                let (state, clock) = (Arc::clone(&state), Arc::clone(&clock));
+                std::thread::spawn(move || {
+                    let _ = serve(stream, &state, clock.as_ref(), expires_in, token_delay);
+                });
+            }
+        }));
+        Ok(self)
+    }
+
+    pub fn url(&self) -> String {
+        self.addr.map(|addr| format!("http://{}", addr)).unwrap_or_default()
+    }
+
+    pub fn token_url(&self) -> String {
+        format!("{}{}", self.url(), TOKEN_PATH)
+    }
+
+    // A client for the API that gets its tokens here, on the server's clock.
+    pub fn core(&self) -> RustCore {
+        let mut core = RustCore::new(&self.url());
+        core.set_auth(Auth::OAuth2(OAuth2::new(&self.token_url(), "test-client", "test-secret")));
+        if let Some(clock) = &self.clock {
+            core.set_clock(clock.clone());
+        }
+        core
+    }
+
+    // Invalidates every token issued so far, as after a key rotation.
+    pub fn revoke_all(&self) {
+        self.state.tokens.lock().unwrap().clear();
+    }
+
+    // Answers the next `count` API requests with 401, whatever their token.
+    pub fn reject_next(&self, count: u32) {
+        self.state.reject_next.store(count, Ordering::SeqCst);
+    }
+
+    pub fn tokens_issued(&self) -> u32 {
+        self.state.issued.load(Ordering::SeqCst)
+    }
+
+    // API requests answered with 401.
+    pub fn rejected(&self) -> u32 {
+        self.state.rejected.load(Ordering::SeqCst)
+    }
+
+    // API requests, token requests excluded.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for TokenServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(addr) = self.addr {
+            // Wake the accept loop so it sees the shutdown flag.
+            let _ = TcpStream::connect(addr);
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve(stream: TcpStream, state: &TokenState, clock: &dyn Clock, expires_in: Option<Duration>, token_delay: Duration) -> std::io::Result<()> {
+    let request = read_request(&stream)?;
+    if request.path.split('?').next() == Some(TOKEN_PATH) {
+        if request.method != "POST" || !request.body.split('&').any(|field| field == "grant_type=client_credentials") {
+            return write_response(stream, 400, &[], &json!({ "error": "unsupported_grant_type" }));
+        }
+        std::thread::sleep(token_delay);
+        let n = state.issued.fetch_add(1, Ordering::SeqCst) + 1;
+        let token = format!("token-{}", n);
+        let expires_at = expires_in.map(|lifetime| clock.now() + lifetime);
+        state.tokens.lock().unwrap().insert(token.clone(), expires_at);
+        let mut body = json!({ "access_token": token, "token_type": "Bearer" });
+        if let Some(lifetime) = expires_in {
+            body["expires_in"] = json!(lifetime.as_secs());
+        }
+        return write_response(stream, 200, &[], &body);
+    }
+
+    let token = request.headers.get("authorization").and_then(|v| v.strip_prefix("Bearer ")).map(str::to_string);
+    state.requests.lock().unwrap().push(request);
+    let forced = state
+        .reject_next
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+        .is_ok();
+    let valid = token.as_ref().is_some_and(|token| {
+        let tokens = state.tokens.lock().unwrap();
+        tokens.get(token).is_some_and(|expires_at| expires_at.is_none_or(|at| clock.now() < at))
+    });
+    if forced || !valid {
+        state.rejected.fetch_add(1, Ordering::SeqCst);
+        return write_response(
+            stream,
+            401,
+            &[("WWW-Authenticate", "Bearer error=\"invalid_token\"")],
+            &json!({ "error": "invalid_token" }),
+        );
+    }
+    write_response(stream, 200, &[], &json!({ "token": token }))
+}
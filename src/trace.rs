@@ -0,0 +1,72 @@
+use crate::capture::{self, Capture, MASK};
+use reqwest::blocking::Request;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::field::{display, Empty};
+use tracing::Span;
+
+// Rewrites a header value for trace output; None leaves the header out.
+pub(crate) type Redactor = Arc<dyn Fn(&str, &str) -> Option<String> + Send + Sync>;
+
+// One `http.request` span per call, covering all its attempts and backoffs,
+// so events from middleware and hooks nest under it. The URL has its query
+// values and credentials stripped as in captures.
+pub(crate) fn request_span(request: &Request, capture: &Capture, redactor: Option<&Redactor>) -> Span {
+    let span = tracing::info_span!(
+        "http.request",
+        http.method = %request.method(),
+        url = %capture::sanitize_url(request.url()),
+        http.status_code = Empty,
+        latency_ms = Empty,
+        retries = Empty,
+        error = Empty,
+    );
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        let headers = headers(request, capture, redactor);
+        tracing::debug!(parent: &span, headers = ?headers, "sending request");
+    }
+    span
+}
+
+pub(crate) fn finish(span: &Span, status: Option<u16>, attempts: usize, elapsed: Duration, error: Option<&dyn std::error::Error>) {
+    if let Some(status) = status {
+        span.record("http.status_code", status);
+    }
+    span.record("latency_ms", elapsed.as_millis() as u64);
+    span.record("retries", attempts.saturating_sub(1) as u64);
+    match error {
+        Some(error) => {
+            span.record("error", display(error));
+            tracing::warn!(parent: span, "request failed: {}", error);
+        }
+        None => tracing::info!(parent: span, "request finished"),
+    }
+}
+
+// Credentials are masked before the redactor sees them, so a redactor can
+// only hide more.
+fn headers(request: &Request, capture: &Capture, redactor: Option<&Redactor>) -> BTreeMap<String, String> {
+    let mut out = BTreeMap::new();
+    for (name, value) in request.headers() {
+        let name = name.as_str();
+        let value = if capture.is_sensitive(name) {
+            MASK.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        let value = match redactor {
+            Some(redactor) => redactor(name, &value),
+            None => Some(value),
+        };
+        if let Some(value) = value {
+            out.entry(name.to_string())
+                .and_modify(|existing: &mut String| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert(value);
+        }
+    }
+    out
+}
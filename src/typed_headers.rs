@@ -0,0 +1,107 @@
+use chrono::DateTime;
+use mime::Mime;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, RETRY_AFTER};
+use reqwest::Url;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Reset values above this are read as epoch seconds rather than a delay.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+// Rate-limit state as reported by the server, from the `RateLimit` structured
+// header (`limit=100, remaining=40, reset=30`, or the newer `"default";r=40;t=30`)
+// or the `RateLimit-*` / `X-RateLimit-*` families.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset: Option<Duration>,
+}
+
+// Typed accessors for common response headers. Missing or unparsable headers
+// come back as `None`.
+pub trait TypedHeaders {
+    fn content_type(&self) -> Option<Mime>;
+    fn content_length(&self) -> Option<u64>;
+    // Relative locations are resolved against `base`, normally the request URL.
+    fn location(&self, base: &Url) -> Option<Url>;
+    // Delay-seconds or an HTTP-date; dates in the past give zero.
+    fn retry_after(&self) -> Option<Duration>;
+    // `X-Poll-Interval` seconds, as sent by GitHub and others.
+    fn poll_interval(&self) -> Option<Duration>;
+    fn rate_limit(&self) -> Option<RateLimit>;
+}
+
+impl TypedHeaders for HeaderMap {
+    fn content_type(&self) -> Option<Mime> {
+        text(self, CONTENT_TYPE.as_str())?.parse().ok()
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        text(self, CONTENT_LENGTH.as_str())?.parse().ok()
+    }
+
+    fn location(&self, base: &Url) -> Option<Url> {
+        base.join(text(self, LOCATION.as_str())?).ok()
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        let value = text(self, RETRY_AFTER.as_str())?;
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let at = DateTime::parse_from_rfc2822(value).ok()?;
+        let at = UNIX_EPOCH + Duration::from_secs(at.timestamp().max(0) as u64);
+        Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        text(self, "x-poll-interval")?.parse().ok().map(Duration::from_secs)
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        let limit = match text(self, "ratelimit") {
+            Some(structured) => {
+                let mut limit = RateLimit::default();
+                for item in structured.split([',', ';']) {
+                    let Some((key, value)) = item.split_once('=') else {
+                        continue;
+                    };
+                    let value = value.trim().parse().ok();
+                    match key.trim() {
+                        "limit" | "q" => limit.limit = value,
+                        "remaining" | "r" => limit.remaining = value,
+                        "reset" | "t" => limit.reset = value.map(reset_delay),
+                        _ => {}
+                    }
+                }
+                limit
+            }
+            None => {
+                let family = |suffix: &str| {
+                    text(self, &format!("ratelimit-{}", suffix))
+                        .or_else(|| text(self, &format!("x-ratelimit-{}", suffix)))
+                        .and_then(|v| v.parse::<u64>().ok())
+                };
+                RateLimit {
+                    limit: family("limit"),
+                    remaining: family("remaining"),
+                    reset: family("reset").map(reset_delay),
+                }
+            }
+        };
+        (limit != RateLimit::default()).then_some(limit)
+    }
+}
+
+fn text<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok().map(str::trim)
+}
+
+fn reset_delay(value: u64) -> Duration {
+    if value < EPOCH_THRESHOLD {
+        return Duration::from_secs(value);
+    }
+    (UNIX_EPOCH + Duration::from_secs(value))
+        .duration_since(SystemTime::now())
+        .unwrap_or_default()
+}
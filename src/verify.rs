@@ -0,0 +1,119 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName};
+use sha2::Sha256;
+use std::fmt;
+use zeroize::Zeroizing;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    Malformed,
+    Mismatch,
+    // The signature header name given to the verifier isn't a valid header name.
+    InvalidHeader(String),
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Missing => write!(f, "Response carries no signature"),
+            SignatureError::Malformed => write!(f, "Response signature could not be decoded"),
+            SignatureError::Mismatch => write!(f, "Response signature does not match the body"),
+            SignatureError::InvalidHeader(header) => write!(f, "Invalid signature header name: {}", header),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+enum VerifyKey {
+    Hmac(Zeroizing<Vec<u8>>),
+    #[cfg(feature = "ed25519")]
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+// Checks a signature header over the (decoded) response body before it is
+// parsed. The header may be hex or base64, optionally behind an `alg=` prefix
+// such as `sha256=`. Streaming responses are not verified.
+pub struct ResponseVerifier {
+    header: HeaderName,
+    key: VerifyKey,
+}
+
+impl ResponseVerifier {
+    // HMAC-SHA256 with a secret shared with the server.
+    pub fn hmac(key: &[u8], header: &str) -> Result<Self, SignatureError> {
+        Ok(ResponseVerifier {
+            header: header_name(header)?,
+            key: VerifyKey::Hmac(Zeroizing::new(key.to_vec())),
+        })
+    }
+
+    #[cfg(feature = "ed25519")]
+    pub fn ed25519(public_key: &[u8; 32], header: &str) -> Result<Self, SignatureError> {
+        let key = ed25519_dalek::VerifyingKey::from_bytes(public_key).map_err(|_| SignatureError::Malformed)?;
+        Ok(ResponseVerifier {
+            header: header_name(header)?,
+            key: VerifyKey::Ed25519(key),
+        })
+    }
+
+    pub(crate) fn signature(&self, headers: &HeaderMap) -> Result<Vec<u8>, SignatureError> {
+        let value = headers
+            .get(&self.header)
+            .ok_or(SignatureError::Missing)?
+            .to_str()
+            .map_err(|_| SignatureError::Malformed)?
+            .trim();
+        let encoded = strip_algorithm(value);
+        decode_hex(encoded)
+            .or_else(|| STANDARD.decode(encoded).ok())
+            .ok_or(SignatureError::Malformed)
+    }
+
+    pub(crate) fn verify(&self, signature: &[u8], body: &[u8]) -> Result<(), SignatureError> {
+        match &self.key {
+            VerifyKey::Hmac(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|_| SignatureError::Malformed)?;
+                mac.update(body);
+                mac.verify_slice(signature).map_err(|_| SignatureError::Mismatch)
+            }
+            #[cfg(feature = "ed25519")]
+            VerifyKey::Ed25519(key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature).map_err(|_| SignatureError::Malformed)?;
+                key.verify_strict(body, &signature).map_err(|_| SignatureError::Mismatch)
+            }
+        }
+    }
+}
+
+// `sha256=abcd` -> `abcd`; base64 padding (`ab==`) is left alone.
+fn strip_algorithm(value: &str) -> &str {
+    match value.split_once('=') {
+        Some((alg, sig))
+            if !alg.is_empty()
+                && alg.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                && !sig.is_empty()
+                && !sig.starts_with('=') =>
+        {
+            sig
+        }
+        _ => value,
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.is_empty() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn header_name(header: &str) -> Result<HeaderName, SignatureError> {
+    HeaderName::try_from(header).map_err(|_| SignatureError::InvalidHeader(header.to_string()))
+}
@@ -0,0 +1,145 @@
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Strategy {
+    // `Accept: application/vnd.{vendor}.v{version}+json`
+    MediaType(String),
+    Header(HeaderName),
+    // `/v{version}` inserted after the base URL's path.
+    PathPrefix,
+}
+
+// The API version a client asks for. Headers are only added when the request
+// doesn't already carry them, so middleware and explicit headers win.
+#[derive(Clone, Debug)]
+pub struct ApiVersion {
+    version: String,
+    strategy: Strategy,
+    response_header: Option<HeaderName>,
+}
+
+impl ApiVersion {
+    pub fn media_type(vendor: &str, version: &str) -> Self {
+        Self::with(version, Strategy::MediaType(vendor.to_string()))
+    }
+
+    // Panics on an invalid header name, like `HeaderName::from_static`.
+    pub fn header(name: &str, version: &str) -> Self {
+        let name = HeaderName::try_from(name).unwrap();
+        let mut version = Self::with(version, Strategy::Header(name.clone()));
+        version.response_header = Some(name);
+        version
+    }
+
+    pub fn path_prefix(version: &str) -> Self {
+        Self::with(version, Strategy::PathPrefix)
+    }
+
+    fn with(version: &str, strategy: Strategy) -> Self {
+        ApiVersion {
+            version: version.trim_start_matches(['v', 'V']).to_string(),
+            strategy,
+            response_header: None,
+        }
+    }
+
+    // Header the server reports its version in. Defaults to the request header
+    // for `header`, and to the Content-Type's vendor version for `media_type`.
+    pub fn response_header(mut self, name: &str) -> Self {
+        self.response_header = Some(HeaderName::try_from(name).unwrap());
+        self
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub(crate) fn apply(&self, request: &mut Request, base_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.strategy {
+            Strategy::MediaType(vendor) => {
+                let accept = format!("application/vnd.{}.v{}+json", vendor, self.version);
+                request.headers_mut().entry(ACCEPT).or_insert(HeaderValue::try_from(accept)?);
+            }
+            Strategy::Header(name) => {
+                let value = HeaderValue::try_from(self.version.as_str())?;
+                request.headers_mut().entry(name).or_insert(value);
+            }
+            Strategy::PathPrefix => {
+                let prefix = format!("/v{}", self.version);
+                let Some(rest) = request.url().as_str().strip_prefix(base_url) else {
+                    return Ok(());
+                };
+                if rest == prefix || rest.starts_with(&format!("{}/", prefix)) {
+                    return Ok(());
+                }
+                let url = reqwest::Url::parse(&format!("{}{}{}", base_url, prefix, rest))?;
+                *request.url_mut() = url;
+            }
+        }
+        Ok(())
+    }
+
+    // The version `request` actually asks for, which per-request overrides
+    // may have changed.
+    pub(crate) fn requested(&self, request: &Request, base_url: &str) -> Option<String> {
+        match &self.strategy {
+            Strategy::MediaType(vendor) => vendor_version(vendor, request.headers().get(ACCEPT)?.to_str().ok()?),
+            Strategy::Header(name) => request.headers().get(name)?.to_str().ok().map(str::to_string),
+            Strategy::PathPrefix => {
+                let rest = request.url().as_str().strip_prefix(base_url)?;
+                let segment = rest.trim_start_matches('/').split(['/', '?', '#']).next()?;
+                segment.strip_prefix('v').map(str::to_string)
+            }
+        }
+    }
+
+    fn served(&self, headers: &HeaderMap) -> Option<String> {
+        if let Some(name) = &self.response_header {
+            return headers.get(name)?.to_str().ok().map(str::to_string);
+        }
+        match &self.strategy {
+            Strategy::MediaType(vendor) => vendor_version(vendor, headers.get(CONTENT_TYPE)?.to_str().ok()?),
+            _ => None,
+        }
+    }
+}
+
+// `application/vnd.foo.v2+json; charset=utf-8` -> `2`
+fn vendor_version(vendor: &str, media_type: &str) -> Option<String> {
+    let media = media_type.split([';', ',']).next()?.trim();
+    let rest = media.strip_prefix(&format!("application/vnd.{}.v", vendor))?;
+    Some(rest.split('+').next()?.to_string())
+}
+
+// What a response said about versioning. `deprecation` and `sunset` are the
+// raw header values (RFC 9745 / RFC 8594).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub endpoint: String,
+    pub requested: Option<String>,
+    pub served: Option<String>,
+    pub deprecation: Option<String>,
+    pub sunset: Option<String>,
+}
+
+pub(crate) type VersionHook = Arc<dyn Fn(&VersionInfo) + Send + Sync>;
+
+// None if the response carries no version, Deprecation or Sunset header.
+pub(crate) fn inspect(
+    endpoint: &str,
+    version: Option<&ApiVersion>,
+    requested: Option<String>,
+    headers: &HeaderMap,
+) -> Option<VersionInfo> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let info = VersionInfo {
+        endpoint: endpoint.to_string(),
+        requested,
+        served: version.and_then(|v| v.served(headers)),
+        deprecation: header("deprecation"),
+        sunset: header("sunset"),
+    };
+    (info.served.is_some() || info.deprecation.is_some() || info.sunset.is_some()).then_some(info)
+}
@@ -0,0 +1,456 @@
+use crate::{ApiError, RustCore};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::blocking::Request;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Larger messages fail the connection instead of being buffered.
+const MAX_MESSAGE_BYTES: u64 = 64 * 1024 * 1024;
+// How long `close` waits for the server to answer the close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketError {
+    // The server answered the upgrade, but not the way RFC 6455 requires.
+    Handshake(String),
+    // A frame the client can't accept: bad opcode, oversized, invalid UTF-8.
+    Protocol(String),
+    // The connection was closed, by either side.
+    Closed,
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebSocketError::Handshake(reason) => write!(f, "WebSocket handshake failed: {}", reason),
+            WebSocketError::Protocol(reason) => write!(f, "WebSocket protocol error: {}", reason),
+            WebSocketError::Closed => write!(f, "WebSocket is closed"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<native_tls::TlsStream<TcpStream>>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(stream) => stream,
+            Stream::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// A WebSocket from `RustCore::connect_ws`. The handshake goes through the
+// request pipeline, so it carries the client's auth, default headers and
+// middleware, and is routed like any other request. Pings are answered while
+// receiving. If the connection drops, the socket reconnects with the client's
+// retry backoff; messages the server sent in between are lost.
+pub struct WebSocket<'a> {
+    core: &'a RustCore,
+    path: String,
+    stream: Option<BufReader<Stream>>,
+    reconnects: u32,
+}
+
+impl<'a> WebSocket<'a> {
+    pub(crate) fn connect(core: &'a RustCore, path: &str) -> Result<Self, ApiError> {
+        let stream = handshake(core, path)?;
+        Ok(WebSocket {
+            core,
+            path: path.to_string(),
+            stream: Some(stream),
+            reconnects: 0,
+        })
+    }
+
+    pub fn send(&mut self, message: Message) -> Result<(), ApiError> {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (TEXT, text.into_bytes()),
+            Message::Binary(data) => (BINARY, data),
+        };
+        match self.write_frame(opcode, &payload) {
+            Err(ApiError::Connection(_)) => {
+                self.reconnect()?;
+                self.write_frame(opcode, &payload)
+            }
+            result => result,
+        }
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), ApiError> {
+        self.send(Message::Text(text.to_string()))
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), ApiError> {
+        self.send(Message::Binary(data.to_vec()))
+    }
+
+    pub fn ping(&mut self, payload: &[u8]) -> Result<(), ApiError> {
+        self.write_frame(PING, payload)
+    }
+
+    // The next message, or None once the server has closed the connection.
+    pub fn receive(&mut self) -> Result<Option<Message>, ApiError> {
+        loop {
+            match self.read_message() {
+                Err(ApiError::Connection(_)) => self.reconnect()?,
+                result => return result,
+            }
+        }
+    }
+
+    // How many times the connection was re-established.
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // Sends a normal close and waits briefly for the server's reply.
+    pub fn close(&mut self) -> Result<(), ApiError> {
+        if self.stream.is_none() {
+            return Ok(());
+        }
+        self.write_frame(CLOSE, &1000u16.to_be_bytes())?;
+        if let Some(stream) = &self.stream {
+            let _ = stream.get_ref().tcp().set_read_timeout(Some(CLOSE_TIMEOUT));
+        }
+        while let Some(stream) = &mut self.stream {
+            match read_frame(stream) {
+                Ok((_, CLOSE, _)) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        self.stream = None;
+        Ok(())
+    }
+
+    fn reconnect(&mut self) -> Result<(), ApiError> {
+        self.stream = None;
+        let policy = self.core.retry_policy.clone();
+        let mut retry = 1;
+        loop {
+            self.core.wait(policy.delay(retry, None, None));
+            match handshake(self.core, &self.path) {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    self.reconnects += 1;
+                    return Ok(());
+                }
+                Err(e) if !policy.should_retry(retry, &reqwest::Method::GET, e.status()) => return Err(e),
+                Err(_) => retry += 1,
+            }
+        }
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), ApiError> {
+        let stream = self.stream.as_mut().ok_or(ApiError::Other(WebSocketError::Closed.into()))?;
+        write_frame(stream.get_mut(), opcode, payload).map_err(|e| ApiError::Connection(e.into()))
+    }
+
+    // Reassembles fragmented messages and answers control frames on the way.
+    fn read_message(&mut self) -> Result<Option<Message>, ApiError> {
+        let mut message: Option<(u8, Vec<u8>)> = None;
+        loop {
+            let Some(stream) = &mut self.stream else {
+                return Ok(None);
+            };
+            let (fin, opcode, payload) = read_frame(stream)?;
+            match opcode {
+                PING => self.write_frame(PONG, &payload)?,
+                PONG => {}
+                CLOSE => {
+                    // Echo the status code, as RFC 6455 asks.
+                    let _ = self.write_frame(CLOSE, payload.get(..2).unwrap_or_default());
+                    self.stream = None;
+                    return Ok(None);
+                }
+                TEXT | BINARY if message.is_none() => message = Some((opcode, payload)),
+                CONTINUATION if message.is_some() => {
+                    let (_, data) = message.as_mut().unwrap();
+                    if (data.len() + payload.len()) as u64 > MAX_MESSAGE_BYTES {
+                        return Err(protocol("message too large"));
+                    }
+                    data.extend_from_slice(&payload);
+                }
+                _ => return Err(protocol(&format!("unexpected opcode {:#x}", opcode))),
+            }
+            if fin && opcode < CLOSE {
+                let (opcode, data) = message.take().unwrap();
+                return match opcode {
+                    TEXT => String::from_utf8(data).map(|text| Some(Message::Text(text))).map_err(|_| protocol("text message is not UTF-8")),
+                    _ => Ok(Some(Message::Binary(data))),
+                };
+            }
+        }
+    }
+}
+
+impl Drop for WebSocket<'_> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn protocol(reason: &str) -> ApiError {
+    ApiError::Decode(WebSocketError::Protocol(reason.to_string()).into())
+}
+
+fn handshake_error(reason: &str) -> ApiError {
+    ApiError::Other(WebSocketError::Handshake(reason.to_string()).into())
+}
+
+fn handshake(core: &RustCore, path: &str) -> Result<BufReader<Stream>, ApiError> {
+    let request = core.websocket_request(path)?;
+    let url = request.url();
+    let host = url.host_str().ok_or_else(|| handshake_error("URL has no host"))?.to_string();
+    let tls = match url.scheme() {
+        "https" | "wss" => true,
+        "http" | "ws" => false,
+        scheme => return Err(handshake_error(&format!("unsupported scheme {}", scheme))),
+    };
+    let port = url.port_or_known_default().unwrap_or(if tls { 443 } else { 80 });
+    let connection = |e: io::Error| match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ApiError::Timeout(e.into()),
+        _ => ApiError::Connection(e.into()),
+    };
+
+    let addr = (host.as_str(), port).to_socket_addrs().map_err(connection)?.next();
+    let addr = addr.ok_or_else(|| ApiError::Connection(format!("{} did not resolve", host).into()))?;
+    let tcp = match core.client_config.connect_timeout {
+        Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+        None => TcpStream::connect(addr),
+    }
+    .map_err(connection)?;
+    // The client timeout covers the handshake; afterwards reads wait as long
+    // as the server stays quiet.
+    tcp.set_read_timeout(core.client_config.timeout).map_err(connection)?;
+    let stream = match tls {
+        true => {
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(core.client_config.accept_invalid_certs)
+                .build()
+                .map_err(|e| ApiError::Connection(e.into()))?;
+            let stream = connector.connect(&host, tcp).map_err(|e| ApiError::Connection(e.to_string().into()))?;
+            Stream::Tls(Box::new(stream))
+        }
+        false => Stream::Plain(tcp),
+    };
+    let mut stream = BufReader::new(stream);
+
+    let mut nonce = [0u8; 16];
+    getrandom::getrandom(&mut nonce).map_err(|e| ApiError::Other(e.into()))?;
+    let key = STANDARD.encode(nonce);
+    stream.get_mut().write_all(upgrade_request(&request, &key).as_bytes()).map_err(connection)?;
+
+    let mut status_line = String::new();
+    stream.read_line(&mut status_line).map_err(connection)?;
+    let status: u16 = status_line.split_whitespace().nth(1).and_then(|s| s.parse().ok()).ok_or_else(|| handshake_error("malformed status line"))?;
+    let mut accept = None;
+    let mut upgraded = false;
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).map_err(connection)? == 0 {
+            return Err(handshake_error("connection closed during handshake"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "sec-websocket-accept" => accept = Some(value.trim().to_string()),
+                "upgrade" => upgraded = value.trim().eq_ignore_ascii_case("websocket"),
+                _ => {}
+            }
+        }
+    }
+    if status != 101 {
+        return Err(ApiError::http(status, String::new(), core.error_translator.as_ref()));
+    }
+    if !upgraded {
+        return Err(handshake_error("server did not upgrade to websocket"));
+    }
+    if accept.as_deref() != Some(&STANDARD.encode(sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))) {
+        return Err(handshake_error("Sec-WebSocket-Accept does not match the key"));
+    }
+    stream.get_ref().tcp().set_read_timeout(None).map_err(connection)?;
+    Ok(stream)
+}
+
+fn upgrade_request(request: &Request, key: &str) -> String {
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut head = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n",
+        target, host, key
+    );
+    for (name, value) in request.headers() {
+        if matches!(name.as_str(), "host" | "upgrade" | "connection" | "accept-encoding" | "content-length") {
+            continue;
+        }
+        if let Ok(value) = value.to_str() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    head.push_str("\r\n");
+    head
+}
+
+// Client frames are always masked.
+fn write_frame(stream: &mut Stream, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let mut mask = [0u8; 4];
+    getrandom::getrandom(&mut mask).map_err(io::Error::other)?;
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+// (fin, opcode, payload)
+fn read_frame(stream: &mut BufReader<Stream>) -> Result<(bool, u8, Vec<u8>), ApiError> {
+    let connection = |e: io::Error| ApiError::Connection(e.into());
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).map_err(connection)?;
+    let (fin, opcode, masked) = (head[0] & 0x80 != 0, head[0] & 0x0F, head[1] & 0x80 != 0);
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).map_err(connection)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len).map_err(connection)?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if len > MAX_MESSAGE_BYTES {
+        return Err(protocol("frame too large"));
+    }
+    if opcode >= CLOSE && (len > 125 || !fin) {
+        return Err(protocol("malformed control frame"));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask).map_err(connection)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload).map_err(connection)?;
+    if masked {
+        payload.iter_mut().enumerate().for_each(|(i, byte)| *byte ^= mask[i % 4]);
+    }
+    Ok((fin, opcode, payload))
+}
+
+// Only for Sec-WebSocket-Accept, which RFC 6455 defines with SHA-1.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}